@@ -0,0 +1,236 @@
+//! Compact set of typed flags that can be used in `Pod`s
+use {
+    bytemuck::{Pod, Zeroable},
+    solana_program::program_error::ProgramError,
+    std::{fmt, marker::PhantomData},
+};
+
+/// A flag that can be stored in a `PodBitflags`, typically implemented by a
+/// fieldless enum
+pub trait BitFlag: Copy + PartialEq + 'static {
+    /// Every flag, used to list the flags that are set
+    const ALL: &'static [Self];
+    /// Index of the bit representing the flag
+    fn bit(self) -> usize;
+    /// Name of the flag, used for serialization
+    fn name(self) -> &'static str;
+}
+
+/// Set of flags of type `F` packed into `N` bytes, with bit `i` stored in
+/// byte `i / 8`
+#[repr(transparent)]
+pub struct PodBitflags<F, const N: usize> {
+    bytes: [u8; N],
+    _flag: PhantomData<F>,
+}
+
+// SAFETY: the struct is `repr(transparent)` over a byte array, `PhantomData`
+// is zero-sized, and every bit pattern is a valid (possibly unknown) flag set
+unsafe impl<F: BitFlag, const N: usize> Zeroable for PodBitflags<F, N> {}
+// SAFETY: see `Zeroable` above
+unsafe impl<F: BitFlag, const N: usize> Pod for PodBitflags<F, N> {}
+
+impl<F, const N: usize> Clone for PodBitflags<F, N> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<F, const N: usize> Copy for PodBitflags<F, N> {}
+
+impl<F, const N: usize> PartialEq for PodBitflags<F, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+impl<F, const N: usize> Eq for PodBitflags<F, N> {}
+
+impl<F, const N: usize> Default for PodBitflags<F, N> {
+    fn default() -> Self {
+        Self::from_bytes([0; N])
+    }
+}
+
+impl<F: BitFlag + fmt::Debug, const N: usize> fmt::Debug for PodBitflags<F, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<F, const N: usize> PodBitflags<F, N> {
+    /// Create from raw bytes
+    pub const fn from_bytes(bytes: [u8; N]) -> Self {
+        Self {
+            bytes,
+            _flag: PhantomData,
+        }
+    }
+
+    /// Raw bytes of the set
+    pub const fn to_bytes(self) -> [u8; N] {
+        self.bytes
+    }
+
+    /// Check that no bit is set
+    pub fn is_empty(&self) -> bool {
+        self.bytes.iter().all(|byte| *byte == 0)
+    }
+}
+
+impl<F: BitFlag, const N: usize> PodBitflags<F, N> {
+    /// Create a set containing the given flags
+    pub fn from_flags(flags: &[F]) -> Result<Self, ProgramError> {
+        let mut bitflags = Self::default();
+        for flag in flags {
+            bitflags.set(*flag)?;
+        }
+        Ok(bitflags)
+    }
+
+    /// Set a flag, failing if its bit does not fit in `N` bytes
+    pub fn set(&mut self, flag: F) -> Result<(), ProgramError> {
+        let bit = flag.bit();
+        let byte = self
+            .bytes
+            .get_mut(bit / 8)
+            .ok_or(ProgramError::InvalidArgument)?;
+        *byte |= 1 << (bit % 8);
+        Ok(())
+    }
+
+    /// Clear a flag
+    pub fn clear(&mut self, flag: F) {
+        let bit = flag.bit();
+        if let Some(byte) = self.bytes.get_mut(bit / 8) {
+            *byte &= !(1 << (bit % 8));
+        }
+    }
+
+    /// Check whether a flag is set
+    pub fn contains(&self, flag: F) -> bool {
+        let bit = flag.bit();
+        self.bytes
+            .get(bit / 8)
+            .map(|byte| byte & (1 << (bit % 8)) != 0)
+            .unwrap_or(false)
+    }
+
+    /// Iterate over the known flags that are set
+    pub fn iter(&self) -> impl Iterator<Item = F> + '_ {
+        F::ALL.iter().copied().filter(|flag| self.contains(*flag))
+    }
+}
+
+#[cfg(feature = "serde-traits")]
+impl<F: BitFlag, const N: usize> serde::Serialize for PodBitflags<F, N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(BitFlag::name))
+    }
+}
+
+#[cfg(feature = "serde-traits")]
+impl<'de, F: BitFlag, const N: usize> serde::Deserialize<'de> for PodBitflags<F, N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let names = Vec::<String>::deserialize(deserializer)?;
+        let mut bitflags = Self::default();
+        for name in names {
+            let flag = F::ALL
+                .iter()
+                .find(|flag| flag.name() == name)
+                .ok_or_else(|| D::Error::custom(format!("unknown flag {}", name)))?;
+            bitflags
+                .set(*flag)
+                .map_err(|_| D::Error::custom(format!("flag {} does not fit", name)))?;
+        }
+        Ok(bitflags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::bytemuck::pod_from_bytes};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Permission {
+        Mint,
+        Burn,
+        Freeze,
+        Overflow,
+    }
+
+    impl BitFlag for Permission {
+        const ALL: &'static [Self] = &[Self::Mint, Self::Burn, Self::Freeze, Self::Overflow];
+        fn bit(self) -> usize {
+            match self {
+                Self::Mint => 0,
+                Self::Burn => 1,
+                Self::Freeze => 9,
+                Self::Overflow => 16,
+            }
+        }
+        fn name(self) -> &'static str {
+            match self {
+                Self::Mint => "mint",
+                Self::Burn => "burn",
+                Self::Freeze => "freeze",
+                Self::Overflow => "overflow",
+            }
+        }
+    }
+
+    type Permissions = PodBitflags<Permission, 2>;
+
+    #[test]
+    fn test_set_clear_contains() {
+        let mut permissions = Permissions::default();
+        assert!(permissions.is_empty());
+
+        permissions.set(Permission::Mint).unwrap();
+        permissions.set(Permission::Freeze).unwrap();
+        assert_eq!(permissions.to_bytes(), [0b1, 0b10]);
+        assert!(permissions.contains(Permission::Mint));
+        assert!(!permissions.contains(Permission::Burn));
+        assert!(permissions.contains(Permission::Freeze));
+        assert_eq!(
+            permissions.iter().collect::<Vec<_>>(),
+            vec![Permission::Mint, Permission::Freeze]
+        );
+
+        permissions.clear(Permission::Mint);
+        assert!(!permissions.contains(Permission::Mint));
+        assert_eq!(
+            permissions,
+            Permissions::from_flags(&[Permission::Freeze]).unwrap()
+        );
+
+        assert_eq!(
+            permissions.set(Permission::Overflow),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert!(!permissions.contains(Permission::Overflow));
+    }
+
+    #[test]
+    fn test_pod() {
+        assert!(pod_from_bytes::<Permissions>(&[]).is_err());
+        let permissions = pod_from_bytes::<Permissions>(&[0b11, 0]).unwrap();
+        assert!(permissions.contains(Permission::Mint));
+        assert!(permissions.contains(Permission::Burn));
+    }
+
+    #[cfg(feature = "serde-traits")]
+    #[test]
+    fn test_bitflags_serde() {
+        let permissions = Permissions::from_flags(&[Permission::Burn, Permission::Freeze]).unwrap();
+
+        let serialized = serde_json::to_string(&permissions).unwrap();
+        assert_eq!(&serialized, "[\"burn\",\"freeze\"]");
+
+        let deserialized = serde_json::from_str::<Permissions>(&serialized).unwrap();
+        assert_eq!(permissions, deserialized);
+
+        assert!(serde_json::from_str::<Permissions>("[\"unknown\"]").is_err());
+        assert!(serde_json::from_str::<Permissions>("[\"overflow\"]").is_err());
+    }
+}
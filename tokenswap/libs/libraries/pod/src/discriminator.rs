@@ -0,0 +1,267 @@
+//! Const computation of 8-byte discriminators from a namespaced hash input
+
+/// Discriminator identifying an account or instruction type, the one
+/// `spl-discriminator` defines
+pub use spl_discriminator::ArrayDiscriminator as Discriminator;
+
+/// Discriminator from the first 8 bytes of the SHA-256 hash of
+/// `"{namespace}:{name}"`, as `spl-discriminator` and Anchor derive them,
+/// usable in const contexts
+pub const fn discriminator_from_namespace(namespace: &str, name: &str) -> Discriminator {
+    discriminator_from_hash_parts(&[namespace.as_bytes(), b":", name.as_bytes()])
+}
+
+/// Discriminator from the first 8 bytes of the SHA-256 hash of the given
+/// input, usable in const contexts
+pub const fn discriminator_from_hash_input(hash_input: &str) -> Discriminator {
+    discriminator_from_hash_parts(&[hash_input.as_bytes()])
+}
+
+const fn discriminator_from_hash_parts(parts: &[&[u8]]) -> Discriminator {
+    let hash = sha256(parts);
+    let mut bytes = [0; Discriminator::LENGTH];
+    let mut i = 0;
+    while i < Discriminator::LENGTH {
+        bytes[i] = hash[i];
+        i += 1;
+    }
+    Discriminator::new(bytes)
+}
+
+/// Check whether some account or instruction data starts with the
+/// discriminator
+pub fn discriminator_matches(discriminator: &Discriminator, data: &[u8]) -> bool {
+    data.get(..Discriminator::LENGTH) == Some(discriminator.as_slice())
+}
+
+/// Serialization of a discriminator as a hex string, for use with
+/// `#[serde(with = "spl_pod::discriminator::serde_hex")]`
+#[cfg(feature = "serde-traits")]
+pub mod serde_hex {
+    use {super::Discriminator, serde::de::Error};
+
+    pub fn serialize<S: serde::Serializer>(
+        discriminator: &Discriminator,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let hex = discriminator
+            .as_slice()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Discriminator, D::Error> {
+        let hex = <String as serde::Deserialize>::deserialize(deserializer)?;
+        if hex.len() != Discriminator::LENGTH * 2 || !hex.is_ascii() {
+            return Err(D::Error::custom("invalid discriminator length"));
+        }
+        let mut bytes = [0; Discriminator::LENGTH];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                .map_err(|_| D::Error::custom("invalid discriminator hex"))?;
+        }
+        Ok(Discriminator::new(bytes))
+    }
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const SHA256_INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Byte at `index` of the concatenation of `parts`, followed by the SHA-256
+/// padding for a message of `len` bytes
+const fn padded_byte(parts: &[&[u8]], len: usize, index: usize) -> u8 {
+    if index < len {
+        let mut offset = index;
+        let mut part = 0;
+        while offset >= parts[part].len() {
+            offset -= parts[part].len();
+            part += 1;
+        }
+        parts[part][offset]
+    } else if index == len {
+        0x80
+    } else {
+        let padded_len = (len + 9 + 63) / 64 * 64;
+        let length_offset = padded_len - 8;
+        if index >= length_offset {
+            let bit_len = (len as u64) * 8;
+            (bit_len >> ((7 - (index - length_offset)) * 8)) as u8
+        } else {
+            0
+        }
+    }
+}
+
+/// SHA-256 of the concatenation of `parts`, usable in const contexts
+const fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut len = 0;
+    let mut i = 0;
+    while i < parts.len() {
+        len += parts[i].len();
+        i += 1;
+    }
+    let num_blocks = (len + 9 + 63) / 64;
+
+    let mut state = SHA256_INITIAL_STATE;
+    let mut block = 0;
+    while block < num_blocks {
+        let mut w = [0u32; 64];
+        let mut t = 0;
+        while t < 16 {
+            let base = block * 64 + t * 4;
+            w[t] = u32::from_be_bytes([
+                padded_byte(parts, len, base),
+                padded_byte(parts, len, base + 1),
+                padded_byte(parts, len, base + 2),
+                padded_byte(parts, len, base + 3),
+            ]);
+            t += 1;
+        }
+        while t < 64 {
+            let s0 = w[t - 15].rotate_right(7) ^ w[t - 15].rotate_right(18) ^ (w[t - 15] >> 3);
+            let s1 = w[t - 2].rotate_right(17) ^ w[t - 2].rotate_right(19) ^ (w[t - 2] >> 10);
+            w[t] = w[t - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[t - 7])
+                .wrapping_add(s1);
+            t += 1;
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        t = 0;
+        while t < 64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(SHA256_K[t])
+                .wrapping_add(w[t]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+            t += 1;
+        }
+
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+        state[4] = state[4].wrapping_add(e);
+        state[5] = state[5].wrapping_add(f);
+        state[6] = state[6].wrapping_add(g);
+        state[7] = state[7].wrapping_add(h);
+        block += 1;
+    }
+
+    let mut hash = [0u8; 32];
+    i = 0;
+    while i < 8 {
+        let word = state[i].to_be_bytes();
+        hash[i * 4] = word[0];
+        hash[i * 4 + 1] = word[1];
+        hash[i * 4 + 2] = word[2];
+        hash[i * 4 + 3] = word[3];
+        i += 1;
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_program::hash::hashv};
+
+    const ACCOUNT_MINT: Discriminator = discriminator_from_namespace("account", "Mint");
+
+    #[test]
+    fn test_matches_solana_hash() {
+        for input in [
+            "",
+            "account:Mint",
+            "spl_token_metadata_interface:token_metadata",
+            // multi-block inputs, including the 55/56 byte padding boundary
+            &"a".repeat(55),
+            &"b".repeat(56),
+            &"c".repeat(64),
+            &"d".repeat(200),
+        ] {
+            assert_eq!(
+                sha256(&[input.as_bytes()]),
+                hashv(&[input.as_bytes()]).to_bytes(),
+                "hash mismatch for input of length {}",
+                input.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_namespace() {
+        assert_eq!(ACCOUNT_MINT, discriminator_from_hash_input("account:Mint"));
+        assert_eq!(
+            ACCOUNT_MINT,
+            Discriminator::new_with_hash_input("account:Mint")
+        );
+        assert_ne!(
+            ACCOUNT_MINT,
+            discriminator_from_namespace("account", "Mint2")
+        );
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut data = ACCOUNT_MINT.as_slice().to_vec();
+        assert!(discriminator_matches(&ACCOUNT_MINT, &data));
+        data.extend_from_slice(&[1, 2, 3]);
+        assert!(discriminator_matches(&ACCOUNT_MINT, &data));
+        assert!(!discriminator_matches(&ACCOUNT_MINT, &data[..4]));
+        assert!(!discriminator_matches(&Discriminator::UNINITIALIZED, &data));
+    }
+
+    #[cfg(feature = "serde-traits")]
+    #[test]
+    fn test_discriminator_serde() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Account {
+            #[serde(with = "serde_hex")]
+            discriminator: Discriminator,
+        }
+        let account = Account {
+            discriminator: Discriminator::new([0, 1, 2, 3, 0xfc, 0xfd, 0xfe, 0xff]),
+        };
+
+        let serialized = serde_json::to_string(&account).unwrap();
+        assert_eq!(&serialized, "{\"discriminator\":\"00010203fcfdfeff\"}");
+
+        let deserialized = serde_json::from_str::<Account>(&serialized).unwrap();
+        assert_eq!(account, deserialized);
+
+        assert!(serde_json::from_str::<Account>("{\"discriminator\":\"0001\"}").is_err());
+        assert!(
+            serde_json::from_str::<Account>("{\"discriminator\":\"zz010203fcfdfeff\"}").is_err()
+        );
+    }
+}
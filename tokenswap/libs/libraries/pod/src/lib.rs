@@ -1,6 +1,8 @@
 //! Crate containing `Pod` types and `bytemuck` utils used in SPL
 
+pub mod bitflags;
 pub mod bytemuck;
+pub mod discriminator;
 pub mod error;
 pub mod optional_keys;
 pub mod primitives;
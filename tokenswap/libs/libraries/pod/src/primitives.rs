@@ -57,6 +57,50 @@ macro_rules! impl_int_conversion {
     };
 }
 
+/// Simple macro for implementing arithmetic helpers on Pod* ints, performing
+/// the operation on the standard int and converting back to little-endian
+/// bytes.
+#[macro_export]
+macro_rules! impl_int_arithmetic {
+    ($P:ty, $I:ty) => {
+        impl $P {
+            /// Checked integer addition, returning `None` on overflow
+            pub fn checked_add(self, rhs: $I) -> Option<Self> {
+                <$I>::from(self).checked_add(rhs).map(Self::from)
+            }
+            /// Checked integer subtraction, returning `None` on overflow
+            pub fn checked_sub(self, rhs: $I) -> Option<Self> {
+                <$I>::from(self).checked_sub(rhs).map(Self::from)
+            }
+            /// Checked integer multiplication, returning `None` on overflow
+            pub fn checked_mul(self, rhs: $I) -> Option<Self> {
+                <$I>::from(self).checked_mul(rhs).map(Self::from)
+            }
+            /// Checked integer division, returning `None` if `rhs == 0` or
+            /// on overflow
+            pub fn checked_div(self, rhs: $I) -> Option<Self> {
+                <$I>::from(self).checked_div(rhs).map(Self::from)
+            }
+            /// Saturating integer addition
+            pub fn saturating_add(self, rhs: $I) -> Self {
+                Self::from(<$I>::from(self).saturating_add(rhs))
+            }
+            /// Saturating integer subtraction
+            pub fn saturating_sub(self, rhs: $I) -> Self {
+                Self::from(<$I>::from(self).saturating_sub(rhs))
+            }
+            /// Little-endian bytes of the value
+            pub const fn to_le_bytes(self) -> [u8; std::mem::size_of::<$I>()] {
+                self.0
+            }
+            /// Create from little-endian bytes
+            pub const fn from_le_bytes(bytes: [u8; std::mem::size_of::<$I>()]) -> Self {
+                Self(bytes)
+            }
+        }
+    };
+}
+
 /// `u16` type that can be used in `Pod`s
 #[cfg_attr(feature = "serde-traits", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde-traits", serde(from = "u16", into = "u16"))]
@@ -64,6 +108,7 @@ macro_rules! impl_int_conversion {
 #[repr(transparent)]
 pub struct PodU16([u8; 2]);
 impl_int_conversion!(PodU16, u16);
+impl_int_arithmetic!(PodU16, u16);
 
 /// `i16` type that can be used in Pods
 #[cfg_attr(feature = "serde-traits", derive(Serialize, Deserialize))]
@@ -72,6 +117,7 @@ impl_int_conversion!(PodU16, u16);
 #[repr(transparent)]
 pub struct PodI16([u8; 2]);
 impl_int_conversion!(PodI16, i16);
+impl_int_arithmetic!(PodI16, i16);
 
 /// `u32` type that can be used in `Pod`s
 #[cfg_attr(
@@ -84,6 +130,7 @@ impl_int_conversion!(PodI16, i16);
 #[repr(transparent)]
 pub struct PodU32([u8; 4]);
 impl_int_conversion!(PodU32, u32);
+impl_int_arithmetic!(PodU32, u32);
 
 /// `u64` type that can be used in Pods
 #[cfg_attr(
@@ -96,6 +143,7 @@ impl_int_conversion!(PodU32, u32);
 #[repr(transparent)]
 pub struct PodU64([u8; 8]);
 impl_int_conversion!(PodU64, u64);
+impl_int_arithmetic!(PodU64, u64);
 
 /// `i64` type that can be used in Pods
 #[cfg_attr(feature = "serde-traits", derive(Serialize, Deserialize))]
@@ -104,6 +152,7 @@ impl_int_conversion!(PodU64, u64);
 #[repr(transparent)]
 pub struct PodI64([u8; 8]);
 impl_int_conversion!(PodI64, i64);
+impl_int_arithmetic!(PodI64, i64);
 
 #[cfg(test)]
 mod tests {
@@ -198,6 +247,36 @@ mod tests {
         assert_eq!(pod_u64, deserialized);
     }
 
+    #[test]
+    fn test_pod_u64_arithmetic() {
+        let pod_u64 = PodU64::from(10);
+        assert_eq!(pod_u64.checked_add(5), Some(PodU64::from(15)));
+        assert_eq!(pod_u64.checked_sub(11), None);
+        assert_eq!(pod_u64.checked_mul(3), Some(PodU64::from(30)));
+        assert_eq!(pod_u64.checked_div(0), None);
+        assert_eq!(PodU64::from(u64::MAX).checked_add(1), None);
+        assert_eq!(
+            PodU64::from(u64::MAX).saturating_add(1),
+            PodU64::from(u64::MAX)
+        );
+        assert_eq!(pod_u64.saturating_sub(11), PodU64::from(0));
+        assert_eq!(pod_u64.to_le_bytes(), 10u64.to_le_bytes());
+        assert_eq!(PodU64::from_le_bytes(10u64.to_le_bytes()), pod_u64);
+    }
+
+    #[test]
+    fn test_pod_i64_arithmetic() {
+        let pod_i64 = PodI64::from(-10);
+        assert_eq!(pod_i64.checked_add(5), Some(PodI64::from(-5)));
+        assert_eq!(pod_i64.checked_sub(i64::MAX), None);
+        assert_eq!(pod_i64.checked_mul(-2), Some(PodI64::from(20)));
+        assert_eq!(PodI64::from(i64::MIN).checked_div(-1), None);
+        assert_eq!(
+            PodI64::from(i64::MIN).saturating_sub(1),
+            PodI64::from(i64::MIN)
+        );
+    }
+
     #[test]
     fn test_pod_i64() {
         assert!(pod_from_bytes::<PodI64>(&[]).is_err());
@@ -0,0 +1,117 @@
+//! Batched derivation of associated token account addresses
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use {
+    solana_sdk::pubkey::Pubkey,
+    spl_associated_token_account::get_associated_token_address_with_program_id,
+    std::{collections::HashMap, sync::Mutex},
+};
+
+/// Associated token account addresses of the owners for the mint, in order.
+///
+/// Each derivation searches for a bump seed, so large batches are derived in
+/// parallel when the `rayon` feature is enabled.
+pub fn derive_atas(owners: &[Pubkey], mint: &Pubkey, program_id: &Pubkey) -> Vec<Pubkey> {
+    let derive =
+        |owner: &Pubkey| get_associated_token_address_with_program_id(owner, mint, program_id);
+    #[cfg(feature = "rayon")]
+    {
+        owners.par_iter().map(derive).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        owners.iter().map(derive).collect()
+    }
+}
+
+/// Memoized associated token account addresses, for planners deriving the
+/// same owners' addresses over and over
+#[derive(Debug, Default)]
+pub struct AtaCache {
+    addresses: Mutex<HashMap<(Pubkey, Pubkey, Pubkey), Pubkey>>,
+}
+
+impl AtaCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Like `derive_atas`, only deriving the addresses not seen before
+    pub fn derive(&self, owners: &[Pubkey], mint: &Pubkey, program_id: &Pubkey) -> Vec<Pubkey> {
+        let key = |owner: &Pubkey| (*owner, *mint, *program_id);
+        // copied out under the lock, so a concurrent `clear` cannot take them
+        // away before they are returned
+        let known = {
+            let addresses = self.addresses.lock().unwrap();
+            owners
+                .iter()
+                .map(|owner| addresses.get(&key(owner)).copied())
+                .collect::<Vec<_>>()
+        };
+        let mut missing = owners
+            .iter()
+            .zip(&known)
+            .filter(|(_, address)| address.is_none())
+            .map(|(owner, _)| *owner)
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+        missing.dedup();
+
+        // derived without holding the lock
+        let derived = missing
+            .iter()
+            .copied()
+            .zip(derive_atas(&missing, mint, program_id))
+            .collect::<HashMap<_, _>>();
+        self.addresses.lock().unwrap().extend(
+            derived
+                .iter()
+                .map(|(owner, address)| (key(owner), *address)),
+        );
+        owners
+            .iter()
+            .zip(known)
+            .map(|(owner, address)| address.unwrap_or_else(|| derived[owner]))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.addresses.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_atas_memoized() {
+        let mint = Pubkey::new_unique();
+        let program_id = spl_token_2022::id();
+        let owners = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let expected = owners
+            .iter()
+            .map(|owner| get_associated_token_address_with_program_id(owner, &mint, &program_id))
+            .collect::<Vec<_>>();
+        assert_eq!(derive_atas(&owners, &mint, &program_id), expected);
+
+        let cache = AtaCache::new();
+        let repeated = [owners[0], owners[1], owners[0]];
+        assert_eq!(
+            cache.derive(&repeated, &mint, &program_id),
+            [expected[0], expected[1], expected[0]]
+        );
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.derive(&owners, &mint, &program_id), expected);
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,358 @@
+//! Audit packages of confidential token accounts, for regulated issuers
+//! exporting what the mint's auditor can see
+
+use {
+    crate::client::TransactionRecord,
+    serde::{Deserialize, Serialize},
+    solana_sdk::{
+        clock::{Slot, UnixTimestamp},
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::Signature,
+        signer::{Signer, SignerError},
+    },
+    spl_token_2022::{
+        extension::confidential_transfer::{
+            instruction::{
+                ConfidentialTransferInstruction, DepositInstructionData, TransferInstructionData,
+                WithdrawInstructionData,
+            },
+            PENDING_BALANCE_LO_BIT_LENGTH,
+        },
+        instruction::{decode_instruction_data, decode_instruction_type, TokenInstruction},
+        proof::decode_proof_instruction_context,
+        solana_zk_token_sdk::{
+            encryption::elgamal::{ElGamalCiphertext, ElGamalKeypair},
+            instruction::transfer::{
+                TransferData, TransferProofContext, TransferWithFeeData,
+                TransferWithFeeProofContext,
+            },
+            zk_token_elgamal::pod::{
+                ElGamalCiphertext as PodElGamalCiphertext, ElGamalPubkey as PodElGamalPubkey,
+                TransferAmountCiphertext,
+            },
+            zk_token_proof_instruction::ProofInstruction,
+        },
+    },
+};
+
+/// Kind of a confidential balance change of an audited account
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEntryKind {
+    Deposit,
+    Withdraw,
+    TransferIn,
+    TransferOut,
+}
+
+/// Confidential balance change of an audited account
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub signature: Signature,
+    pub slot: Slot,
+    pub block_time: Option<UnixTimestamp>,
+    pub kind: AuditEntryKind,
+    /// Other account of a transfer
+    pub counterparty: Option<Pubkey>,
+    /// Amount, gross of any transfer fee. `None` for transfers the auditor
+    /// keys could not decrypt, e.g. those proven with context state accounts,
+    /// which are closed once used.
+    pub amount: Option<u64>,
+    /// Net change of the confidential balance since the start of the range.
+    /// Unknown from the first entry whose amount could not be decrypted on.
+    pub running_balance_change: Option<i128>,
+}
+
+/// Audit trail of one confidential account
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountAudit {
+    pub address: Pubkey,
+    pub owner: Pubkey,
+    /// Whether the account is approved for confidential transfers
+    pub approved: bool,
+    /// ElGamal public key of the account, base64-encoded
+    pub elgamal_pubkey: String,
+    /// Balance changes, oldest first
+    pub entries: Vec<AuditEntry>,
+}
+
+impl AccountAudit {
+    /// Number of entries whose amount could not be decrypted
+    pub fn undecrypted(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.amount.is_none())
+            .count()
+    }
+}
+
+/// Audit trail of confidential accounts of a mint over a range of slots
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditPackage {
+    pub program_id: Pubkey,
+    pub mint: Pubkey,
+    pub decimals: u8,
+    /// Auditor ElGamal public key currently configured on the mint,
+    /// base64-encoded
+    pub auditor_elgamal_pubkey: Option<String>,
+    /// First slot of the range
+    pub from_slot: Slot,
+    /// Slot after the last one of the range
+    pub to_slot: Slot,
+    /// Slot observed when the package was assembled
+    pub generated_at_slot: Slot,
+    pub accounts: Vec<AccountAudit>,
+}
+
+impl AuditPackage {
+    /// Bytes covered by the signature: the package serialized as JSON
+    pub fn message(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("audit packages always serialize")
+    }
+
+    pub fn sign(self, signer: &dyn Signer) -> Result<SignedAuditPackage, SignerError> {
+        let signature = signer.try_sign_message(&self.message())?;
+        Ok(SignedAuditPackage {
+            signer: signer.try_pubkey()?,
+            signature,
+            package: self,
+        })
+    }
+}
+
+/// Audit package signed by its issuer
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedAuditPackage {
+    pub package: AuditPackage,
+    pub signer: Pubkey,
+    pub signature: Signature,
+}
+
+impl SignedAuditPackage {
+    /// Whether the signature is valid for the package and the signer
+    pub fn verify(&self) -> bool {
+        self.signature
+            .verify(self.signer.as_ref(), &self.package.message())
+    }
+}
+
+/// Confidential balance changes of `account` in a successful transaction,
+/// without running balances.
+///
+/// Only top-level instructions are decoded, since proofs are located
+/// relative to them.
+pub(crate) fn decode_audit_entries(
+    program_id: &Pubkey,
+    account: &Pubkey,
+    record: &TransactionRecord,
+    auditor_keys: &[&ElGamalKeypair],
+) -> Vec<AuditEntry> {
+    if record.err.is_some() {
+        return vec![];
+    }
+    let mut entries = vec![];
+    for (index, instruction) in record.instructions.iter().enumerate() {
+        if instruction.program_id != *program_id
+            || !matches!(
+                TokenInstruction::unpack(&instruction.data),
+                Ok(TokenInstruction::ConfidentialTransferExtension)
+            )
+        {
+            continue;
+        }
+        let data = &instruction.data[1..];
+        let key = |position: usize| instruction.accounts.get(position).map(|meta| meta.pubkey);
+        let mut entry = |kind, counterparty, amount: Option<u64>| {
+            entries.push(AuditEntry {
+                signature: record.signature,
+                slot: record.slot,
+                block_time: record.block_time,
+                kind,
+                counterparty,
+                amount,
+                running_balance_change: None,
+            })
+        };
+
+        match decode_instruction_type(data) {
+            Ok(ConfidentialTransferInstruction::Deposit) if key(0) == Some(*account) => {
+                if let Ok(deposit) = decode_instruction_data::<DepositInstructionData>(data) {
+                    entry(AuditEntryKind::Deposit, None, Some(deposit.amount.into()));
+                }
+            }
+            Ok(ConfidentialTransferInstruction::Withdraw) if key(0) == Some(*account) => {
+                if let Ok(withdraw) = decode_instruction_data::<WithdrawInstructionData>(data) {
+                    entry(AuditEntryKind::Withdraw, None, Some(withdraw.amount.into()));
+                }
+            }
+            Ok(
+                transfer @ (ConfidentialTransferInstruction::Transfer
+                | ConfidentialTransferInstruction::TransferWithSplitProofs),
+            ) => {
+                let (source, destination) = (key(0), key(2));
+                if source != Some(*account) && destination != Some(*account) {
+                    continue;
+                }
+                let amount = match transfer {
+                    ConfidentialTransferInstruction::Transfer => {
+                        decode_instruction_data::<TransferInstructionData>(data)
+                            .ok()
+                            .and_then(|transfer| {
+                                let proof_index = (index as i64)
+                                    .checked_add(i64::from(transfer.proof_instruction_offset))?;
+                                record.instructions.get(usize::try_from(proof_index).ok()?)
+                            })
+                            .and_then(|proof| decrypt_transfer_amount(proof, auditor_keys))
+                    }
+                    _ => None,
+                };
+                if source == Some(*account) {
+                    entry(AuditEntryKind::TransferOut, destination, amount);
+                }
+                if destination == Some(*account) {
+                    entry(AuditEntryKind::TransferIn, source, amount);
+                }
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Decrypt the amount of an inline transfer proof with whichever auditor key
+/// it was encrypted for
+fn decrypt_transfer_amount(proof: &Instruction, auditor_keys: &[&ElGamalKeypair]) -> Option<u64> {
+    let (auditor, ciphertext_lo, ciphertext_hi) =
+        match ProofInstruction::instruction_type(&proof.data)? {
+            ProofInstruction::VerifyTransfer => {
+                let context =
+                    decode_proof_instruction_context::<TransferData, TransferProofContext>(
+                        ProofInstruction::VerifyTransfer,
+                        proof,
+                    )
+                    .ok()?;
+                (
+                    context.transfer_pubkeys.auditor,
+                    context.ciphertext_lo,
+                    context.ciphertext_hi,
+                )
+            }
+            ProofInstruction::VerifyTransferWithFee => {
+                let context = decode_proof_instruction_context::<
+                    TransferWithFeeData,
+                    TransferWithFeeProofContext,
+                >(ProofInstruction::VerifyTransferWithFee, proof)
+                .ok()?;
+                (
+                    context.transfer_with_fee_pubkeys.auditor,
+                    context.ciphertext_lo,
+                    context.ciphertext_hi,
+                )
+            }
+            _ => return None,
+        };
+    // decrypting with the wrong key would search the whole 32-bit range
+    let keypair = auditor_keys
+        .iter()
+        .find(|keypair| PodElGamalPubkey::from(*keypair.pubkey()) == auditor)?;
+
+    let decrypt = |ciphertext: &TransferAmountCiphertext| {
+        // the commitment, then the handles of the source, destination and
+        // auditor
+        let bytes = bytemuck::bytes_of(ciphertext);
+        let mut auditor_ciphertext = [0; 64];
+        auditor_ciphertext[..32].copy_from_slice(&bytes[..32]);
+        auditor_ciphertext[32..].copy_from_slice(&bytes[96..128]);
+        let auditor_ciphertext: ElGamalCiphertext =
+            PodElGamalCiphertext(auditor_ciphertext).try_into().ok()?;
+        keypair.secret().decrypt_u32(&auditor_ciphertext)
+    };
+    let amount_lo = decrypt(&ciphertext_lo)?;
+    let amount_hi = decrypt(&ciphertext_hi)?;
+    amount_hi
+        .checked_shl(PENDING_BALANCE_LO_BIT_LENGTH)?
+        .checked_add(amount_lo)
+}
+
+/// Fill in the running balance changes of entries sorted oldest first. An
+/// undecrypted amount leaves them unknown from its entry on, rather than
+/// silently counting it as zero.
+pub(crate) fn accumulate(entries: &mut [AuditEntry]) {
+    let mut balance_change = Some(0i128);
+    for entry in entries {
+        balance_change = balance_change
+            .zip(entry.amount)
+            .map(|(balance_change, amount)| {
+                let amount = i128::from(amount);
+                match entry.kind {
+                    AuditEntryKind::Deposit | AuditEntryKind::TransferIn => balance_change + amount,
+                    AuditEntryKind::Withdraw | AuditEntryKind::TransferOut => {
+                        balance_change - amount
+                    }
+                }
+            });
+        entry.running_balance_change = balance_change;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::signature::Keypair};
+
+    #[test]
+    fn test_sign_and_verify() {
+        let entry = |kind, amount| AuditEntry {
+            signature: Signature::new_unique(),
+            slot: 1,
+            block_time: None,
+            kind,
+            counterparty: None,
+            amount,
+            running_balance_change: None,
+        };
+        let mut entries = vec![
+            entry(AuditEntryKind::Deposit, Some(100)),
+            entry(AuditEntryKind::TransferOut, Some(30)),
+            entry(AuditEntryKind::TransferIn, None),
+            entry(AuditEntryKind::Withdraw, Some(20)),
+        ];
+        accumulate(&mut entries);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.running_balance_change)
+                .collect::<Vec<_>>(),
+            [Some(100), Some(70), None, None]
+        );
+
+        let package = AuditPackage {
+            program_id: spl_token_2022::id(),
+            mint: Pubkey::new_unique(),
+            decimals: 6,
+            auditor_elgamal_pubkey: None,
+            from_slot: 0,
+            to_slot: 10,
+            generated_at_slot: 12,
+            accounts: vec![AccountAudit {
+                address: Pubkey::new_unique(),
+                owner: Pubkey::new_unique(),
+                approved: true,
+                elgamal_pubkey: String::new(),
+                entries,
+            }],
+        };
+        assert_eq!(package.accounts[0].undecrypted(), 1);
+
+        let issuer = Keypair::new();
+        let signed = package.sign(&issuer).unwrap();
+        assert!(signed.verify());
+
+        // still valid once serialized, and tampering is detected
+        let serialized = serde_json::to_string(&signed).unwrap();
+        let mut deserialized: SignedAuditPackage = serde_json::from_str(&serialized).unwrap();
+        assert!(deserialized.verify());
+        deserialized.package.accounts[0].entries[0].amount = Some(1_000);
+        assert!(!deserialized.verify());
+    }
+}
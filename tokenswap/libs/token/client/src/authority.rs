@@ -0,0 +1,143 @@
+//! Authorities of mints and token accounts, including those of extensions
+
+use {
+    solana_sdk::pubkey::Pubkey,
+    spl_token_2022::{
+        extension::{BaseStateWithExtensions, ExtensionType},
+        instruction::AuthorityType,
+        state::Mint,
+    },
+};
+
+/// Every authority that can be changed on a mint or token account.
+///
+/// Most are changed with `SetAuthority`, but the token metadata and token
+/// group update authorities are changed through their own interfaces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Authority {
+    MintTokens,
+    FreezeAccount,
+    AccountOwner {
+        account: Pubkey,
+    },
+    CloseAccount {
+        account: Pubkey,
+    },
+    TransferFeeConfig,
+    WithheldWithdraw,
+    CloseMint,
+    InterestRate,
+    PermanentDelegate,
+    ConfidentialTransferMint,
+    TransferHookProgramId,
+    ConfidentialTransferFeeConfig,
+    MetadataPointer,
+    GroupPointer,
+    GroupMemberPointer,
+    /// Update authority of the token metadata stored in the mint
+    TokenMetadata,
+    /// Update authority of the token group stored in the mint
+    TokenGroup,
+}
+
+impl Authority {
+    /// The `SetAuthority` type changing this authority, if any
+    pub fn authority_type(&self) -> Option<AuthorityType> {
+        match self {
+            Self::MintTokens => Some(AuthorityType::MintTokens),
+            Self::FreezeAccount => Some(AuthorityType::FreezeAccount),
+            Self::AccountOwner { .. } => Some(AuthorityType::AccountOwner),
+            Self::CloseAccount { .. } => Some(AuthorityType::CloseAccount),
+            Self::TransferFeeConfig => Some(AuthorityType::TransferFeeConfig),
+            Self::WithheldWithdraw => Some(AuthorityType::WithheldWithdraw),
+            Self::CloseMint => Some(AuthorityType::CloseMint),
+            Self::InterestRate => Some(AuthorityType::InterestRate),
+            Self::PermanentDelegate => Some(AuthorityType::PermanentDelegate),
+            Self::ConfidentialTransferMint => Some(AuthorityType::ConfidentialTransferMint),
+            Self::TransferHookProgramId => Some(AuthorityType::TransferHookProgramId),
+            Self::ConfidentialTransferFeeConfig => {
+                Some(AuthorityType::ConfidentialTransferFeeConfig)
+            }
+            Self::MetadataPointer => Some(AuthorityType::MetadataPointer),
+            Self::GroupPointer => Some(AuthorityType::GroupPointer),
+            Self::GroupMemberPointer => Some(AuthorityType::GroupMemberPointer),
+            Self::TokenMetadata | Self::TokenGroup => None,
+        }
+    }
+
+    /// Extension of the mint holding this authority, if any
+    pub fn required_extension(&self) -> Option<ExtensionType> {
+        match self {
+            Self::MintTokens
+            | Self::FreezeAccount
+            | Self::AccountOwner { .. }
+            | Self::CloseAccount { .. } => None,
+            Self::TransferFeeConfig | Self::WithheldWithdraw => {
+                Some(ExtensionType::TransferFeeConfig)
+            }
+            Self::CloseMint => Some(ExtensionType::MintCloseAuthority),
+            Self::InterestRate => Some(ExtensionType::InterestBearingConfig),
+            Self::PermanentDelegate => Some(ExtensionType::PermanentDelegate),
+            Self::ConfidentialTransferMint => Some(ExtensionType::ConfidentialTransferMint),
+            Self::TransferHookProgramId => Some(ExtensionType::TransferHook),
+            Self::ConfidentialTransferFeeConfig => {
+                Some(ExtensionType::ConfidentialTransferFeeConfig)
+            }
+            Self::MetadataPointer => Some(ExtensionType::MetadataPointer),
+            Self::GroupPointer => Some(ExtensionType::GroupPointer),
+            Self::GroupMemberPointer => Some(ExtensionType::GroupMemberPointer),
+            Self::TokenMetadata => Some(ExtensionType::TokenMetadata),
+            Self::TokenGroup => Some(ExtensionType::TokenGroup),
+        }
+    }
+
+    /// The token account holding this authority, or `None` for the mint
+    pub fn account(&self) -> Option<&Pubkey> {
+        match self {
+            Self::AccountOwner { account } | Self::CloseAccount { account } => Some(account),
+            _ => None,
+        }
+    }
+
+    /// Whether a mint with the given extensions has this authority
+    pub fn is_supported_by<S: BaseStateWithExtensions<Mint>>(&self, mint: &S) -> bool {
+        match self.required_extension() {
+            Some(extension_type) => mint
+                .get_extension_types()
+                .map(|extension_types| extension_types.contains(&extension_type))
+                .unwrap_or(false),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        spl_token_2022::extension::{
+            mint_close_authority::MintCloseAuthority, transfer_hook::TransferHook,
+            StateWithExtensionsMut,
+        },
+    };
+
+    #[test]
+    fn test_supported_by_mint_extensions() {
+        let space = ExtensionType::try_calculate_account_len::<Mint>(&[
+            ExtensionType::MintCloseAuthority,
+            ExtensionType::TransferHook,
+        ])
+        .unwrap();
+        let mut data = vec![0; space];
+        let mut mint = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        mint.init_extension::<MintCloseAuthority>(true).unwrap();
+        mint.init_extension::<TransferHook>(true).unwrap();
+
+        assert!(Authority::MintTokens.is_supported_by(&mint));
+        assert!(Authority::CloseMint.is_supported_by(&mint));
+        assert!(Authority::TransferHookProgramId.is_supported_by(&mint));
+        assert!(!Authority::InterestRate.is_supported_by(&mint));
+        assert!(!Authority::TokenMetadata.is_supported_by(&mint));
+        assert_eq!(Authority::TokenGroup.authority_type(), None);
+    }
+}
@@ -0,0 +1,210 @@
+//! Packing of many small operations into as few transactions as possible
+
+use {
+    crate::token::{TokenError, TokenResult},
+    solana_sdk::{
+        instruction::Instruction, message::Message, packet::PACKET_DATA_SIZE, pubkey::Pubkey,
+    },
+    std::ops::Range,
+};
+
+/// Bytes of a transaction left free when packing, for the memo and compute
+/// budget instructions added when it is built
+pub const TRANSACTION_SIZE_MARGIN: usize = 128;
+
+/// Serialized size of a transaction of the instructions, with its signatures
+pub fn transaction_size(instructions: &[Instruction], payer: &Pubkey) -> usize {
+    let message = Message::new(instructions, Some(payer));
+    let signatures = usize::from(message.header.num_required_signatures);
+    // the signature count is a short vector length, one byte below 128
+    1 + signatures * 64 + bincode::serialized_size(&message).unwrap_or(u64::MAX) as usize
+}
+
+/// Split groups of instructions into consecutive runs, each fitting in one
+/// transaction. Groups are never split, so a group too large on its own
+/// still gets a run, and fails when sent.
+pub fn pack(groups: &[Vec<Instruction>], payer: &Pubkey) -> Vec<Range<usize>> {
+    let max_size = PACKET_DATA_SIZE - TRANSACTION_SIZE_MARGIN;
+    let mut runs = vec![];
+    let mut start = 0;
+    let mut instructions: Vec<Instruction> = vec![];
+    for (index, group) in groups.iter().enumerate() {
+        let run_len = instructions.len();
+        instructions.extend_from_slice(group);
+        if index > start && transaction_size(&instructions, payer) > max_size {
+            runs.push(start..index);
+            start = index;
+            instructions.drain(..run_len);
+        }
+    }
+    if start < groups.len() {
+        runs.push(start..groups.len());
+    }
+    runs
+}
+
+/// Split items into consecutive runs, each as long as fits in a transaction
+/// of the one instruction built for it, e.g. for instructions taking any
+/// number of source accounts. Every run has at least one item.
+pub fn chunk<E>(
+    len: usize,
+    payer: &Pubkey,
+    instruction: impl Fn(Range<usize>) -> Result<Instruction, E>,
+) -> Result<Vec<Range<usize>>, E> {
+    let max_size = PACKET_DATA_SIZE - TRANSACTION_SIZE_MARGIN;
+    let mut runs = vec![];
+    let mut start = 0;
+    while start < len {
+        let mut end = start + 1;
+        while end < len && transaction_size(&[instruction(start..end + 1)?], payer) <= max_size {
+            end += 1;
+        }
+        runs.push(start..end);
+        start = end;
+    }
+    Ok(runs)
+}
+
+/// Outcome of a batched operation: the result of every transaction sent, and
+/// which transaction carried each item
+#[derive(Debug)]
+pub struct BatchOutcome<O> {
+    pub transactions: Vec<TokenResult<O>>,
+    /// Index in `transactions` of the transaction carrying each item, in the
+    /// order of the items
+    pub item_transactions: Vec<usize>,
+}
+
+impl<O> BatchOutcome<O> {
+    pub fn new(transactions: Vec<TokenResult<O>>, runs: &[Range<usize>]) -> Self {
+        let item_transactions = runs
+            .iter()
+            .enumerate()
+            .flat_map(|(transaction, run)| run.clone().map(move |_| transaction))
+            .collect();
+        Self {
+            transactions,
+            item_transactions,
+        }
+    }
+
+    /// Outcome for the items of runs, from an outcome whose items are the
+    /// runs, e.g. the chunks of `chunk`
+    pub fn for_runs(self, runs: &[Range<usize>]) -> Self {
+        let item_transactions = runs
+            .iter()
+            .zip(&self.item_transactions)
+            .flat_map(|(run, transaction)| run.clone().map(move |_| *transaction))
+            .collect();
+        Self {
+            transactions: self.transactions,
+            item_transactions,
+        }
+    }
+
+    /// Result of the transaction that carried the item
+    pub fn item(&self, index: usize) -> Result<&O, &TokenError> {
+        self.transactions[self.item_transactions[index]].as_ref()
+    }
+
+    /// Items whose transaction failed, e.g. to retry them
+    pub fn failed_items(&self) -> Vec<usize> {
+        (0..self.item_transactions.len())
+            .filter(|index| self.item(*index).is_err())
+            .collect()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.transactions.iter().all(Result::is_ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::instruction::AccountMeta};
+
+    #[test]
+    fn test_pack() {
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let groups = (0..40)
+            .map(|_| {
+                vec![Instruction::new_with_bytes(
+                    program_id,
+                    &[0; 8],
+                    vec![AccountMeta::new(Pubkey::new_unique(), false)],
+                )]
+            })
+            .collect::<Vec<_>>();
+
+        let runs = pack(&groups, &payer);
+        assert!(runs.len() > 1);
+        assert_eq!(runs.first().unwrap().start, 0);
+        assert_eq!(runs.last().unwrap().end, groups.len());
+        for (run, next) in runs.iter().zip(runs.iter().skip(1)) {
+            assert_eq!(run.end, next.start);
+        }
+        for run in &runs {
+            let instructions = groups[run.clone()].concat();
+            assert!(
+                transaction_size(&instructions, &payer)
+                    <= PACKET_DATA_SIZE - TRANSACTION_SIZE_MARGIN
+            );
+        }
+        assert!(pack(&[], &payer).is_empty());
+    }
+
+    #[test]
+    fn test_chunk() {
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let accounts = (0..100).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let instruction = |run: Range<usize>| {
+            Ok::<_, ()>(Instruction::new_with_bytes(
+                program_id,
+                &[0; 8],
+                accounts[run]
+                    .iter()
+                    .map(|account| AccountMeta::new(*account, false))
+                    .collect(),
+            ))
+        };
+
+        let runs = chunk(accounts.len(), &payer, instruction).unwrap();
+        assert!(runs.len() > 1);
+        assert_eq!(runs.first().unwrap().start, 0);
+        assert_eq!(runs.last().unwrap().end, accounts.len());
+        for (run, next) in runs.iter().zip(runs.iter().skip(1)) {
+            assert_eq!(run.end, next.start);
+            // each run but the last is as long as fits
+            assert!(
+                transaction_size(&[instruction(run.start..run.end + 1).unwrap()], &payer)
+                    > PACKET_DATA_SIZE - TRANSACTION_SIZE_MARGIN
+            );
+        }
+        for run in &runs {
+            assert!(
+                transaction_size(&[instruction(run.clone()).unwrap()], &payer)
+                    <= PACKET_DATA_SIZE - TRANSACTION_SIZE_MARGIN
+            );
+        }
+        assert_eq!(chunk(0, &payer, instruction), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_outcome() {
+        let outcome = BatchOutcome::new(
+            vec![Ok(1), Err(TokenError::AccountNotFound), Ok(3)],
+            &[0..2, 2..3, 3..5],
+        );
+        assert_eq!(outcome.item_transactions, [0, 0, 1, 2, 2]);
+        assert_eq!(outcome.item(1), Ok(&1));
+        assert_eq!(outcome.item(2), Err(&TokenError::AccountNotFound));
+        assert_eq!(outcome.failed_items(), [2]);
+        assert!(!outcome.is_success());
+
+        let outcome = outcome.for_runs(&[0..1, 1..4, 4..5, 5..7, 7..8]);
+        assert_eq!(outcome.item_transactions, [0, 0, 0, 0, 0, 1, 1, 2]);
+        assert_eq!(outcome.failed_items(), [5, 6]);
+    }
+}
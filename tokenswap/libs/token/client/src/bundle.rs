@@ -0,0 +1,178 @@
+//! Submission of transaction bundles that land atomically in one block
+
+use {
+    crate::client::ProgramClientResult,
+    async_trait::async_trait,
+    base64::{prelude::BASE64_STANDARD, Engine},
+    serde_json::{json, Value},
+    solana_rpc_client::nonblocking::rpc_client::RpcClient,
+    solana_rpc_client_api::request::RpcRequest,
+    solana_sdk::{
+        instruction::Instruction, pubkey::Pubkey, system_instruction, transaction::Transaction,
+    },
+    std::{fmt, sync::Arc},
+};
+
+/// Maximum number of transactions in a bundle accepted by block builders
+pub const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+
+/// Endpoint accepting groups of transactions that are executed in order in
+/// the same block, or not at all
+#[async_trait]
+pub trait BundleSender: Send + Sync {
+    /// Submit the bundle, returning the identifier given by the endpoint.
+    ///
+    /// Acceptance does not mean the bundle landed, the transactions must still
+    /// be confirmed.
+    async fn send_bundle(&self, transactions: &[Transaction]) -> ProgramClientResult<String>;
+
+    /// Instruction paying the block builder, sent in a transaction of its own
+    /// at the end of every bundle. Not paid when the transactions are sent
+    /// one by one instead.
+    fn tip_instruction(&self, _payer: &Pubkey) -> Option<Instruction> {
+        None
+    }
+}
+
+/// Bundle sender for block-builder endpoints implementing the `sendBundle`
+/// JSON-RPC method
+pub struct RpcBundleSender {
+    client: Arc<RpcClient>,
+    tip: Option<(Pubkey, u64)>,
+}
+
+impl fmt::Debug for RpcBundleSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RpcBundleSender")
+            .field("url", &self.client.url())
+            .field("tip", &self.tip)
+            .finish()
+    }
+}
+
+impl RpcBundleSender {
+    pub fn new(client: Arc<RpcClient>) -> Self {
+        Self { client, tip: None }
+    }
+
+    /// Tip `lamports` to `tip_account` with every bundle, as most block
+    /// builders require
+    pub fn with_tip(mut self, tip_account: Pubkey, lamports: u64) -> Self {
+        self.tip = Some((tip_account, lamports));
+        self
+    }
+}
+
+#[async_trait]
+impl BundleSender for RpcBundleSender {
+    async fn send_bundle(&self, transactions: &[Transaction]) -> ProgramClientResult<String> {
+        if transactions.is_empty() || transactions.len() > MAX_BUNDLE_TRANSACTIONS {
+            return Err(format!(
+                "Cannot send bundle: {} transactions, expected 1 to {}",
+                transactions.len(),
+                MAX_BUNDLE_TRANSACTIONS
+            )
+            .into());
+        }
+        if !transactions.iter().all(Transaction::is_signed) {
+            return Err("Cannot send bundle: not fully signed".into());
+        }
+
+        let encoded = transactions
+            .iter()
+            .map(|transaction| {
+                bincode::serialize(transaction).map(|bytes| BASE64_STANDARD.encode(bytes))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let bundle_id: Value = self
+            .client
+            .send(
+                RpcRequest::Custom {
+                    method: "sendBundle",
+                },
+                json!([encoded, { "encoding": "base64" }]),
+            )
+            .await?;
+        bundle_id
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("Unexpected sendBundle response: {}", bundle_id).into())
+    }
+
+    fn tip_instruction(&self, payer: &Pubkey) -> Option<Instruction> {
+        self.tip.map(|(tip_account, lamports)| {
+            system_instruction::transfer(payer, &tip_account, lamports)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{client::ProgramRpcClientSendTransaction, mock::MockProgramClient, token::Token},
+        solana_program_test::tokio,
+        solana_sdk::signature::{Keypair, Signer},
+    };
+
+    /// Block builder rejecting every bundle
+    struct RejectingBundleSender {
+        tip_account: Pubkey,
+    }
+
+    #[async_trait]
+    impl BundleSender for RejectingBundleSender {
+        async fn send_bundle(&self, _transactions: &[Transaction]) -> ProgramClientResult<String> {
+            Err("bundles are not accepted".into())
+        }
+
+        fn tip_instruction(&self, payer: &Pubkey) -> Option<Instruction> {
+            Some(system_instruction::transfer(
+                payer,
+                &self.tip_account,
+                1_000,
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_pays_no_tip() {
+        let client = Arc::new(MockProgramClient::<ProgramRpcClientSendTransaction>::new());
+        let tip_account = Pubkey::new_unique();
+        let token = Token::new(
+            client.clone(),
+            &spl_token_2022::id(),
+            &Pubkey::new_unique(),
+            None,
+            Arc::new(Keypair::new()),
+        )
+        .with_bundle_sender(Arc::new(RejectingBundleSender { tip_account }));
+
+        let authority = Keypair::new();
+        let first = [system_instruction::transfer(
+            &authority.pubkey(),
+            &Pubkey::new_unique(),
+            1,
+        )];
+        let second = [system_instruction::transfer(
+            &authority.pubkey(),
+            &Pubkey::new_unique(),
+            2,
+        )];
+        let signatures = token
+            .process_ix_bundle(&[(&first[..], &[&authority]), (&second[..], &[&authority])])
+            .await
+            .unwrap();
+
+        let sent = client.sent_transactions();
+        assert_eq!(
+            sent.iter()
+                .map(|transaction| transaction.signatures[0])
+                .collect::<Vec<_>>(),
+            signatures
+        );
+        assert!(sent
+            .iter()
+            .all(|transaction| !transaction.message.account_keys.contains(&tip_account)));
+    }
+}
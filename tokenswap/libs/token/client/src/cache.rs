@@ -0,0 +1,463 @@
+//! Program client caching reads on hot paths
+
+use {
+    crate::client::{
+        AccountOverrides, ProgramClient, ProgramClientResult, ReadConsistency, SendTransaction,
+        SignatureStatus, SimulateTransaction, TransactionRecord,
+    },
+    async_trait::async_trait,
+    solana_rpc_client_api::filter::RpcFilterType,
+    solana_sdk::{
+        account::Account,
+        clock::{Clock, Slot},
+        commitment_config::CommitmentConfig,
+        epoch_info::EpochInfo,
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::Transaction,
+    },
+    std::{
+        collections::HashMap,
+        fmt,
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+};
+
+/// Default time an account stays cached, about one slot
+pub const DEFAULT_ACCOUNT_TTL: Duration = Duration::from_millis(400);
+/// Default time a rent-exempt minimum stays cached
+pub const DEFAULT_RENT_TTL: Duration = Duration::from_secs(3_600);
+/// Default time a blockhash stays cached
+pub const DEFAULT_BLOCKHASH_TTL: Duration = Duration::from_secs(1);
+
+struct Cached<V> {
+    value: V,
+    fetched_at: Instant,
+}
+
+impl<V: Clone> Cached<V> {
+    fn new(value: V) -> Self {
+        Self {
+            value,
+            fetched_at: Instant::now(),
+        }
+    }
+
+    fn fresh(&self, ttl: Duration) -> Option<V> {
+        (self.fetched_at.elapsed() < ttl).then(|| self.value.clone())
+    }
+}
+
+/// Wraps a program client, caching accounts, rent-exempt minimums and the
+/// latest blockhash for configurable times.
+///
+/// Accounts written by a transaction sent through the wrapper are dropped from
+/// the cache, both before it is sent and once the send returns, whether or
+/// not it succeeds, and reads that must observe a given
+/// transaction always reach the inner client.
+pub struct CachedProgramClient<C> {
+    inner: C,
+    account_ttl: Duration,
+    rent_ttl: Duration,
+    blockhash_ttl: Duration,
+    accounts: Mutex<HashMap<Pubkey, Cached<Option<Account>>>>,
+    rents: Mutex<HashMap<usize, Cached<u64>>>,
+    blockhash: Mutex<Option<Cached<Hash>>>,
+}
+
+impl<C> fmt::Debug for CachedProgramClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachedProgramClient")
+            .field("account_ttl", &self.account_ttl)
+            .field("rent_ttl", &self.rent_ttl)
+            .field("blockhash_ttl", &self.blockhash_ttl)
+            .field("cached_accounts", &self.accounts.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl<C> CachedProgramClient<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            account_ttl: DEFAULT_ACCOUNT_TTL,
+            rent_ttl: DEFAULT_RENT_TTL,
+            blockhash_ttl: DEFAULT_BLOCKHASH_TTL,
+            accounts: Mutex::new(HashMap::new()),
+            rents: Mutex::new(HashMap::new()),
+            blockhash: Mutex::new(None),
+        }
+    }
+
+    pub fn with_account_ttl(mut self, account_ttl: Duration) -> Self {
+        self.account_ttl = account_ttl;
+        self
+    }
+
+    pub fn with_rent_ttl(mut self, rent_ttl: Duration) -> Self {
+        self.rent_ttl = rent_ttl;
+        self
+    }
+
+    pub fn with_blockhash_ttl(mut self, blockhash_ttl: Duration) -> Self {
+        self.blockhash_ttl = blockhash_ttl;
+        self
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Drop an account from the cache, e.g. after it was written elsewhere
+    pub fn invalidate(&self, address: &Pubkey) {
+        self.accounts.lock().unwrap().remove(address);
+    }
+
+    /// Drop everything from the cache
+    pub fn clear(&self) {
+        self.accounts.lock().unwrap().clear();
+        self.rents.lock().unwrap().clear();
+        *self.blockhash.lock().unwrap() = None;
+    }
+
+    fn invalidate_writable(&self, transaction: &Transaction) {
+        let message = &transaction.message;
+        let mut accounts = self.accounts.lock().unwrap();
+        for (index, address) in message.account_keys.iter().enumerate() {
+            if message.is_writable(index) {
+                accounts.remove(address);
+            }
+        }
+    }
+
+    fn cache_account(&self, address: Pubkey, account: &Option<Account>) {
+        self.accounts
+            .lock()
+            .unwrap()
+            .insert(address, Cached::new(account.clone()));
+    }
+}
+
+#[async_trait]
+impl<ST, C> ProgramClient<ST> for CachedProgramClient<C>
+where
+    ST: SendTransaction + SimulateTransaction + Send + Sync,
+    C: ProgramClient<ST> + Send + Sync,
+{
+    async fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> ProgramClientResult<u64> {
+        let cached = self
+            .rents
+            .lock()
+            .unwrap()
+            .get(&data_len)
+            .and_then(|cached| cached.fresh(self.rent_ttl));
+        if let Some(lamports) = cached {
+            return Ok(lamports);
+        }
+        let lamports = self
+            .inner
+            .get_minimum_balance_for_rent_exemption(data_len)
+            .await?;
+        self.rents
+            .lock()
+            .unwrap()
+            .insert(data_len, Cached::new(lamports));
+        Ok(lamports)
+    }
+
+    async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
+        let cached = self
+            .blockhash
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|cached| cached.fresh(self.blockhash_ttl));
+        if let Some(blockhash) = cached {
+            return Ok(blockhash);
+        }
+        let blockhash = self.inner.get_latest_blockhash().await?;
+        *self.blockhash.lock().unwrap() = Some(Cached::new(blockhash));
+        Ok(blockhash)
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> ProgramClientResult<ST::Output> {
+        // before, so reads racing the send are not served the old state, and
+        // after, in case such a read cached it again
+        self.invalidate_writable(transaction);
+        let result = self.inner.send_transaction(transaction).await;
+        self.invalidate_writable(transaction);
+        result
+    }
+
+    async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>> {
+        let cached = self
+            .accounts
+            .lock()
+            .unwrap()
+            .get(&address)
+            .and_then(|cached| cached.fresh(self.account_ttl));
+        if let Some(account) = cached {
+            return Ok(account);
+        }
+        let account = self.inner.get_account(address).await?;
+        self.cache_account(address, &account);
+        Ok(account)
+    }
+
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        match consistency {
+            ReadConsistency::Any => self.get_account(address).await,
+            ReadConsistency::AfterSignature(_) | ReadConsistency::MinContextSlot(_) => {
+                let account = self
+                    .inner
+                    .get_account_with_consistency(address, consistency)
+                    .await?;
+                self.cache_account(address, &account);
+                Ok(account)
+            }
+        }
+    }
+
+    /// Always reaches the inner client, and does not cache the account, since
+    /// cached accounts may have been read at another commitment level
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.inner
+            .get_account_with_commitment(address, commitment)
+            .await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.inner.simulate_transaction(transaction).await
+    }
+
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.inner
+            .simulate_transaction_with_overrides(transaction, overrides)
+            .await
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        self.inner.get_signature_statuses(signatures).await
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        self.inner.get_recent_prioritization_fees(addresses).await
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        self.inner
+            .get_signatures_for_address(address, before, until, limit)
+            .await
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        self.inner.get_transaction(signature).await
+    }
+
+    async fn get_program_accounts_with_filters(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        self.inner
+            .get_program_accounts_with_filters(program_id, filters)
+            .await
+    }
+
+    async fn get_program_accounts_with_context(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        self.inner
+            .get_program_accounts_with_context(program_id, filters)
+            .await
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        self.inner.get_epoch_info().await
+    }
+
+    /// Never cached, since the clock moves every slot
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        self.inner.get_sysvar_clock().await
+    }
+
+    async fn get_token_largest_accounts(
+        &self,
+        mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        self.inner.get_token_largest_accounts(mint).await
+    }
+
+    /// Only the accounts not cached are read from the inner client
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        let mut accounts = {
+            let cached = self.accounts.lock().unwrap();
+            addresses
+                .iter()
+                .map(|address| {
+                    cached
+                        .get(address)
+                        .and_then(|cached| cached.fresh(self.account_ttl))
+                })
+                .collect::<Vec<_>>()
+        };
+        let missing = addresses
+            .iter()
+            .zip(&accounts)
+            .filter(|(_, account)| account.is_none())
+            .map(|(address, _)| *address)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            let fetched = self.inner.get_multiple_accounts(&missing).await?;
+            let mut fetched = missing.into_iter().zip(fetched);
+            for account in accounts.iter_mut().filter(|account| account.is_none()) {
+                let Some((address, fetched_account)) = fetched.next() else {
+                    break;
+                };
+                self.cache_account(address, &fetched_account);
+                *account = Some(fetched_account);
+            }
+        }
+        Ok(accounts.into_iter().map(Option::flatten).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ClientMethod, ProgramRpcClientSendTransaction},
+            mock::MockProgramClient,
+        },
+        solana_sdk::{signature::Keypair, signer::Signer, system_instruction},
+    };
+
+    fn account() -> Account {
+        Account {
+            lamports: 1,
+            ..Account::default()
+        }
+    }
+
+    #[test]
+    fn test_caches_reads() {
+        let address = Pubkey::new_unique();
+        let client = CachedProgramClient::new(
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+                .with_account(&address, account()),
+        )
+        .with_account_ttl(Duration::from_secs(60));
+        let inner: &dyn ProgramClient<ProgramRpcClientSendTransaction> = &client;
+        futures::executor::block_on(async {
+            assert_eq!(inner.get_account(address).await.unwrap(), Some(account()));
+            assert_eq!(inner.get_account(address).await.unwrap(), Some(account()));
+            inner
+                .get_minimum_balance_for_rent_exemption(165)
+                .await
+                .unwrap();
+            inner
+                .get_minimum_balance_for_rent_exemption(165)
+                .await
+                .unwrap();
+            inner.get_latest_blockhash().await.unwrap();
+            inner.get_latest_blockhash().await.unwrap();
+            assert_eq!(
+                client.inner().calls(),
+                [
+                    ClientMethod::GetAccount,
+                    ClientMethod::GetMinimumBalanceForRentExemption,
+                    ClientMethod::GetLatestBlockhash,
+                ]
+            );
+
+            // reads that must observe a slot always reach the inner client
+            client.inner().clear_calls();
+            inner
+                .get_account_with_consistency(address, ReadConsistency::MinContextSlot(0))
+                .await
+                .unwrap();
+            assert_eq!(client.inner().calls(), [ClientMethod::GetAccount]);
+
+            client.invalidate(&address);
+            client.inner().clear_calls();
+            inner.get_account(address).await.unwrap();
+            assert_eq!(client.inner().calls(), [ClientMethod::GetAccount]);
+        });
+    }
+
+    #[test]
+    fn test_send_invalidates_written_accounts() {
+        let payer = Keypair::new();
+        let (written, read) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let client = CachedProgramClient::new(
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+                .with_account(&written, account())
+                .with_account(&read, account()),
+        )
+        .with_account_ttl(Duration::from_secs(60));
+        let inner: &dyn ProgramClient<ProgramRpcClientSendTransaction> = &client;
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &written, 1)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        );
+        futures::executor::block_on(async {
+            for address in [written, read] {
+                inner.get_account(address).await.unwrap();
+            }
+
+            // even when the send fails, the transaction may have landed
+            client
+                .inner()
+                .fail_next(ClientMethod::SendTransaction, "unavailable");
+            assert!(inner.send_transaction(&transaction).await.is_err());
+            client.inner().clear_calls();
+            for address in [written, read] {
+                inner.get_account(address).await.unwrap();
+            }
+            assert_eq!(client.inner().calls(), [ClientMethod::GetAccount]);
+        });
+    }
+}
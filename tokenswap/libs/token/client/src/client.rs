@@ -1,14 +1,56 @@
 use {
     async_trait::async_trait,
+    base64::{prelude::BASE64_STANDARD, Engine},
+    futures::future::select_ok,
+    serde::{Deserialize, Serialize},
+    serde_json::json,
+    solana_account_decoder::UiAccountEncoding,
     solana_banks_interface::BanksTransactionResultWithSimulation,
-    solana_program_test::{tokio::sync::Mutex, BanksClient, ProgramTestContext},
-    solana_rpc_client::nonblocking::rpc_client::RpcClient,
-    solana_rpc_client_api::response::RpcSimulateTransactionResult,
+    solana_program_test::{
+        tokio::{sync::Mutex, time},
+        BanksClient, ProgramTestContext,
+    },
+    solana_rpc_client::{
+        http_sender::HttpSender,
+        nonblocking::rpc_client::RpcClient,
+        rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClientConfig},
+    },
+    solana_rpc_client_api::{
+        config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig},
+        filter::RpcFilterType,
+        request::RpcRequest,
+        response::{OptionalContext, RpcKeyedAccount, RpcSimulateTransactionResult},
+    },
     solana_sdk::{
-        account::Account, hash::Hash, pubkey::Pubkey, signature::Signature,
-        transaction::Transaction,
+        account::Account,
+        clock::{Clock, Slot, UnixTimestamp},
+        commitment_config::CommitmentConfig,
+        epoch_info::EpochInfo,
+        epoch_schedule::EpochSchedule,
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        signature::Signature,
+        sysvar,
+        transaction::{Transaction, TransactionError},
+        transaction_context::TransactionReturnData,
+    },
+    solana_transaction_status::{
+        EncodedConfirmedTransactionWithStatusMeta, UiInstruction, UiLoadedAddresses,
+        UiTransactionEncoding,
     },
-    std::{fmt, future::Future, pin::Pin, sync::Arc},
+    std::{
+        collections::HashMap,
+        fmt,
+        future::Future,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    },
+    thiserror::Error,
 };
 
 type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
@@ -20,7 +62,75 @@ pub trait SendTransaction {
 
 /// Basic trait for simulating transactions in a validator.
 pub trait SimulateTransaction {
-    type SimulationOutput;
+    type SimulationOutput: SimulationResult;
+}
+
+/// Details of a simulation that generic code can inspect, whatever the
+/// backend. Every accessor returns `None` when the backend did not report it.
+pub trait SimulationResult {
+    fn logs(&self) -> Option<Vec<String>>;
+    fn units_consumed(&self) -> Option<u64>;
+    fn return_data(&self) -> Option<TransactionReturnData>;
+    fn error(&self) -> Option<TransactionError>;
+}
+
+impl SimulationResult for BanksTransactionResultWithSimulation {
+    fn logs(&self) -> Option<Vec<String>> {
+        self.simulation_details
+            .as_ref()
+            .map(|details| details.logs.clone())
+    }
+
+    fn units_consumed(&self) -> Option<u64> {
+        self.simulation_details
+            .as_ref()
+            .map(|details| details.units_consumed)
+    }
+
+    fn return_data(&self) -> Option<TransactionReturnData> {
+        self.simulation_details
+            .as_ref()
+            .and_then(|details| details.return_data.clone())
+    }
+
+    fn error(&self) -> Option<TransactionError> {
+        self.result.clone().and_then(Result::err)
+    }
+}
+
+impl SimulationResult for RpcClientResponse {
+    fn logs(&self) -> Option<Vec<String>> {
+        match self {
+            Self::Simulation(simulation) => simulation.logs.clone(),
+            _ => None,
+        }
+    }
+
+    fn units_consumed(&self) -> Option<u64> {
+        match self {
+            Self::Simulation(simulation) => simulation.units_consumed,
+            _ => None,
+        }
+    }
+
+    /// Return data is always base64-encoded by the RPC
+    fn return_data(&self) -> Option<TransactionReturnData> {
+        let Self::Simulation(simulation) = self else {
+            return None;
+        };
+        let return_data = simulation.return_data.as_ref()?;
+        Some(TransactionReturnData {
+            program_id: return_data.program_id.parse().ok()?,
+            data: BASE64_STANDARD.decode(&return_data.data.0).ok()?,
+        })
+    }
+
+    fn error(&self) -> Option<TransactionError> {
+        match self {
+            Self::Simulation(simulation) => simulation.err.clone(),
+            _ => None,
+        }
+    }
 }
 
 /// Extends basic `SendTransaction` trait with function `send` where client is
@@ -108,7 +218,7 @@ pub trait SimulateTransactionRpc: SimulateTransaction {
 #[derive(Debug, Clone, Copy, Default)]
 pub struct ProgramRpcClientSendTransaction;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RpcClientResponse {
     Signature(Signature),
     Transaction(Transaction),
@@ -139,6 +249,129 @@ impl SendTransactionRpc for ProgramRpcClientSendTransaction {
     }
 }
 
+/// Send transaction to validator using `RpcClient::send_transaction`, without
+/// waiting for confirmation. Meant to be paired with a `ConfirmationService`
+/// when many transactions are in flight.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgramRpcClientSendTransactionUnconfirmed;
+
+impl SendTransaction for ProgramRpcClientSendTransactionUnconfirmed {
+    type Output = RpcClientResponse;
+}
+
+impl SendTransactionRpc for ProgramRpcClientSendTransactionUnconfirmed {
+    fn send<'a>(
+        &self,
+        client: &'a RpcClient,
+        transaction: &'a Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::Output>> {
+        Box::pin(async move {
+            if !transaction.is_signed() {
+                return Err("Cannot send transaction: not fully signed".into());
+            }
+
+            client
+                .send_transaction(transaction)
+                .await
+                .map(RpcClientResponse::Signature)
+                .map_err(Into::into)
+        })
+    }
+}
+
+impl SimulateTransaction for ProgramRpcClientSendTransactionUnconfirmed {
+    type SimulationOutput = RpcClientResponse;
+}
+
+impl SimulateTransactionRpc for ProgramRpcClientSendTransactionUnconfirmed {
+    fn simulate<'a>(
+        &self,
+        client: &'a RpcClient,
+        transaction: &'a Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::SimulationOutput>> {
+        ProgramRpcClientSendTransaction.simulate(client, transaction)
+    }
+}
+
+/// Broadcast transactions to several RPC endpoints at once, returning the
+/// output of the first one to accept it, and failing only if all of them fail.
+///
+/// Every endpoint sends with `inner`, so pairing it with
+/// `ProgramRpcClientSendTransactionUnconfirmed` returns on the first endpoint
+/// that accepts the transaction, while `ProgramRpcClientSendTransaction`
+/// returns on the first one to confirm it. Simulation only uses the client's
+/// own endpoint.
+#[derive(Clone)]
+pub struct ProgramRpcClientRaceSendTransaction<ST> {
+    inner: ST,
+    endpoints: Vec<Arc<RpcClient>>,
+}
+
+impl<ST: fmt::Debug> fmt::Debug for ProgramRpcClientRaceSendTransaction<ST> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgramRpcClientRaceSendTransaction")
+            .field("inner", &self.inner)
+            .field(
+                "endpoints",
+                &self
+                    .endpoints
+                    .iter()
+                    .map(|endpoint| endpoint.url())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<ST> ProgramRpcClientRaceSendTransaction<ST> {
+    /// Send with `inner` to the client's endpoint and to every one of
+    /// `endpoints`
+    pub fn new(inner: ST, endpoints: Vec<Arc<RpcClient>>) -> Self {
+        Self { inner, endpoints }
+    }
+}
+
+impl<ST: SendTransaction> SendTransaction for ProgramRpcClientRaceSendTransaction<ST> {
+    type Output = ST::Output;
+}
+
+impl<ST> SendTransactionRpc for ProgramRpcClientRaceSendTransaction<ST>
+where
+    ST: SendTransactionRpc + Clone + Send + Sync + 'static,
+{
+    fn send<'a>(
+        &self,
+        client: &'a RpcClient,
+        transaction: &'a Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::Output>> {
+        let inner = self.inner.clone();
+        let endpoints = self.endpoints.clone();
+        Box::pin(async move {
+            let mut sends = vec![inner.send(client, transaction)];
+            for endpoint in endpoints.iter() {
+                sends.push(inner.send(endpoint, transaction));
+            }
+            select_ok(sends).await.map(|(output, _)| output)
+        })
+    }
+}
+
+impl<ST: SimulateTransaction> SimulateTransaction for ProgramRpcClientRaceSendTransaction<ST> {
+    type SimulationOutput = ST::SimulationOutput;
+}
+
+impl<ST: SimulateTransactionRpc> SimulateTransactionRpc
+    for ProgramRpcClientRaceSendTransaction<ST>
+{
+    fn simulate<'a>(
+        &self,
+        client: &'a RpcClient,
+        transaction: &'a Transaction,
+    ) -> BoxFuture<'a, ProgramClientResult<Self::SimulationOutput>> {
+        self.inner.simulate(client, transaction)
+    }
+}
+
 impl SimulateTransaction for ProgramRpcClientSendTransaction {
     type SimulationOutput = RpcClientResponse;
 }
@@ -162,9 +395,299 @@ impl SimulateTransactionRpc for ProgramRpcClientSendTransaction {
 pub type ProgramClientError = Box<dyn std::error::Error + Send + Sync>;
 pub type ProgramClientResult<T> = Result<T, ProgramClientError>;
 
+/// Status of a transaction known to the backend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureStatus {
+    /// Slot the transaction was processed in
+    pub slot: Slot,
+    /// Error, if the transaction failed
+    pub err: Option<TransactionError>,
+    /// Whether the transaction reached the commitment level of the backend
+    pub confirmed: bool,
+}
+
+/// Account states injected in place of the real ones for a simulation
+pub type AccountOverrides = HashMap<Pubkey, Account>;
+
+/// Transaction fetched from the ledger, with its instructions resolved
+/// against its account keys, including those loaded from lookup tables
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionRecord {
+    pub signature: Signature,
+    pub slot: Slot,
+    pub block_time: Option<UnixTimestamp>,
+    /// Error, if the transaction failed
+    pub err: Option<TransactionError>,
+    /// Top-level instructions
+    pub instructions: Vec<Instruction>,
+    /// Instructions invoked by each top-level instruction, by index
+    pub inner_instructions: Vec<Vec<Instruction>>,
+}
+
+impl TransactionRecord {
+    /// Record of a legacy transaction processed by a local backend, which
+    /// reports no inner instructions
+    pub(crate) fn from_transaction(
+        signature: Signature,
+        slot: Slot,
+        transaction: &Transaction,
+        err: Option<TransactionError>,
+    ) -> Self {
+        let message = &transaction.message;
+        let num_signed = usize::from(message.header.num_required_signatures);
+        let num_writable_signed =
+            num_signed.saturating_sub(usize::from(message.header.num_readonly_signed_accounts));
+        let num_writable_unsigned = message
+            .account_keys
+            .len()
+            .saturating_sub(usize::from(message.header.num_readonly_unsigned_accounts));
+        let account_meta = |index: &u8| {
+            let index = usize::from(*index);
+            AccountMeta {
+                pubkey: message.account_keys[index],
+                is_signer: index < num_signed,
+                is_writable: index < num_writable_signed
+                    || (index >= num_signed && index < num_writable_unsigned),
+            }
+        };
+        let instructions = message
+            .instructions
+            .iter()
+            .map(|compiled| Instruction {
+                program_id: message.account_keys[usize::from(compiled.program_id_index)],
+                accounts: compiled.accounts.iter().map(account_meta).collect(),
+                data: compiled.data.clone(),
+            })
+            .collect::<Vec<_>>();
+        Self {
+            signature,
+            slot,
+            block_time: None,
+            err,
+            inner_instructions: vec![vec![]; instructions.len()],
+            instructions,
+        }
+    }
+
+    fn from_encoded(
+        signature: Signature,
+        encoded: EncodedConfirmedTransactionWithStatusMeta,
+    ) -> ProgramClientResult<Self> {
+        let transaction = encoded
+            .transaction
+            .transaction
+            .decode()
+            .ok_or("Unable to decode transaction")?;
+        let meta = encoded.transaction.meta;
+
+        let message = &transaction.message;
+        let header = message.header();
+        let static_keys = message.static_account_keys();
+        let num_signed = usize::from(header.num_required_signatures);
+        let num_writable_signed =
+            num_signed.saturating_sub(usize::from(header.num_readonly_signed_accounts));
+        let num_writable_unsigned = static_keys
+            .len()
+            .saturating_sub(usize::from(header.num_readonly_unsigned_accounts));
+
+        let mut account_metas = static_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| AccountMeta {
+                pubkey: *key,
+                is_signer: i < num_signed,
+                is_writable: i < num_writable_signed
+                    || (i >= num_signed && i < num_writable_unsigned),
+            })
+            .collect::<Vec<_>>();
+        // only versioned transactions using lookup tables load addresses
+        let loaded_addresses = meta
+            .as_ref()
+            .and_then(|meta| Option::<UiLoadedAddresses>::from(meta.loaded_addresses.clone()));
+        if let Some(loaded_addresses) = loaded_addresses {
+            for (addresses, is_writable) in [
+                (loaded_addresses.writable, true),
+                (loaded_addresses.readonly, false),
+            ] {
+                for address in addresses {
+                    account_metas.push(AccountMeta {
+                        pubkey: address.parse()?,
+                        is_signer: false,
+                        is_writable,
+                    });
+                }
+            }
+        }
+
+        let resolve = |program_id_index: u8, accounts: &[u8], data: Vec<u8>| {
+            let account_meta = |index: &u8| {
+                account_metas
+                    .get(usize::from(*index))
+                    .cloned()
+                    .ok_or("Instruction account index out of range")
+            };
+            Ok::<_, ProgramClientError>(Instruction {
+                program_id: account_meta(&program_id_index)?.pubkey,
+                accounts: accounts
+                    .iter()
+                    .map(account_meta)
+                    .collect::<Result<_, _>>()?,
+                data,
+            })
+        };
+
+        let instructions = message
+            .instructions()
+            .iter()
+            .map(|compiled| {
+                resolve(
+                    compiled.program_id_index,
+                    &compiled.accounts,
+                    compiled.data.clone(),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut inner_instructions = vec![vec![]; instructions.len()];
+        let ui_inner_instructions = meta
+            .as_ref()
+            .and_then(|meta| Option::<Vec<_>>::from(meta.inner_instructions.clone()))
+            .unwrap_or_default();
+        for ui_inner in ui_inner_instructions {
+            let Some(inner) = inner_instructions.get_mut(usize::from(ui_inner.index)) else {
+                continue;
+            };
+            for ui_instruction in ui_inner.instructions {
+                if let UiInstruction::Compiled(compiled) = ui_instruction {
+                    let data = bs58::decode(&compiled.data).into_vec()?;
+                    inner.push(resolve(
+                        compiled.program_id_index,
+                        &compiled.accounts,
+                        data,
+                    )?);
+                }
+            }
+        }
+
+        Ok(Self {
+            signature,
+            slot: encoded.slot,
+            block_time: encoded.block_time,
+            err: meta.and_then(|meta| meta.err),
+            instructions,
+            inner_instructions,
+        })
+    }
+}
+
+/// What a read must observe, used to route it to a node that can serve it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReadConsistency {
+    /// Any recent state is fine, so the read can go to a replica
+    #[default]
+    Any,
+    /// The state must include the given transaction, e.g. to verify a write
+    AfterSignature(Signature),
+    /// The state must be at least as recent as the given slot, e.g. the one a
+    /// write was observed in, so a lagging node cannot miss it
+    MinContextSlot(Slot),
+}
+
+/// How long a read waits for its transaction, or its slot, to be visible
+pub const READ_AFTER_SIGNATURE_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often a read waiting for its transaction retries
+pub const READ_AFTER_SIGNATURE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long an RPC request may take unless configured otherwise
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long sending a transaction may take, including its confirmation
+pub const DEFAULT_SEND_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long a program account scan may take
+pub const DEFAULT_PROGRAM_ACCOUNTS_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Methods of a program client, e.g. to weigh them against a rate limit or to
+/// configure their timeouts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClientMethod {
+    GetMinimumBalanceForRentExemption,
+    GetLatestBlockhash,
+    SendTransaction,
+    GetAccount,
+    SimulateTransaction,
+    GetSignatureStatuses,
+    GetRecentPrioritizationFees,
+    GetSignaturesForAddress,
+    GetTransaction,
+    GetProgramAccounts,
+    GetEpochInfo,
+    GetSysvarClock,
+    GetTokenLargestAccounts,
+    GetMultipleAccounts,
+}
+
+/// An RPC request did not complete within its timeout. Returned boxed as a
+/// `ProgramClientError`, so callers can `downcast_ref` to tell it apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("{method:?} request timed out after {timeout:?}")]
+pub struct RequestTimeout {
+    pub method: ClientMethod,
+    pub timeout: Duration,
+}
+
+/// A client does not implement a method, e.g. one added to `ProgramClient`
+/// after the client was written. Returned boxed as a `ProgramClientError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("{0:?} is not supported by this client")]
+pub struct Unsupported(pub ClientMethod);
+
+/// Sending a transaction did not complete within its timeout, so it may or may
+/// not land: check the status of its signature before sending it again.
+/// Returned boxed as a `ProgramClientError` instead of a `RequestTimeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("outcome of transaction {signature} unknown after {timeout:?}")]
+pub struct SendOutcomeUnknown {
+    pub signature: Signature,
+    pub timeout: Duration,
+}
+
+fn default_request_timeout(method: ClientMethod) -> Duration {
+    match method {
+        ClientMethod::SendTransaction => DEFAULT_SEND_TRANSACTION_TIMEOUT,
+        ClientMethod::GetProgramAccounts => DEFAULT_PROGRAM_ACCOUNTS_TIMEOUT,
+        _ => DEFAULT_REQUEST_TIMEOUT,
+    }
+}
+
+/// Most accounts a node returns for the largest accounts of a mint
+pub const MAX_LARGEST_ACCOUNTS: usize = 20;
+
+/// Most accounts a node returns for one request of multiple accounts
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Epoch information as of the clock, for clients without a node to ask
+pub(crate) fn epoch_info_from_clock(
+    clock: &Clock,
+    epoch_schedule: &EpochSchedule,
+    block_height: u64,
+) -> EpochInfo {
+    let (epoch, slot_index) = epoch_schedule.get_epoch_and_slot_index(clock.slot);
+    EpochInfo {
+        epoch,
+        slot_index,
+        slots_in_epoch: epoch_schedule.get_slots_in_epoch(epoch),
+        absolute_slot: clock.slot,
+        block_height,
+        transaction_count: None,
+    }
+}
+
 /// Generic client interface for programs.
+///
+/// Methods beyond the basic ones default to failing with `Unsupported`, so a
+/// client only implements those its backend can serve. Clients are shared
+/// across tasks, so they must be `Send` and `Sync`.
 #[async_trait]
-pub trait ProgramClient<ST>
+pub trait ProgramClient<ST>: Send + Sync
 where
     ST: SendTransaction + SimulateTransaction,
 {
@@ -179,10 +702,118 @@ where
 
     async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>>;
 
+    async fn get_account_with_consistency(
+        &self,
+        _address: Pubkey,
+        _consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        Err(Unsupported(ClientMethod::GetAccount).into())
+    }
+
+    /// Get an account as of the given commitment level. Backends with a
+    /// single state serve it whatever the level.
+    async fn get_account_with_commitment(
+        &self,
+        _address: Pubkey,
+        _commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        Err(Unsupported(ClientMethod::GetAccount).into())
+    }
+
     async fn simulate_transaction(
         &self,
         transaction: &Transaction,
     ) -> ProgramClientResult<ST::SimulationOutput>;
+
+    /// Simulate the transaction as if the given accounts had the given states
+    async fn simulate_transaction_with_overrides(
+        &self,
+        _transaction: &Transaction,
+        _overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        Err(Unsupported(ClientMethod::SimulateTransaction).into())
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        _signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        Err(Unsupported(ClientMethod::GetSignatureStatuses).into())
+    }
+
+    /// Prioritization fees, in micro-lamports per compute unit, paid in recent
+    /// slots by transactions write-locking any of the addresses
+    async fn get_recent_prioritization_fees(
+        &self,
+        _addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        Err(Unsupported(ClientMethod::GetRecentPrioritizationFees).into())
+    }
+
+    /// Signatures of transactions involving the address, newest first,
+    /// starting before `before` and stopping at `until` (both excluded)
+    async fn get_signatures_for_address(
+        &self,
+        _address: &Pubkey,
+        _before: Option<Signature>,
+        _until: Option<Signature>,
+        _limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        Err(Unsupported(ClientMethod::GetSignaturesForAddress).into())
+    }
+
+    async fn get_transaction(
+        &self,
+        _signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        Err(Unsupported(ClientMethod::GetTransaction).into())
+    }
+
+    /// Accounts owned by the program matching all of the filters
+    async fn get_program_accounts_with_filters(
+        &self,
+        _program_id: &Pubkey,
+        _filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        Err(Unsupported(ClientMethod::GetProgramAccounts).into())
+    }
+
+    /// Accounts owned by the program matching all of the filters, with the
+    /// slot they were read at, e.g. to stamp a snapshot of them
+    async fn get_program_accounts_with_context(
+        &self,
+        _program_id: &Pubkey,
+        _filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        Err(Unsupported(ClientMethod::GetProgramAccounts).into())
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        Err(Unsupported(ClientMethod::GetEpochInfo).into())
+    }
+
+    /// Get the clock sysvar, e.g. for the current epoch and timestamp
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        Err(Unsupported(ClientMethod::GetSysvarClock).into())
+    }
+
+    /// Addresses and balances of the largest accounts of the mint, largest
+    /// first, up to `MAX_LARGEST_ACCOUNTS` of them
+    async fn get_token_largest_accounts(
+        &self,
+        _mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        Err(Unsupported(ClientMethod::GetTokenLargestAccounts).into())
+    }
+
+    /// Get several accounts at once, in the order of the addresses, `None`
+    /// for those that do not exist
+    async fn get_multiple_accounts(
+        &self,
+        _addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        Err(Unsupported(ClientMethod::GetMultipleAccounts).into())
+    }
 }
 
 enum ProgramBanksClientContext {
@@ -282,12 +913,175 @@ where
         })
         .await
     }
+
+    /// Overrides are only supported when created from a `ProgramTestContext`:
+    /// the accounts are set for the simulation and restored afterwards, while
+    /// holding the lock so no other transaction observes them.
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        if overrides.is_empty() {
+            return self.simulate_transaction(transaction).await;
+        }
+        let ProgramBanksClientContext::Context(context) = &self.context else {
+            return Err("Account overrides require a client created from a context".into());
+        };
+
+        let mut context = context.lock().await;
+        let mut originals = Vec::with_capacity(overrides.len());
+        for address in overrides.keys() {
+            let original = context.banks_client.get_account(*address).await?;
+            originals.push((*address, original));
+        }
+        for (address, account) in overrides {
+            context.set_account(address, &account.clone().into());
+        }
+
+        let result = self
+            .send
+            .simulate(&mut context.banks_client, transaction.clone())
+            .await;
+
+        for (address, original) in originals {
+            context.set_account(&address, &original.unwrap_or_default().into());
+        }
+        result
+    }
+
+    /// Banks clients have a single bank, so every read observes every
+    /// processed transaction
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        _consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.get_account(address).await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.run_in_lock(|client| {
+            Box::pin(async move {
+                client
+                    .get_account_with_commitment(address, commitment.commitment)
+                    .await
+                    .map_err(Into::into)
+            })
+        })
+        .await
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        self.run_in_lock(|client| {
+            let signatures = signatures.to_vec();
+            Box::pin(async move {
+                let mut statuses = Vec::with_capacity(signatures.len());
+                for signature in signatures {
+                    let status = client.get_transaction_status(signature).await?;
+                    statuses.push(status.map(|status| SignatureStatus {
+                        slot: status.slot,
+                        err: status.err,
+                        confirmed: true,
+                    }));
+                }
+                Ok(statuses)
+            })
+        })
+        .await
+    }
+
+    /// Banks clients have no fee market, so no fees are reported
+    async fn get_recent_prioritization_fees(
+        &self,
+        _addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        Ok(vec![])
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        self.run_in_lock(|client| {
+            Box::pin(async move {
+                let clock = client.get_sysvar::<Clock>().await?;
+                let epoch_schedule = client.get_sysvar::<EpochSchedule>().await?;
+                let block_height = client.get_root_block_height().await?;
+                Ok(epoch_info_from_clock(&clock, &epoch_schedule, block_height))
+            })
+        })
+        .await
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        self.run_in_lock(|client| {
+            Box::pin(async move { client.get_sysvar::<Clock>().await.map_err(Into::into) })
+        })
+        .await
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        self.run_in_lock(|client| {
+            let addresses = addresses.to_vec();
+            Box::pin(async move {
+                let mut accounts = Vec::with_capacity(addresses.len());
+                for address in addresses {
+                    accounts.push(client.get_account(address).await?);
+                }
+                Ok::<_, ProgramClientError>(accounts)
+            })
+        })
+        .await
+    }
+}
+
+/// RPC client of the node whose HTTP client keeps up to
+/// `max_idle_connections` connections alive while idle for up to
+/// `keep_alive`, so the concurrent requests of large batch jobs reuse them
+/// instead of opening new ones. Requests are only bounded by the timeouts of
+/// `ProgramRpcClient`.
+pub fn pooled_rpc_client<U: ToString>(
+    url: U,
+    config: RpcClientConfig,
+    max_idle_connections: usize,
+    keep_alive: Duration,
+) -> ProgramClientResult<RpcClient> {
+    let client = reqwest::Client::builder()
+        .pool_max_idle_per_host(max_idle_connections)
+        .pool_idle_timeout(keep_alive)
+        .build()?;
+    Ok(RpcClient::new_sender(
+        HttpSender::new_with_client(url, client),
+        config,
+    ))
 }
 
 /// Program client for `RpcClient` from crate `solana-client`.
+///
+/// Transactions and reads that must observe them go to the main node, while
+/// reads with `ReadConsistency::Any` are spread over the read replicas, if
+/// any.
+///
+/// Concurrent requests share the connections of each node's HTTP client, see
+/// `pooled_rpc_client` to size them for large batch jobs.
+///
+/// Every request fails with a `RequestTimeout` once it outlives its method's
+/// timeout, so a hung node cannot stall a flow indefinitely. Sending a
+/// transaction fails with a `SendOutcomeUnknown` instead, as it may still land.
 pub struct ProgramRpcClient<ST> {
     client: Arc<RpcClient>,
     send: ST,
+    read_replicas: Vec<Arc<RpcClient>>,
+    next_replica: AtomicUsize,
+    timeouts: HashMap<ClientMethod, Duration>,
 }
 
 impl<ST> fmt::Debug for ProgramRpcClient<ST> {
@@ -298,7 +1092,118 @@ impl<ST> fmt::Debug for ProgramRpcClient<ST> {
 
 impl<ST> ProgramRpcClient<ST> {
     pub fn new(client: Arc<RpcClient>, send: ST) -> Self {
-        Self { client, send }
+        Self {
+            client,
+            send,
+            read_replicas: vec![],
+            next_replica: AtomicUsize::new(0),
+            timeouts: HashMap::new(),
+        }
+    }
+
+    /// Fail requests of the method that take longer than `timeout`
+    pub fn with_timeout(mut self, method: ClientMethod, timeout: Duration) -> Self {
+        self.timeouts.insert(method, timeout);
+        self
+    }
+
+    fn timeout(&self, method: ClientMethod) -> Duration {
+        self.timeouts
+            .get(&method)
+            .copied()
+            .unwrap_or_else(|| default_request_timeout(method))
+    }
+
+    /// Run a request of the method, failing it once it outlives its timeout
+    async fn timed<T, E, F>(&self, method: ClientMethod, request: F) -> ProgramClientResult<T>
+    where
+        E: Into<ProgramClientError>,
+        F: Future<Output = Result<T, E>>,
+    {
+        let timeout = self.timeout(method);
+        match time::timeout(timeout, request).await {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => Err(RequestTimeout { method, timeout }.into()),
+        }
+    }
+
+    pub fn with_read_replicas(mut self, read_replicas: Vec<Arc<RpcClient>>) -> Self {
+        self.read_replicas = read_replicas;
+        self
+    }
+
+    /// Client of the main node
+    fn main_client(&self) -> &RpcClient {
+        &self.client
+    }
+
+    fn read_client(&self) -> &RpcClient {
+        if self.read_replicas.is_empty() {
+            return self.main_client();
+        }
+        let index = self.next_replica.fetch_add(1, Ordering::Relaxed);
+        &self.read_replicas[index % self.read_replicas.len()]
+    }
+
+    /// Read an account from the main client once it has seen the slot the
+    /// transaction was processed in
+    async fn get_account_after_signature(
+        &self,
+        address: Pubkey,
+        signature: Signature,
+    ) -> ProgramClientResult<Option<Account>> {
+        let start = Instant::now();
+        let min_context_slot = loop {
+            let status = self
+                .timed(
+                    ClientMethod::GetSignatureStatuses,
+                    self.main_client().get_signature_statuses(&[signature]),
+                )
+                .await?
+                .value
+                .pop()
+                .flatten();
+            if let Some(status) = status {
+                break status.slot;
+            }
+            if start.elapsed() >= READ_AFTER_SIGNATURE_TIMEOUT {
+                return Err(format!("Transaction {} not found", signature).into());
+            }
+            time::sleep(READ_AFTER_SIGNATURE_POLL_INTERVAL).await;
+        };
+        self.get_account_at_min_context_slot(address, min_context_slot, start)
+            .await
+    }
+
+    /// Read an account from the main client once it has reached the slot,
+    /// retrying until `READ_AFTER_SIGNATURE_TIMEOUT` has elapsed since `start`
+    async fn get_account_at_min_context_slot(
+        &self,
+        address: Pubkey,
+        min_context_slot: Slot,
+        start: Instant,
+    ) -> ProgramClientResult<Option<Account>> {
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64Zstd),
+            commitment: Some(self.client.commitment()),
+            data_slice: None,
+            min_context_slot: Some(min_context_slot),
+        };
+        loop {
+            // fails until the node reaches the slot at its commitment
+            match self
+                .timed(
+                    ClientMethod::GetAccount,
+                    self.main_client()
+                        .get_account_with_config(&address, config.clone()),
+                )
+                .await
+            {
+                Ok(response) => return Ok(response.value),
+                Err(error) if start.elapsed() >= READ_AFTER_SIGNATURE_TIMEOUT => return Err(error),
+                Err(_) => time::sleep(READ_AFTER_SIGNATURE_POLL_INTERVAL).await,
+            }
+        }
     }
 }
 
@@ -311,34 +1216,318 @@ where
         &self,
         data_len: usize,
     ) -> ProgramClientResult<u64> {
-        self.client
-            .get_minimum_balance_for_rent_exemption(data_len)
-            .await
-            .map_err(Into::into)
+        self.timed(
+            ClientMethod::GetMinimumBalanceForRentExemption,
+            self.main_client()
+                .get_minimum_balance_for_rent_exemption(data_len),
+        )
+        .await
     }
 
     async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
-        self.client.get_latest_blockhash().await.map_err(Into::into)
+        self.timed(
+            ClientMethod::GetLatestBlockhash,
+            self.main_client().get_latest_blockhash(),
+        )
+        .await
     }
 
     async fn send_transaction(&self, transaction: &Transaction) -> ProgramClientResult<ST::Output> {
-        self.send.send(&self.client, transaction).await
+        let timeout = self.timeout(ClientMethod::SendTransaction);
+        match time::timeout(timeout, self.send.send(self.main_client(), transaction)).await {
+            Ok(result) => result,
+            Err(_) => Err(SendOutcomeUnknown {
+                signature: transaction.signatures[0],
+                timeout,
+            }
+            .into()),
+        }
     }
 
     async fn simulate_transaction(
         &self,
         transaction: &Transaction,
     ) -> ProgramClientResult<ST::SimulationOutput> {
-        self.send.simulate(&self.client, transaction).await
+        self.timed(
+            ClientMethod::SimulateTransaction,
+            self.send.simulate(self.main_client(), transaction),
+        )
+        .await
     }
 
     async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>> {
         Ok(self
-            .client
-            .get_account_with_commitment(&address, self.client.commitment())
+            .timed(
+                ClientMethod::GetAccount,
+                self.main_client()
+                    .get_account_with_commitment(&address, self.client.commitment()),
+            )
+            .await?
+            .value)
+    }
+
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        if !overrides.is_empty() {
+            return Err("Account overrides are not supported by RPC simulation".into());
+        }
+        self.simulate_transaction(transaction).await
+    }
+
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        match consistency {
+            ReadConsistency::Any => {
+                let client = self.read_client();
+                Ok(self
+                    .timed(
+                        ClientMethod::GetAccount,
+                        client.get_account_with_commitment(&address, client.commitment()),
+                    )
+                    .await?
+                    .value)
+            }
+            ReadConsistency::AfterSignature(signature) => {
+                self.get_account_after_signature(address, signature).await
+            }
+            ReadConsistency::MinContextSlot(min_context_slot) => {
+                self.get_account_at_min_context_slot(address, min_context_slot, Instant::now())
+                    .await
+            }
+        }
+    }
+
+    /// Served by the read replicas, if any, like reads with
+    /// `ReadConsistency::Any`
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        Ok(self
+            .timed(
+                ClientMethod::GetAccount,
+                self.read_client()
+                    .get_account_with_commitment(&address, commitment),
+            )
             .await?
             .value)
     }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        let commitment = self.client.commitment();
+        Ok(self
+            .timed(
+                ClientMethod::GetSignatureStatuses,
+                self.main_client().get_signature_statuses(signatures),
+            )
+            .await?
+            .value
+            .into_iter()
+            .map(|status| {
+                status.map(|status| SignatureStatus {
+                    slot: status.slot,
+                    confirmed: status.satisfies_commitment(commitment),
+                    err: status.err,
+                })
+            })
+            .collect())
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        Ok(self
+            .timed(
+                ClientMethod::GetRecentPrioritizationFees,
+                self.main_client().get_recent_prioritization_fees(addresses),
+            )
+            .await?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect())
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        let config = GetConfirmedSignaturesForAddress2Config {
+            before,
+            until,
+            limit: Some(limit),
+            commitment: Some(self.client.commitment()),
+        };
+        self.timed(
+            ClientMethod::GetSignaturesForAddress,
+            self.main_client()
+                .get_signatures_for_address_with_config(address, config),
+        )
+        .await?
+        .into_iter()
+        .map(|status| status.signature.parse().map_err(Into::into))
+        .collect()
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(self.client.commitment()),
+            max_supported_transaction_version: Some(0),
+        };
+        // not found is `null` rather than an error
+        let encoded: Option<EncodedConfirmedTransactionWithStatusMeta> = self
+            .timed(
+                ClientMethod::GetTransaction,
+                self.main_client().send(
+                    RpcRequest::GetTransaction,
+                    json!([signature.to_string(), config]),
+                ),
+            )
+            .await?;
+        encoded
+            .map(|encoded| TransactionRecord::from_encoded(*signature, encoded))
+            .transpose()
+    }
+
+    async fn get_program_accounts_with_filters(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        let client = self.read_client();
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64Zstd),
+                commitment: Some(client.commitment()),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        self.timed(
+            ClientMethod::GetProgramAccounts,
+            client.get_program_accounts_with_config(program_id, config),
+        )
+        .await
+    }
+
+    async fn get_program_accounts_with_context(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        let client = self.read_client();
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64Zstd),
+                commitment: Some(client.commitment()),
+                ..RpcAccountInfoConfig::default()
+            },
+            with_context: Some(true),
+            ..RpcProgramAccountsConfig::default()
+        };
+        // `RpcClient` drops the context of the response, so send it raw
+        let response: OptionalContext<Vec<RpcKeyedAccount>> = self
+            .timed(
+                ClientMethod::GetProgramAccounts,
+                client.send(
+                    RpcRequest::GetProgramAccounts,
+                    json!([program_id.to_string(), config]),
+                ),
+            )
+            .await?;
+        let OptionalContext::Context(response) = response else {
+            return Err("Program accounts returned without their context".into());
+        };
+        let accounts = response
+            .value
+            .into_iter()
+            .map(|keyed_account| {
+                let account = keyed_account
+                    .account
+                    .decode::<Account>()
+                    .ok_or("Program account could not be decoded")?;
+                Ok::<_, ProgramClientError>((keyed_account.pubkey.parse()?, account))
+            })
+            .collect::<ProgramClientResult<Vec<_>>>()?;
+        Ok((response.context.slot, accounts))
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        self.timed(
+            ClientMethod::GetEpochInfo,
+            self.main_client().get_epoch_info(),
+        )
+        .await
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        let account = self
+            .timed(
+                ClientMethod::GetSysvarClock,
+                self.main_client()
+                    .get_account_with_commitment(&sysvar::clock::id(), self.client.commitment()),
+            )
+            .await?
+            .value
+            .ok_or("Clock sysvar not found")?;
+        bincode::deserialize(&account.data).map_err(Into::into)
+    }
+
+    async fn get_token_largest_accounts(
+        &self,
+        mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        let client = self.read_client();
+        self.timed(
+            ClientMethod::GetTokenLargestAccounts,
+            client.get_token_largest_accounts(mint),
+        )
+        .await?
+        .into_iter()
+        .map(|balance| {
+            Ok::<_, ProgramClientError>((balance.address.parse()?, balance.amount.amount.parse()?))
+        })
+        .collect()
+    }
+
+    /// Requests at most `MAX_MULTIPLE_ACCOUNTS` accounts at a time
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        let mut accounts = Vec::with_capacity(addresses.len());
+        for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            accounts.extend(
+                self.timed(
+                    ClientMethod::GetMultipleAccounts,
+                    self.main_client()
+                        .get_multiple_accounts_with_commitment(chunk, self.client.commitment()),
+                )
+                .await?
+                .value,
+            );
+        }
+        Ok(accounts)
+    }
 }
 
 /// Program client for offline signing.
@@ -395,4 +1584,276 @@ where
     async fn get_account(&self, _address: Pubkey) -> ProgramClientResult<Option<Account>> {
         Err("Unable to fetch account in offline mode".into())
     }
+
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        _overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.simulate_transaction(transaction).await
+    }
+
+    async fn get_account_with_consistency(
+        &self,
+        _address: Pubkey,
+        _consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        Err("Unable to fetch account in offline mode".into())
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        _address: Pubkey,
+        _commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        Err("Unable to fetch account in offline mode".into())
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        _signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        Err("Unable to fetch signature statuses in offline mode".into())
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        _addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        Err("Unable to fetch prioritization fees in offline mode".into())
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        _address: &Pubkey,
+        _before: Option<Signature>,
+        _until: Option<Signature>,
+        _limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        Err("Unable to fetch transaction history in offline mode".into())
+    }
+
+    async fn get_transaction(
+        &self,
+        _signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        Err("Unable to fetch transaction history in offline mode".into())
+    }
+
+    async fn get_program_accounts_with_filters(
+        &self,
+        _program_id: &Pubkey,
+        _filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        Err("Unable to fetch program accounts in offline mode".into())
+    }
+
+    async fn get_program_accounts_with_context(
+        &self,
+        _program_id: &Pubkey,
+        _filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        Err("Unable to fetch program accounts in offline mode".into())
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        Err("Unable to fetch epoch information in offline mode".into())
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        Err("Unable to fetch clock in offline mode".into())
+    }
+
+    async fn get_token_largest_accounts(
+        &self,
+        _mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        Err("Unable to fetch largest accounts in offline mode".into())
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        _addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        Err("Unable to fetch accounts in offline mode".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        futures::future,
+        solana_program_test::{tokio, ProgramTest},
+        solana_sdk::{signature::Keypair, signer::Signer},
+    };
+
+    /// Sender whose transactions never complete
+    struct HangingSend;
+
+    impl SendTransaction for HangingSend {
+        type Output = RpcClientResponse;
+    }
+
+    impl SendTransactionRpc for HangingSend {
+        fn send<'a>(
+            &self,
+            _client: &'a RpcClient,
+            _transaction: &'a Transaction,
+        ) -> BoxFuture<'a, ProgramClientResult<Self::Output>> {
+            Box::pin(future::pending())
+        }
+    }
+
+    impl SimulateTransaction for HangingSend {
+        type SimulationOutput = RpcClientResponse;
+    }
+
+    impl SimulateTransactionRpc for HangingSend {
+        fn simulate<'a>(
+            &self,
+            _client: &'a RpcClient,
+            _transaction: &'a Transaction,
+        ) -> BoxFuture<'a, ProgramClientResult<Self::SimulationOutput>> {
+            Box::pin(future::pending())
+        }
+    }
+
+    /// Client implementing only the methods without a default
+    struct BasicClient;
+
+    #[async_trait]
+    impl ProgramClient<ProgramRpcClientSendTransaction> for BasicClient {
+        async fn get_minimum_balance_for_rent_exemption(
+            &self,
+            _data_len: usize,
+        ) -> ProgramClientResult<u64> {
+            Ok(0)
+        }
+
+        async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
+            Ok(Hash::default())
+        }
+
+        async fn send_transaction(
+            &self,
+            transaction: &Transaction,
+        ) -> ProgramClientResult<RpcClientResponse> {
+            Ok(RpcClientResponse::Signature(transaction.signatures[0]))
+        }
+
+        async fn get_account(&self, _address: Pubkey) -> ProgramClientResult<Option<Account>> {
+            Ok(None)
+        }
+
+        async fn simulate_transaction(
+            &self,
+            transaction: &Transaction,
+        ) -> ProgramClientResult<RpcClientResponse> {
+            Ok(RpcClientResponse::Transaction(transaction.clone()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_by_default() {
+        let client = BasicClient;
+        let error = client
+            .get_signature_statuses(&[Signature::default()])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Unsupported>(),
+            Some(&Unsupported(ClientMethod::GetSignatureStatuses))
+        );
+        let error = client
+            .get_token_largest_accounts(&Pubkey::new_unique())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Unsupported>(),
+            Some(&Unsupported(ClientMethod::GetTokenLargestAccounts))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_banks_client_unsupported() {
+        let context = ProgramTest::default().start_with_context().await;
+        let client = ProgramBanksClient::new_from_context(
+            Arc::new(Mutex::new(context)),
+            ProgramBanksClientProcessTransaction,
+        );
+        // history and scans are left to the trait, so callers can fall back
+        let error = client
+            .get_program_accounts_with_filters(&spl_token_2022::id(), vec![])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Unsupported>(),
+            Some(&Unsupported(ClientMethod::GetProgramAccounts))
+        );
+        let error = client
+            .get_program_accounts_with_context(&spl_token_2022::id(), vec![])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Unsupported>(),
+            Some(&Unsupported(ClientMethod::GetProgramAccounts))
+        );
+        let error = client
+            .get_signatures_for_address(&Pubkey::new_unique(), None, None, 10)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Unsupported>(),
+            Some(&Unsupported(ClientMethod::GetSignaturesForAddress))
+        );
+        let error = client
+            .get_transaction(&Signature::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<Unsupported>(),
+            Some(&Unsupported(ClientMethod::GetTransaction))
+        );
+    }
+
+    #[test]
+    fn test_pooled_rpc_client_keeps_config() {
+        let client = pooled_rpc_client(
+            "http://127.0.0.1:8899",
+            RpcClientConfig::with_commitment(CommitmentConfig::finalized()),
+            16,
+            Duration::from_secs(90),
+        )
+        .unwrap();
+        assert_eq!(client.url(), "http://127.0.0.1:8899");
+        assert_eq!(client.commitment(), CommitmentConfig::finalized());
+    }
+
+    #[tokio::test]
+    async fn test_send_timeout_outcome_unknown() {
+        let timeout = Duration::from_millis(10);
+        let client = ProgramRpcClient::new(
+            Arc::new(RpcClient::new_mock("succeeds".to_string())),
+            HangingSend,
+        )
+        .with_timeout(ClientMethod::SendTransaction, timeout);
+        let payer = Keypair::new();
+        let transaction = Transaction::new_signed_with_payer(
+            &[],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        );
+
+        let error = client.send_transaction(&transaction).await.unwrap_err();
+        assert!(!error.is::<RequestTimeout>());
+        assert_eq!(
+            error.downcast_ref::<SendOutcomeUnknown>(),
+            Some(&SendOutcomeUnknown {
+                signature: transaction.signatures[0],
+                timeout,
+            })
+        );
+    }
 }
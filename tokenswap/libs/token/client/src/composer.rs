@@ -0,0 +1,479 @@
+//! Composition of operations on several mints into one atomic transaction
+
+use {
+    crate::{
+        client::{SendTransaction, SimulateTransaction},
+        sanitize::{check_instructions, InstructionPolicy, InstructionRejection},
+        token::{Token, TokenError, TokenMemo, TokenResult},
+    },
+    solana_sdk::{instruction::Instruction, pubkey::Pubkey, signer::signers::Signers},
+    std::{iter, ptr},
+};
+
+enum Operation<'a, T> {
+    Transfer {
+        token: &'a Token<T>,
+        source: Pubkey,
+        destination: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+    },
+    MintTo {
+        token: &'a Token<T>,
+        destination: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+    },
+    Burn {
+        token: &'a Token<T>,
+        source: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+    },
+    Instructions(Vec<Instruction>),
+}
+
+/// Builds a single transaction out of operations on several `Token`s, e.g. to
+/// burn one mint and mint another atomically.
+///
+/// The transaction is paid for and sent through the first token. Every other
+/// token must share its payer, and their pending memos are included along with
+/// its own. The memos are only consumed if the transaction is processed: they
+/// stay pending when composing or processing fails, and after a simulation.
+/// Instructions are only built when the transaction is processed, so
+/// multisig signers and transfer-hook accounts are resolved against the final
+/// set of signers.
+///
+/// ```
+/// use {
+///     solana_sdk::{pubkey::Pubkey, signature::{Keypair, Signer}},
+///     spl_token_client::{
+///         client::ProgramRpcClientSendTransaction, composer::TokenTransactionComposer,
+///         mock::MockProgramClient, token::Token,
+///     },
+///     spl_token_2022::state::{Account, Mint},
+///     std::sync::Arc,
+/// };
+///
+/// let program_id = spl_token_2022::id();
+/// let (old_mint, new_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+/// let (old_account, new_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+/// let owner = Keypair::new();
+/// let mint_authority = Keypair::new();
+/// let client = Arc::new(
+///     MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+///         .with_mint(&old_mint, &program_id, Mint { decimals: 6, is_initialized: true, ..Mint::default() })
+///         .with_token_account(&old_account, &program_id, Account { mint: old_mint, owner: owner.pubkey(), amount: 50, ..Account::default() }),
+/// );
+/// let payer = Arc::new(Keypair::new());
+/// let old = Token::new(client.clone(), &program_id, &old_mint, Some(6), payer.clone());
+/// let new = Token::new(client.clone(), &program_id, &new_mint, Some(6), payer);
+///
+/// // migrate holdings: burn the old token and mint the new one atomically
+/// futures::executor::block_on(
+///     TokenTransactionComposer::new(&old)
+///         .burn(&old, &old_account, &owner.pubkey(), 50)
+///         .mint_to(&new, &new_account, &mint_authority.pubkey(), 50)
+///         .process(&[&owner, &mint_authority]),
+/// )
+/// .unwrap();
+///
+/// let sent = client.sent_transactions();
+/// assert_eq!(sent.len(), 1);
+/// assert_eq!(sent[0].message.instructions.len(), 2);
+/// ```
+pub struct TokenTransactionComposer<'a, T> {
+    base: &'a Token<T>,
+    operations: Vec<Operation<'a, T>>,
+}
+
+impl<'a, T> TokenTransactionComposer<'a, T>
+where
+    T: SendTransaction + SimulateTransaction,
+{
+    pub fn new(base: &'a Token<T>) -> Self {
+        Self {
+            base,
+            operations: vec![],
+        }
+    }
+
+    pub fn transfer(
+        mut self,
+        token: &'a Token<T>,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Self {
+        self.operations.push(Operation::Transfer {
+            token,
+            source: *source,
+            destination: *destination,
+            authority: *authority,
+            amount,
+        });
+        self
+    }
+
+    pub fn mint_to(
+        mut self,
+        token: &'a Token<T>,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Self {
+        self.operations.push(Operation::MintTo {
+            token,
+            destination: *destination,
+            authority: *authority,
+            amount,
+        });
+        self
+    }
+
+    pub fn burn(
+        mut self,
+        token: &'a Token<T>,
+        source: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Self {
+        self.operations.push(Operation::Burn {
+            token,
+            source: *source,
+            authority: *authority,
+            amount,
+        });
+        self
+    }
+
+    /// Add arbitrary instructions, in order with the other operations. They
+    /// are signed as they are, so only add trusted ones this way, and
+    /// externally supplied ones through `instructions_checked`.
+    pub fn instructions(mut self, instructions: &[Instruction]) -> Self {
+        self.operations
+            .push(Operation::Instructions(instructions.to_vec()));
+        self
+    }
+
+    /// Add externally supplied instructions, in order with the other
+    /// operations, if they all follow the policy, e.g. the one of
+    /// `ClientContext::instruction_policy`. The payer is protected even if
+    /// the policy does not list it.
+    pub fn instructions_checked(
+        self,
+        instructions: &[Instruction],
+        policy: &InstructionPolicy,
+    ) -> Result<Self, Vec<InstructionRejection>> {
+        let policy = policy
+            .clone()
+            .with_protected_account(self.base.payer_pubkey());
+        check_instructions(instructions, &policy)?;
+        Ok(self.instructions(instructions))
+    }
+
+    fn tokens(&self) -> Vec<&'a Token<T>> {
+        let mut tokens: Vec<&'a Token<T>> = vec![];
+        for operation in &self.operations {
+            let token = match operation {
+                Operation::Transfer { token, .. }
+                | Operation::MintTo { token, .. }
+                | Operation::Burn { token, .. } => *token,
+                Operation::Instructions(_) => continue,
+            };
+            if !ptr::eq(token, self.base) && !tokens.iter().any(|t| ptr::eq(*t, token)) {
+                tokens.push(token);
+            }
+        }
+        tokens
+    }
+
+    async fn build_instructions<S: Signers>(
+        &self,
+        signing_keypairs: &S,
+    ) -> TokenResult<Vec<Instruction>> {
+        let tokens = self.tokens();
+        let payer = self.base.payer_pubkey();
+        if tokens.iter().any(|token| token.payer_pubkey() != payer) {
+            return Err(TokenError::PayerMismatch);
+        }
+
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let mut instructions = vec![];
+        // the base token's memo is added when constructing the transaction
+        for token in tokens {
+            if let Some(memo_instruction) = token.take_memo_instruction(signing_keypairs)? {
+                instructions.push(memo_instruction);
+            }
+        }
+
+        for operation in &self.operations {
+            match operation {
+                Operation::Transfer {
+                    token,
+                    source,
+                    destination,
+                    authority,
+                    amount,
+                } => {
+                    token.check_transfer(source, destination, *amount)?;
+                    let decimals = token.get_decimals().await?;
+                    let multisig_signers = token.get_multisig_signers(authority, &signing_pubkeys);
+                    instructions.push(
+                        token
+                            .transfer_checked_instruction(
+                                source,
+                                destination,
+                                authority,
+                                &multisig_signers,
+                                *amount,
+                                decimals,
+                            )
+                            .await?,
+                    );
+                }
+                Operation::MintTo {
+                    token,
+                    destination,
+                    authority,
+                    amount,
+                } => {
+                    let multisig_signers = token.get_multisig_signers(authority, &signing_pubkeys);
+                    instructions.push(token.mint_to_instruction(
+                        destination,
+                        authority,
+                        &multisig_signers,
+                        *amount,
+                    )?);
+                }
+                Operation::Burn {
+                    token,
+                    source,
+                    authority,
+                    amount,
+                } => {
+                    token.check_burn(source, *amount).await?;
+                    let multisig_signers = token.get_multisig_signers(authority, &signing_pubkeys);
+                    instructions.push(token.burn_instruction(
+                        source,
+                        authority,
+                        &multisig_signers,
+                        *amount,
+                    )?);
+                }
+                Operation::Instructions(operation_instructions) => {
+                    instructions.extend_from_slice(operation_instructions);
+                }
+            }
+        }
+        Ok(instructions)
+    }
+
+    /// Pending memos of every token, to put back if they are not sent
+    fn pending_memos(&self) -> Vec<(&'a Token<T>, Option<TokenMemo>)> {
+        iter::once(self.base)
+            .chain(self.tokens())
+            .map(|token| (token, token.pending_memo()))
+            .collect()
+    }
+
+    /// Send all operations in one transaction
+    pub async fn process<S: Signers>(&self, signing_keypairs: &S) -> TokenResult<T::Output> {
+        let memos = self.pending_memos();
+        let output = async {
+            let instructions = self.build_instructions(signing_keypairs).await?;
+            self.base.process_ixs(&instructions, signing_keypairs).await
+        }
+        .await;
+        if output.is_err() {
+            for (token, memo) in memos {
+                token.restore_memo(memo);
+            }
+        }
+        output
+    }
+
+    /// Simulate all operations in one transaction, leaving the memos pending
+    pub async fn simulate<S: Signers>(
+        &self,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::SimulationOutput> {
+        let memos = self.pending_memos();
+        let output = async {
+            let instructions = self.build_instructions(signing_keypairs).await?;
+            self.base
+                .simulate_ixs(&instructions, signing_keypairs)
+                .await
+        }
+        .await;
+        for (token, memo) in memos {
+            token.restore_memo(memo);
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ClientMethod, ProgramRpcClientSendTransaction},
+            mock::MockProgramClient,
+        },
+        solana_sdk::{
+            instruction::AccountMeta, signature::Keypair, signer::Signer, system_instruction,
+        },
+        spl_token_2022::{instruction::TokenInstruction, state::Mint},
+        std::sync::Arc,
+    };
+
+    #[test]
+    fn test_instructions_checked() {
+        let client = Arc::new(MockProgramClient::<ProgramRpcClientSendTransaction>::new());
+        let payer = Arc::new(Keypair::new());
+        let token = Token::new(
+            client,
+            &spl_token_2022::id(),
+            &Pubkey::new_unique(),
+            None,
+            payer.clone(),
+        );
+        let policy = InstructionPolicy::new(&[spl_memo::id()]);
+
+        let memo = spl_memo::build_memo(b"external", &[]);
+        assert!(TokenTransactionComposer::new(&token)
+            .instructions_checked(&[memo.clone()], &policy)
+            .is_ok());
+
+        // draining the payer is rejected, even though the policy does not
+        // protect it
+        let mut drain = spl_memo::build_memo(b"drain", &[]);
+        drain.accounts.push(AccountMeta::new(payer.pubkey(), true));
+        let rejections = TokenTransactionComposer::new(&token)
+            .instructions_checked(&[memo, drain], &policy)
+            .err()
+            .unwrap();
+        assert_eq!(
+            rejections,
+            [
+                InstructionRejection::ProtectedSigner {
+                    index: 1,
+                    account: payer.pubkey(),
+                },
+                InstructionRejection::ProtectedWritable {
+                    index: 1,
+                    account: payer.pubkey(),
+                },
+            ]
+        );
+
+        let transfer =
+            system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000_000);
+        let rejections = TokenTransactionComposer::new(&token)
+            .instructions_checked(&[transfer], &policy)
+            .err()
+            .unwrap();
+        assert!(
+            rejections.contains(&InstructionRejection::ProgramNotAllowed {
+                index: 0,
+                program_id: solana_sdk::system_program::id(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_transfer_checked() {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let client = Arc::new(
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new().with_mint(
+                &mint,
+                &program_id,
+                Mint {
+                    decimals: 3,
+                    is_initialized: true,
+                    ..Mint::default()
+                },
+            ),
+        );
+        // not told its decimals
+        let token = Token::new(
+            client.clone(),
+            &program_id,
+            &mint,
+            None,
+            Arc::new(Keypair::new()),
+        );
+        let authority = Keypair::new();
+
+        futures::executor::block_on(
+            TokenTransactionComposer::new(&token)
+                .transfer(
+                    &token,
+                    &Pubkey::new_unique(),
+                    &Pubkey::new_unique(),
+                    &authority.pubkey(),
+                    5,
+                )
+                .process(&[&authority]),
+        )
+        .unwrap();
+        let sent = client.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            TokenInstruction::unpack(&sent[0].message.instructions[0].data).unwrap(),
+            TokenInstruction::TransferChecked {
+                amount: 5,
+                decimals: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_memos_pending_until_sent() {
+        let program_id = spl_token_2022::id();
+        let client = Arc::new(MockProgramClient::<ProgramRpcClientSendTransaction>::new());
+        let payer = Arc::new(Keypair::new());
+        let token = Token::new(
+            client.clone(),
+            &program_id,
+            &Pubkey::new_unique(),
+            None,
+            payer.clone(),
+        );
+        let other = Token::new(
+            client.clone(),
+            &program_id,
+            &Pubkey::new_unique(),
+            None,
+            payer,
+        );
+        let mint_authority = Keypair::new();
+        token.with_memo("base", vec![]);
+        other.with_memo("other", vec![]);
+        let composer = TokenTransactionComposer::new(&token)
+            .mint_to(&token, &Pubkey::new_unique(), &mint_authority.pubkey(), 1)
+            .mint_to(&other, &Pubkey::new_unique(), &mint_authority.pubkey(), 1);
+
+        futures::executor::block_on(async {
+            composer.simulate(&[&mint_authority]).await.unwrap();
+            assert!(token.pending_memo().is_some());
+            assert!(other.pending_memo().is_some());
+
+            client.fail_next(ClientMethod::SendTransaction, "unavailable");
+            composer.process(&[&mint_authority]).await.unwrap_err();
+            assert!(token.pending_memo().is_some());
+            assert!(other.pending_memo().is_some());
+
+            composer.process(&[&mint_authority]).await.unwrap();
+            assert!(token.pending_memo().is_none());
+            assert!(other.pending_memo().is_none());
+            let sent = client.sent_transactions();
+            assert_eq!(sent.len(), 1);
+            // both memos and both mints
+            assert_eq!(sent[0].message.instructions.len(), 4);
+        });
+    }
+}
@@ -0,0 +1,83 @@
+//! Derivation of the encryption keys of confidential accounts from a wallet
+//! signer, so they never need to be stored
+
+use {
+    crate::token::{TokenError, TokenResult},
+    solana_sdk::{
+        pubkey::Pubkey,
+        signer::{Signer, SignerError},
+    },
+    spl_token_2022::solana_zk_token_sdk::encryption::{
+        auth_encryption::AeKey, elgamal::ElGamalKeypair,
+    },
+};
+
+/// Seed the keys are derived with: the account address, or nothing for the
+/// keys the CLI uses for every account of the signer
+fn public_seed(account: Option<&Pubkey>) -> Vec<u8> {
+    account.map_or_else(Vec::new, |account| account.to_bytes().to_vec())
+}
+
+/// ElGamal keypair of the account, derived from the signature of a fixed
+/// message by its owner. Without an account, the keypair is the one the CLI
+/// derives for all accounts of the signer.
+pub fn derive_elgamal_keypair(
+    signer: &dyn Signer,
+    account: Option<&Pubkey>,
+) -> TokenResult<ElGamalKeypair> {
+    ElGamalKeypair::new_from_signer(signer, &public_seed(account))
+        .map_err(|error| TokenError::Key(SignerError::Custom(error.to_string())))
+}
+
+/// Authenticated encryption key of the account, derived from the signature
+/// of a fixed message by its owner. Without an account, the key is the one
+/// the CLI derives for all accounts of the signer.
+pub fn derive_ae_key(signer: &dyn Signer, account: Option<&Pubkey>) -> TokenResult<AeKey> {
+    AeKey::new_from_signer(signer, &public_seed(account))
+        .map_err(|error| TokenError::Key(SignerError::Custom(error.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::signature::Keypair};
+
+    #[test]
+    fn test_derive_keys() {
+        let owner = Keypair::new();
+        let account = Pubkey::new_unique();
+
+        let elgamal_keypair = derive_elgamal_keypair(&owner, Some(&account)).unwrap();
+        assert_eq!(
+            derive_elgamal_keypair(&owner, Some(&account))
+                .unwrap()
+                .pubkey(),
+            elgamal_keypair.pubkey()
+        );
+        assert_ne!(
+            derive_elgamal_keypair(&owner, Some(&Pubkey::new_unique()))
+                .unwrap()
+                .pubkey(),
+            elgamal_keypair.pubkey()
+        );
+        assert_eq!(
+            derive_elgamal_keypair(&owner, None).unwrap().pubkey(),
+            ElGamalKeypair::new_from_signer(&owner, b"")
+                .unwrap()
+                .pubkey()
+        );
+
+        let ciphertext = derive_ae_key(&owner, Some(&account)).unwrap().encrypt(42);
+        assert_eq!(
+            derive_ae_key(&owner, Some(&account))
+                .unwrap()
+                .decrypt(&ciphertext),
+            Some(42)
+        );
+        assert_eq!(
+            derive_ae_key(&Keypair::new(), Some(&account))
+                .unwrap()
+                .decrypt(&ciphertext),
+            None
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Batched confirmation of in-flight transactions
+
+use {
+    crate::{
+        client::{ProgramClient, ProgramClientResult, SignatureStatus},
+        subscription::SubscriptionClient,
+    },
+    futures::channel::oneshot,
+    solana_program_test::tokio::{sync::Mutex as AsyncMutex, time},
+    solana_sdk::signature::Signature,
+    std::{
+        collections::HashMap,
+        fmt,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+};
+
+/// Maximum number of signatures queried in one status request
+const MAX_SIGNATURES_PER_QUERY: usize = 256;
+
+/// Default interval between status queries
+pub const DEFAULT_CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Default time to wait for a transaction to be confirmed
+pub const DEFAULT_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(90);
+
+type Waiters = HashMap<Signature, Vec<oneshot::Sender<SignatureStatus>>>;
+
+/// Tracks outstanding signatures and confirms all of them with a single status
+/// query per poll, instead of one polling loop per transaction.
+///
+/// There is no background task: whichever waiter is due first performs the
+/// query for everyone, and the others are woken with their results. With a
+/// subscription client, each waiter is also notified as soon as its
+/// transaction is confirmed, and polling only covers missed notifications.
+pub struct ConfirmationService<T> {
+    client: Arc<dyn ProgramClient<T>>,
+    waiters: Mutex<Waiters>,
+    poll_lock: AsyncMutex<()>,
+    poll_interval: Duration,
+    timeout: Duration,
+    subscription_client: Option<Arc<dyn SubscriptionClient>>,
+}
+
+impl<T> fmt::Debug for ConfirmationService<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfirmationService")
+            .field("outstanding", &self.waiters.lock().unwrap().len())
+            .field("poll_interval", &self.poll_interval)
+            .field("timeout", &self.timeout)
+            .field("subscriptions", &self.subscription_client.is_some())
+            .finish()
+    }
+}
+
+impl<T> ConfirmationService<T> {
+    pub fn new(client: Arc<dyn ProgramClient<T>>) -> Self {
+        Self {
+            client,
+            waiters: Mutex::new(HashMap::new()),
+            poll_lock: AsyncMutex::new(()),
+            poll_interval: DEFAULT_CONFIRMATION_POLL_INTERVAL,
+            timeout: DEFAULT_CONFIRMATION_TIMEOUT,
+            subscription_client: None,
+        }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Wait for confirmation notifications instead of only polling
+    pub fn with_subscription_client(
+        mut self,
+        subscription_client: Arc<dyn SubscriptionClient>,
+    ) -> Self {
+        self.subscription_client = Some(subscription_client);
+        self
+    }
+
+    /// Number of signatures still waiting for confirmation
+    pub fn outstanding(&self) -> usize {
+        self.waiters.lock().unwrap().len()
+    }
+
+    /// Wait until the transaction with the given signature is confirmed, or
+    /// has failed, returning its status
+    pub async fn confirm(&self, signature: Signature) -> ProgramClientResult<SignatureStatus> {
+        let (sender, mut receiver) = oneshot::channel();
+        self.waiters
+            .lock()
+            .unwrap()
+            .entry(signature)
+            .or_default()
+            .push(sender);
+        // polling still works if the node cannot be subscribed to
+        let mut subscription = match &self.subscription_client {
+            Some(subscription_client) => subscription_client
+                .subscribe_signature(&signature)
+                .await
+                .ok(),
+            None => None,
+        };
+
+        let start = Instant::now();
+        loop {
+            if let Ok(Some(status)) = receiver.try_recv() {
+                return Ok(status);
+            }
+            if start.elapsed() >= self.timeout {
+                drop(receiver);
+                self.forget(&signature);
+                return Err(format!("Timed out waiting for confirmation of {}", signature).into());
+            }
+
+            if let Ok(_guard) = self.poll_lock.try_lock() {
+                if let Err(error) = self.poll().await {
+                    drop(receiver);
+                    self.forget(&signature);
+                    return Err(error);
+                }
+                if let Ok(Some(status)) = receiver.try_recv() {
+                    return Ok(status);
+                }
+            }
+
+            if let Some(notifications) = subscription.as_mut() {
+                match time::timeout(self.poll_interval, notifications.next()).await {
+                    Ok(Some(status)) => {
+                        self.resolve(&signature, &status);
+                        return Ok(status);
+                    }
+                    Ok(None) => subscription = None,
+                    Err(_) => {}
+                }
+            } else {
+                // woken early if another waiter's poll resolves this signature
+                if let Ok(Ok(status)) = time::timeout(self.poll_interval, &mut receiver).await {
+                    return Ok(status);
+                }
+            }
+        }
+    }
+
+    /// Query the status of every outstanding signature once, resolving the
+    /// ones that are confirmed or failed
+    pub async fn poll(&self) -> ProgramClientResult<()> {
+        let signatures = {
+            let mut waiters = self.waiters.lock().unwrap();
+            // waiters whose `confirm` future was dropped, e.g. by a timeout
+            waiters.retain(|_, senders| {
+                senders.retain(|sender| !sender.is_canceled());
+                !senders.is_empty()
+            });
+            waiters.keys().copied().collect::<Vec<_>>()
+        };
+
+        for chunk in signatures.chunks(MAX_SIGNATURES_PER_QUERY) {
+            let statuses = self.client.get_signature_statuses(chunk).await?;
+            let mut waiters = self.waiters.lock().unwrap();
+            for (signature, status) in chunk.iter().zip(statuses) {
+                let Some(status) = status else {
+                    continue;
+                };
+                if status.confirmed || status.err.is_some() {
+                    for sender in waiters.remove(signature).unwrap_or_default() {
+                        let _ = sender.send(status.clone());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Wake every waiter of a signature whose status was notified
+    fn resolve(&self, signature: &Signature, status: &SignatureStatus) {
+        let senders = self.waiters.lock().unwrap().remove(signature);
+        for sender in senders.unwrap_or_default() {
+            let _ = sender.send(status.clone());
+        }
+    }
+
+    fn forget(&self, signature: &Signature) {
+        let mut waiters = self.waiters.lock().unwrap();
+        if let Some(senders) = waiters.get_mut(signature) {
+            senders.retain(|sender| !sender.is_canceled());
+            if senders.is_empty() {
+                waiters.remove(signature);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{client::ProgramRpcClientSendTransaction, mock::MockProgramClient},
+        solana_program_test::tokio,
+        solana_sdk::{
+            hash::Hash, pubkey::Pubkey, signature::Keypair, signer::Signer, system_instruction,
+            transaction::Transaction,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_concurrent_waiters() {
+        // the first waiter's poll is still in flight when the second waits
+        let client = Arc::new(
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+                .with_latency(Duration::from_millis(50)),
+        );
+        let payer = Keypair::new();
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        );
+        client.send_transaction(&transaction).await.unwrap();
+
+        let service = ConfirmationService::new(client.clone())
+            .with_poll_interval(Duration::from_secs(1))
+            .with_timeout(Duration::from_secs(5));
+        let signature = transaction.signatures[0];
+        let (first, second) =
+            futures::join!(service.confirm(signature), service.confirm(signature));
+        assert!(first.unwrap().confirmed);
+        assert!(second.unwrap().confirmed);
+        assert_eq!(service.outstanding(), 0);
+    }
+}
@@ -0,0 +1,355 @@
+//! Shared client context with cluster-specific defaults
+
+use {
+    crate::{
+        client::{ProgramClient, SendTransaction, SimulateTransaction},
+        confirmation::ConfirmationService,
+        rent::RentCache,
+        sanitize::InstructionPolicy,
+        subscription::SubscriptionClient,
+        token::{FeeEscalation, Token},
+    },
+    solana_rpc_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{
+        commitment_config::CommitmentConfig, compute_budget, pubkey::Pubkey, signer::Signer,
+        system_program,
+    },
+    std::{fmt, sync::Arc, time::Duration},
+};
+
+/// Cluster a client talks to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Cluster {
+    Localnet,
+    Devnet,
+    Testnet,
+    MainnetBeta,
+}
+impl Cluster {
+    /// Guess the cluster from an RPC url, defaulting to localnet for unknown
+    /// hosts
+    pub fn from_url(url: &str) -> Self {
+        if url.contains("devnet") {
+            Self::Devnet
+        } else if url.contains("testnet") {
+            Self::Testnet
+        } else if url.contains("mainnet") {
+            Self::MainnetBeta
+        } else {
+            Self::Localnet
+        }
+    }
+}
+
+/// Defaults for every knob of the client that depends on the cluster
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClusterProfile {
+    pub cluster: Cluster,
+    /// Commitment used for reads, by the RPC client and the tokens of the
+    /// context
+    pub commitment: CommitmentConfig,
+    /// Compute-unit price, in micro-lamports, added to every transaction
+    pub compute_unit_price: Option<u64>,
+    /// Retry policy for transactions that fail to confirm in time, used by
+    /// `Token::process_ixs_with_fee_escalation` through
+    /// `Token::fee_escalation`
+    pub fee_escalation: FeeEscalation,
+    /// How long to wait for a new blockhash, and how often to ask for one
+    pub blockhash_poll_timeout: Duration,
+    pub blockhash_poll_interval: Duration,
+    /// How long to wait for a sent transaction to be confirmed, and how often
+    /// to query its status
+    pub confirmation_timeout: Duration,
+    pub confirmation_poll_interval: Duration,
+    /// Programs expected to be invoked by this client
+    pub known_program_ids: Vec<Pubkey>,
+}
+
+impl ClusterProfile {
+    pub fn localnet() -> Self {
+        Self {
+            cluster: Cluster::Localnet,
+            commitment: CommitmentConfig::confirmed(),
+            compute_unit_price: None,
+            fee_escalation: FeeEscalation {
+                compute_unit_prices: vec![0],
+                confirmation_timeout: Duration::from_secs(30),
+            },
+            blockhash_poll_timeout: Duration::from_secs(5),
+            blockhash_poll_interval: Duration::from_millis(100),
+            confirmation_timeout: Duration::from_secs(30),
+            confirmation_poll_interval: Duration::from_millis(100),
+            known_program_ids: default_known_program_ids(),
+        }
+    }
+
+    pub fn devnet() -> Self {
+        Self {
+            cluster: Cluster::Devnet,
+            commitment: CommitmentConfig::confirmed(),
+            compute_unit_price: None,
+            fee_escalation: FeeEscalation {
+                compute_unit_prices: vec![0, 1_000, 10_000],
+                confirmation_timeout: Duration::from_secs(30),
+            },
+            blockhash_poll_timeout: Duration::from_secs(5),
+            blockhash_poll_interval: Duration::from_millis(200),
+            confirmation_timeout: Duration::from_secs(60),
+            confirmation_poll_interval: Duration::from_millis(500),
+            known_program_ids: default_known_program_ids(),
+        }
+    }
+
+    pub fn testnet() -> Self {
+        Self {
+            cluster: Cluster::Testnet,
+            ..Self::devnet()
+        }
+    }
+
+    pub fn mainnet_beta() -> Self {
+        Self {
+            cluster: Cluster::MainnetBeta,
+            commitment: CommitmentConfig::confirmed(),
+            compute_unit_price: Some(1_000),
+            fee_escalation: FeeEscalation {
+                compute_unit_prices: vec![1_000, 10_000, 100_000, 1_000_000],
+                confirmation_timeout: Duration::from_secs(20),
+            },
+            blockhash_poll_timeout: Duration::from_secs(10),
+            blockhash_poll_interval: Duration::from_millis(200),
+            confirmation_timeout: Duration::from_secs(90),
+            confirmation_poll_interval: Duration::from_millis(500),
+            known_program_ids: default_known_program_ids(),
+        }
+    }
+
+    pub fn for_cluster(cluster: Cluster) -> Self {
+        match cluster {
+            Cluster::Localnet => Self::localnet(),
+            Cluster::Devnet => Self::devnet(),
+            Cluster::Testnet => Self::testnet(),
+            Cluster::MainnetBeta => Self::mainnet_beta(),
+        }
+    }
+
+    /// Profile for the cluster guessed from an RPC url
+    pub fn from_url(url: &str) -> Self {
+        Self::for_cluster(Cluster::from_url(url))
+    }
+
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn with_compute_unit_price(mut self, compute_unit_price: Option<u64>) -> Self {
+        self.compute_unit_price = compute_unit_price;
+        self
+    }
+
+    pub fn with_fee_escalation(mut self, fee_escalation: FeeEscalation) -> Self {
+        self.fee_escalation = fee_escalation;
+        self
+    }
+
+    pub fn with_known_program_id(mut self, program_id: Pubkey) -> Self {
+        if !self.known_program_ids.contains(&program_id) {
+            self.known_program_ids.push(program_id);
+        }
+        self
+    }
+
+    /// RPC client using the profile's commitment
+    pub fn rpc_client(&self, url: String) -> Arc<RpcClient> {
+        Arc::new(RpcClient::new_with_commitment(url, self.commitment))
+    }
+
+    /// Instruction policy allowing only the known programs
+    pub fn instruction_policy(&self) -> InstructionPolicy {
+        InstructionPolicy::new(&self.known_program_ids)
+    }
+}
+
+fn default_known_program_ids() -> Vec<Pubkey> {
+    vec![
+        system_program::id(),
+        compute_budget::id(),
+        spl_token::id(),
+        spl_token_2022::id(),
+        spl_associated_token_account::id(),
+        spl_memo::id(),
+    ]
+}
+
+/// Client, payer and cluster profile shared by every token a service works
+/// with
+pub struct ClientContext<T> {
+    client: Arc<dyn ProgramClient<T>>,
+    payer: Arc<dyn Signer>,
+    profile: ClusterProfile,
+    confirmation_service: Arc<ConfirmationService<T>>,
+    rent_cache: Arc<RentCache>,
+    subscription_client: Option<Arc<dyn SubscriptionClient>>,
+}
+
+impl<T> fmt::Debug for ClientContext<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientContext")
+            .field("payer", &self.payer.pubkey())
+            .field("profile", &self.profile)
+            .finish()
+    }
+}
+
+impl<T> ClientContext<T>
+where
+    T: SendTransaction + SimulateTransaction,
+{
+    pub fn new(
+        client: Arc<dyn ProgramClient<T>>,
+        payer: Arc<dyn Signer>,
+        profile: ClusterProfile,
+    ) -> Self {
+        let confirmation_service = Arc::new(
+            ConfirmationService::new(client.clone())
+                .with_poll_interval(profile.confirmation_poll_interval)
+                .with_timeout(profile.confirmation_timeout),
+        );
+        Self {
+            client,
+            payer,
+            profile,
+            confirmation_service,
+            rent_cache: Arc::new(RentCache::new()),
+            subscription_client: None,
+        }
+    }
+
+    /// Confirm transactions and watch accounts with pushed notifications
+    pub fn with_subscription_client(
+        mut self,
+        subscription_client: Arc<dyn SubscriptionClient>,
+    ) -> Self {
+        self.confirmation_service = Arc::new(
+            ConfirmationService::new(self.client.clone())
+                .with_poll_interval(self.profile.confirmation_poll_interval)
+                .with_timeout(self.profile.confirmation_timeout)
+                .with_subscription_client(subscription_client.clone()),
+        );
+        self.subscription_client = Some(subscription_client);
+        self
+    }
+
+    pub fn client(&self) -> &Arc<dyn ProgramClient<T>> {
+        &self.client
+    }
+
+    pub fn payer(&self) -> &Arc<dyn Signer> {
+        &self.payer
+    }
+
+    pub fn profile(&self) -> &ClusterProfile {
+        &self.profile
+    }
+
+    pub fn confirmation_service(&self) -> &Arc<ConfirmationService<T>> {
+        &self.confirmation_service
+    }
+
+    pub fn rent_cache(&self) -> &Arc<RentCache> {
+        &self.rent_cache
+    }
+
+    /// Token configured with the profile's defaults
+    pub fn token(&self, program_id: &Pubkey, address: &Pubkey, decimals: Option<u8>) -> Token<T> {
+        let token = Token::new(
+            self.client.clone(),
+            program_id,
+            address,
+            decimals,
+            self.payer.clone(),
+        )
+        .with_blockhash_polling(
+            self.profile.blockhash_poll_timeout,
+            self.profile.blockhash_poll_interval,
+        )
+        .with_confirmation_service(self.confirmation_service.clone())
+        .with_rent_cache(self.rent_cache.clone())
+        .with_read_commitment(self.profile.commitment)
+        .with_fee_escalation(self.profile.fee_escalation.clone());
+        let token = match &self.subscription_client {
+            Some(subscription_client) => {
+                token.with_subscription_client(subscription_client.clone())
+            }
+            None => token,
+        };
+        match self.profile.compute_unit_price {
+            Some(compute_unit_price) => token.with_compute_unit_price(compute_unit_price),
+            None => token,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{client::ProgramRpcClientSendTransaction, mock::MockProgramClient},
+        solana_sdk::signature::Keypair,
+    };
+
+    #[test]
+    fn test_cluster_from_url() {
+        assert_eq!(
+            Cluster::from_url("https://api.devnet.solana.com"),
+            Cluster::Devnet
+        );
+        assert_eq!(
+            Cluster::from_url("https://api.testnet.solana.com"),
+            Cluster::Testnet
+        );
+        assert_eq!(
+            Cluster::from_url("https://api.mainnet-beta.solana.com"),
+            Cluster::MainnetBeta
+        );
+        assert_eq!(
+            Cluster::from_url("http://127.0.0.1:8899"),
+            Cluster::Localnet
+        );
+    }
+
+    #[test]
+    fn test_profile_overrides() {
+        let program_id = Pubkey::new_unique();
+        let profile = ClusterProfile::mainnet_beta()
+            .with_compute_unit_price(None)
+            .with_known_program_id(program_id)
+            .with_known_program_id(spl_token::id());
+        assert_eq!(profile.compute_unit_price, None);
+        assert_eq!(
+            profile.known_program_ids.len(),
+            default_known_program_ids().len() + 1
+        );
+        assert_eq!(ClusterProfile::testnet().cluster, Cluster::Testnet);
+    }
+
+    #[test]
+    fn test_token_uses_profile() {
+        let fee_escalation = FeeEscalation {
+            compute_unit_prices: vec![5, 50],
+            confirmation_timeout: Duration::from_secs(1),
+        };
+        let context = ClientContext::<ProgramRpcClientSendTransaction>::new(
+            Arc::new(MockProgramClient::new()),
+            Arc::new(Keypair::new()),
+            ClusterProfile::devnet()
+                .with_commitment(CommitmentConfig::finalized())
+                .with_fee_escalation(fee_escalation.clone()),
+        );
+        let token = context.token(&spl_token_2022::id(), &Pubkey::new_unique(), None);
+        assert_eq!(token.fee_escalation(), &fee_escalation);
+        assert!(format!("{token:?}")
+            .contains("read_commitment: Some(CommitmentConfig { commitment: Finalized })"));
+    }
+}
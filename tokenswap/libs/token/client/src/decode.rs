@@ -0,0 +1,129 @@
+//! Decoding of token program accounts whose type is not known in advance,
+//! e.g. raw account updates received by an indexer
+
+use {
+    solana_sdk::{program_error::ProgramError, program_pack::Pack},
+    spl_token_2022::{
+        extension::StateWithExtensionsOwned,
+        state::{Account, Mint, Multisig},
+    },
+};
+
+/// Any initialized account of the token programs
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenProgramAccount {
+    Mint(StateWithExtensionsOwned<Mint>),
+    Account(StateWithExtensionsOwned<Account>),
+    Multisig(Multisig),
+}
+
+/// Detect the type of a token program account from its data and unpack it,
+/// with its extensions if any. Works for accounts of both token programs.
+pub fn decode_token_program_account(data: &[u8]) -> Result<TokenProgramAccount, ProgramError> {
+    // accounts with extensions are never sized like a multisig
+    if data.len() == Multisig::LEN {
+        return Multisig::unpack(data).map(TokenProgramAccount::Multisig);
+    }
+    // the padding of a mint is never an initialized account state
+    if let Ok(account) = StateWithExtensionsOwned::<Account>::unpack(data.to_vec()) {
+        return Ok(TokenProgramAccount::Account(account));
+    }
+    StateWithExtensionsOwned::<Mint>::unpack(data.to_vec())
+        .map(TokenProgramAccount::Mint)
+        .map_err(|_| ProgramError::InvalidAccountData)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::{program_option::COption, pubkey::Pubkey},
+        spl_token_2022::{
+            extension::{
+                mint_close_authority::MintCloseAuthority, BaseStateWithExtensions, ExtensionType,
+                StateWithExtensionsMut,
+            },
+            state::AccountState,
+        },
+    };
+
+    #[test]
+    fn test_decode() {
+        let mint = Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Mint::default()
+        };
+        let mut data = vec![0; Mint::LEN];
+        mint.pack_into_slice(&mut data);
+        match decode_token_program_account(&data).unwrap() {
+            TokenProgramAccount::Mint(decoded) => assert_eq!(decoded.base, mint),
+            decoded => panic!("decoded {:?}", decoded),
+        }
+
+        let account = Account {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount: 42,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let mut data = vec![0; Account::LEN];
+        account.pack_into_slice(&mut data);
+        match decode_token_program_account(&data).unwrap() {
+            TokenProgramAccount::Account(decoded) => assert_eq!(decoded.base, account),
+            decoded => panic!("decoded {:?}", decoded),
+        }
+
+        let multisig = Multisig {
+            m: 1,
+            n: 1,
+            is_initialized: true,
+            ..Multisig::default()
+        };
+        let mut data = vec![0; Multisig::LEN];
+        multisig.pack_into_slice(&mut data);
+        assert_eq!(
+            decode_token_program_account(&data),
+            Ok(TokenProgramAccount::Multisig(multisig))
+        );
+
+        assert_eq!(
+            decode_token_program_account(&[1; 10]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_decode_mint_with_extensions() {
+        let close_authority = Pubkey::new_unique();
+        let len =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::MintCloseAuthority])
+                .unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.base = Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            is_initialized: true,
+            ..Mint::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        state
+            .init_extension::<MintCloseAuthority>(true)
+            .unwrap()
+            .close_authority = Some(close_authority).try_into().unwrap();
+
+        let TokenProgramAccount::Mint(mint) = decode_token_program_account(&data).unwrap() else {
+            panic!("not decoded as a mint");
+        };
+        assert_eq!(
+            Option::<Pubkey>::from(
+                mint.get_extension::<MintCloseAuthority>()
+                    .unwrap()
+                    .close_authority
+            ),
+            Some(close_authority)
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Projection of the costs of a planned distribution of tokens
+
+use solana_sdk::{
+    instruction::AccountMeta, pubkey::Pubkey, transaction::TransactionError,
+    transaction_context::TransactionReturnData,
+};
+
+/// Hypothetical transfer, in base units of the mint
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedTransfer {
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+}
+
+/// Set of hypothetical transfers of one mint
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DistributionPlan {
+    pub transfers: Vec<PlannedTransfer>,
+}
+
+impl DistributionPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn transfer(
+        mut self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Self {
+        self.transfers.push(PlannedTransfer {
+            source: *source,
+            destination: *destination,
+            authority: *authority,
+            amount,
+        });
+        self
+    }
+}
+
+/// Projected outcome of one planned transfer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferProjection {
+    pub transfer: PlannedTransfer,
+    /// Transfer fee withheld from the amount, at the current epoch's rate
+    pub transfer_fee: u64,
+    /// Program invoked by the mint's transfer hook, if any
+    pub hook_program_id: Option<Pubkey>,
+    /// Extra accounts the transfer hook requires
+    pub hook_accounts: Vec<AccountMeta>,
+    /// Compute units consumed in simulation, if the client can simulate
+    pub units_consumed: Option<u64>,
+    /// Signature and priority fees, in lamports
+    pub network_fee: u64,
+    /// Program logs, including the transfer hook's
+    pub logs: Vec<String>,
+    /// Data returned by the last program invoked
+    pub return_data: Option<TransactionReturnData>,
+    /// Error the transfer would fail with against the current state
+    pub error: Option<TransactionError>,
+}
+
+/// Projected revenue and costs of a distribution plan.
+///
+/// Each transfer is simulated on its own against the current state, so
+/// transfers that depend on earlier ones in the plan may be reported as
+/// failing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DistributionProjection {
+    pub transfers: Vec<TransferProjection>,
+    /// Compute-unit price, in micro-lamports, used for the network fees
+    pub compute_unit_price: Option<u64>,
+    pub total_transfer_fees: u64,
+    pub total_network_fees: u64,
+}
+
+impl DistributionProjection {
+    pub fn new(transfers: Vec<TransferProjection>, compute_unit_price: Option<u64>) -> Self {
+        let total_transfer_fees = transfers
+            .iter()
+            .filter(|projection| projection.error.is_none())
+            .fold(0u64, |total, projection| {
+                total.saturating_add(projection.transfer_fee)
+            });
+        // failed transactions are still charged
+        let total_network_fees = transfers.iter().fold(0u64, |total, projection| {
+            total.saturating_add(projection.network_fee)
+        });
+        Self {
+            transfers,
+            compute_unit_price,
+            total_transfer_fees,
+            total_network_fees,
+        }
+    }
+
+    /// Transfers that would fail against the current state
+    pub fn failed(&self) -> impl Iterator<Item = &TransferProjection> {
+        self.transfers
+            .iter()
+            .filter(|projection| projection.error.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::instruction::InstructionError};
+
+    fn projection(
+        amount: u64,
+        transfer_fee: u64,
+        error: Option<TransactionError>,
+    ) -> TransferProjection {
+        let key = Pubkey::new_unique();
+        TransferProjection {
+            transfer: PlannedTransfer {
+                source: key,
+                destination: key,
+                authority: key,
+                amount,
+            },
+            transfer_fee,
+            hook_program_id: None,
+            hook_accounts: vec![],
+            units_consumed: Some(6_000),
+            network_fee: 5_000,
+            logs: vec![],
+            return_data: None,
+            error,
+        }
+    }
+
+    #[test]
+    fn test_projection_totals() {
+        let projection = DistributionProjection::new(
+            vec![
+                projection(1_000, 10, None),
+                projection(2_000, 20, None),
+                projection(
+                    3_000,
+                    30,
+                    Some(TransactionError::InstructionError(
+                        0,
+                        InstructionError::InsufficientFunds,
+                    )),
+                ),
+            ],
+            None,
+        );
+        assert_eq!(projection.total_transfer_fees, 30);
+        assert_eq!(projection.total_network_fees, 15_000);
+        assert_eq!(projection.failed().count(), 1);
+    }
+}
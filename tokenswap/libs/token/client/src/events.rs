@@ -0,0 +1,1130 @@
+//! Decoded token events, and their export for analytics
+
+use {
+    crate::{
+        client::{SendTransaction, SimulateTransaction, TransactionRecord},
+        token::{Token, TokenError, TokenResult},
+    },
+    futures::StreamExt,
+    solana_sdk::{
+        clock::{Slot, UnixTimestamp},
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::Signature,
+    },
+    spl_token_2022::{
+        extension::{transfer_fee::instruction::TransferFeeInstruction, StateWithExtensions},
+        instruction::TokenInstruction,
+        state::Account,
+    },
+    std::{collections::HashMap, fmt, io, ops::Range},
+};
+
+/// Kind of a decoded token instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenEventKind {
+    InitializeAccount,
+    Transfer,
+    MintTo,
+    Burn,
+    Approve,
+    Revoke,
+    CloseAccount,
+    FreezeAccount,
+    ThawAccount,
+}
+
+impl fmt::Display for TokenEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::InitializeAccount => "initialize_account",
+            Self::Transfer => "transfer",
+            Self::MintTo => "mint_to",
+            Self::Burn => "burn",
+            Self::Approve => "approve",
+            Self::Revoke => "revoke",
+            Self::CloseAccount => "close_account",
+            Self::FreezeAccount => "freeze_account",
+            Self::ThawAccount => "thaw_account",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Token instruction executed by a successful transaction.
+///
+/// `source` is the account debited or acted upon, and `destination` the
+/// account credited, the delegate of an approval, or the recipient of a
+/// closed account's lamports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenEvent {
+    pub signature: Signature,
+    pub slot: Slot,
+    pub block_time: Option<UnixTimestamp>,
+    pub instruction_index: u32,
+    /// Position among the instructions invoked by the top-level instruction
+    pub inner_instruction_index: Option<u32>,
+    pub kind: TokenEventKind,
+    /// Not known for instructions that do not take the mint
+    pub mint: Option<Pubkey>,
+    pub source: Option<Pubkey>,
+    pub destination: Option<Pubkey>,
+    pub authority: Option<Pubkey>,
+    pub amount: Option<u64>,
+    pub decimals: Option<u8>,
+    pub fee: Option<u64>,
+}
+
+/// Columns of every export, in order
+pub const EVENT_COLUMNS: [&str; 14] = [
+    "signature",
+    "slot",
+    "block_time",
+    "instruction_index",
+    "inner_instruction_index",
+    "kind",
+    "mint",
+    "source",
+    "destination",
+    "authority",
+    "amount",
+    "decimals",
+    "fee",
+    "program_id",
+];
+
+struct DecodedInstruction {
+    kind: TokenEventKind,
+    mint: Option<Pubkey>,
+    source: Option<Pubkey>,
+    destination: Option<Pubkey>,
+    authority: Option<Pubkey>,
+    amount: Option<u64>,
+    decimals: Option<u8>,
+    fee: Option<u64>,
+}
+
+#[allow(deprecated)]
+fn decode_instruction(instruction: &Instruction) -> Option<DecodedInstruction> {
+    let account = |index: usize| instruction.accounts.get(index).map(|meta| meta.pubkey);
+    let decoded = |kind, mint, source, destination, authority, amount, decimals| {
+        Some(DecodedInstruction {
+            kind,
+            mint,
+            source,
+            destination,
+            authority,
+            amount,
+            decimals,
+            fee: None,
+        })
+    };
+    match TokenInstruction::unpack(&instruction.data).ok()? {
+        TokenInstruction::InitializeAccount => decoded(
+            TokenEventKind::InitializeAccount,
+            account(1),
+            account(0),
+            None,
+            account(2),
+            None,
+            None,
+        ),
+        TokenInstruction::InitializeAccount2 { owner }
+        | TokenInstruction::InitializeAccount3 { owner } => decoded(
+            TokenEventKind::InitializeAccount,
+            account(1),
+            account(0),
+            None,
+            Some(owner),
+            None,
+            None,
+        ),
+        TokenInstruction::Transfer { amount } => decoded(
+            TokenEventKind::Transfer,
+            None,
+            account(0),
+            account(1),
+            account(2),
+            Some(amount),
+            None,
+        ),
+        TokenInstruction::TransferChecked { amount, decimals } => decoded(
+            TokenEventKind::Transfer,
+            account(1),
+            account(0),
+            account(2),
+            account(3),
+            Some(amount),
+            Some(decimals),
+        ),
+        TokenInstruction::TransferFeeExtension(
+            TransferFeeInstruction::TransferCheckedWithFee {
+                amount,
+                decimals,
+                fee,
+            },
+        ) => Some(DecodedInstruction {
+            kind: TokenEventKind::Transfer,
+            mint: account(1),
+            source: account(0),
+            destination: account(2),
+            authority: account(3),
+            amount: Some(amount),
+            decimals: Some(decimals),
+            fee: Some(fee),
+        }),
+        TokenInstruction::MintTo { amount } => decoded(
+            TokenEventKind::MintTo,
+            account(0),
+            None,
+            account(1),
+            account(2),
+            Some(amount),
+            None,
+        ),
+        TokenInstruction::MintToChecked { amount, decimals } => decoded(
+            TokenEventKind::MintTo,
+            account(0),
+            None,
+            account(1),
+            account(2),
+            Some(amount),
+            Some(decimals),
+        ),
+        TokenInstruction::Burn { amount } => decoded(
+            TokenEventKind::Burn,
+            account(1),
+            account(0),
+            None,
+            account(2),
+            Some(amount),
+            None,
+        ),
+        TokenInstruction::BurnChecked { amount, decimals } => decoded(
+            TokenEventKind::Burn,
+            account(1),
+            account(0),
+            None,
+            account(2),
+            Some(amount),
+            Some(decimals),
+        ),
+        TokenInstruction::Approve { amount } => decoded(
+            TokenEventKind::Approve,
+            None,
+            account(0),
+            account(1),
+            account(2),
+            Some(amount),
+            None,
+        ),
+        TokenInstruction::ApproveChecked { amount, decimals } => decoded(
+            TokenEventKind::Approve,
+            account(1),
+            account(0),
+            account(2),
+            account(3),
+            Some(amount),
+            Some(decimals),
+        ),
+        TokenInstruction::Revoke => decoded(
+            TokenEventKind::Revoke,
+            None,
+            account(0),
+            None,
+            account(1),
+            None,
+            None,
+        ),
+        TokenInstruction::CloseAccount => decoded(
+            TokenEventKind::CloseAccount,
+            None,
+            account(0),
+            account(1),
+            account(2),
+            None,
+            None,
+        ),
+        TokenInstruction::FreezeAccount => decoded(
+            TokenEventKind::FreezeAccount,
+            account(1),
+            account(0),
+            None,
+            account(2),
+            None,
+            None,
+        ),
+        TokenInstruction::ThawAccount => decoded(
+            TokenEventKind::ThawAccount,
+            account(1),
+            account(0),
+            None,
+            account(2),
+            None,
+            None,
+        ),
+        _ => None,
+    }
+}
+
+/// Events of a successful transaction for the given token program, skipping
+/// those of other mints.
+///
+/// Events whose instruction does not take the mint, like unchecked transfers,
+/// get the mint of another event of the transaction on the same account. Those
+/// still without a mint are kept, with `mint` set to `None`.
+pub fn decode_events(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    record: &TransactionRecord,
+) -> Vec<TokenEvent> {
+    if record.err.is_some() {
+        return vec![];
+    }
+    let top_level = record
+        .instructions
+        .iter()
+        .enumerate()
+        .map(|(index, instruction)| (index, None, instruction));
+    let inner = record
+        .inner_instructions
+        .iter()
+        .enumerate()
+        .flat_map(|(index, instructions)| {
+            instructions
+                .iter()
+                .enumerate()
+                .map(move |(inner_index, instruction)| (index, Some(inner_index), instruction))
+        });
+    let mut events = top_level
+        .chain(inner)
+        .filter(|(_, _, instruction)| instruction.program_id == *program_id)
+        .filter_map(|(index, inner_index, instruction)| {
+            let decoded = decode_instruction(instruction)?;
+            Some(TokenEvent {
+                signature: record.signature,
+                slot: record.slot,
+                block_time: record.block_time,
+                instruction_index: index as u32,
+                inner_instruction_index: inner_index.map(|inner_index| inner_index as u32),
+                kind: decoded.kind,
+                mint: decoded.mint,
+                source: decoded.source,
+                destination: decoded.destination,
+                authority: decoded.authority,
+                amount: decoded.amount,
+                decimals: decoded.decimals,
+                fee: decoded.fee,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut account_mints = HashMap::new();
+    for event in &events {
+        if let Some(event_mint) = event.mint {
+            for account in token_accounts(event) {
+                account_mints.entry(account).or_insert(event_mint);
+            }
+        }
+    }
+    for event in events.iter_mut().filter(|event| event.mint.is_none()) {
+        event.mint = token_accounts(event).find_map(|account| account_mints.get(&account).copied());
+    }
+    events.retain(|event| event.mint.map_or(true, |event_mint| event_mint == *mint));
+
+    // inner instructions run within their top-level instruction
+    events.sort_by_key(|event| (event.instruction_index, event.inner_instruction_index));
+    events
+}
+
+/// Token accounts an event names, which all belong to its mint
+fn token_accounts(event: &TokenEvent) -> impl Iterator<Item = Pubkey> {
+    let destination = match event.kind {
+        TokenEventKind::Transfer | TokenEventKind::MintTo => event.destination,
+        // the delegate, or the recipient of the lamports
+        _ => None,
+    };
+    event.source.into_iter().chain(destination)
+}
+
+/// A token operation as seen from one account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountOperationKind {
+    TransferIn,
+    TransferOut,
+    MintTo,
+    Burn,
+    /// Transfer fee withheld in the account from a transfer it received
+    FeeWithheld,
+}
+
+/// An operation changing the balance of a token account. The amount of a
+/// `TransferIn` is the amount credited, net of the fee withheld, which the
+/// `FeeWithheld` operation that follows it reports, if any.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountOperation {
+    pub signature: Signature,
+    pub slot: Slot,
+    pub block_time: Option<UnixTimestamp>,
+    pub kind: AccountOperationKind,
+    /// The other account of a transfer
+    pub counterparty: Option<Pubkey>,
+    pub amount: u64,
+}
+
+/// Operations of the events changing the balance of the account
+pub fn account_operations(address: &Pubkey, events: &[TokenEvent]) -> Vec<AccountOperation> {
+    let mut operations = vec![];
+    for event in events {
+        let Some(amount) = event.amount else {
+            continue;
+        };
+        let mut push = |kind, counterparty, amount| {
+            operations.push(AccountOperation {
+                signature: event.signature,
+                slot: event.slot,
+                block_time: event.block_time,
+                kind,
+                counterparty,
+                amount,
+            })
+        };
+        let is_source = event.source == Some(*address);
+        let is_destination = event.destination == Some(*address);
+        match event.kind {
+            TokenEventKind::Transfer => {
+                if is_source {
+                    push(AccountOperationKind::TransferOut, event.destination, amount);
+                }
+                if is_destination {
+                    // the fee is withheld from what the destination receives
+                    let fee = event.fee.unwrap_or(0);
+                    push(
+                        AccountOperationKind::TransferIn,
+                        event.source,
+                        amount.saturating_sub(fee),
+                    );
+                    if fee > 0 {
+                        push(AccountOperationKind::FeeWithheld, None, fee);
+                    }
+                }
+            }
+            TokenEventKind::MintTo if is_destination => {
+                push(AccountOperationKind::MintTo, None, amount)
+            }
+            TokenEventKind::Burn if is_source => push(AccountOperationKind::Burn, None, amount),
+            _ => {}
+        }
+    }
+    operations
+}
+
+/// Format of an export
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row, empty for missing values
+    Csv,
+    /// Parquet, with one row group per page of history
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+trait EventWriter {
+    fn write_events(&mut self, program_id: &Pubkey, events: &[TokenEvent]) -> io::Result<()>;
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+struct CsvWriter<W> {
+    writer: W,
+}
+
+impl<W: io::Write> CsvWriter<W> {
+    fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", EVENT_COLUMNS.join(","))?;
+        Ok(Self { writer })
+    }
+}
+
+fn csv_field<V: ToString>(value: Option<V>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+impl<W: io::Write> EventWriter for CsvWriter<W> {
+    fn write_events(&mut self, program_id: &Pubkey, events: &[TokenEvent]) -> io::Result<()> {
+        for event in events {
+            writeln!(
+                self.writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                event.signature,
+                event.slot,
+                csv_field(event.block_time),
+                event.instruction_index,
+                csv_field(event.inner_instruction_index),
+                event.kind,
+                csv_field(event.mint),
+                csv_field(event.source),
+                csv_field(event.destination),
+                csv_field(event.authority),
+                csv_field(event.amount),
+                csv_field(event.decimals),
+                csv_field(event.fee),
+                program_id,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_writer {
+    use {
+        super::{EventWriter, TokenEvent, EVENT_COLUMNS},
+        arrow_array::{
+            ArrayRef, Int64Array, RecordBatch, StringArray, UInt32Array, UInt64Array, UInt8Array,
+        },
+        arrow_schema::{DataType, Field, Schema, SchemaRef},
+        parquet::arrow::ArrowWriter,
+        solana_sdk::pubkey::Pubkey,
+        std::{io, sync::Arc},
+    };
+
+    fn schema() -> SchemaRef {
+        let data_types = [
+            (DataType::Utf8, false),
+            (DataType::UInt64, false),
+            (DataType::Int64, true),
+            (DataType::UInt32, false),
+            (DataType::UInt32, true),
+            (DataType::Utf8, false),
+            (DataType::Utf8, true),
+            (DataType::Utf8, true),
+            (DataType::Utf8, true),
+            (DataType::Utf8, true),
+            (DataType::UInt64, true),
+            (DataType::UInt8, true),
+            (DataType::UInt64, true),
+            (DataType::Utf8, false),
+        ];
+        Arc::new(Schema::new(
+            EVENT_COLUMNS
+                .iter()
+                .zip(data_types)
+                .map(|(name, (data_type, nullable))| Field::new(*name, data_type, nullable))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    fn to_io_error<E: std::error::Error + Send + Sync + 'static>(error: E) -> io::Error {
+        io::Error::new(io::ErrorKind::Other, error)
+    }
+
+    pub(super) struct ParquetWriter<W: io::Write + Send> {
+        writer: ArrowWriter<W>,
+    }
+
+    impl<W: io::Write + Send> ParquetWriter<W> {
+        pub(super) fn new(writer: W) -> io::Result<Self> {
+            let writer = ArrowWriter::try_new(writer, schema(), None).map_err(to_io_error)?;
+            Ok(Self { writer })
+        }
+    }
+
+    impl<W: io::Write + Send> EventWriter for ParquetWriter<W> {
+        fn write_events(&mut self, program_id: &Pubkey, events: &[TokenEvent]) -> io::Result<()> {
+            if events.is_empty() {
+                return Ok(());
+            }
+            let pubkey_column = |pubkey: fn(&TokenEvent) -> Option<Pubkey>| -> ArrayRef {
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| pubkey(event).map(|pubkey| pubkey.to_string()))
+                        .collect::<StringArray>(),
+                )
+            };
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| Some(event.signature.to_string()))
+                        .collect::<StringArray>(),
+                ),
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| event.slot)
+                        .collect::<UInt64Array>(),
+                ),
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| event.block_time)
+                        .collect::<Int64Array>(),
+                ),
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| event.instruction_index)
+                        .collect::<UInt32Array>(),
+                ),
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| event.inner_instruction_index)
+                        .collect::<UInt32Array>(),
+                ),
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| Some(event.kind.to_string()))
+                        .collect::<StringArray>(),
+                ),
+                pubkey_column(|event| event.mint),
+                pubkey_column(|event| event.source),
+                pubkey_column(|event| event.destination),
+                pubkey_column(|event| event.authority),
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| event.amount)
+                        .collect::<UInt64Array>(),
+                ),
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| event.decimals)
+                        .collect::<UInt8Array>(),
+                ),
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|event| event.fee)
+                        .collect::<UInt64Array>(),
+                ),
+                Arc::new(
+                    events
+                        .iter()
+                        .map(|_| Some(program_id.to_string()))
+                        .collect::<StringArray>(),
+                ),
+            ];
+            let batch = RecordBatch::try_new(schema(), columns).map_err(to_io_error)?;
+            self.writer.write(&batch).map_err(to_io_error)?;
+            // one row group per page keeps memory bounded
+            self.writer.flush().map_err(to_io_error)
+        }
+
+        fn finish(self: Box<Self>) -> io::Result<()> {
+            self.writer.close().map(|_| ()).map_err(to_io_error)
+        }
+    }
+}
+
+/// Mint of the token account, from its current state, or `None` if it is
+/// not a token account of the program anymore
+async fn resolve_account_mint<T>(
+    token: &Token<T>,
+    account_mints: &mut HashMap<Pubkey, Option<Pubkey>>,
+    account: Option<Pubkey>,
+) -> TokenResult<Option<Pubkey>>
+where
+    T: SendTransaction + SimulateTransaction,
+{
+    let Some(account) = account else {
+        return Ok(None);
+    };
+    if let Some(account_mint) = account_mints.get(&account) {
+        return Ok(*account_mint);
+    }
+    let account_mint = token
+        .client()
+        .get_account(account)
+        .await
+        .map_err(TokenError::Client)?
+        .filter(|state| state.owner == *token.program_id())
+        .and_then(|state| {
+            StateWithExtensions::<Account>::unpack(&state.data)
+                .ok()
+                .map(|state| state.base.mint)
+        });
+    account_mints.insert(account, account_mint);
+    Ok(account_mint)
+}
+
+fn io_error(error: io::Error) -> TokenError {
+    TokenError::Client(Box::new(error))
+}
+
+/// Stream the events of the token's mint in the given slot range, newest
+/// first, to the writer, returning the number of events written.
+///
+/// History is fetched a page at a time, and each page is written before the
+/// next is fetched, so exports of long ranges run in bounded memory.
+///
+/// Events whose transaction does not tell their mint are attributed from the
+/// current state of their account, and skipped if it is closed or belongs to
+/// another mint.
+///
+/// ```
+/// use {
+///     solana_sdk::{pubkey::Pubkey, signature::{Keypair, Signer}},
+///     spl_token_client::{
+///         client::ProgramRpcClientSendTransaction,
+///         events::{self, ExportFormat},
+///         mock::MockProgramClient,
+///         token::Token,
+///     },
+///     std::{io, sync::Arc},
+/// };
+///
+/// let mint = Pubkey::new_unique();
+/// let mint_authority = Keypair::new();
+/// let client = Arc::new(MockProgramClient::<ProgramRpcClientSendTransaction>::new());
+/// let token = Token::new(client, &spl_token_2022::id(), &mint, Some(6), Arc::new(Keypair::new()));
+///
+/// futures::executor::block_on(async {
+///     let destination = Pubkey::new_unique();
+///     token
+///         .mint_to(&destination, &mint_authority.pubkey(), 100, &[&mint_authority])
+///         .await
+///         .unwrap();
+///
+///     let written = events::export(&token, 0..u64::MAX, ExportFormat::Csv, io::sink())
+///         .await
+///         .unwrap();
+///     assert_eq!(written, 1);
+/// });
+/// ```
+pub async fn export<T, W>(
+    token: &Token<T>,
+    range: Range<Slot>,
+    format: ExportFormat,
+    writer: W,
+) -> TokenResult<u64>
+where
+    T: SendTransaction + SimulateTransaction,
+    W: io::Write + Send + 'static,
+{
+    let mut event_writer: Box<dyn EventWriter> = match format {
+        ExportFormat::Csv => Box::new(CsvWriter::new(writer).map_err(io_error)?),
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => {
+            Box::new(parquet_writer::ParquetWriter::new(writer).map_err(io_error)?)
+        }
+    };
+
+    let client = token.client();
+    let program_id = token.program_id();
+    let mint = *token.get_address();
+    let mut account_mints = HashMap::new();
+    let mut written = 0u64;
+    let mut pages = token.signature_pages(&mint, None, usize::MAX);
+    'pages: while let Some(signatures) = pages.next().await {
+        let mut events = vec![];
+        for signature in &signatures? {
+            let Some(record) = client
+                .get_transaction(signature)
+                .await
+                .map_err(TokenError::Client)?
+            else {
+                continue;
+            };
+            if record.slot >= range.end {
+                continue;
+            }
+            if record.slot < range.start {
+                event_writer
+                    .write_events(program_id, &events)
+                    .map_err(io_error)?;
+                written += events.len() as u64;
+                break 'pages;
+            }
+            for mut event in decode_events(program_id, &mint, &record) {
+                if event.mint.is_none() {
+                    event.mint =
+                        resolve_account_mint(token, &mut account_mints, event.source).await?;
+                }
+                if event.mint == Some(mint) {
+                    events.push(event);
+                }
+            }
+        }
+        event_writer
+            .write_events(program_id, &events)
+            .map_err(io_error)?;
+        written += events.len() as u64;
+    }
+    event_writer.finish().map_err(io_error)?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ProgramClient, ProgramRpcClientSendTransaction},
+            mock::MockProgramClient,
+        },
+        solana_sdk::{
+            account::Account as SolanaAccount, hash::Hash, signature::Keypair, signer::Signer,
+            transaction::Transaction,
+        },
+        spl_token_2022::{
+            extension::{
+                transfer_fee::{TransferFee, TransferFeeConfig},
+                ExtensionType, StateWithExtensionsMut,
+            },
+            instruction,
+            state::{AccountState, Mint},
+        },
+        std::sync::Arc,
+    };
+
+    #[test]
+    fn test_decode_events() {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+
+        let record = TransactionRecord {
+            signature: Signature::new_unique(),
+            slot: 42,
+            block_time: Some(1_700_000_000),
+            err: None,
+            instructions: vec![
+                instruction::transfer_checked(
+                    &program_id,
+                    &source,
+                    &mint,
+                    &destination,
+                    &authority,
+                    &[],
+                    10,
+                    6,
+                )
+                .unwrap(),
+                // another mint
+                instruction::burn_checked(
+                    &program_id,
+                    &source,
+                    &Pubkey::new_unique(),
+                    &authority,
+                    &[],
+                    1,
+                    6,
+                )
+                .unwrap(),
+            ],
+            inner_instructions: vec![
+                vec![],
+                vec![
+                    instruction::mint_to(&program_id, &mint, &destination, &authority, &[], 5)
+                        .unwrap(),
+                ],
+            ],
+        };
+
+        let events = decode_events(&program_id, &mint, &record);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, TokenEventKind::Transfer);
+        assert_eq!(events[0].source, Some(source));
+        assert_eq!(events[0].destination, Some(destination));
+        assert_eq!(events[0].amount, Some(10));
+        assert_eq!(events[1].kind, TokenEventKind::MintTo);
+        assert_eq!(events[1].instruction_index, 1);
+        assert_eq!(events[1].inner_instruction_index, Some(0));
+    }
+
+    #[test]
+    fn test_decode_events_without_mint() {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let (account, other_account, unknown) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let authority = Pubkey::new_unique();
+        let transfer = |source: &Pubkey| {
+            instruction::transfer(&program_id, source, source, &authority, &[], 1).unwrap()
+        };
+        let record = TransactionRecord {
+            signature: Signature::new_unique(),
+            slot: 42,
+            block_time: None,
+            err: None,
+            instructions: vec![
+                instruction::mint_to(&program_id, &mint, &account, &authority, &[], 5).unwrap(),
+                instruction::burn_checked(
+                    &program_id,
+                    &other_account,
+                    &Pubkey::new_unique(),
+                    &authority,
+                    &[],
+                    1,
+                    6,
+                )
+                .unwrap(),
+                transfer(&account),
+                transfer(&other_account),
+                transfer(&unknown),
+            ],
+            inner_instructions: vec![vec![]; 5],
+        };
+
+        // unchecked transfers take the mint other events give their account
+        let events = decode_events(&program_id, &mint, &record);
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.instruction_index, event.mint))
+                .collect::<Vec<_>>(),
+            [(0, Some(mint)), (2, Some(mint)), (4, None)]
+        );
+    }
+
+    #[test]
+    fn test_export_resolves_mints() {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let (account, other_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let token_account = |mint| Account {
+            mint,
+            owner: Pubkey::new_unique(),
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let client = Arc::new(
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+                .with_token_account(&account, &program_id, token_account(mint))
+                .with_token_account(
+                    &other_account,
+                    &program_id,
+                    token_account(Pubkey::new_unique()),
+                ),
+        );
+        let token = Token::new(
+            client.clone(),
+            &program_id,
+            &mint,
+            None,
+            Arc::new(Keypair::new()),
+        );
+        let authority = Keypair::new();
+        let transfer = |source: &Pubkey| {
+            instruction::transfer(&program_id, source, source, &authority.pubkey(), &[], 1).unwrap()
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                instruction::mint_to(
+                    &program_id,
+                    &mint,
+                    &Pubkey::new_unique(),
+                    &authority.pubkey(),
+                    &[],
+                    5,
+                )
+                .unwrap(),
+                transfer(&account),
+                transfer(&other_account),
+                transfer(&Pubkey::new_unique()),
+            ],
+            Some(&authority.pubkey()),
+            &[&authority],
+            Hash::default(),
+        );
+
+        futures::executor::block_on(async {
+            client.send_transaction(&transaction).await.unwrap();
+            // the mint, and the transfer of an account of the mint
+            let written = export(&token, 0..u64::MAX, ExportFormat::Csv, io::sink())
+                .await
+                .unwrap();
+            assert_eq!(written, 2);
+        });
+    }
+
+    #[test]
+    fn test_account_history_fee() {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let (source, destination) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let len =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+                .unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.base = Mint {
+            is_initialized: true,
+            ..Mint::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        config.older_transfer_fee = transfer_fee;
+        config.newer_transfer_fee = transfer_fee;
+        let client = Arc::new(
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new().with_account(
+                &mint,
+                SolanaAccount {
+                    lamports: 1,
+                    data,
+                    owner: program_id,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            ),
+        );
+        let token = Token::new(
+            client.clone(),
+            &program_id,
+            &mint,
+            Some(0),
+            Arc::new(Keypair::new()),
+        );
+        let authority = Keypair::new();
+        // a plain checked transfer, which does not state the fee
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction::transfer_checked(
+                &program_id,
+                &source,
+                &mint,
+                &destination,
+                &authority.pubkey(),
+                &[],
+                1_000,
+                0,
+            )
+            .unwrap()],
+            Some(&authority.pubkey()),
+            &[&authority],
+            Hash::default(),
+        );
+
+        futures::executor::block_on(async {
+            client.send_transaction(&transaction).await.unwrap();
+            let operations = token.get_account_history(&destination, 10).await.unwrap();
+            assert_eq!(
+                operations
+                    .iter()
+                    .map(|operation| (operation.kind, operation.amount))
+                    .collect::<Vec<_>>(),
+                [
+                    (AccountOperationKind::TransferIn, 990),
+                    (AccountOperationKind::FeeWithheld, 10),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_account_operations() {
+        let address = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let event = |kind, source, destination, amount, fee| TokenEvent {
+            signature: Signature::default(),
+            slot: 1,
+            block_time: None,
+            instruction_index: 0,
+            inner_instruction_index: None,
+            kind,
+            mint: None,
+            source,
+            destination,
+            authority: None,
+            amount,
+            decimals: None,
+            fee,
+        };
+        let events = [
+            event(TokenEventKind::MintTo, None, Some(address), Some(100), None),
+            event(
+                TokenEventKind::Transfer,
+                Some(address),
+                Some(other),
+                Some(30),
+                None,
+            ),
+            event(
+                TokenEventKind::Transfer,
+                Some(other),
+                Some(address),
+                Some(20),
+                Some(1),
+            ),
+            event(TokenEventKind::Burn, Some(address), None, Some(5), None),
+            // not this account's balance
+            event(TokenEventKind::Burn, Some(other), None, Some(5), None),
+            event(
+                TokenEventKind::Approve,
+                Some(address),
+                Some(other),
+                Some(5),
+                None,
+            ),
+        ];
+
+        let operations = account_operations(&address, &events);
+        assert_eq!(
+            operations
+                .iter()
+                .map(|operation| (operation.kind, operation.counterparty, operation.amount))
+                .collect::<Vec<_>>(),
+            [
+                (AccountOperationKind::MintTo, None, 100),
+                (AccountOperationKind::TransferOut, Some(other), 30),
+                (AccountOperationKind::TransferIn, Some(other), 19),
+                (AccountOperationKind::FeeWithheld, None, 1),
+                (AccountOperationKind::Burn, None, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_csv_columns_stable() {
+        let mut output = vec![];
+        {
+            let mut writer = CsvWriter::new(&mut output).unwrap();
+            writer
+                .write_events(
+                    &spl_token_2022::id(),
+                    &[TokenEvent {
+                        signature: Signature::default(),
+                        slot: 1,
+                        block_time: None,
+                        instruction_index: 0,
+                        inner_instruction_index: None,
+                        kind: TokenEventKind::Revoke,
+                        mint: None,
+                        source: None,
+                        destination: None,
+                        authority: None,
+                        amount: None,
+                        decimals: None,
+                        fee: None,
+                    }],
+                )
+                .unwrap();
+        }
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], EVENT_COLUMNS.join(","));
+        assert_eq!(
+            lines[1].split(',').count(),
+            EVENT_COLUMNS.len(),
+            "every row has every column"
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! Program client failing over between several endpoints
+
+use {
+    crate::client::{
+        AccountOverrides, ProgramClient, ProgramClientError, ProgramClientResult, ReadConsistency,
+        RequestTimeout, SendOutcomeUnknown, SendTransaction, SignatureStatus, SimulateTransaction,
+        TransactionRecord,
+    },
+    async_trait::async_trait,
+    solana_program_test::tokio::time,
+    solana_rpc_client_api::{
+        client_error::{Error as ClientError, ErrorKind as ClientErrorKind},
+        custom_error::{
+            JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+            JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+        },
+        filter::RpcFilterType,
+        request::RpcError,
+    },
+    solana_sdk::{
+        account::Account,
+        clock::{Clock, Slot},
+        commitment_config::CommitmentConfig,
+        epoch_info::EpochInfo,
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::Transaction,
+        transport::TransportError,
+    },
+    std::{
+        fmt,
+        future::Future,
+        io,
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+};
+
+/// Default time to wait for one endpoint before trying the next
+pub const DEFAULT_FAILOVER_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default time an endpoint that failed is only used as a last resort
+pub const DEFAULT_FAILOVER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Whether an error says the endpoint could not be reached, or could not
+/// serve the request, as opposed to an answer every endpoint would give,
+/// like a failed simulation, a program error or a missing account
+fn is_endpoint_failure(error: &ProgramClientError) -> bool {
+    // resending a transaction whose outcome is unknown elsewhere is safe, as
+    // it keeps its signature and so lands at most once
+    if error.is::<RequestTimeout>() || error.is::<SendOutcomeUnknown>() || error.is::<io::Error>() {
+        return true;
+    }
+    if let Some(error) = error.downcast_ref::<TransportError>() {
+        return matches!(error, TransportError::IoError(_));
+    }
+    match error.downcast_ref::<ClientError>().map(ClientError::kind) {
+        Some(ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_)) => true,
+        Some(ClientErrorKind::RpcError(RpcError::RpcRequestError(_))) => true,
+        Some(ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. })) => matches!(
+            *code,
+            JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+                | JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+        ),
+        _ => false,
+    }
+}
+
+/// Wraps an ordered list of program clients, sending every request to the
+/// first healthy one and retrying on the next when it cannot be reached or
+/// times out. Errors any endpoint would return, like program errors, failed
+/// simulations or missing accounts, are returned as they are.
+///
+/// An endpoint that fails is tried after the healthy ones until its cooldown
+/// passes, or until `check_health` finds it responsive again. Resending a
+/// transaction elsewhere is safe, since the cluster processes a signature at
+/// most once.
+pub struct FailoverProgramClient<C> {
+    endpoints: Vec<C>,
+    unhealthy_until: Mutex<Vec<Option<Instant>>>,
+    request_timeout: Duration,
+    cooldown: Duration,
+}
+
+impl<C> fmt::Debug for FailoverProgramClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FailoverProgramClient")
+            .field("endpoints", &self.endpoints.len())
+            .field("healthy", &self.healthy())
+            .field("request_timeout", &self.request_timeout)
+            .field("cooldown", &self.cooldown)
+            .finish()
+    }
+}
+
+impl<C> FailoverProgramClient<C> {
+    /// Panics if `endpoints` is empty
+    pub fn new(endpoints: Vec<C>) -> Self {
+        assert!(!endpoints.is_empty(), "at least one endpoint is required");
+        Self {
+            unhealthy_until: Mutex::new(vec![None; endpoints.len()]),
+            endpoints,
+            request_timeout: DEFAULT_FAILOVER_REQUEST_TIMEOUT,
+            cooldown: DEFAULT_FAILOVER_COOLDOWN,
+        }
+    }
+
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    pub fn endpoints(&self) -> &[C] {
+        &self.endpoints
+    }
+
+    /// Whether each endpoint is currently considered healthy, in order
+    pub fn healthy(&self) -> Vec<bool> {
+        let now = Instant::now();
+        self.unhealthy_until
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|until| until.map(|until| until <= now).unwrap_or(true))
+            .collect()
+    }
+
+    /// Healthy endpoints in order, then the others
+    fn attempt_order(&self) -> Vec<usize> {
+        let healthy = self.healthy();
+        let (mut order, unhealthy): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len()).partition(|index| healthy[*index]);
+        order.extend(unhealthy);
+        order
+    }
+
+    fn mark(&self, index: usize, healthy: bool) {
+        self.unhealthy_until.lock().unwrap()[index] =
+            (!healthy).then(|| Instant::now() + self.cooldown);
+    }
+
+    async fn with_failover<'a, R, F, Fut>(&'a self, call: F) -> ProgramClientResult<R>
+    where
+        F: Fn(&'a C) -> Fut,
+        Fut: Future<Output = ProgramClientResult<R>>,
+    {
+        let mut last_error: Option<ProgramClientError> = None;
+        for index in self.attempt_order() {
+            match time::timeout(self.request_timeout, call(&self.endpoints[index])).await {
+                Ok(Ok(result)) => {
+                    self.mark(index, true);
+                    return Ok(result);
+                }
+                Ok(Err(error)) if is_endpoint_failure(&error) => last_error = Some(error),
+                Ok(Err(error)) => {
+                    // the endpoint answered, so it is healthy
+                    self.mark(index, true);
+                    return Err(error);
+                }
+                Err(_) => {
+                    last_error = Some(format!("Endpoint {} timed out", index).into());
+                }
+            }
+            self.mark(index, false);
+        }
+        Err(last_error.unwrap())
+    }
+
+    /// Ask every endpoint for a blockhash, marking the ones that answer in
+    /// time healthy and the others unhealthy
+    pub async fn check_health<ST>(&self)
+    where
+        ST: SendTransaction + SimulateTransaction,
+        C: ProgramClient<ST>,
+    {
+        for (index, endpoint) in self.endpoints.iter().enumerate() {
+            let healthy = matches!(
+                time::timeout(self.request_timeout, endpoint.get_latest_blockhash()).await,
+                Ok(Ok(_))
+            );
+            self.mark(index, healthy);
+        }
+    }
+}
+
+#[async_trait]
+impl<ST, C> ProgramClient<ST> for FailoverProgramClient<C>
+where
+    ST: SendTransaction + SimulateTransaction + Send + Sync,
+    C: ProgramClient<ST> + Send + Sync,
+{
+    async fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> ProgramClientResult<u64> {
+        self.with_failover(|client| client.get_minimum_balance_for_rent_exemption(data_len))
+            .await
+    }
+
+    async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
+        self.with_failover(|client| client.get_latest_blockhash())
+            .await
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> ProgramClientResult<ST::Output> {
+        self.with_failover(|client| client.send_transaction(transaction))
+            .await
+    }
+
+    async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>> {
+        self.with_failover(|client| client.get_account(address))
+            .await
+    }
+
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.with_failover(|client| client.get_account_with_consistency(address, consistency))
+            .await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.with_failover(|client| client.get_account_with_commitment(address, commitment))
+            .await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.with_failover(|client| client.simulate_transaction(transaction))
+            .await
+    }
+
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.with_failover(|client| {
+            client.simulate_transaction_with_overrides(transaction, overrides)
+        })
+        .await
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        self.with_failover(|client| client.get_signature_statuses(signatures))
+            .await
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        self.with_failover(|client| client.get_recent_prioritization_fees(addresses))
+            .await
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        self.with_failover(|client| {
+            client.get_signatures_for_address(address, before, until, limit)
+        })
+        .await
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        self.with_failover(|client| client.get_transaction(signature))
+            .await
+    }
+
+    async fn get_program_accounts_with_filters(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        self.with_failover(|client| {
+            client.get_program_accounts_with_filters(program_id, filters.clone())
+        })
+        .await
+    }
+
+    async fn get_program_accounts_with_context(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        self.with_failover(|client| {
+            client.get_program_accounts_with_context(program_id, filters.clone())
+        })
+        .await
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        self.with_failover(|client| client.get_epoch_info()).await
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        self.with_failover(|client| client.get_sysvar_clock()).await
+    }
+
+    async fn get_token_largest_accounts(
+        &self,
+        mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        self.with_failover(|client| client.get_token_largest_accounts(mint))
+            .await
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        self.with_failover(|client| client.get_multiple_accounts(addresses))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ClientMethod, ProgramRpcClientSendTransaction},
+            mock::MockProgramClient,
+        },
+        solana_program_test::tokio,
+        solana_sdk::{instruction::InstructionError, transaction::TransactionError},
+    };
+
+    type Client = dyn ProgramClient<ProgramRpcClientSendTransaction>;
+
+    #[tokio::test]
+    async fn test_failover_only_on_endpoint_failures() {
+        let failover = FailoverProgramClient::new(vec![
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new(),
+            MockProgramClient::new(),
+        ]);
+        let client: &Client = &failover;
+
+        failover.endpoints()[0].fail_next(
+            ClientMethod::GetLatestBlockhash,
+            io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused"),
+        );
+        assert!(client.get_latest_blockhash().await.is_ok());
+        assert_eq!(failover.healthy(), [false, true]);
+        assert_eq!(
+            failover.endpoints()[1].calls(),
+            [ClientMethod::GetLatestBlockhash]
+        );
+
+        // every endpoint would fail the same transaction
+        failover.endpoints()[1].fail_next(
+            ClientMethod::GetLatestBlockhash,
+            TransportError::TransactionError(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(1),
+            )),
+        );
+        assert!(client.get_latest_blockhash().await.is_err());
+        assert_eq!(failover.healthy(), [false, true]);
+        assert_eq!(
+            failover.endpoints()[0].calls(),
+            [ClientMethod::GetLatestBlockhash]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_timeout() {
+        let failover = FailoverProgramClient::new(vec![
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+                .with_latency(Duration::from_secs(5)),
+            MockProgramClient::new(),
+        ])
+        .with_request_timeout(Duration::from_millis(50));
+        let client: &Client = &failover;
+
+        assert!(client.get_latest_blockhash().await.is_ok());
+        assert_eq!(failover.healthy(), [false, true]);
+        assert_eq!(
+            failover.endpoints()[1].calls(),
+            [ClientMethod::GetLatestBlockhash]
+        );
+
+        // the slow endpoint is now only tried last
+        failover.endpoints()[1].fail_next(
+            ClientMethod::GetLatestBlockhash,
+            io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused"),
+        );
+        let error = client.get_latest_blockhash().await.unwrap_err();
+        assert_eq!(error.to_string(), "Endpoint 0 timed out");
+        assert_eq!(failover.healthy(), [false, false]);
+        assert_eq!(failover.endpoints()[0].calls().len(), 2);
+    }
+}
@@ -0,0 +1,306 @@
+//! Formatting and parsing of token amounts for display
+
+use thiserror::Error;
+
+/// Where the symbol goes relative to the number
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymbolPosition {
+    /// e.g. `$1.50`
+    Prefix,
+    /// e.g. `1.50 USDC`
+    #[default]
+    Suffix,
+}
+
+/// Options for formatting and parsing amounts.
+///
+/// The default has no grouping, a `.` decimal separator, no symbol, and trims
+/// trailing zeros, like `spl_token_2022::amount_to_ui_amount_string_trimmed`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AmountFormat {
+    grouping_separator: Option<char>,
+    decimal_separator: char,
+    significant_digits: Option<usize>,
+    symbol: Option<(String, SymbolPosition)>,
+    trim_trailing_zeros: bool,
+}
+
+impl Default for AmountFormat {
+    fn default() -> Self {
+        Self {
+            grouping_separator: None,
+            decimal_separator: '.',
+            significant_digits: None,
+            symbol: None,
+            trim_trailing_zeros: true,
+        }
+    }
+}
+
+impl AmountFormat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `1,234.5`
+    pub fn en() -> Self {
+        Self::new().with_grouping(',')
+    }
+
+    /// `1.234,5`
+    pub fn de() -> Self {
+        Self::new().with_grouping('.').with_decimal_separator(',')
+    }
+
+    /// `1 234,5`, grouped with a narrow no-break space
+    pub fn fr() -> Self {
+        Self::new()
+            .with_grouping('\u{202f}')
+            .with_decimal_separator(',')
+    }
+
+    /// Separate thousands with the given character
+    pub fn with_grouping(mut self, separator: char) -> Self {
+        self.grouping_separator = Some(separator);
+        self
+    }
+
+    pub fn with_decimal_separator(mut self, separator: char) -> Self {
+        self.decimal_separator = separator;
+        self
+    }
+
+    /// Truncate the fractional part to the given number of significant
+    /// digits. The integer part is never truncated.
+    pub fn with_significant_digits(mut self, significant_digits: usize) -> Self {
+        self.significant_digits = Some(significant_digits);
+        self
+    }
+
+    /// Add the symbol, separated by a space when it is a suffix
+    pub fn with_symbol<S: Into<String>>(mut self, symbol: S, position: SymbolPosition) -> Self {
+        self.symbol = Some((symbol.into(), position));
+        self
+    }
+
+    /// Keep every decimal of the mint, e.g. to align amounts in a column
+    pub fn with_trailing_zeros(mut self) -> Self {
+        self.trim_trailing_zeros = false;
+        self
+    }
+}
+
+/// Errors parsing an amount
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub enum AmountParseError {
+    #[error("no amount")]
+    Empty,
+    #[error("invalid character {0:?}")]
+    InvalidCharacter(char),
+    #[error("misplaced grouping separator")]
+    MisplacedGrouping,
+    #[error("more than {0} decimals")]
+    TooManyDecimals(u8),
+    #[error("amount too large")]
+    Overflow,
+}
+
+fn group(integer: &str, separator: char) -> String {
+    let mut grouped = String::with_capacity(integer.len() + integer.len() / 3);
+    for (index, digit) in integer.chars().enumerate() {
+        if index > 0 && (integer.len() - index) % 3 == 0 {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Format a raw amount of a mint with the given decimals
+pub fn amount(amount: u64, decimals: u8, options: &AmountFormat) -> String {
+    let digits = format!("{:0>width$}", amount, width = usize::from(decimals) + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - usize::from(decimals));
+
+    let mut fraction = fraction.to_string();
+    if let Some(significant_digits) = options.significant_digits {
+        let kept = if integer != "0" {
+            significant_digits.saturating_sub(integer.len())
+        } else {
+            // leading zeros of the fraction are not significant
+            let leading_zeros = fraction.len() - fraction.trim_start_matches('0').len();
+            leading_zeros + significant_digits
+        };
+        fraction.truncate(kept);
+    }
+    if options.trim_trailing_zeros {
+        fraction.truncate(fraction.trim_end_matches('0').len());
+    }
+
+    let mut number = match options.grouping_separator {
+        Some(separator) => group(integer, separator),
+        None => integer.to_string(),
+    };
+    if !fraction.is_empty() {
+        number.push(options.decimal_separator);
+        number.push_str(&fraction);
+    }
+    match &options.symbol {
+        Some((symbol, SymbolPosition::Prefix)) => format!("{}{}", symbol, number),
+        Some((symbol, SymbolPosition::Suffix)) => format!("{} {}", number, symbol),
+        None => number,
+    }
+}
+
+/// Parse an amount formatted with the given options into a raw amount of a
+/// mint with the given decimals.
+///
+/// Parsing is strict: grouping separators must delimit groups of three
+/// digits, and any precision beyond the mint's decimals is rejected rather
+/// than rounded.
+pub fn parse_amount(
+    input: &str,
+    decimals: u8,
+    options: &AmountFormat,
+) -> Result<u64, AmountParseError> {
+    let mut number = input.trim();
+    if let Some((symbol, position)) = &options.symbol {
+        number = match position {
+            SymbolPosition::Prefix => number.strip_prefix(symbol.as_str()),
+            SymbolPosition::Suffix => number.strip_suffix(symbol.as_str()),
+        }
+        .unwrap_or(number)
+        .trim();
+    }
+    if number.is_empty() {
+        return Err(AmountParseError::Empty);
+    }
+
+    let (integer, fraction) = match number.split_once(options.decimal_separator) {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (number, ""),
+    };
+    let integer = match options.grouping_separator {
+        Some(separator) if integer.contains(separator) => {
+            let groups = integer.split(separator).collect::<Vec<_>>();
+            let well_formed = groups.iter().enumerate().all(|(index, group)| {
+                if index == 0 {
+                    (1..=3).contains(&group.len())
+                } else {
+                    group.len() == 3
+                }
+            });
+            if !well_formed {
+                return Err(AmountParseError::MisplacedGrouping);
+            }
+            groups.concat()
+        }
+        _ => integer.to_string(),
+    };
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(AmountParseError::Empty);
+    }
+    if let Some(invalid) = integer
+        .chars()
+        .chain(fraction.chars())
+        .find(|c| !c.is_ascii_digit())
+    {
+        return Err(AmountParseError::InvalidCharacter(invalid));
+    }
+    if fraction.len() > usize::from(decimals) {
+        return Err(AmountParseError::TooManyDecimals(decimals));
+    }
+
+    let digits = format!(
+        "{}{:0<width$}",
+        integer,
+        fraction,
+        width = usize::from(decimals)
+    );
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    digits.parse().map_err(|_| AmountParseError::Overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_amount() {
+        let default = AmountFormat::new();
+        assert_eq!(amount(1_234_500_000, 6, &default), "1234.5");
+        assert_eq!(amount(0, 6, &default), "0");
+        assert_eq!(amount(42, 0, &default), "42");
+        assert_eq!(amount(1, 9, &default), "0.000000001");
+
+        assert_eq!(
+            amount(1_234_567_890_000, 6, &AmountFormat::en()),
+            "1,234,567.89"
+        );
+        assert_eq!(
+            amount(1_234_567_890_000, 6, &AmountFormat::de()),
+            "1.234.567,89"
+        );
+        assert_eq!(
+            amount(
+                1_500_000,
+                6,
+                &AmountFormat::en().with_symbol("$", SymbolPosition::Prefix)
+            ),
+            "$1.5"
+        );
+        assert_eq!(
+            amount(
+                1_500_000,
+                6,
+                &AmountFormat::new()
+                    .with_symbol("USDC", SymbolPosition::Suffix)
+                    .with_trailing_zeros()
+            ),
+            "1.500000 USDC"
+        );
+
+        // truncated, never rounded up
+        let significant = AmountFormat::new().with_significant_digits(3);
+        assert_eq!(amount(1_239_999, 6, &significant), "1.23");
+        assert_eq!(amount(1_239, 6, &significant), "0.00123");
+        assert_eq!(amount(123_456_999_999, 6, &significant), "123456");
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        let en = AmountFormat::en().with_symbol("$", SymbolPosition::Prefix);
+        assert_eq!(parse_amount("$1,234.5", 6, &en), Ok(1_234_500_000));
+        assert_eq!(parse_amount("1234.5", 6, &en), Ok(1_234_500_000));
+        assert_eq!(parse_amount(".5", 6, &en), Ok(500_000));
+        assert_eq!(
+            parse_amount("1.234,5", 6, &AmountFormat::de()),
+            Ok(1_234_500_000)
+        );
+
+        assert_eq!(
+            parse_amount("1.0000001", 6, &en),
+            Err(AmountParseError::TooManyDecimals(6))
+        );
+        assert_eq!(
+            parse_amount("12,34.5", 6, &en),
+            Err(AmountParseError::MisplacedGrouping)
+        );
+        assert_eq!(
+            parse_amount("1e6", 6, &en),
+            Err(AmountParseError::InvalidCharacter('e'))
+        );
+        assert_eq!(
+            parse_amount("18446744073709.551616", 6, &en),
+            Err(AmountParseError::Overflow)
+        );
+        assert_eq!(parse_amount("$", 6, &en), Err(AmountParseError::Empty));
+
+        // round trip
+        let fr = AmountFormat::fr();
+        let formatted = amount(9_876_543_210, 3, &fr);
+        assert_eq!(parse_amount(&formatted, 3, &fr), Ok(9_876_543_210));
+    }
+}
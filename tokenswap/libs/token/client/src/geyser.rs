@@ -0,0 +1,644 @@
+//! Program client reading accounts from a Yellowstone Geyser gRPC feed
+
+use {
+    crate::client::{
+        AccountOverrides, ProgramClient, ProgramClientResult, ReadConsistency, SendTransaction,
+        SignatureStatus, SimulateTransaction, TransactionRecord,
+    },
+    async_trait::async_trait,
+    futures_util::{Stream, StreamExt},
+    solana_program_test::tokio::{
+        self,
+        sync::{broadcast, oneshot},
+        task::JoinHandle,
+        time,
+    },
+    solana_rpc_client_api::filter::RpcFilterType,
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        clock::{Clock, Slot},
+        commitment_config::CommitmentConfig,
+        epoch_info::EpochInfo,
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::Transaction,
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        fmt,
+        sync::{Arc, RwLock},
+        time::Duration,
+    },
+    yellowstone_grpc_client::GeyserGrpcClient,
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+        SubscribeRequestFilterAccounts, SubscribeUpdate, SubscribeUpdateAccountInfo,
+    },
+};
+
+/// Default time to wait before reconnecting a dropped feed
+pub const DEFAULT_GEYSER_RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of updates a slow streaming consumer can lag behind before missing
+/// some
+const UPDATES_CAPACITY: usize = 4_096;
+
+/// Accounts to follow on the feed: the given accounts, and every account
+/// owned by the given programs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeyserAccountFilter {
+    pub accounts: Vec<Pubkey>,
+    pub owners: Vec<Pubkey>,
+}
+
+impl GeyserAccountFilter {
+    /// Every account of the given token program
+    pub fn program(program_id: &Pubkey) -> Self {
+        Self {
+            accounts: vec![],
+            owners: vec![*program_id],
+        }
+    }
+
+    fn request(&self) -> SubscribeRequest {
+        let filter = SubscribeRequestFilterAccounts {
+            account: self.accounts.iter().map(ToString::to_string).collect(),
+            owner: self.owners.iter().map(ToString::to_string).collect(),
+            ..SubscribeRequestFilterAccounts::default()
+        };
+        SubscribeRequest {
+            accounts: HashMap::from([("spl-token-client".to_string(), filter)]),
+            commitment: Some(CommitmentLevel::Confirmed as i32),
+            ..SubscribeRequest::default()
+        }
+    }
+}
+
+/// New state of an account received from the feed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeyserAccountUpdate {
+    pub address: Pubkey,
+    pub slot: Slot,
+    /// `None` once the account is closed
+    pub account: Option<Account>,
+}
+
+fn decode_update(slot: Slot, info: SubscribeUpdateAccountInfo) -> Option<GeyserAccountUpdate> {
+    let address = Pubkey::try_from(info.pubkey.as_slice()).ok()?;
+    let account = (info.lamports > 0)
+        .then(|| {
+            Some(Account {
+                lamports: info.lamports,
+                data: info.data,
+                owner: Pubkey::try_from(info.owner.as_slice()).ok()?,
+                executable: info.executable,
+                rent_epoch: info.rent_epoch,
+            })
+        })
+        .flatten();
+    Some(GeyserAccountUpdate {
+        address,
+        slot,
+        account,
+    })
+}
+
+#[derive(Default)]
+struct GeyserState {
+    accounts: HashMap<Pubkey, Account>,
+    /// Accounts known to be closed since the feed connected
+    closed: HashSet<Pubkey>,
+    slot: Slot,
+}
+
+impl GeyserState {
+    fn apply(&mut self, update: &GeyserAccountUpdate) {
+        self.slot = self.slot.max(update.slot);
+        match &update.account {
+            Some(account) => {
+                self.closed.remove(&update.address);
+                self.accounts.insert(update.address, account.clone());
+            }
+            None => {
+                self.accounts.remove(&update.address);
+                self.closed.insert(update.address);
+            }
+        }
+    }
+
+    /// Forget every account state, so they are read from the inner client
+    /// again
+    fn reset(&mut self) {
+        self.accounts.clear();
+        self.closed.clear();
+    }
+}
+
+/// Apply the feed's updates until it ends, then forget them: changes missed
+/// until the feed is reconnected would otherwise leave stale states served as
+/// current
+async fn follow<S, E>(
+    stream: S,
+    state: &RwLock<GeyserState>,
+    updates: &broadcast::Sender<GeyserAccountUpdate>,
+) where
+    S: Stream<Item = Result<SubscribeUpdate, E>>,
+{
+    futures_util::pin_mut!(stream);
+    while let Some(Ok(message)) = stream.next().await {
+        let Some(UpdateOneof::Account(update)) = message.update_oneof else {
+            continue;
+        };
+        let Some(update) = update
+            .account
+            .and_then(|info| decode_update(update.slot, info))
+        else {
+            continue;
+        };
+        state.write().unwrap().apply(&update);
+        // nobody may be listening
+        let _ = updates.send(update);
+    }
+    state.write().unwrap().reset();
+}
+
+/// Wraps a program client, serving reads of the followed accounts from a
+/// Yellowstone Geyser gRPC feed.
+///
+/// Geyser only pushes changes, so an account is served from the feed once it
+/// has changed since the feed last connected, and from the inner client until
+/// then. When the feed drops, every account is read from the inner client
+/// again until it changes on the reconnected feed.
+/// Scans of a followed program merge both. Transactions and everything else
+/// go to the inner client. Updates are also available as a stream through
+/// `subscribe`.
+pub struct GeyserProgramClient<C> {
+    inner: C,
+    filter: GeyserAccountFilter,
+    state: Arc<RwLock<GeyserState>>,
+    updates: broadcast::Sender<GeyserAccountUpdate>,
+    task: JoinHandle<()>,
+}
+
+impl<C> fmt::Debug for GeyserProgramClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.read().unwrap();
+        f.debug_struct("GeyserProgramClient")
+            .field("filter", &self.filter)
+            .field("accounts", &state.accounts.len())
+            .field("slot", &state.slot)
+            .finish()
+    }
+}
+
+impl<C> Drop for GeyserProgramClient<C> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl<C> GeyserProgramClient<C> {
+    /// Connect to the gRPC endpoint and follow the filtered accounts,
+    /// reconnecting whenever the feed drops
+    pub async fn connect<E: Into<String>>(
+        endpoint: E,
+        x_token: Option<String>,
+        filter: GeyserAccountFilter,
+        inner: C,
+    ) -> ProgramClientResult<Self> {
+        Self::connect_with_reconnect_interval(
+            endpoint,
+            x_token,
+            filter,
+            inner,
+            DEFAULT_GEYSER_RECONNECT_INTERVAL,
+        )
+        .await
+    }
+
+    pub async fn connect_with_reconnect_interval<E: Into<String>>(
+        endpoint: E,
+        x_token: Option<String>,
+        filter: GeyserAccountFilter,
+        inner: C,
+        reconnect_interval: Duration,
+    ) -> ProgramClientResult<Self> {
+        let endpoint = endpoint.into();
+        let state = Arc::new(RwLock::new(GeyserState::default()));
+        let (updates, _) = broadcast::channel(UPDATES_CAPACITY);
+        let (connected_sender, connected) = oneshot::channel();
+
+        let task = tokio::spawn({
+            let request = filter.request();
+            let state = state.clone();
+            let updates = updates.clone();
+            async move {
+                let mut connected_sender = Some(connected_sender);
+                loop {
+                    let stream =
+                        match GeyserGrpcClient::connect(endpoint.clone(), x_token.clone(), None) {
+                            Ok(mut client) => client.subscribe_once2(request.clone()).await,
+                            Err(error) => Err(error.into()),
+                        };
+                    match stream {
+                        Ok(stream) => {
+                            if let Some(sender) = connected_sender.take() {
+                                let _ = sender.send(Ok(()));
+                            }
+                            follow(stream, &state, &updates).await;
+                        }
+                        Err(error) => {
+                            // the first connection must succeed
+                            if let Some(sender) = connected_sender.take() {
+                                let _ = sender.send(Err(error.to_string()));
+                                return;
+                            }
+                        }
+                    }
+                    time::sleep(reconnect_interval).await;
+                }
+            }
+        });
+
+        match connected.await {
+            Ok(Ok(())) => Ok(Self {
+                inner,
+                filter,
+                state,
+                updates,
+                task,
+            }),
+            Ok(Err(error)) => Err(error.into()),
+            Err(_) => Err("Geyser feed stopped before connecting".into()),
+        }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Stream of account updates received from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<GeyserAccountUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Highest slot seen on the feed
+    pub fn slot(&self) -> Slot {
+        self.state.read().unwrap().slot
+    }
+
+    /// Scanned accounts of the program, with newer states from the feed
+    /// replacing them if the feed watches the program
+    fn merge_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: &[RpcFilterType],
+        accounts: Vec<(Pubkey, Account)>,
+    ) -> Vec<(Pubkey, Account)> {
+        if !self.filter.owners.contains(program_id) {
+            return accounts;
+        }
+        // newer states from the feed replace the scanned ones
+        let state = self.state.read().unwrap();
+        let matches = |account: &Account| {
+            account.owner == *program_id && {
+                let account = AccountSharedData::from(account.clone());
+                filters.iter().all(|filter| filter.allows(&account))
+            }
+        };
+        let mut merged = accounts
+            .into_iter()
+            .filter(|(address, _)| {
+                !state.closed.contains(address) && !state.accounts.contains_key(address)
+            })
+            .collect::<Vec<_>>();
+        merged.extend(
+            state
+                .accounts
+                .iter()
+                .filter(|(_, account)| matches(account))
+                .map(|(address, account)| (*address, account.clone())),
+        );
+        merged
+    }
+
+    /// Account state from the feed, or `None` if it has not changed since the
+    /// feed connected
+    fn feed_account(&self, address: &Pubkey) -> Option<Option<Account>> {
+        let state = self.state.read().unwrap();
+        if state.closed.contains(address) {
+            return Some(None);
+        }
+        state.accounts.get(address).cloned().map(Some)
+    }
+}
+
+#[async_trait]
+impl<ST, C> ProgramClient<ST> for GeyserProgramClient<C>
+where
+    ST: SendTransaction + SimulateTransaction + Send + Sync,
+    C: ProgramClient<ST> + Send + Sync,
+{
+    async fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> ProgramClientResult<u64> {
+        self.inner
+            .get_minimum_balance_for_rent_exemption(data_len)
+            .await
+    }
+
+    async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
+        self.inner.get_latest_blockhash().await
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> ProgramClientResult<ST::Output> {
+        self.inner.send_transaction(transaction).await
+    }
+
+    async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>> {
+        match self.feed_account(&address) {
+            Some(account) => Ok(account),
+            None => self.inner.get_account(address).await,
+        }
+    }
+
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        match consistency {
+            // the feed may not have caught up with the transaction yet
+            ReadConsistency::AfterSignature(_) => {
+                self.inner
+                    .get_account_with_consistency(address, consistency)
+                    .await
+            }
+            ReadConsistency::MinContextSlot(min_context_slot) => {
+                match self.feed_account(&address) {
+                    Some(account) if self.slot() >= min_context_slot => Ok(account),
+                    _ => {
+                        self.inner
+                            .get_account_with_consistency(address, consistency)
+                            .await
+                    }
+                }
+            }
+            ReadConsistency::Any => self.get_account(address).await,
+        }
+    }
+
+    /// Always reaches the inner client, since the feed streams at a single
+    /// commitment level
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.inner
+            .get_account_with_commitment(address, commitment)
+            .await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.inner.simulate_transaction(transaction).await
+    }
+
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.inner
+            .simulate_transaction_with_overrides(transaction, overrides)
+            .await
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        self.inner.get_signature_statuses(signatures).await
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        self.inner.get_recent_prioritization_fees(addresses).await
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        self.inner
+            .get_signatures_for_address(address, before, until, limit)
+            .await
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        self.inner.get_transaction(signature).await
+    }
+
+    async fn get_program_accounts_with_filters(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        let accounts = self
+            .inner
+            .get_program_accounts_with_filters(program_id, filters.clone())
+            .await?;
+        Ok(self.merge_program_accounts(program_id, &filters, accounts))
+    }
+
+    /// The slot is the later of the scan's and the feed's, since the feed's
+    /// newer states replace the scanned ones
+    async fn get_program_accounts_with_context(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        let (slot, accounts) = self
+            .inner
+            .get_program_accounts_with_context(program_id, filters.clone())
+            .await?;
+        if !self.filter.owners.contains(program_id) {
+            return Ok((slot, accounts));
+        }
+        Ok((
+            slot.max(self.slot()),
+            self.merge_program_accounts(program_id, &filters, accounts),
+        ))
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        self.inner.get_epoch_info().await
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        self.inner.get_sysvar_clock().await
+    }
+
+    async fn get_token_largest_accounts(
+        &self,
+        mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        self.inner.get_token_largest_accounts(mint).await
+    }
+
+    /// Accounts the feed has not seen change are read from the inner client
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        let mut accounts = addresses
+            .iter()
+            .map(|address| self.feed_account(address))
+            .collect::<Vec<_>>();
+        let missing = addresses
+            .iter()
+            .zip(&accounts)
+            .filter(|(_, account)| account.is_none())
+            .map(|(address, _)| *address)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            let mut fetched = self
+                .inner
+                .get_multiple_accounts(&missing)
+                .await?
+                .into_iter();
+            for account in accounts.iter_mut().filter(|account| account.is_none()) {
+                *account = Some(fetched.next().flatten());
+            }
+        }
+        Ok(accounts.into_iter().map(Option::flatten).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ClientMethod, ProgramRpcClientSendTransaction},
+            mock::MockProgramClient,
+        },
+        futures::stream,
+        yellowstone_grpc_proto::prelude::SubscribeUpdateAccount,
+    };
+
+    #[test]
+    fn test_decode_update() {
+        let address = Pubkey::new_unique();
+        let owner = spl_token_2022::id();
+        let info = SubscribeUpdateAccountInfo {
+            pubkey: address.to_bytes().to_vec(),
+            lamports: 10,
+            owner: owner.to_bytes().to_vec(),
+            data: vec![1, 2, 3],
+            ..SubscribeUpdateAccountInfo::default()
+        };
+        let update = decode_update(5, info.clone()).unwrap();
+        assert_eq!(update.address, address);
+        assert_eq!(update.account.as_ref().unwrap().owner, owner);
+
+        let mut state = GeyserState::default();
+        state.apply(&update);
+        assert_eq!(state.slot, 5);
+
+        // closed accounts have no lamports left
+        let closed = decode_update(
+            6,
+            SubscribeUpdateAccountInfo {
+                lamports: 0,
+                ..info
+            },
+        )
+        .unwrap();
+        assert_eq!(closed.account, None);
+        state.apply(&closed);
+        assert!(state.closed.contains(&address));
+        assert!(state.accounts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reads_fall_back_after_feed_drops() {
+        let address = Pubkey::new_unique();
+        let owner = spl_token_2022::id();
+        let stored = Account {
+            lamports: 1,
+            owner,
+            ..Account::default()
+        };
+        let (updates, _) = broadcast::channel(UPDATES_CAPACITY);
+        let client = GeyserProgramClient {
+            inner: MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+                .with_account(&address, stored.clone()),
+            filter: GeyserAccountFilter {
+                accounts: vec![address],
+                owners: vec![],
+            },
+            state: Arc::new(RwLock::new(GeyserState::default())),
+            updates,
+            task: tokio::spawn(async {}),
+        };
+        let dyn_client: &dyn ProgramClient<ProgramRpcClientSendTransaction> = &client;
+        let mut received = client.subscribe();
+
+        // a feed that sends one change and drops
+        let info = SubscribeUpdateAccountInfo {
+            pubkey: address.to_bytes().to_vec(),
+            lamports: 10,
+            owner: owner.to_bytes().to_vec(),
+            ..SubscribeUpdateAccountInfo::default()
+        };
+        let message = SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(info.clone()),
+                slot: 5,
+                ..SubscribeUpdateAccount::default()
+            })),
+            ..SubscribeUpdate::default()
+        };
+        let update = decode_update(5, info).unwrap();
+
+        // while the feed is up, the change is served without the inner client
+        client.state.write().unwrap().apply(&update);
+        assert_eq!(
+            dyn_client.get_account(address).await.unwrap(),
+            update.account
+        );
+        assert!(client.inner().calls().is_empty());
+
+        follow(
+            stream::iter([Ok::<_, ()>(message)]),
+            &client.state,
+            &client.updates,
+        )
+        .await;
+        assert_eq!(received.recv().await.unwrap(), update);
+
+        // changes may be missed until the feed reconnects
+        assert_eq!(
+            dyn_client.get_account(address).await.unwrap(),
+            Some(stored.clone())
+        );
+        assert_eq!(client.inner().calls(), [ClientMethod::GetAccount]);
+        assert_eq!(
+            dyn_client.get_multiple_accounts(&[address]).await.unwrap(),
+            [Some(stored)]
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Periodic harvesting of the transfer fees withheld in token accounts
+
+use {
+    crate::{
+        batch::{self, BatchOutcome},
+        client::{SendTransaction, SimulateTransaction},
+        token::{Token, TokenResult},
+    },
+    futures::stream::{self, LocalBoxStream, StreamExt},
+    solana_sdk::{
+        pubkey::Pubkey,
+        signer::{signers::Signers, Signer},
+    },
+    spl_token_2022::extension::{
+        transfer_fee::{self, TransferFeeConfig},
+        BaseStateWithExtensions,
+    },
+    std::{ops::Range, time::Duration},
+};
+
+/// Default time between harvests
+pub const DEFAULT_HARVEST_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Result of one harvest
+#[derive(Debug)]
+pub struct HarvestReport<O> {
+    /// Accounts found withholding fees, with the amounts withheld
+    pub sources: Vec<(Pubkey, u64)>,
+    /// Result of the harvest for each source, in order
+    pub harvest: BatchOutcome<O>,
+    /// Result of the withdrawal from the mint to the treasury, if there is a
+    /// treasury and the mint had fees to withdraw, or the error reading the
+    /// fees withheld in the mint
+    pub withdrawal: Option<TokenResult<O>>,
+}
+
+/// Crank moving the fees withheld in the accounts of a mint to the mint, and
+/// optionally on to a treasury account.
+///
+/// Each harvest finds the accounts withholding fees, harvests them in as few
+/// transactions as fit, then withdraws everything withheld in the mint to the
+/// treasury. Harvesting is permissionless and paid by the token's payer; only
+/// the withdrawal needs the mint's withdraw withheld authority to sign.
+///
+/// Once the harvest is sent, its outcome is always reported: failures of the
+/// withdrawal that follows, including reading the mint, are reported with it.
+pub struct FeeHarvester<'a, T> {
+    token: &'a Token<T>,
+    interval: Duration,
+    /// Destination account and withdraw withheld authority
+    treasury: Option<(Pubkey, Pubkey)>,
+    /// Accounts to harvest, instead of scanning the mint's
+    sources: Option<Vec<Pubkey>>,
+}
+
+impl<'a, T> FeeHarvester<'a, T>
+where
+    T: SendTransaction + SimulateTransaction,
+{
+    pub fn new(token: &'a Token<T>) -> Self {
+        Self {
+            token,
+            interval: DEFAULT_HARVEST_INTERVAL,
+            treasury: None,
+            sources: None,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Withdraw the fees withheld in the mint to the destination after each
+    /// harvest, through the authority, which must be among the signers
+    pub fn with_treasury(
+        mut self,
+        destination: Pubkey,
+        withdraw_withheld_authority: Pubkey,
+    ) -> Self {
+        self.treasury = Some((destination, withdraw_withheld_authority));
+        self
+    }
+
+    /// Harvest only these accounts instead of scanning every account of the
+    /// mint, e.g. with a client that cannot scan, such as a banks client
+    pub fn with_sources(mut self, sources: Vec<Pubkey>) -> Self {
+        self.sources = Some(sources);
+        self
+    }
+
+    /// Harvest once, then withdraw to the treasury if there is one
+    pub async fn harvest<S: Signers>(
+        &self,
+        signing_keypairs: &S,
+    ) -> TokenResult<HarvestReport<T::Output>> {
+        let sources = match &self.sources {
+            Some(sources) => self.token.get_withheld_fees(sources).await?,
+            None => self.token.find_accounts_with_withheld_fees().await?,
+        };
+        let addresses = sources
+            .iter()
+            .map(|(address, _)| address)
+            .collect::<Vec<_>>();
+        let instruction = |run: Range<usize>| {
+            transfer_fee::instruction::harvest_withheld_tokens_to_mint(
+                self.token.program_id(),
+                self.token.get_address(),
+                &addresses[run],
+            )
+        };
+        let runs = batch::chunk(addresses.len(), &self.token.payer_pubkey(), instruction)?;
+        let groups = runs
+            .iter()
+            .map(|run| Ok(vec![instruction(run.clone())?]))
+            .collect::<TokenResult<Vec<_>>>()?;
+        let harvest = self
+            .token
+            .process_ix_batches(&groups, &[] as &[&dyn Signer; 0])
+            .await
+            .for_runs(&runs);
+
+        let withdrawal = match &self.treasury {
+            Some((destination, authority)) => {
+                self.withdraw(destination, authority, signing_keypairs)
+                    .await
+            }
+            None => None,
+        };
+
+        Ok(HarvestReport {
+            sources,
+            harvest,
+            withdrawal,
+        })
+    }
+
+    /// Withdraw the fees withheld in the mint, if there are any
+    async fn withdraw<S: Signers>(
+        &self,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> Option<TokenResult<T::Output>> {
+        match self.withheld_in_mint().await {
+            Ok(0) => None,
+            Ok(_) => Some(
+                self.token
+                    .withdraw_withheld_tokens_from_mint(destination, authority, signing_keypairs)
+                    .await,
+            ),
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    async fn withheld_in_mint(&self) -> TokenResult<u64> {
+        let mint = self.token.get_mint_info().await?;
+        Ok(mint
+            .get_extension::<TransferFeeConfig>()?
+            .withheld_amount
+            .into())
+    }
+
+    /// Harvest now and then after every interval, yielding the report of each
+    /// harvest. Errors are yielded too, and the next harvest still happens.
+    ///
+    /// The stream borrows the token, which is not `Sync` whatever its signers,
+    /// so it cannot be sent to another thread: drive it on the task that owns
+    /// the token, e.g. in a `LocalSet`.
+    pub fn run<'b, S: Signers>(
+        &'b self,
+        signing_keypairs: &'b S,
+    ) -> LocalBoxStream<'b, TokenResult<HarvestReport<T::Output>>> {
+        stream::unfold(true, move |first| async move {
+            if !first {
+                self.token.sleep(self.interval).await;
+            }
+            Some((self.harvest(signing_keypairs).await, false))
+        })
+        .boxed_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ClientMethod, ProgramRpcClientSendTransaction},
+            mock::MockProgramClient,
+            token::TokenError,
+        },
+        solana_sdk::{account::Account as SolanaAccount, signature::Keypair},
+        spl_token_2022::{
+            extension::{
+                transfer_fee::TransferFeeAmount, BaseStateWithExtensionsMut, ExtensionType,
+                StateWithExtensionsMut,
+            },
+            state::{Account, AccountState, Mint},
+        },
+        std::sync::Arc,
+    };
+
+    fn program_account(data: Vec<u8>) -> SolanaAccount {
+        SolanaAccount {
+            lamports: 1,
+            data,
+            owner: spl_token_2022::id(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn mint_withholding(withdraw_withheld_authority: &Pubkey, withheld_amount: u64) -> Vec<u8> {
+        let len =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+                .unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.base = Mint {
+            is_initialized: true,
+            ..Mint::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        config.withdraw_withheld_authority = Some(*withdraw_withheld_authority).try_into().unwrap();
+        config.withheld_amount = withheld_amount.into();
+        data
+    }
+
+    fn account_withholding(mint: &Pubkey, withheld_amount: u64) -> Vec<u8> {
+        let len = ExtensionType::try_calculate_account_len::<Account>(&[
+            ExtensionType::TransferFeeAmount,
+        ])
+        .unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Account>::unpack_uninitialized(&mut data).unwrap();
+        state.base = Account {
+            mint: *mint,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        state
+            .init_extension::<TransferFeeAmount>(true)
+            .unwrap()
+            .withheld_amount = withheld_amount.into();
+        data
+    }
+
+    #[test]
+    fn test_harvest() {
+        let mint = Pubkey::new_unique();
+        let (withholding, empty, closed) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let treasury = Pubkey::new_unique();
+        let authority = Keypair::new();
+        let client = Arc::new(
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+                .with_account(
+                    &mint,
+                    program_account(mint_withholding(&authority.pubkey(), 5)),
+                )
+                .with_account(&withholding, program_account(account_withholding(&mint, 7)))
+                .with_account(&empty, program_account(account_withholding(&mint, 0))),
+        );
+        let token = Token::new(
+            client.clone(),
+            &spl_token_2022::id(),
+            &mint,
+            Some(0),
+            Arc::new(Keypair::new()),
+        );
+        let harvester = FeeHarvester::new(&token)
+            .with_sources(vec![withholding, empty, closed])
+            .with_treasury(treasury, authority.pubkey());
+
+        let report = futures::executor::block_on(harvester.harvest(&[&authority])).unwrap();
+        assert_eq!(report.sources, [(withholding, 7)]);
+        assert!(report.harvest.is_success());
+        assert!(matches!(report.withdrawal, Some(Ok(_))));
+        assert_eq!(client.sent_transactions().len(), 2);
+
+        // once harvested, failing to read the mint is reported with the harvest
+        client.fail_next(ClientMethod::GetAccount, "node unavailable");
+        let report = futures::executor::block_on(harvester.harvest(&[&authority])).unwrap();
+        assert!(report.harvest.is_success());
+        assert!(matches!(
+            report.withdrawal,
+            Some(Err(TokenError::Client(_)))
+        ));
+        assert_eq!(client.sent_transactions().len(), 3);
+    }
+}
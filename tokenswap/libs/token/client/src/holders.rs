@@ -0,0 +1,149 @@
+//! Holders of a mint's tokens, e.g. for dashboards, airdrop eligibility and
+//! delegation monitoring
+
+use {
+    crate::format::{self, AmountFormat},
+    solana_sdk::pubkey::Pubkey,
+};
+
+/// A token account and its balance
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenHolder {
+    pub address: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub ui_amount: String,
+}
+
+impl TokenHolder {
+    pub fn new(address: Pubkey, owner: Pubkey, amount: u64, decimals: u8) -> Self {
+        Self {
+            address,
+            owner,
+            amount,
+            ui_amount: format::amount(amount, decimals, &AmountFormat::new()),
+        }
+    }
+}
+
+/// A token account delegating part of its balance
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DelegatedAccount {
+    pub address: Pubkey,
+    pub owner: Pubkey,
+    pub delegated_amount: u64,
+}
+
+/// Keep the `limit` largest holders, largest first. Equal balances are
+/// ordered by address so the result does not depend on the input order.
+pub fn largest(mut holders: Vec<TokenHolder>, limit: usize) -> Vec<TokenHolder> {
+    holders.sort_unstable_by(|a, b| {
+        b.amount
+            .cmp(&a.amount)
+            .then_with(|| a.address.cmp(&b.address))
+    });
+    holders.truncate(limit);
+    holders
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ClientMethod, ProgramRpcClientSendTransaction},
+            mock::MockProgramClient,
+            token::{Token, TokenError},
+        },
+        solana_sdk::signature::Keypair,
+        spl_token_2022::state::{Account, AccountState, Mint},
+        std::sync::Arc,
+    };
+
+    #[test]
+    fn test_largest() {
+        let owner = Pubkey::new_unique();
+        let holders = [5, 12_500_000, 0, 5, 700]
+            .into_iter()
+            .map(|amount| TokenHolder::new(Pubkey::new_unique(), owner, amount, 6))
+            .collect::<Vec<_>>();
+
+        let largest = largest(holders.clone(), 3);
+        assert_eq!(
+            largest
+                .iter()
+                .map(|holder| holder.amount)
+                .collect::<Vec<_>>(),
+            [12_500_000, 700, 5]
+        );
+        assert_eq!(largest[0].ui_amount, "12.5");
+        assert_eq!(
+            largest[2].address,
+            holders[0].address.min(holders[3].address)
+        );
+    }
+
+    #[test]
+    fn test_get_largest_holders() {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let mut client = MockProgramClient::<ProgramRpcClientSendTransaction>::new().with_mint(
+            &mint,
+            &program_id,
+            Mint {
+                decimals: 2,
+                is_initialized: true,
+                ..Mint::default()
+            },
+        );
+        let owners = [Pubkey::new_unique(), Pubkey::new_unique()];
+        for (owner, amount) in owners.iter().zip([100, 300]) {
+            client = client.with_token_account(
+                &Pubkey::new_unique(),
+                &program_id,
+                Account {
+                    mint,
+                    owner: *owner,
+                    amount,
+                    state: AccountState::Initialized,
+                    ..Account::default()
+                },
+            );
+        }
+        let client = Arc::new(client);
+        let token = Token::new(
+            client.clone(),
+            &program_id,
+            &mint,
+            None,
+            Arc::new(Keypair::new()),
+        );
+
+        futures::executor::block_on(async {
+            let holders = token.get_largest_holders(2).await.unwrap();
+            assert_eq!(
+                holders
+                    .iter()
+                    .map(|holder| (holder.owner, holder.amount))
+                    .collect::<Vec<_>>(),
+                [(owners[1], 300), (owners[0], 100)]
+            );
+            // the owners are read at once
+            assert_eq!(
+                client
+                    .calls()
+                    .iter()
+                    .filter(|method| **method == ClientMethod::GetMultipleAccounts)
+                    .count(),
+                1
+            );
+
+            // a failing index is not mistaken for a missing one
+            client.fail_next(ClientMethod::GetTokenLargestAccounts, "unavailable");
+            assert!(matches!(
+                token.get_largest_holders(2).await,
+                Err(TokenError::Client(_))
+            ));
+        });
+    }
+}
@@ -0,0 +1,192 @@
+//! Resolution of the extra accounts required by transfer hooks
+
+use {
+    crate::token::{TokenError, TokenResult},
+    futures::{future, Future},
+    solana_sdk::{instruction::AccountMeta, pubkey::Pubkey},
+    spl_token_2022::{instruction, offchain},
+    spl_transfer_hook_interface::offchain::AccountDataResult,
+    std::collections::HashMap,
+};
+
+/// Accounts the mint's transfer hook requires for a transfer, looking up
+/// account data with the given function. Empty for mints without a hook.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn extra_account_metas<F, Fut>(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    fetch_account_data_fn: F,
+) -> TokenResult<Vec<AccountMeta>>
+where
+    F: Fn(Pubkey) -> Fut,
+    Fut: Future<Output = AccountDataResult>,
+{
+    // only the accounts of the instruction matter for the resolution
+    let mut instruction = instruction::transfer_checked(
+        program_id,
+        source,
+        mint,
+        destination,
+        authority,
+        &[],
+        amount,
+        0,
+    )?;
+    let base_accounts = instruction.accounts.len();
+    offchain::add_extra_account_metas(
+        &mut instruction,
+        source,
+        mint,
+        destination,
+        authority,
+        amount,
+        fetch_account_data_fn,
+    )
+    .await
+    .map_err(|_| TokenError::AccountNotFound)?;
+    Ok(instruction.accounts.split_off(base_accounts))
+}
+
+/// Accounts the mint's transfer hook requires for a transfer, resolved from
+/// preloaded account data instead of a client, e.g. against a snapshot or in
+/// tests. The data must include the mint, the hook's validation account, and
+/// any account whose data seeds an extra account.
+#[allow(clippy::too_many_arguments)]
+pub async fn resolve_transfer_hook_metas_offline(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    accounts: &HashMap<Pubkey, Vec<u8>>,
+) -> TokenResult<Vec<AccountMeta>> {
+    extra_account_metas(
+        program_id,
+        mint,
+        source,
+        destination,
+        authority,
+        amount,
+        |address| future::ready(Ok(accounts.get(&address).cloned())),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_program_test::tokio,
+        solana_sdk::program_option::COption,
+        spl_tlv_account_resolution::{account::ExtraAccountMeta, state::ExtraAccountMetaList},
+        spl_token_2022::{
+            extension::{transfer_hook::TransferHook, ExtensionType, StateWithExtensionsMut},
+            state::Mint,
+        },
+        spl_transfer_hook_interface::{
+            get_extra_account_metas_address, instruction::ExecuteInstruction,
+        },
+    };
+
+    fn mint_data(hook_program_id: Option<Pubkey>) -> Vec<u8> {
+        let extension_types = if hook_program_id.is_some() {
+            vec![ExtensionType::TransferHook]
+        } else {
+            vec![]
+        };
+        let len = ExtensionType::try_calculate_account_len::<Mint>(&extension_types).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.base = Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            is_initialized: true,
+            ..Mint::default()
+        };
+        state.pack_base();
+        if hook_program_id.is_some() {
+            state.init_account_type().unwrap();
+            state
+                .init_extension::<TransferHook>(true)
+                .unwrap()
+                .program_id = hook_program_id.try_into().unwrap();
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_resolve_offline() {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let hook_program_id = Pubkey::new_unique();
+        let (source, destination, authority) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let extra = Pubkey::new_unique();
+        let validation = get_extra_account_metas_address(&mint, &hook_program_id);
+        let extra_account_metas = [ExtraAccountMeta::new_with_pubkey(&extra, false, true).unwrap()];
+        let mut validation_data =
+            vec![0; ExtraAccountMetaList::size_of(extra_account_metas.len()).unwrap()];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut validation_data,
+            &extra_account_metas,
+        )
+        .unwrap();
+
+        let mut accounts = HashMap::from([(mint, mint_data(Some(hook_program_id)))]);
+        assert_eq!(
+            resolve_transfer_hook_metas_offline(
+                &program_id,
+                &mint,
+                &source,
+                &destination,
+                &authority,
+                1,
+                &accounts
+            )
+            .await,
+            Err(TokenError::AccountNotFound)
+        );
+
+        accounts.insert(validation, validation_data);
+        let metas = resolve_transfer_hook_metas_offline(
+            &program_id,
+            &mint,
+            &source,
+            &destination,
+            &authority,
+            1,
+            &accounts,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            metas,
+            [
+                AccountMeta::new(extra, false),
+                AccountMeta::new_readonly(hook_program_id, false),
+                AccountMeta::new_readonly(validation, false),
+            ]
+        );
+
+        let accounts = HashMap::from([(mint, mint_data(None))]);
+        let metas = resolve_transfer_hook_metas_offline(
+            &program_id,
+            &mint,
+            &source,
+            &destination,
+            &authority,
+            1,
+            &accounts,
+        )
+        .await
+        .unwrap();
+        assert!(metas.is_empty());
+    }
+}
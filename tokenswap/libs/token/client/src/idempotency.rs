@@ -0,0 +1,211 @@
+//! Client-side ledger of idempotency keys, used to avoid sending the same
+//! operation twice when a caller retries after a timeout
+
+use {
+    crate::persistence::KvStore,
+    solana_sdk::signature::Signature,
+    std::{
+        collections::{HashMap, VecDeque},
+        io,
+        sync::Mutex,
+    },
+};
+
+/// State recorded for an idempotency key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdempotencyRecord {
+    /// The key is reserved, but no transaction has been signed yet
+    Pending,
+    /// A transaction with the given signature was submitted for the key
+    Submitted(Signature),
+}
+
+/// Storage for idempotency keys
+pub trait IdempotencyStore: Send + Sync {
+    /// Look up the record for a key
+    fn get(&self, key: &str) -> io::Result<Option<IdempotencyRecord>>;
+
+    /// Reserve a key, returning `false` if it was already present
+    fn reserve(&self, key: &str) -> io::Result<bool>;
+
+    /// Record the signature of the transaction submitted for a key
+    fn record(&self, key: &str, signature: Signature) -> io::Result<()>;
+
+    /// Forget a key, allowing the operation to be submitted again
+    fn remove(&self, key: &str) -> io::Result<()>;
+}
+
+/// In-memory idempotency store remembering a bounded number of recent keys
+#[derive(Debug)]
+pub struct InMemoryIdempotencyStore {
+    capacity: usize,
+    inner: Mutex<InMemoryIdempotencyStoreInner>,
+}
+
+#[derive(Debug, Default)]
+struct InMemoryIdempotencyStoreInner {
+    records: HashMap<String, IdempotencyRecord>,
+    order: VecDeque<String>,
+}
+
+impl InMemoryIdempotencyStore {
+    /// Default number of keys remembered
+    pub const DEFAULT_CAPACITY: usize = 10_000;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(InMemoryIdempotencyStoreInner::default()),
+        }
+    }
+}
+
+impl Default for InMemoryIdempotencyStore {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    fn get(&self, key: &str) -> io::Result<Option<IdempotencyRecord>> {
+        Ok(self.inner.lock().unwrap().records.get(key).copied())
+    }
+
+    fn reserve(&self, key: &str) -> io::Result<bool> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.records.contains_key(key) {
+            return Ok(false);
+        }
+        while inner.order.len() >= self.capacity {
+            match inner.order.pop_front() {
+                Some(oldest) => {
+                    inner.records.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+        inner
+            .records
+            .insert(key.to_string(), IdempotencyRecord::Pending);
+        inner.order.push_back(key.to_string());
+        Ok(true)
+    }
+
+    fn record(&self, key: &str, signature: Signature) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(record) = inner.records.get_mut(key) {
+            *record = IdempotencyRecord::Submitted(signature);
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.records.remove(key).is_some() {
+            inner.order.retain(|k| k != key);
+        }
+        Ok(())
+    }
+}
+
+/// Idempotency store persisted in a key-value store, under the
+/// `idempotency/` prefix.
+///
+/// Unlike `InMemoryIdempotencyStore`, keys are never evicted: the store
+/// remembers every key until it is removed.
+#[derive(Debug, Default)]
+pub struct KvIdempotencyStore<S> {
+    store: S,
+}
+
+impl<S: KvStore> KvIdempotencyStore<S> {
+    const PREFIX: &'static [u8] = b"idempotency/";
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    fn key(key: &str) -> Vec<u8> {
+        [Self::PREFIX, key.as_bytes()].concat()
+    }
+}
+
+impl<S: KvStore> IdempotencyStore for KvIdempotencyStore<S> {
+    /// A pending key is stored with an empty value, a submitted one with the
+    /// bytes of its signature
+    fn get(&self, key: &str) -> io::Result<Option<IdempotencyRecord>> {
+        self.store
+            .get(&Self::key(key))?
+            .map(|value| match value.len() {
+                0 => Ok(IdempotencyRecord::Pending),
+                _ => Signature::try_from(value.as_slice())
+                    .map(IdempotencyRecord::Submitted)
+                    .map_err(|_| {
+                        io::Error::new(io::ErrorKind::InvalidData, "invalid stored signature")
+                    }),
+            })
+            .transpose()
+    }
+
+    fn reserve(&self, key: &str) -> io::Result<bool> {
+        self.store.insert_new(&Self::key(key), &[])
+    }
+
+    fn record(&self, key: &str, signature: Signature) -> io::Result<()> {
+        let key = Self::key(key);
+        if self.store.get(&key)?.is_some() {
+            self.store.insert(&key, signature.as_ref())?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        self.store.remove(&Self::key(key)).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::persistence::MemoryStore};
+
+    fn reserve_record_remove(store: &dyn IdempotencyStore) {
+        assert!(store.reserve("mint-1").unwrap());
+        assert!(!store.reserve("mint-1").unwrap());
+        assert_eq!(
+            store.get("mint-1").unwrap(),
+            Some(IdempotencyRecord::Pending)
+        );
+
+        let signature = Signature::new_unique();
+        store.record("mint-1", signature).unwrap();
+        assert_eq!(
+            store.get("mint-1").unwrap(),
+            Some(IdempotencyRecord::Submitted(signature))
+        );
+
+        store.remove("mint-1").unwrap();
+        assert_eq!(store.get("mint-1").unwrap(), None);
+        assert!(store.reserve("mint-1").unwrap());
+    }
+
+    #[test]
+    fn in_memory_reserve_record_remove() {
+        reserve_record_remove(&InMemoryIdempotencyStore::default());
+    }
+
+    #[test]
+    fn kv_reserve_record_remove() {
+        reserve_record_remove(&KvIdempotencyStore::new(MemoryStore::new()));
+    }
+
+    #[test]
+    fn evicts_oldest() {
+        let store = InMemoryIdempotencyStore::new(2);
+        assert!(store.reserve("a").unwrap());
+        assert!(store.reserve("b").unwrap());
+        assert!(store.reserve("c").unwrap());
+        assert_eq!(store.get("a").unwrap(), None);
+        assert_eq!(store.get("b").unwrap(), Some(IdempotencyRecord::Pending));
+        assert_eq!(store.get("c").unwrap(), Some(IdempotencyRecord::Pending));
+    }
+}
@@ -0,0 +1,171 @@
+//! Journal of signed transactions that are held back for later submission
+
+use {
+    crate::persistence::{KvStore, MemoryStore},
+    solana_sdk::{
+        clock::UnixTimestamp, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+    },
+    std::io,
+};
+
+/// A signed durable-nonce transaction waiting to be released
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// The fully signed transaction
+    pub transaction: Transaction,
+    /// Unix timestamp before which the transaction must not be submitted
+    pub release_after: UnixTimestamp,
+    /// Durable nonce account the transaction was signed against
+    pub nonce_account: Pubkey,
+    /// Authority of the durable nonce account
+    pub nonce_authority: Pubkey,
+}
+
+impl JournalEntry {
+    /// Signature identifying the entry
+    pub fn signature(&self) -> Signature {
+        self.transaction.signatures[0]
+    }
+
+    fn serialize(&self) -> io::Result<Vec<u8>> {
+        bincode::serialize(&(
+            &self.transaction,
+            self.release_after,
+            self.nonce_account,
+            self.nonce_authority,
+        ))
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    fn deserialize(data: &[u8]) -> io::Result<Self> {
+        let (transaction, release_after, nonce_account, nonce_authority) =
+            bincode::deserialize(data)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(Self {
+            transaction,
+            release_after,
+            nonce_account,
+            nonce_authority,
+        })
+    }
+}
+
+/// Storage for journal entries, keyed by transaction signature
+pub trait Journal: Send + Sync {
+    /// Add an entry, replacing any entry with the same signature
+    fn insert(&self, entry: JournalEntry) -> io::Result<()>;
+
+    /// Look up an entry
+    fn get(&self, signature: &Signature) -> io::Result<Option<JournalEntry>>;
+
+    /// Remove an entry, returning it if it was present
+    fn remove(&self, signature: &Signature) -> io::Result<Option<JournalEntry>>;
+
+    /// All entries currently in the journal
+    fn entries(&self) -> io::Result<Vec<JournalEntry>>;
+}
+
+/// Journal persisted in a key-value store, under the `journal/` prefix
+#[derive(Debug, Default)]
+pub struct KvJournal<S> {
+    store: S,
+}
+
+/// Journal kept in memory for the lifetime of the process
+pub type InMemoryJournal = KvJournal<MemoryStore>;
+
+impl<S: KvStore> KvJournal<S> {
+    const PREFIX: &'static [u8] = b"journal/";
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    fn key(signature: &Signature) -> Vec<u8> {
+        [Self::PREFIX, signature.as_ref()].concat()
+    }
+}
+
+impl<S: KvStore> Journal for KvJournal<S> {
+    fn insert(&self, entry: JournalEntry) -> io::Result<()> {
+        self.store
+            .insert(&Self::key(&entry.signature()), &entry.serialize()?)
+    }
+
+    fn get(&self, signature: &Signature) -> io::Result<Option<JournalEntry>> {
+        self.store
+            .get(&Self::key(signature))?
+            .map(|data| JournalEntry::deserialize(&data))
+            .transpose()
+    }
+
+    fn remove(&self, signature: &Signature) -> io::Result<Option<JournalEntry>> {
+        self.store
+            .remove(&Self::key(signature))?
+            .map(|data| JournalEntry::deserialize(&data))
+            .transpose()
+    }
+
+    fn entries(&self) -> io::Result<Vec<JournalEntry>> {
+        self.store
+            .scan_prefix(Self::PREFIX)?
+            .iter()
+            .map(|(_, data)| JournalEntry::deserialize(data))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::{
+            hash::Hash, message::Message, signature::Keypair, signer::Signer, system_instruction,
+        },
+    };
+
+    fn entry(release_after: UnixTimestamp) -> JournalEntry {
+        let payer = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+        let message = Message::new_with_nonce(
+            vec![system_instruction::transfer(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(&payer.pubkey()),
+            &nonce_account,
+            &payer.pubkey(),
+        );
+        JournalEntry {
+            transaction: Transaction::new(&[&payer], message, Hash::new_unique()),
+            release_after,
+            nonce_account,
+            nonce_authority: payer.pubkey(),
+        }
+    }
+
+    #[test]
+    fn test_kv_journal() {
+        let journal = InMemoryJournal::new(MemoryStore::new());
+        let (first, second) = (entry(10), entry(20));
+        journal.insert(first.clone()).unwrap();
+        journal.insert(second.clone()).unwrap();
+
+        assert_eq!(
+            journal.get(&first.signature()).unwrap(),
+            Some(first.clone())
+        );
+        let mut entries = journal.entries().unwrap();
+        entries.sort_by_key(|entry| entry.release_after);
+        assert_eq!(entries, [first.clone(), second.clone()]);
+
+        assert_eq!(
+            journal.remove(&first.signature()).unwrap(),
+            Some(first.clone())
+        );
+        assert_eq!(journal.remove(&first.signature()).unwrap(), None);
+        assert_eq!(journal.get(&first.signature()).unwrap(), None);
+        assert_eq!(journal.entries().unwrap(), [second]);
+    }
+}
@@ -0,0 +1,288 @@
+//! Encryption keys of confidential accounts, kept encrypted at rest in a
+//! key-value store
+
+use {
+    crate::{
+        confidential_keys,
+        payment::ConfidentialKeys,
+        persistence::KvStore,
+        token::{TokenError, TokenResult},
+    },
+    chacha20poly1305::{
+        aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+        ChaCha20Poly1305, Key, Nonce,
+    },
+    solana_sdk::{
+        pubkey::Pubkey,
+        signer::{EncodableKey, Signer},
+    },
+    spl_token_2022::solana_zk_token_sdk::encryption::{
+        auth_encryption::AeKey, elgamal::ElGamalKeypair,
+    },
+    std::io,
+    zeroize::Zeroizing,
+};
+
+/// Length of the nonce stored before each encrypted entry
+const NONCE_LEN: usize = 12;
+
+/// Encryption keys of a confidential account, as taken by the `_with_keys`
+/// confidential methods of `Token`
+///
+/// ```
+/// use {
+///     solana_sdk::{pubkey::Pubkey, signature::{Keypair, Signer}},
+///     spl_token_client::{
+///         client::ProgramRpcClientSendTransaction,
+///         keyring::{ConfidentialAccountKeys, Keyring},
+///         mock::MockProgramClient,
+///         persistence::MemoryStore,
+///         token::Token,
+///     },
+///     std::sync::Arc,
+/// };
+///
+/// let program_id = spl_token_2022::id();
+/// let mint = Pubkey::new_unique();
+/// let client = Arc::new(MockProgramClient::<ProgramRpcClientSendTransaction>::new());
+/// let token = Token::new(client.clone(), &program_id, &mint, Some(6), Arc::new(Keypair::new()));
+///
+/// // onboard an account: derive its keys and keep them
+/// let owner = Keypair::new();
+/// let account = Pubkey::new_unique();
+/// let keys = ConfidentialAccountKeys::derive(&owner, Some(&account)).unwrap();
+/// let keyring = Keyring::new(MemoryStore::new(), &[7; 32]);
+/// keyring.save(&owner.pubkey(), &account, &keys).unwrap();
+///
+/// // later, configure it with the stored keys
+/// let keys = keyring.keys(&owner.pubkey(), &account).unwrap();
+/// futures::executor::block_on(token.confidential_transfer_configure_token_account_with_keys(
+///     &account,
+///     &owner.pubkey(),
+///     None,
+///     None,
+///     &keys,
+///     &[&owner],
+/// ))
+/// .unwrap();
+///
+/// // the configuration, followed by the proof of the ElGamal public key
+/// let sent = client.sent_transactions();
+/// assert_eq!(sent.len(), 1);
+/// assert_eq!(sent[0].message.instructions.len(), 2);
+/// assert_eq!(keyring.accounts(&owner.pubkey()).unwrap(), [account]);
+/// ```
+pub struct ConfidentialAccountKeys {
+    pub elgamal_keypair: ElGamalKeypair,
+    pub aes_key: AeKey,
+}
+
+impl ConfidentialAccountKeys {
+    /// Keys derived from the signature of the account's owner, see
+    /// `confidential_keys`
+    pub fn derive(signer: &dyn Signer, account: Option<&Pubkey>) -> TokenResult<Self> {
+        Ok(Self {
+            elgamal_keypair: confidential_keys::derive_elgamal_keypair(signer, account)?,
+            aes_key: confidential_keys::derive_ae_key(signer, account)?,
+        })
+    }
+
+    /// The secrets in the clear, wiped from memory once dropped
+    fn serialize(&self) -> io::Result<Zeroizing<Vec<u8>>> {
+        let encode = |error: Box<dyn std::error::Error>| {
+            io::Error::new(io::ErrorKind::InvalidInput, error.to_string())
+        };
+        let elgamal_keypair = Zeroizing::new(
+            self.elgamal_keypair
+                .write(&mut io::sink())
+                .map_err(encode)?,
+        );
+        let aes_key = Zeroizing::new(self.aes_key.write(&mut io::sink()).map_err(encode)?);
+        Ok(Zeroizing::new(serde_json::to_vec(&(
+            elgamal_keypair.as_str(),
+            aes_key.as_str(),
+        ))?))
+    }
+
+    fn deserialize(data: &[u8]) -> io::Result<Self> {
+        let decode = |error: Box<dyn std::error::Error>| {
+            io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+        };
+        let (elgamal_keypair, aes_key): (String, String) = serde_json::from_slice(data)?;
+        let (elgamal_keypair, aes_key) = (Zeroizing::new(elgamal_keypair), Zeroizing::new(aes_key));
+        Ok(Self {
+            elgamal_keypair: ElGamalKeypair::read(&mut elgamal_keypair.as_bytes())
+                .map_err(decode)?,
+            aes_key: AeKey::read(&mut aes_key.as_bytes()).map_err(decode)?,
+        })
+    }
+}
+
+/// Keys of confidential accounts by owner and account, persisted in a
+/// key-value store under the `keyring/` prefix.
+///
+/// Entries are encrypted with ChaCha20-Poly1305 under a 32-byte key held by
+/// the caller, e.g. from a secrets manager, and bound to their owner and
+/// account, so an entry moved to another account fails to decrypt.
+pub struct Keyring<S> {
+    store: S,
+    cipher: ChaCha20Poly1305,
+}
+
+impl<'a> From<&'a ConfidentialAccountKeys> for ConfidentialKeys<'a> {
+    fn from(keys: &'a ConfidentialAccountKeys) -> Self {
+        Self {
+            elgamal_keypair: &keys.elgamal_keypair,
+            aes_key: &keys.aes_key,
+        }
+    }
+}
+
+impl<S: KvStore> Keyring<S> {
+    const PREFIX: &'static [u8] = b"keyring/";
+
+    pub fn new(store: S, encryption_key: &[u8; 32]) -> Self {
+        Self {
+            store,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(encryption_key)),
+        }
+    }
+
+    fn key(owner: &Pubkey, account: &Pubkey) -> Vec<u8> {
+        [Self::PREFIX, owner.as_ref(), account.as_ref()].concat()
+    }
+
+    /// Store the keys of the account, replacing any previous ones
+    pub fn save(
+        &self,
+        owner: &Pubkey,
+        account: &Pubkey,
+        keys: &ConfidentialAccountKeys,
+    ) -> io::Result<()> {
+        let key = Self::key(owner, account);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &keys.serialize()?,
+                    aad: &key,
+                },
+            )
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "encryption failed"))?;
+        self.store
+            .insert(&key, &[nonce.as_slice(), &ciphertext].concat())
+    }
+
+    /// Keys of the account, if stored. Fails if they cannot be decrypted,
+    /// e.g. with another encryption key.
+    pub fn load(
+        &self,
+        owner: &Pubkey,
+        account: &Pubkey,
+    ) -> io::Result<Option<ConfidentialAccountKeys>> {
+        let key = Self::key(owner, account);
+        let Some(value) = self.store.get(&key)? else {
+            return Ok(None);
+        };
+        if value.len() < NONCE_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated keyring entry",
+            ));
+        }
+        let (nonce, ciphertext) = value.split_at(NONCE_LEN);
+        let plaintext = Zeroizing::new(
+            self.cipher
+                .decrypt(
+                    Nonce::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad: &key,
+                    },
+                )
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "decryption failed"))?,
+        );
+        ConfidentialAccountKeys::deserialize(&plaintext).map(Some)
+    }
+
+    /// Keys of the account, as taken by the `_with_keys` confidential methods
+    /// of `Token`. Fails if none are stored.
+    pub fn keys(&self, owner: &Pubkey, account: &Pubkey) -> TokenResult<ConfidentialAccountKeys> {
+        self.load(owner, account)
+            .map_err(TokenError::Persistence)?
+            .ok_or(TokenError::MissingConfidentialKeys(*account))
+    }
+
+    /// Remove the keys of the account, returning whether they were stored
+    pub fn remove(&self, owner: &Pubkey, account: &Pubkey) -> io::Result<bool> {
+        Ok(self.store.remove(&Self::key(owner, account))?.is_some())
+    }
+
+    /// Every account of the owner with stored keys
+    pub fn accounts(&self, owner: &Pubkey) -> io::Result<Vec<Pubkey>> {
+        let prefix = [Self::PREFIX, owner.as_ref()].concat();
+        self.store
+            .scan_prefix(&prefix)?
+            .into_iter()
+            .map(|(key, _)| {
+                Pubkey::try_from(&key[prefix.len()..])
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid keyring key"))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, crate::persistence::MemoryStore, solana_sdk::signature::Keypair, std::sync::Arc,
+    };
+
+    #[test]
+    fn test_save_load() {
+        let store = Arc::new(MemoryStore::new());
+        let keyring = Keyring::new(store.clone(), &[7; 32]);
+        let owner = Keypair::new();
+        let account = Pubkey::new_unique();
+        let keys = ConfidentialAccountKeys::derive(&owner, Some(&account)).unwrap();
+
+        assert!(keyring.load(&owner.pubkey(), &account).unwrap().is_none());
+        keyring.save(&owner.pubkey(), &account, &keys).unwrap();
+        let loaded = keyring.load(&owner.pubkey(), &account).unwrap().unwrap();
+        assert_eq!(
+            loaded.elgamal_keypair.pubkey(),
+            keys.elgamal_keypair.pubkey()
+        );
+        assert_eq!(loaded.aes_key.decrypt(&keys.aes_key.encrypt(42)), Some(42));
+        assert_eq!(keyring.accounts(&owner.pubkey()).unwrap(), [account]);
+
+        // the secrets are not stored in the clear
+        let (_, value) = store.scan_prefix(b"keyring/").unwrap().remove(0);
+        let plaintext = keys.serialize().unwrap();
+        assert!(!value
+            .windows(plaintext.len())
+            .any(|window| window == &plaintext[..]));
+
+        // another encryption key, or another account, cannot decrypt it
+        assert!(Keyring::new(store.clone(), &[8; 32])
+            .load(&owner.pubkey(), &account)
+            .is_err());
+        let other_account = Pubkey::new_unique();
+        store
+            .insert(
+                &Keyring::<MemoryStore>::key(&owner.pubkey(), &other_account),
+                &value,
+            )
+            .unwrap();
+        assert!(keyring.load(&owner.pubkey(), &other_account).is_err());
+
+        assert!(keyring.remove(&owner.pubkey(), &account).unwrap());
+        assert!(!keyring.remove(&owner.pubkey(), &account).unwrap());
+        assert_eq!(
+            keyring.keys(&owner.pubkey(), &account).err(),
+            Some(TokenError::MissingConfidentialKeys(account))
+        );
+    }
+}
@@ -1,6 +1,48 @@
 #![allow(clippy::arithmetic_side_effects)]
+pub mod ata;
+pub mod audit;
+pub mod authority;
+pub mod batch;
+pub mod bundle;
+pub mod cache;
 pub mod client;
+pub mod composer;
+pub mod confidential_keys;
+pub mod confirmation;
+pub mod context;
+pub mod decode;
+pub mod distribution;
+pub mod events;
+pub mod failover;
+pub mod format;
+#[cfg(feature = "geyser")]
+pub mod geyser;
+pub mod harvest;
+pub mod holders;
+pub mod hook;
+pub mod idempotency;
+pub mod journal;
+pub mod keyring;
+#[cfg(feature = "litesvm")]
+pub mod lite_svm;
+pub mod metadata;
+pub mod metrics;
+pub mod mock;
+pub mod multisig;
+pub mod orchestrator;
 pub mod output;
+pub mod payment;
+pub mod persistence;
+pub mod priority_fee;
+pub mod rate_history;
+pub mod rate_limit;
+pub mod rent;
+pub mod replay;
+pub mod sanitize;
+pub mod snapshot;
+pub mod subscription;
+pub mod summary;
+pub mod supply;
 pub mod token;
 
 /// Helper functions to generate split zero-knowledge proofs for confidential
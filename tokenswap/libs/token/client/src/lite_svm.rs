@@ -0,0 +1,479 @@
+//! Program client running transactions in an in-process LiteSVM
+
+use {
+    crate::client::{
+        epoch_info_from_clock, AccountOverrides, ProgramClient, ProgramClientResult,
+        ReadConsistency, SendTransaction, SignatureStatus, SimulateTransaction, SimulationResult,
+        TransactionRecord,
+    },
+    ::litesvm::{
+        types::{FailedTransactionMetadata, SimulatedTransactionInfo},
+        LiteSVM,
+    },
+    async_trait::async_trait,
+    solana_sdk::{
+        account::Account,
+        clock::{Clock, Slot},
+        commitment_config::CommitmentConfig,
+        epoch_info::EpochInfo,
+        epoch_schedule::EpochSchedule,
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::{Transaction, TransactionError},
+        transaction_context::TransactionReturnData,
+        transport::TransportError,
+    },
+    std::{fmt, sync::Mutex},
+};
+
+/// Process transactions in a LiteSVM, returning their signature
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgramLiteSvmProcessTransaction;
+
+impl SendTransaction for ProgramLiteSvmProcessTransaction {
+    type Output = Signature;
+}
+
+impl SimulateTransaction for ProgramLiteSvmProcessTransaction {
+    type SimulationOutput = LiteSvmSimulation;
+}
+
+/// Outcome of a transaction simulated in a LiteSVM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiteSvmSimulation {
+    pub err: Option<TransactionError>,
+    pub logs: Vec<String>,
+    pub units_consumed: u64,
+    pub return_data: TransactionReturnData,
+}
+
+impl LiteSvmSimulation {
+    fn from_result(result: Result<SimulatedTransactionInfo, FailedTransactionMetadata>) -> Self {
+        let (err, meta) = match result {
+            Ok(info) => (None, info.meta),
+            Err(failed) => (Some(failed.err), failed.meta),
+        };
+        Self {
+            err,
+            logs: meta.logs,
+            units_consumed: meta.compute_units_consumed,
+            return_data: meta.return_data,
+        }
+    }
+}
+
+impl SimulationResult for LiteSvmSimulation {
+    fn logs(&self) -> Option<Vec<String>> {
+        Some(self.logs.clone())
+    }
+
+    fn units_consumed(&self) -> Option<u64> {
+        Some(self.units_consumed)
+    }
+
+    fn return_data(&self) -> Option<TransactionReturnData> {
+        (!self.return_data.data.is_empty()).then(|| self.return_data.clone())
+    }
+
+    fn error(&self) -> Option<TransactionError> {
+        self.err.clone()
+    }
+}
+
+struct ProcessedTransaction {
+    slot: Slot,
+    transaction: Transaction,
+    err: Option<TransactionError>,
+}
+
+/// Program client for an in-process `LiteSVM`, which runs transactions
+/// synchronously without a validator or a test runtime.
+///
+/// Load the token programs and any transfer-hook programs into the VM before
+/// creating the client. Processed transactions are kept to serve the history
+/// methods; failed ones are kept too, as a validator would.
+pub struct ProgramLiteSvmClient {
+    svm: Mutex<LiteSVM>,
+    history: Mutex<Vec<ProcessedTransaction>>,
+}
+
+impl fmt::Debug for ProgramLiteSvmClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgramLiteSvmClient")
+            .field("processed", &self.history.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl ProgramLiteSvmClient {
+    pub fn new(svm: LiteSVM) -> Self {
+        Self {
+            svm: Mutex::new(svm),
+            history: Mutex::new(vec![]),
+        }
+    }
+
+    /// Run a function with exclusive access to the VM, e.g. to set accounts
+    /// or warp the clock
+    pub fn with_svm<F, O>(&self, f: F) -> O
+    where
+        F: FnOnce(&mut LiteSVM) -> O,
+    {
+        f(&mut self.svm.lock().unwrap())
+    }
+
+    fn slot(svm: &LiteSVM) -> Slot {
+        svm.get_sysvar::<Clock>().slot
+    }
+
+    /// The account, unless it has no lamports: the VM cannot delete accounts,
+    /// so one that did not exist is restored without lamports, which the
+    /// runtime treats the same as a missing account
+    fn get_live_account(svm: &LiteSVM, address: &Pubkey) -> Option<Account> {
+        svm.get_account(address)
+            .filter(|account| account.lamports > 0)
+    }
+}
+
+#[async_trait]
+impl ProgramClient<ProgramLiteSvmProcessTransaction> for ProgramLiteSvmClient {
+    async fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> ProgramClientResult<u64> {
+        Ok(self
+            .svm
+            .lock()
+            .unwrap()
+            .minimum_balance_for_rent_exemption(data_len))
+    }
+
+    async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
+        Ok(self.svm.lock().unwrap().latest_blockhash())
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> ProgramClientResult<Signature> {
+        let mut svm = self.svm.lock().unwrap();
+        let slot = Self::slot(&svm);
+        let result = svm.send_transaction(transaction.clone());
+        let err = result.as_ref().err().map(|failed| failed.err.clone());
+        self.history.lock().unwrap().push(ProcessedTransaction {
+            slot,
+            transaction: transaction.clone(),
+            err: err.clone(),
+        });
+        match err {
+            // same error as the banks client, so tests run on either
+            Some(err) => Err(Box::new(TransportError::TransactionError(err))),
+            None => Ok(transaction.signatures[0]),
+        }
+    }
+
+    async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>> {
+        Ok(Self::get_live_account(&self.svm.lock().unwrap(), &address))
+    }
+
+    /// The VM processes transactions synchronously, so every read observes
+    /// every processed transaction
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        _consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.get_account(address).await
+    }
+
+    /// There is a single state, so every commitment level reads it
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        _commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.get_account(address).await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ProgramClientResult<LiteSvmSimulation> {
+        let result = self
+            .svm
+            .lock()
+            .unwrap()
+            .simulate_transaction(transaction.clone());
+        Ok(LiteSvmSimulation::from_result(result))
+    }
+
+    /// The accounts are set for the simulation and restored afterwards, while
+    /// holding the lock so no other transaction observes them. Every account
+    /// set is restored, even if setting another one fails, and the ones that
+    /// did not exist are read as missing again.
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        overrides: &AccountOverrides,
+    ) -> ProgramClientResult<LiteSvmSimulation> {
+        if overrides.is_empty() {
+            return self.simulate_transaction(transaction).await;
+        }
+        let mut svm = self.svm.lock().unwrap();
+        let mut originals = Vec::with_capacity(overrides.len());
+        let applied = overrides.iter().try_for_each(|(address, account)| {
+            originals.push((*address, Self::get_live_account(&svm, address)));
+            svm.set_account(*address, account.clone())
+        });
+
+        let result = applied.map(|()| svm.simulate_transaction(transaction.clone()));
+
+        let mut restored = Ok(());
+        for (address, original) in originals.into_iter().rev() {
+            restored = restored.and(svm.set_account(address, original.unwrap_or_default()));
+        }
+        restored?;
+        Ok(LiteSvmSimulation::from_result(result?))
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        let history = self.history.lock().unwrap();
+        Ok(signatures
+            .iter()
+            .map(|signature| {
+                history
+                    .iter()
+                    .rev()
+                    .find(|processed| processed.transaction.signatures.first() == Some(signature))
+                    .map(|processed| SignatureStatus {
+                        slot: processed.slot,
+                        err: processed.err.clone(),
+                        confirmed: true,
+                    })
+            })
+            .collect())
+    }
+
+    /// The VM has no fee market, so no fees are reported
+    async fn get_recent_prioritization_fees(
+        &self,
+        _addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        Ok(vec![])
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        let history = self.history.lock().unwrap();
+        let signatures = history
+            .iter()
+            .rev()
+            .filter(|processed| processed.transaction.message.account_keys.contains(address))
+            .filter_map(|processed| processed.transaction.signatures.first().copied());
+        let signatures = match before {
+            Some(before) => signatures
+                .skip_while(|signature| *signature != before)
+                .skip(1)
+                .collect::<Vec<_>>(),
+            None => signatures.collect(),
+        };
+        Ok(signatures
+            .into_iter()
+            .take_while(|signature| Some(*signature) != until)
+            .take(limit)
+            .collect())
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        let history = self.history.lock().unwrap();
+        Ok(history
+            .iter()
+            .rev()
+            .find(|processed| processed.transaction.signatures.first() == Some(signature))
+            .map(|processed| {
+                TransactionRecord::from_transaction(
+                    *signature,
+                    processed.slot,
+                    &processed.transaction,
+                    processed.err.clone(),
+                )
+            }))
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        let svm = self.svm.lock().unwrap();
+        let clock = svm.get_sysvar::<Clock>();
+        // the VM has no blocks apart from its slots
+        Ok(epoch_info_from_clock(
+            &clock,
+            &svm.get_sysvar::<EpochSchedule>(),
+            clock.slot,
+        ))
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        Ok(self.svm.lock().unwrap().get_sysvar::<Clock>())
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        let svm = self.svm.lock().unwrap();
+        Ok(addresses
+            .iter()
+            .map(|address| Self::get_live_account(&svm, address))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ClientMethod, Unsupported},
+            token::Token,
+        },
+        solana_sdk::{native_token::LAMPORTS_PER_SOL, signature::Keypair, signer::Signer},
+        std::sync::Arc,
+    };
+
+    #[test]
+    fn test_mint_and_transfer() {
+        let payer = Keypair::new();
+        let mut svm = LiteSVM::new();
+        svm.airdrop(&payer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let client = Arc::new(ProgramLiteSvmClient::new(svm));
+        let mint = Keypair::new();
+        let mint_authority = Keypair::new();
+        let token = Token::new(
+            client.clone(),
+            &spl_token_2022::id(),
+            &mint.pubkey(),
+            Some(6),
+            Arc::new(payer),
+        );
+        let (alice, bob) = (Keypair::new(), Keypair::new());
+        let alice_account = token.get_associated_token_address(&alice.pubkey());
+        let bob_account = token.get_associated_token_address(&bob.pubkey());
+
+        // no runtime needed, the VM processes every transaction synchronously
+        futures::executor::block_on(async {
+            token
+                .create_mint(&mint_authority.pubkey(), None, vec![], &[&mint])
+                .await
+                .unwrap();
+            token
+                .create_associated_token_account(&alice.pubkey())
+                .await
+                .unwrap();
+            token
+                .create_associated_token_account(&bob.pubkey())
+                .await
+                .unwrap();
+            token
+                .mint_to(
+                    &alice_account,
+                    &mint_authority.pubkey(),
+                    100,
+                    &[&mint_authority],
+                )
+                .await
+                .unwrap();
+            token
+                .transfer(&alice_account, &bob_account, &alice.pubkey(), 40, &[&alice])
+                .await
+                .unwrap();
+
+            let alice_state = token.get_account_info(&alice_account).await.unwrap();
+            assert_eq!(alice_state.base.amount, 60);
+            let bob_state = token.get_account_info(&bob_account).await.unwrap();
+            assert_eq!(bob_state.base.amount, 40);
+            assert_eq!(token.get_mint_info().await.unwrap().base.supply, 100);
+
+            // too much is rejected by the program
+            token
+                .transfer(&alice_account, &bob_account, &alice.pubkey(), 61, &[&alice])
+                .await
+                .unwrap_err();
+        });
+    }
+
+    #[test]
+    fn test_overrides_restored() {
+        let payer = Keypair::new();
+        let mut svm = LiteSVM::new();
+        svm.airdrop(&payer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let client = ProgramLiteSvmClient::new(svm);
+        let client: &dyn ProgramClient<ProgramLiteSvmProcessTransaction> = &client;
+        let (existing, missing) = (payer.pubkey(), Pubkey::new_unique());
+        let overrides = AccountOverrides::from([
+            (
+                existing,
+                Account {
+                    lamports: 1,
+                    ..Account::default()
+                },
+            ),
+            (
+                missing,
+                Account {
+                    lamports: 1,
+                    ..Account::default()
+                },
+            ),
+        ]);
+
+        futures::executor::block_on(async {
+            let transaction = Transaction::new_signed_with_payer(
+                &[solana_sdk::system_instruction::transfer(
+                    &payer.pubkey(),
+                    &missing,
+                    1,
+                )],
+                Some(&payer.pubkey()),
+                &[&payer],
+                client.get_latest_blockhash().await.unwrap(),
+            );
+            client
+                .simulate_transaction_with_overrides(&transaction, &overrides)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                client
+                    .get_account(existing)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .lamports,
+                LAMPORTS_PER_SOL
+            );
+            assert_eq!(client.get_account(missing).await.unwrap(), None);
+            assert_eq!(
+                client.get_multiple_accounts(&[missing]).await.unwrap(),
+                [None]
+            );
+
+            // scans are left to the trait, so callers can fall back
+            let error = client
+                .get_program_accounts_with_filters(&spl_token_2022::id(), vec![])
+                .await
+                .unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<Unsupported>(),
+                Some(&Unsupported(ClientMethod::GetProgramAccounts))
+            );
+        });
+    }
+}
@@ -0,0 +1,181 @@
+//! Token metadata resolved from wherever a mint keeps it: in the mint itself,
+//! in an account its metadata pointer designates, or, with the `metaplex`
+//! feature, in its Metaplex metadata account
+
+use {
+    solana_sdk::{program_error::ProgramError, pubkey::Pubkey},
+    spl_token_metadata_interface::state::TokenMetadata,
+    spl_type_length_value::state::{TlvState, TlvStateBorrowed},
+};
+
+/// Program owning Metaplex metadata accounts
+#[cfg(feature = "metaplex")]
+pub const METAPLEX_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// Where the metadata of a mint was found
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataSource {
+    /// The mint's own token metadata extension
+    Mint,
+    /// Token metadata in another account, designated by the metadata pointer
+    Account(Pubkey),
+    /// A Metaplex metadata account, for mints without a metadata pointer
+    Metaplex(Pubkey),
+}
+
+/// Metadata of a mint, whatever its source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedTokenMetadata {
+    pub source: MetadataSource,
+    pub update_authority: Option<Pubkey>,
+    pub mint: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    /// Always empty for Metaplex metadata
+    pub additional_metadata: Vec<(String, String)>,
+}
+
+impl ResolvedTokenMetadata {
+    pub fn new(source: MetadataSource, metadata: TokenMetadata) -> Self {
+        Self {
+            source,
+            update_authority: metadata.update_authority.into(),
+            mint: metadata.mint,
+            name: metadata.name,
+            symbol: metadata.symbol,
+            uri: metadata.uri,
+            additional_metadata: metadata.additional_metadata,
+        }
+    }
+}
+
+/// Token metadata stored as a TLV entry of an account other than the mint
+pub fn unpack_external_metadata(
+    address: Pubkey,
+    data: &[u8],
+) -> Result<ResolvedTokenMetadata, ProgramError> {
+    let state = TlvStateBorrowed::unpack(data)?;
+    let metadata = state.get_first_variable_len_value::<TokenMetadata>()?;
+    Ok(ResolvedTokenMetadata::new(
+        MetadataSource::Account(address),
+        metadata,
+    ))
+}
+
+/// Address of the Metaplex metadata account of a mint
+#[cfg(feature = "metaplex")]
+pub fn metaplex_metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"metadata", METAPLEX_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &METAPLEX_PROGRAM_ID,
+    )
+    .0
+}
+
+/// Read the leading fields of a Metaplex metadata account. Metaplex pads
+/// strings with zeros, which are trimmed.
+#[cfg(feature = "metaplex")]
+pub fn unpack_metaplex_metadata(
+    address: Pubkey,
+    data: &[u8],
+) -> Result<ResolvedTokenMetadata, ProgramError> {
+    /// Discriminator of `MetadataV1` accounts
+    const METADATA_V1_KEY: u8 = 4;
+
+    fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], ProgramError> {
+        if data.len() < len {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (taken, rest) = data.split_at(len);
+        *data = rest;
+        Ok(taken)
+    }
+    fn take_pubkey(data: &mut &[u8]) -> Result<Pubkey, ProgramError> {
+        Pubkey::try_from(take(data, 32)?).map_err(|_| ProgramError::InvalidAccountData)
+    }
+    fn take_string(data: &mut &[u8]) -> Result<String, ProgramError> {
+        let len = u32::from_le_bytes(take(data, 4)?.try_into().unwrap());
+        let bytes = take(data, len as usize)?;
+        let string = std::str::from_utf8(bytes).map_err(|_| ProgramError::InvalidAccountData)?;
+        Ok(string.trim_end_matches('\0').to_string())
+    }
+
+    let mut data = data;
+    if take(&mut data, 1)? != [METADATA_V1_KEY] {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(ResolvedTokenMetadata {
+        source: MetadataSource::Metaplex(address),
+        update_authority: Some(take_pubkey(&mut data)?),
+        mint: take_pubkey(&mut data)?,
+        name: take_string(&mut data)?,
+        symbol: take_string(&mut data)?,
+        uri: take_string(&mut data)?,
+        additional_metadata: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, spl_type_length_value::state::TlvStateMut};
+
+    #[test]
+    fn test_unpack_external_metadata() {
+        let metadata = TokenMetadata {
+            mint: Pubkey::new_unique(),
+            name: "Token".to_string(),
+            symbol: "TKN".to_string(),
+            uri: "https://example.com/token.json".to_string(),
+            additional_metadata: vec![("key".to_string(), "value".to_string())],
+            ..TokenMetadata::default()
+        };
+        let mut data = vec![0; metadata.tlv_size_of().unwrap()];
+        TlvStateMut::unpack(&mut data)
+            .unwrap()
+            .alloc_and_pack_variable_len_entry(&metadata, false)
+            .unwrap();
+
+        let address = Pubkey::new_unique();
+        let resolved = unpack_external_metadata(address, &data).unwrap();
+        assert_eq!(resolved.source, MetadataSource::Account(address));
+        assert_eq!(
+            resolved,
+            ResolvedTokenMetadata::new(resolved.source, metadata)
+        );
+
+        assert!(unpack_external_metadata(address, &[]).is_err());
+    }
+
+    #[cfg(feature = "metaplex")]
+    #[test]
+    fn test_unpack_metaplex_metadata() {
+        let update_authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut data = vec![4];
+        data.extend_from_slice(update_authority.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        for (value, padded_len) in [("Token", 32), ("TKN", 10), ("https://example.com", 200)] {
+            data.extend_from_slice(&(padded_len as u32).to_le_bytes());
+            let mut padded = value.as_bytes().to_vec();
+            padded.resize(padded_len, 0);
+            data.extend_from_slice(&padded);
+        }
+        // seller fee basis points and the remaining fields are not read
+        data.extend_from_slice(&[0; 16]);
+
+        let address = metaplex_metadata_address(&mint);
+        let resolved = unpack_metaplex_metadata(address, &data).unwrap();
+        assert_eq!(resolved.source, MetadataSource::Metaplex(address));
+        assert_eq!(resolved.update_authority, Some(update_authority));
+        assert_eq!(resolved.mint, mint);
+        assert_eq!(
+            (resolved.name.as_str(), resolved.symbol.as_str()),
+            ("Token", "TKN")
+        );
+        assert_eq!(resolved.uri, "https://example.com");
+
+        assert!(unpack_metaplex_metadata(address, &data[..40]).is_err());
+    }
+}
@@ -0,0 +1,415 @@
+//! Request metrics of a program client, for operators monitoring the RPC
+//! consumption of token operations
+
+use {
+    crate::client::{
+        AccountOverrides, ClientMethod, ProgramClient, ProgramClientResult, ReadConsistency,
+        SendTransaction, SignatureStatus, SimulateTransaction, TransactionRecord,
+    },
+    async_trait::async_trait,
+    solana_rpc_client_api::filter::RpcFilterType,
+    solana_sdk::{
+        account::Account,
+        clock::{Clock, Slot},
+        commitment_config::CommitmentConfig,
+        epoch_info::EpochInfo,
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::Transaction,
+    },
+    std::{
+        collections::HashMap,
+        fmt,
+        future::Future,
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+};
+
+/// Upper bounds of the latency buckets, in milliseconds. Slower requests go
+/// to a last, unbounded bucket.
+pub const LATENCY_BUCKETS_MS: [u64; 12] = [1, 2, 5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000];
+
+/// Histogram of request latencies over `LATENCY_BUCKETS_MS`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BUCKETS_MS.len() + 1],
+    total: Duration,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let millis = latency.as_millis();
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| millis <= u128::from(*bound))
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+        self.total += latency;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count()).ok()?;
+        (count > 0).then(|| self.total / count)
+    }
+
+    /// Upper bound of each bucket, `None` for the last one, and its count
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        LATENCY_BUCKETS_MS
+            .iter()
+            .map(|bound| Some(Duration::from_millis(*bound)))
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().copied())
+    }
+
+    /// Upper bound of the bucket holding the given quantile, e.g. `0.99`.
+    /// `Some(None)` means the quantile is in the unbounded bucket.
+    pub fn quantile(&self, quantile: f64) -> Option<Option<Duration>> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        self.buckets().find_map(|(bound, bucket_count)| {
+            seen += bucket_count;
+            (seen >= rank).then_some(bound)
+        })
+    }
+}
+
+/// Metrics of one method
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MethodMetrics {
+    pub requests: u64,
+    pub errors: u64,
+    pub latency: LatencyHistogram,
+}
+
+/// Metrics of every method called on an `InstrumentedProgramClient`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientMetrics {
+    pub methods: HashMap<ClientMethod, MethodMetrics>,
+}
+
+impl ClientMetrics {
+    pub fn get(&self, method: ClientMethod) -> Option<&MethodMetrics> {
+        self.methods.get(&method)
+    }
+
+    pub fn total_requests(&self) -> u64 {
+        self.methods.values().map(|metrics| metrics.requests).sum()
+    }
+
+    pub fn total_errors(&self) -> u64 {
+        self.methods.values().map(|metrics| metrics.errors).sum()
+    }
+}
+
+/// Wraps a program client, counting requests and errors and timing every
+/// request per method.
+///
+/// Keep an `Arc` of the wrapper to read its metrics once it is handed to a
+/// `Token`.
+pub struct InstrumentedProgramClient<C> {
+    inner: C,
+    metrics: Mutex<ClientMetrics>,
+}
+
+impl<C> fmt::Debug for InstrumentedProgramClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstrumentedProgramClient")
+            .field("metrics", &self.metrics.lock().unwrap())
+            .finish()
+    }
+}
+
+impl<C> InstrumentedProgramClient<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            metrics: Mutex::new(ClientMetrics::default()),
+        }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Snapshot of the metrics so far
+    pub fn metrics(&self) -> ClientMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    /// Return the metrics so far and start over, e.g. to report them
+    /// periodically
+    pub fn take_metrics(&self) -> ClientMetrics {
+        std::mem::take(&mut *self.metrics.lock().unwrap())
+    }
+
+    async fn observe<T, F>(&self, method: ClientMethod, request: F) -> ProgramClientResult<T>
+    where
+        F: Future<Output = ProgramClientResult<T>>,
+    {
+        let start = Instant::now();
+        let result = request.await;
+        let latency = start.elapsed();
+
+        let mut metrics = self.metrics.lock().unwrap();
+        let method_metrics = metrics.methods.entry(method).or_default();
+        method_metrics.requests += 1;
+        if result.is_err() {
+            method_metrics.errors += 1;
+        }
+        method_metrics.latency.record(latency);
+        result
+    }
+}
+
+#[async_trait]
+impl<ST, C> ProgramClient<ST> for InstrumentedProgramClient<C>
+where
+    ST: SendTransaction + SimulateTransaction + Send + Sync,
+    C: ProgramClient<ST> + Send + Sync,
+{
+    async fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> ProgramClientResult<u64> {
+        self.observe(
+            ClientMethod::GetMinimumBalanceForRentExemption,
+            self.inner.get_minimum_balance_for_rent_exemption(data_len),
+        )
+        .await
+    }
+
+    async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
+        self.observe(
+            ClientMethod::GetLatestBlockhash,
+            self.inner.get_latest_blockhash(),
+        )
+        .await
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> ProgramClientResult<ST::Output> {
+        self.observe(
+            ClientMethod::SendTransaction,
+            self.inner.send_transaction(transaction),
+        )
+        .await
+    }
+
+    async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>> {
+        self.observe(ClientMethod::GetAccount, self.inner.get_account(address))
+            .await
+    }
+
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.observe(
+            ClientMethod::GetAccount,
+            self.inner
+                .get_account_with_consistency(address, consistency),
+        )
+        .await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.observe(
+            ClientMethod::GetAccount,
+            self.inner.get_account_with_commitment(address, commitment),
+        )
+        .await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.observe(
+            ClientMethod::SimulateTransaction,
+            self.inner.simulate_transaction(transaction),
+        )
+        .await
+    }
+
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.observe(
+            ClientMethod::SimulateTransaction,
+            self.inner
+                .simulate_transaction_with_overrides(transaction, overrides),
+        )
+        .await
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        self.observe(
+            ClientMethod::GetSignatureStatuses,
+            self.inner.get_signature_statuses(signatures),
+        )
+        .await
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        self.observe(
+            ClientMethod::GetRecentPrioritizationFees,
+            self.inner.get_recent_prioritization_fees(addresses),
+        )
+        .await
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        self.observe(
+            ClientMethod::GetSignaturesForAddress,
+            self.inner
+                .get_signatures_for_address(address, before, until, limit),
+        )
+        .await
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        self.observe(
+            ClientMethod::GetTransaction,
+            self.inner.get_transaction(signature),
+        )
+        .await
+    }
+
+    async fn get_program_accounts_with_filters(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        self.observe(
+            ClientMethod::GetProgramAccounts,
+            self.inner
+                .get_program_accounts_with_filters(program_id, filters),
+        )
+        .await
+    }
+
+    async fn get_program_accounts_with_context(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        self.observe(
+            ClientMethod::GetProgramAccounts,
+            self.inner
+                .get_program_accounts_with_context(program_id, filters),
+        )
+        .await
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        self.observe(ClientMethod::GetEpochInfo, self.inner.get_epoch_info())
+            .await
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        self.observe(ClientMethod::GetSysvarClock, self.inner.get_sysvar_clock())
+            .await
+    }
+
+    async fn get_token_largest_accounts(
+        &self,
+        mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        self.observe(
+            ClientMethod::GetTokenLargestAccounts,
+            self.inner.get_token_largest_accounts(mint),
+        )
+        .await
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        self.observe(
+            ClientMethod::GetMultipleAccounts,
+            self.inner.get_multiple_accounts(addresses),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{client::ProgramRpcClientSendTransaction, mock::MockProgramClient},
+    };
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        for millis in [1, 3, 3, 40, 9_003] {
+            histogram.record(Duration::from_millis(millis));
+        }
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.mean(), Some(Duration::from_millis(1_810)));
+        assert_eq!(
+            histogram.quantile(0.5),
+            Some(Some(Duration::from_millis(5)))
+        );
+        assert_eq!(
+            histogram.quantile(0.8),
+            Some(Some(Duration::from_millis(50)))
+        );
+        assert_eq!(histogram.quantile(1.0), Some(None));
+    }
+
+    #[test]
+    fn test_instrumented_client() {
+        let mock = MockProgramClient::<ProgramRpcClientSendTransaction>::new();
+        mock.fail_next(ClientMethod::GetAccount, "unavailable");
+        let client = InstrumentedProgramClient::new(mock);
+        let inner: &dyn ProgramClient<ProgramRpcClientSendTransaction> = &client;
+        futures::executor::block_on(async {
+            let address = Pubkey::new_unique();
+            assert!(inner.get_account(address).await.is_err());
+            assert!(inner.get_account(address).await.is_ok());
+            assert!(inner.get_latest_blockhash().await.is_ok());
+        });
+
+        let metrics = client.take_metrics();
+        let get_account = metrics.get(ClientMethod::GetAccount).unwrap();
+        assert_eq!((get_account.requests, get_account.errors), (2, 1));
+        assert_eq!(get_account.latency.count(), 2);
+        assert_eq!((metrics.total_requests(), metrics.total_errors()), (3, 1));
+        assert_eq!(client.metrics(), ClientMetrics::default());
+    }
+}
@@ -0,0 +1,706 @@
+//! In-memory program client for tests and examples
+
+use {
+    crate::{
+        client::{
+            epoch_info_from_clock, AccountOverrides, ClientMethod, ProgramClient,
+            ProgramClientError, ProgramClientResult, ReadConsistency, RpcClientResponse,
+            SendTransaction, SignatureStatus, SimulateTransaction, TransactionRecord,
+            MAX_LARGEST_ACCOUNTS,
+        },
+        token::{default_sleep, SleepFn},
+    },
+    async_trait::async_trait,
+    solana_rpc_client_api::filter::RpcFilterType,
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        clock::{Clock, Slot},
+        commitment_config::CommitmentConfig,
+        epoch_info::EpochInfo,
+        epoch_schedule::EpochSchedule,
+        hash::Hash,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        signature::Signature,
+        sysvar,
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token_2022::{
+        extension::StateWithExtensions,
+        state::{Account as TokenAccount, Mint},
+    },
+    std::{
+        collections::{HashMap, VecDeque},
+        fmt,
+        marker::PhantomData,
+        sync::Mutex,
+        time::Duration,
+    },
+};
+
+struct LandedTransaction {
+    slot: Slot,
+    transaction: Transaction,
+    err: Option<TransactionError>,
+}
+
+#[derive(Default)]
+struct MockState {
+    accounts: HashMap<Pubkey, Account>,
+    /// Sent transactions, oldest first
+    transactions: Vec<LandedTransaction>,
+    slot: Slot,
+    calls: Vec<ClientMethod>,
+    errors: HashMap<ClientMethod, VecDeque<ProgramClientError>>,
+    transaction_errors: VecDeque<TransactionError>,
+    simulations: VecDeque<RpcClientResponse>,
+}
+
+/// Program client holding accounts in memory, without running any program.
+///
+/// Sent transactions are recorded, land in consecutive slots and succeed
+/// unless scripted otherwise, and are then reported by the history methods.
+/// Accounts are only changed by the test itself, so the mock suits checking
+/// what an operation sends rather than its effects.
+///
+/// Every call is recorded, can be delayed, and can be scripted to fail, to
+/// exercise retries and error handling.
+///
+/// ```
+/// use {
+///     solana_sdk::{pubkey::Pubkey, signature::{Keypair, Signer}},
+///     spl_token_client::{
+///         client::ProgramRpcClientSendTransaction, mock::MockProgramClient, token::Token,
+///     },
+///     spl_token_2022::state::Mint,
+///     std::sync::Arc,
+/// };
+///
+/// let mint = Pubkey::new_unique();
+/// let mint_authority = Keypair::new();
+/// let client = Arc::new(MockProgramClient::<ProgramRpcClientSendTransaction>::new().with_mint(
+///     &mint,
+///     &spl_token_2022::id(),
+///     Mint {
+///         mint_authority: Some(mint_authority.pubkey()).into(),
+///         decimals: 6,
+///         is_initialized: true,
+///         ..Mint::default()
+///     },
+/// ));
+/// let token = Token::new(
+///     client.clone(),
+///     &spl_token_2022::id(),
+///     &mint,
+///     Some(6),
+///     Arc::new(Keypair::new()),
+/// );
+///
+/// futures::executor::block_on(async {
+///     let info = token.get_mint_info().await.unwrap();
+///     assert_eq!(info.base.decimals, 6);
+///
+///     let destination = Pubkey::new_unique();
+///     token
+///         .mint_to(&destination, &mint_authority.pubkey(), 100, &[&mint_authority])
+///         .await
+///         .unwrap();
+/// });
+/// assert_eq!(client.sent_transactions().len(), 1);
+/// ```
+pub struct MockProgramClient<ST> {
+    state: Mutex<MockState>,
+    blockhash: Hash,
+    rent: Rent,
+    latency: Duration,
+    sleep: SleepFn,
+    _send: PhantomData<ST>,
+}
+
+impl<ST> fmt::Debug for MockProgramClient<ST> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("MockProgramClient")
+            .field("accounts", &state.accounts.len())
+            .field("transactions", &state.transactions.len())
+            .field("slot", &state.slot)
+            .field("calls", &state.calls.len())
+            .field("latency", &self.latency)
+            .finish()
+    }
+}
+
+impl<ST> Default for MockProgramClient<ST> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<ST> MockProgramClient<ST> {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(MockState::default()),
+            blockhash: Hash::new_unique(),
+            rent: Rent::default(),
+            latency: Duration::ZERO,
+            sleep: default_sleep(),
+            _send: PhantomData,
+        }
+    }
+
+    pub fn with_blockhash(mut self, blockhash: Hash) -> Self {
+        self.blockhash = blockhash;
+        self
+    }
+
+    pub fn with_rent(mut self, rent: Rent) -> Self {
+        self.rent = rent;
+        self
+    }
+
+    /// Delay every call, e.g. to test timeouts, through the sleep set with
+    /// `with_sleep`
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Use a custom sleep implementation for the latency, e.g. the one given
+    /// to `Token::with_sleep` when not running on tokio
+    pub fn with_sleep(mut self, sleep: SleepFn) -> Self {
+        self.sleep = sleep;
+        self
+    }
+
+    pub fn with_account(self, address: &Pubkey, account: Account) -> Self {
+        self.set_account(address, account);
+        self
+    }
+
+    /// Add a mint without extensions, funded for rent exemption
+    pub fn with_mint(self, address: &Pubkey, program_id: &Pubkey, mint: Mint) -> Self {
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(mint, &mut data).unwrap();
+        let account = self.funded_account(program_id, data);
+        self.with_account(address, account)
+    }
+
+    /// Add a token account without extensions, funded for rent exemption
+    pub fn with_token_account(
+        self,
+        address: &Pubkey,
+        program_id: &Pubkey,
+        token_account: TokenAccount,
+    ) -> Self {
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(token_account, &mut data).unwrap();
+        let account = self.funded_account(program_id, data);
+        self.with_account(address, account)
+    }
+
+    fn funded_account(&self, owner: &Pubkey, data: Vec<u8>) -> Account {
+        Account {
+            lamports: self.rent.minimum_balance(data.len()),
+            data,
+            owner: *owner,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    pub fn set_account(&self, address: &Pubkey, account: Account) {
+        self.state
+            .lock()
+            .unwrap()
+            .accounts
+            .insert(*address, account);
+    }
+
+    pub fn remove_account(&self, address: &Pubkey) -> Option<Account> {
+        self.state.lock().unwrap().accounts.remove(address)
+    }
+
+    /// Fail the next call of the method with the error, without any effect
+    pub fn fail_next(&self, method: ClientMethod, error: impl Into<ProgramClientError>) {
+        self.state
+            .lock()
+            .unwrap()
+            .errors
+            .entry(method)
+            .or_default()
+            .push_back(error.into());
+    }
+
+    /// Land the next sent transaction with the error, as if the program had
+    /// failed it
+    pub fn fail_next_transaction(&self, error: TransactionError) {
+        self.state
+            .lock()
+            .unwrap()
+            .transaction_errors
+            .push_back(error);
+    }
+
+    /// Return the response from the next simulation, instead of the
+    /// transaction
+    pub fn script_simulation(&self, response: RpcClientResponse) {
+        self.state.lock().unwrap().simulations.push_back(response);
+    }
+
+    /// Transactions sent so far, oldest first
+    pub fn sent_transactions(&self) -> Vec<Transaction> {
+        self.state
+            .lock()
+            .unwrap()
+            .transactions
+            .iter()
+            .map(|landed| landed.transaction.clone())
+            .collect()
+    }
+
+    /// Methods called so far, oldest first
+    pub fn calls(&self) -> Vec<ClientMethod> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    pub fn clear_calls(&self) {
+        self.state.lock().unwrap().calls.clear();
+    }
+
+    /// Record the call, wait out the latency, and fail it if scripted to
+    async fn begin(&self, method: ClientMethod) -> ProgramClientResult<()> {
+        let error = {
+            let mut state = self.state.lock().unwrap();
+            state.calls.push(method);
+            state
+                .errors
+                .get_mut(&method)
+                .and_then(|errors| errors.pop_front())
+        };
+        if !self.latency.is_zero() {
+            (self.sleep)(self.latency).await;
+        }
+        error.map_or(Ok(()), Err)
+    }
+
+    /// The clock sysvar account if one was set, or a clock at the current
+    /// slot
+    fn clock(&self) -> Clock {
+        let state = self.state.lock().unwrap();
+        state
+            .accounts
+            .get(&sysvar::clock::id())
+            .and_then(|account| bincode::deserialize(&account.data).ok())
+            .unwrap_or_else(|| Clock {
+                slot: state.slot,
+                epoch: EpochSchedule::default().get_epoch(state.slot),
+                ..Clock::default()
+            })
+    }
+
+    fn landed(&self, signature: &Signature) -> Option<(Slot, Option<TransactionError>)> {
+        self.state
+            .lock()
+            .unwrap()
+            .transactions
+            .iter()
+            .find(|landed| landed.transaction.signatures.first() == Some(signature))
+            .map(|landed| (landed.slot, landed.err.clone()))
+    }
+
+    fn record(&self, signature: &Signature) -> Option<TransactionRecord> {
+        let state = self.state.lock().unwrap();
+        let LandedTransaction {
+            slot,
+            transaction,
+            err,
+        } = state
+            .transactions
+            .iter()
+            .find(|landed| landed.transaction.signatures.first() == Some(signature))?;
+        Some(TransactionRecord::from_transaction(
+            *signature,
+            *slot,
+            transaction,
+            err.clone(),
+        ))
+    }
+}
+
+#[async_trait]
+impl<ST> ProgramClient<ST> for MockProgramClient<ST>
+where
+    ST: SendTransaction<Output = RpcClientResponse>
+        + SimulateTransaction<SimulationOutput = RpcClientResponse>
+        + Send
+        + Sync,
+{
+    async fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> ProgramClientResult<u64> {
+        self.begin(ClientMethod::GetMinimumBalanceForRentExemption)
+            .await?;
+        Ok(self.rent.minimum_balance(data_len))
+    }
+
+    async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
+        self.begin(ClientMethod::GetLatestBlockhash).await?;
+        Ok(self.blockhash)
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> ProgramClientResult<ST::Output> {
+        self.begin(ClientMethod::SendTransaction).await?;
+        let mut state = self.state.lock().unwrap();
+        state.slot += 1;
+        let slot = state.slot;
+        let err = state.transaction_errors.pop_front();
+        state.transactions.push(LandedTransaction {
+            slot,
+            transaction: transaction.clone(),
+            err: err.clone(),
+        });
+        match err {
+            Some(err) => Err(err.into()),
+            None => Ok(RpcClientResponse::Signature(
+                transaction.signatures.first().copied().unwrap_or_default(),
+            )),
+        }
+    }
+
+    async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>> {
+        self.begin(ClientMethod::GetAccount).await?;
+        Ok(self.state.lock().unwrap().accounts.get(&address).cloned())
+    }
+
+    /// Reads at a slot the mock has not reached yet fail, as they do on a
+    /// node that has not caught up
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.begin(ClientMethod::GetAccount).await?;
+        let state = self.state.lock().unwrap();
+        if let ReadConsistency::MinContextSlot(min_context_slot) = consistency {
+            if min_context_slot > state.slot {
+                return Err(format!(
+                    "minimum context slot {min_context_slot} has not been reached, at slot {}",
+                    state.slot
+                )
+                .into());
+            }
+        }
+        Ok(state.accounts.get(&address).cloned())
+    }
+
+    /// There is a single state, so every commitment level reads it
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        _commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.get_account(address).await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.begin(ClientMethod::SimulateTransaction).await?;
+        let scripted = self.state.lock().unwrap().simulations.pop_front();
+        Ok(scripted.unwrap_or_else(|| RpcClientResponse::Transaction(transaction.clone())))
+    }
+
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        _overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.simulate_transaction(transaction).await
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        self.begin(ClientMethod::GetSignatureStatuses).await?;
+        Ok(signatures
+            .iter()
+            .map(|signature| {
+                self.landed(signature).map(|(slot, err)| SignatureStatus {
+                    slot,
+                    err,
+                    confirmed: true,
+                })
+            })
+            .collect())
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        _addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        self.begin(ClientMethod::GetRecentPrioritizationFees)
+            .await?;
+        Ok(vec![])
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        self.begin(ClientMethod::GetSignaturesForAddress).await?;
+        let state = self.state.lock().unwrap();
+        let signatures = state
+            .transactions
+            .iter()
+            .rev()
+            .filter(|landed| landed.transaction.message.account_keys.contains(address))
+            .filter_map(|landed| landed.transaction.signatures.first().copied());
+        let signatures = match before {
+            Some(before) => signatures
+                .skip_while(|signature| *signature != before)
+                .skip(1)
+                .collect::<Vec<_>>(),
+            None => signatures.collect(),
+        };
+        Ok(signatures
+            .into_iter()
+            .take_while(|signature| Some(*signature) != until)
+            .take(limit)
+            .collect())
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        self.begin(ClientMethod::GetTransaction).await?;
+        Ok(self.record(signature))
+    }
+
+    async fn get_program_accounts_with_filters(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        self.get_program_accounts_with_context(program_id, filters)
+            .await
+            .map(|(_, accounts)| accounts)
+    }
+
+    async fn get_program_accounts_with_context(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        self.begin(ClientMethod::GetProgramAccounts).await?;
+        let state = self.state.lock().unwrap();
+        let accounts = state
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id)
+            .filter(|(_, account)| {
+                let account = AccountSharedData::from((*account).clone());
+                filters.iter().all(|filter| filter.allows(&account))
+            })
+            .map(|(address, account)| (*address, account.clone()))
+            .collect();
+        Ok((state.slot, accounts))
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        self.begin(ClientMethod::GetEpochInfo).await?;
+        let clock = self.clock();
+        Ok(epoch_info_from_clock(
+            &clock,
+            &EpochSchedule::default(),
+            clock.slot,
+        ))
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        self.begin(ClientMethod::GetSysvarClock).await?;
+        Ok(self.clock())
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        self.begin(ClientMethod::GetMultipleAccounts).await?;
+        let state = self.state.lock().unwrap();
+        Ok(addresses
+            .iter()
+            .map(|address| state.accounts.get(address).cloned())
+            .collect())
+    }
+
+    async fn get_token_largest_accounts(
+        &self,
+        mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        self.begin(ClientMethod::GetTokenLargestAccounts).await?;
+        let state = self.state.lock().unwrap();
+        let mut balances = state
+            .accounts
+            .iter()
+            .filter_map(|(address, account)| {
+                let account = StateWithExtensions::<TokenAccount>::unpack(&account.data).ok()?;
+                (account.base.mint == *mint).then_some((*address, account.base.amount))
+            })
+            .collect::<Vec<_>>();
+        balances.sort_by(|(_, a), (_, b)| b.cmp(a));
+        balances.truncate(MAX_LARGEST_ACCOUNTS);
+        Ok(balances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::client::ProgramRpcClientSendTransaction,
+        futures::future,
+        solana_sdk::{
+            instruction::InstructionError, signature::Keypair, signer::Signer, system_instruction,
+        },
+        std::sync::Arc,
+    };
+
+    type Client = dyn ProgramClient<ProgramRpcClientSendTransaction>;
+
+    #[test]
+    fn test_latency_through_sleep() {
+        let slept = Arc::new(Mutex::new(vec![]));
+        let sleep: SleepFn = {
+            let slept = slept.clone();
+            Arc::new(move |duration| {
+                slept.lock().unwrap().push(duration);
+                Box::pin(future::ready(()))
+            })
+        };
+        let mock = MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+            .with_latency(Duration::from_millis(5))
+            .with_sleep(sleep);
+        let client: &Client = &mock;
+        // no tokio runtime needed
+        futures::executor::block_on(client.get_latest_blockhash()).unwrap();
+        assert_eq!(*slept.lock().unwrap(), [Duration::from_millis(5)]);
+    }
+
+    #[test]
+    fn test_scripted_failures() {
+        let mock = MockProgramClient::<ProgramRpcClientSendTransaction>::new();
+        let client: &Client = &mock;
+        let payer = Keypair::new();
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        );
+
+        mock.fail_next(ClientMethod::GetLatestBlockhash, "unavailable");
+        mock.fail_next_transaction(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(1),
+        ));
+        futures::executor::block_on(async {
+            assert!(client.get_latest_blockhash().await.is_err());
+            assert!(client.get_latest_blockhash().await.is_ok());
+
+            assert!(client.send_transaction(&transaction).await.is_err());
+            let statuses = client
+                .get_signature_statuses(&transaction.signatures)
+                .await
+                .unwrap();
+            assert!(statuses[0].as_ref().unwrap().err.is_some());
+        });
+        assert_eq!(
+            mock.calls(),
+            [
+                ClientMethod::GetLatestBlockhash,
+                ClientMethod::GetLatestBlockhash,
+                ClientMethod::SendTransaction,
+                ClientMethod::GetSignatureStatuses,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_min_context_slot() {
+        let address = Pubkey::new_unique();
+        let mock = MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+            .with_account(&address, Account::default());
+        let client: &Client = &mock;
+        let payer = Keypair::new();
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        );
+
+        futures::executor::block_on(async {
+            // rejected until a transaction lands in the slot
+            assert!(client
+                .get_account_with_consistency(address, ReadConsistency::MinContextSlot(1))
+                .await
+                .is_err());
+            client.send_transaction(&transaction).await.unwrap();
+            assert!(client
+                .get_account_with_consistency(address, ReadConsistency::MinContextSlot(1))
+                .await
+                .unwrap()
+                .is_some());
+            assert!(client
+                .get_account_with_consistency(address, ReadConsistency::MinContextSlot(2))
+                .await
+                .is_err());
+        });
+    }
+
+    #[test]
+    fn test_epoch_info_from_clock() {
+        let schedule = EpochSchedule::default();
+        let epoch = schedule.first_normal_epoch + 1;
+        let clock = Clock {
+            slot: schedule.get_first_slot_in_epoch(epoch) + 7,
+            epoch,
+            ..Clock::default()
+        };
+        let mock = MockProgramClient::<ProgramRpcClientSendTransaction>::new().with_account(
+            &sysvar::clock::id(),
+            Account {
+                lamports: 1,
+                data: bincode::serialize(&clock).unwrap(),
+                owner: sysvar::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        let client: &Client = &mock;
+        futures::executor::block_on(async {
+            assert_eq!(client.get_sysvar_clock().await.unwrap(), clock);
+
+            let epoch_info = client.get_epoch_info().await.unwrap();
+            assert_eq!(epoch_info.epoch, epoch);
+            assert_eq!(epoch_info.slot_index, 7);
+            assert_eq!(epoch_info.absolute_slot, clock.slot);
+        });
+    }
+}
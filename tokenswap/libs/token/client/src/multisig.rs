@@ -0,0 +1,86 @@
+//! Inspection of token program multisig accounts
+
+use {solana_sdk::pubkey::Pubkey, spl_token_2022::state::Multisig};
+
+/// A multisig account: `m` of its `n` signers must sign
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultisigInfo {
+    pub address: Pubkey,
+    pub m: u8,
+    pub n: u8,
+    pub signers: Vec<Pubkey>,
+}
+
+impl MultisigInfo {
+    pub fn new(address: Pubkey, multisig: &Multisig) -> Self {
+        Self {
+            address,
+            m: multisig.m,
+            n: multisig.n,
+            signers: multisig
+                .signers
+                .iter()
+                .take(usize::from(multisig.n))
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Signers of the multisig among the given keys, each counted once
+    pub fn matching_signers(&self, keys: &[Pubkey]) -> Vec<Pubkey> {
+        let mut matching = vec![];
+        for signer in &self.signers {
+            if keys.contains(signer) && !matching.contains(signer) {
+                matching.push(*signer);
+            }
+        }
+        matching
+    }
+
+    /// Signatures still needed once the given keys have signed
+    pub fn signatures_needed(&self, keys: &[Pubkey]) -> usize {
+        usize::from(self.m).saturating_sub(self.matching_signers(keys).len())
+    }
+
+    /// Signers that have not signed yet, any `signatures_needed` of which
+    /// would complete the signatures
+    pub fn remaining_signers(&self, keys: &[Pubkey]) -> Vec<Pubkey> {
+        self.signers
+            .iter()
+            .filter(|signer| !keys.contains(signer))
+            .copied()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, spl_token_2022::instruction::MAX_SIGNERS};
+
+    #[test]
+    fn test_signatures_needed() {
+        let keys = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let mut signers = [Pubkey::default(); MAX_SIGNERS];
+        signers[..3].copy_from_slice(&keys);
+        let info = MultisigInfo::new(
+            Pubkey::new_unique(),
+            &Multisig {
+                m: 2,
+                n: 3,
+                is_initialized: true,
+                signers,
+            },
+        );
+        assert_eq!(info.signers, keys);
+
+        assert_eq!(info.signatures_needed(&[]), 2);
+        assert_eq!(info.signatures_needed(&[keys[1], keys[1]]), 1);
+        assert_eq!(info.remaining_signers(&[keys[1]]), [keys[0], keys[2]]);
+        assert_eq!(info.signatures_needed(&[keys[0], keys[2]]), 0);
+        assert_eq!(info.signatures_needed(&[Pubkey::new_unique()]), 2);
+    }
+}
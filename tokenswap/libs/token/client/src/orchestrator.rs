@@ -0,0 +1,195 @@
+//! Bounded, named execution of the steps of multi-transaction flows
+
+use {
+    crate::token::{TokenError, TokenResult},
+    futures::{future::LocalBoxFuture, stream, StreamExt},
+    std::fmt,
+};
+
+/// Default number of steps of a flow running at the same time
+pub const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Failure of one named step of a flow
+#[derive(Debug, PartialEq)]
+pub struct StepFailure {
+    pub step: String,
+    pub error: TokenError,
+}
+
+/// Outcome of a flow in which at least one step failed
+#[derive(Debug, PartialEq)]
+pub struct OrchestrationError {
+    /// Steps that succeeded, whose effects may need to be undone
+    pub completed: Vec<String>,
+    /// Steps that failed, in order
+    pub failed: Vec<StepFailure>,
+    /// Cleanup hooks that failed after the flow did
+    pub cleanup_failed: Vec<StepFailure>,
+}
+
+impl fmt::Display for OrchestrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self
+            .failed
+            .iter()
+            .map(|failure| format!("{} ({})", failure.step, failure.error))
+            .collect::<Vec<_>>();
+        write!(f, "failed steps: {}", failed.join(", "))?;
+        if !self.completed.is_empty() {
+            write!(f, "; completed steps: {}", self.completed.join(", "))?;
+        }
+        if !self.cleanup_failed.is_empty() {
+            let cleanup_failed = self
+                .cleanup_failed
+                .iter()
+                .map(|failure| format!("{} ({})", failure.step, failure.error))
+                .collect::<Vec<_>>();
+            write!(f, "; failed cleanups: {}", cleanup_failed.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the steps of a flow with bounded concurrency, reporting every failure
+/// along with the steps that completed, instead of stopping at the first
+/// error like `try_join!`.
+///
+/// Cleanup hooks only run, in order, if some step failed. `Token` uses it for
+/// its parallel flows, and it can drive application flows the same way.
+pub struct Orchestrator<'a, O> {
+    max_concurrency: usize,
+    steps: Vec<(String, LocalBoxFuture<'a, TokenResult<O>>)>,
+    cleanups: Vec<(String, LocalBoxFuture<'a, TokenResult<()>>)>,
+}
+
+impl<'a, O> Orchestrator<'a, O> {
+    pub fn new(max_concurrency: usize) -> Self {
+        Self {
+            max_concurrency: max_concurrency.max(1),
+            steps: vec![],
+            cleanups: vec![],
+        }
+    }
+
+    pub fn step<N: Into<String>>(
+        mut self,
+        name: N,
+        step: LocalBoxFuture<'a, TokenResult<O>>,
+    ) -> Self {
+        self.steps.push((name.into(), step));
+        self
+    }
+
+    pub fn on_failure<N: Into<String>>(
+        mut self,
+        name: N,
+        cleanup: LocalBoxFuture<'a, TokenResult<()>>,
+    ) -> Self {
+        self.cleanups.push((name.into(), cleanup));
+        self
+    }
+
+    /// Run every step, returning their outputs in the order they were added
+    pub async fn run(self) -> TokenResult<Vec<O>> {
+        let results = stream::iter(
+            self.steps
+                .into_iter()
+                .map(|(name, step)| async move { (name, step.await) }),
+        )
+        .buffered(self.max_concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+        let mut outputs = Vec::with_capacity(results.len());
+        let mut completed = vec![];
+        let mut failed = vec![];
+        for (step, result) in results {
+            match result {
+                Ok(output) => {
+                    outputs.push(output);
+                    completed.push(step);
+                }
+                Err(error) => failed.push(StepFailure { step, error }),
+            }
+        }
+        if failed.is_empty() {
+            return Ok(outputs);
+        }
+
+        let mut cleanup_failed = vec![];
+        for (step, cleanup) in self.cleanups {
+            if let Err(error) = cleanup.await {
+                cleanup_failed.push(StepFailure { step, error });
+            }
+        }
+        Err(TokenError::Orchestration(OrchestrationError {
+            completed,
+            failed,
+            cleanup_failed,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        futures::FutureExt,
+        solana_program_test::tokio,
+        std::sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    #[tokio::test]
+    async fn test_outputs_in_order() {
+        let outputs = Orchestrator::new(2)
+            .step("a", async { Ok(1) }.boxed_local())
+            .step("b", async { Ok(2) }.boxed_local())
+            .step("c", async { Ok(3) }.boxed_local())
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(outputs, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_failures_reported_with_cleanup() {
+        let cleanups = AtomicUsize::new(0);
+        let error = Orchestrator::new(1)
+            .step("a", async { Ok(1) }.boxed_local())
+            .step(
+                "b",
+                async { Err(TokenError::AccountNotFound) }.boxed_local(),
+            )
+            .step("c", async { Err(TokenError::NotEnoughFunds) }.boxed_local())
+            .on_failure(
+                "undo a",
+                async {
+                    cleanups.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }
+                .boxed_local(),
+            )
+            .run()
+            .await
+            .unwrap_err();
+
+        assert_eq!(cleanups.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            error,
+            TokenError::Orchestration(OrchestrationError {
+                completed: vec!["a".to_string()],
+                failed: vec![
+                    StepFailure {
+                        step: "b".to_string(),
+                        error: TokenError::AccountNotFound,
+                    },
+                    StepFailure {
+                        step: "c".to_string(),
+                        error: TokenError::NotEnoughFunds,
+                    },
+                ],
+                cleanup_failed: vec![],
+            })
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Payments through a single entry point, which picks the transfer variant
+//! required by the mint and the destination
+
+use {
+    solana_sdk::pubkey::Pubkey,
+    spl_token_2022::solana_zk_token_sdk::encryption::{
+        auth_encryption::AeKey, elgamal::ElGamalKeypair,
+    },
+    std::fmt,
+};
+
+/// Recipient of a payment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaymentDestination {
+    /// Wallet whose associated token account receives the payment
+    Owner(Pubkey),
+    /// Token account receiving the payment
+    Account(Pubkey),
+}
+
+/// Amount of a payment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaymentAmount {
+    /// Base units of the mint
+    Raw(u64),
+    /// Amount in whole tokens, e.g. `"12.5"`, parsed strictly against the
+    /// mint's decimals
+    Ui(String),
+}
+
+/// Whether a payment may, or must, be sent confidentially
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Confidentiality {
+    #[default]
+    Public,
+    /// Send confidentially if the mint, both accounts and the keys allow it,
+    /// publicly otherwise
+    Preferred,
+    Required,
+}
+
+/// Whether to create the destination owner's associated token account
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CreateAtaPolicy {
+    /// Fail if the account does not exist
+    #[default]
+    Never,
+    IfMissing,
+}
+
+/// Keys of the source account, required for confidential payments
+#[derive(Clone, Copy)]
+pub struct ConfidentialKeys<'a> {
+    pub elgamal_keypair: &'a ElGamalKeypair,
+    pub aes_key: &'a AeKey,
+}
+
+impl fmt::Debug for ConfidentialKeys<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfidentialKeys")
+            .field("elgamal_pubkey", &self.elgamal_keypair.pubkey())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Payment to send with `Token::send_payment`
+#[derive(Clone, Debug)]
+pub struct PaymentRequest<'a> {
+    pub source: Pubkey,
+    pub authority: Pubkey,
+    pub destination: PaymentDestination,
+    pub amount: PaymentAmount,
+    pub memo: Option<String>,
+    pub confidentiality: Confidentiality,
+    pub confidential_keys: Option<ConfidentialKeys<'a>>,
+    /// Largest transfer fee the sender accepts, in base units
+    pub max_fee: Option<u64>,
+    pub create_ata: CreateAtaPolicy,
+}
+
+impl<'a> PaymentRequest<'a> {
+    pub fn new(
+        source: &Pubkey,
+        authority: &Pubkey,
+        destination: PaymentDestination,
+        amount: PaymentAmount,
+    ) -> Self {
+        Self {
+            source: *source,
+            authority: *authority,
+            destination,
+            amount,
+            memo: None,
+            confidentiality: Confidentiality::default(),
+            confidential_keys: None,
+            max_fee: None,
+            create_ata: CreateAtaPolicy::default(),
+        }
+    }
+
+    pub fn with_memo<M: Into<String>>(mut self, memo: M) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
+
+    pub fn with_confidentiality(
+        mut self,
+        confidentiality: Confidentiality,
+        keys: ConfidentialKeys<'a>,
+    ) -> Self {
+        self.confidentiality = confidentiality;
+        self.confidential_keys = Some(keys);
+        self
+    }
+
+    pub fn with_max_fee(mut self, max_fee: u64) -> Self {
+        self.max_fee = Some(max_fee);
+        self
+    }
+
+    pub fn with_create_ata(mut self, create_ata: CreateAtaPolicy) -> Self {
+        self.create_ata = create_ata;
+        self
+    }
+}
+
+/// Transfer variant a payment was sent with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaymentKind {
+    Transfer,
+    /// Transfer asserting the mint's transfer fee
+    TransferWithFee,
+    /// Transfer carrying the accounts of the mint's transfer hook
+    HookedTransfer,
+    Confidential,
+}
+
+/// Outcome of a payment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentReceipt<O> {
+    pub output: O,
+    pub kind: PaymentKind,
+    /// Token account that received the payment
+    pub destination: Pubkey,
+    /// Amount sent, in base units, including the transfer fee
+    pub amount: u64,
+    /// Transfer fee withheld from the amount
+    pub fee: u64,
+    /// Whether the destination associated token account was created
+    pub created_destination: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{client::ProgramRpcClientSendTransaction, mock::MockProgramClient, token::Token},
+        solana_sdk::signature::{Keypair, Signer},
+        spl_token_2022::state::{Account, AccountState, Mint},
+        std::sync::Arc,
+    };
+
+    #[test]
+    fn test_send_payment_creates_destination() {
+        let mint = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let authority = Keypair::new();
+        let client = Arc::new(
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+                .with_mint(
+                    &mint,
+                    &spl_token_2022::id(),
+                    Mint {
+                        decimals: 2,
+                        is_initialized: true,
+                        ..Mint::default()
+                    },
+                )
+                .with_token_account(
+                    &source,
+                    &spl_token_2022::id(),
+                    Account {
+                        mint,
+                        owner: authority.pubkey(),
+                        amount: 1_000,
+                        state: AccountState::Initialized,
+                        ..Account::default()
+                    },
+                ),
+        );
+        let token = Token::new(
+            client.clone(),
+            &spl_token_2022::id(),
+            &mint,
+            Some(2),
+            Arc::new(Keypair::new()),
+        );
+
+        let owner = Pubkey::new_unique();
+        let request = PaymentRequest::new(
+            &source,
+            &authority.pubkey(),
+            PaymentDestination::Owner(owner),
+            PaymentAmount::Ui("1.25".to_string()),
+        );
+        futures::executor::block_on(async {
+            // a failed payment leaves no memo behind for the next transaction
+            assert!(token
+                .send_payment(&request.clone().with_memo("invoice 41"), &[&authority])
+                .await
+                .is_err());
+            assert!(client.sent_transactions().is_empty());
+
+            let receipt = token
+                .send_payment(
+                    &request.clone().with_create_ata(CreateAtaPolicy::IfMissing),
+                    &[&authority],
+                )
+                .await
+                .unwrap();
+            assert_eq!(receipt.kind, PaymentKind::Transfer);
+            assert_eq!(
+                receipt.destination,
+                token.get_associated_token_address(&owner)
+            );
+            assert_eq!((receipt.amount, receipt.fee), (125, 0));
+            assert!(receipt.created_destination);
+
+            token
+                .send_payment(
+                    &request
+                        .with_create_ata(CreateAtaPolicy::IfMissing)
+                        .with_memo("invoice 42"),
+                    &[&authority],
+                )
+                .await
+                .unwrap();
+        });
+        let sent = client.sent_transactions();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].message.instructions.len(), 2);
+        assert_eq!(sent[1].message.instructions.len(), 3);
+        assert!(sent[1].message.account_keys.contains(&spl_memo::id()));
+    }
+}
@@ -0,0 +1,372 @@
+//! Key-value persistence shared by the stateful parts of the client, such as
+//! the journal and the idempotency store.
+//!
+//! Implement `KvStore` once for your own database to back all of them. The
+//! stores here are reference implementations: in memory, a directory of
+//! files, and SQLite with the `sqlite` feature.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Byte-oriented key-value store.
+///
+/// Subsystems sharing a store keep their keys under distinct prefixes.
+pub trait KvStore: Send + Sync {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
+
+    /// Set the value of a key, replacing any previous value
+    fn insert(&self, key: &[u8], value: &[u8]) -> io::Result<()>;
+
+    /// Set the value of a key only if it is absent, returning whether it was
+    /// set. The check and the write must be atomic.
+    fn insert_new(&self, key: &[u8], value: &[u8]) -> io::Result<bool>;
+
+    /// Remove a key, returning its value if it was present
+    fn remove(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
+
+    /// Every key starting with the prefix, and its value, ordered by key
+    fn scan_prefix(&self, prefix: &[u8]) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>>;
+}
+
+impl<S: KvStore + ?Sized> KvStore for Arc<S> {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        (**self).get(key)
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        (**self).insert(key, value)
+    }
+
+    fn insert_new(&self, key: &[u8], value: &[u8]) -> io::Result<bool> {
+        (**self).insert_new(key, value)
+    }
+
+    fn remove(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        (**self).remove(key)
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        (**self).scan_prefix(prefix)
+    }
+}
+
+/// Store kept in memory for the lifetime of the process
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: Mutex<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KvStore for MemoryStore {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn insert_new(&self, key: &[u8], value: &[u8]) -> io::Result<bool> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains_key(key) {
+            return Ok(false);
+        }
+        entries.insert(key.to_vec(), value.to_vec());
+        Ok(true)
+    }
+
+    fn remove(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.entries.lock().unwrap().remove(key))
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+/// Store keeping one file per key in a directory, named by the hex-encoded
+/// key, so keys are limited to half the file system's maximum name length.
+///
+/// Values are written to a temporary file, synced to disk, and renamed into
+/// place, so a crash never leaves a partial value. `insert_new` is only atomic within a
+/// process: the directory must not be shared by several processes.
+#[derive(Debug)]
+pub struct FileStore {
+    directory: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileStore {
+    /// Open the store in the directory, creating it if needed
+    pub fn open<P: AsRef<Path>>(directory: P) -> io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory: directory.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        })
+    }
+
+    fn path(&self, key: &[u8]) -> PathBuf {
+        self.directory.join(hex_encode(key))
+    }
+
+    fn read(path: &Path) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(path) {
+            Ok(value) => Ok(Some(value)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn write(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        let path = self.path(key);
+        let temporary = path.with_extension("tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(value)?;
+        // the value must be durable before the rename makes it visible
+        file.sync_all()?;
+        fs::rename(temporary, path)?;
+        self.sync_directory()
+    }
+
+    /// Make renames and removals in the directory durable
+    #[cfg(unix)]
+    fn sync_directory(&self) -> io::Result<()> {
+        File::open(&self.directory)?.sync_all()
+    }
+
+    #[cfg(not(unix))]
+    fn sync_directory(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl KvStore for FileStore {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        Self::read(&self.path(key))
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        self.write(key, value)
+    }
+
+    fn insert_new(&self, key: &[u8], value: &[u8]) -> io::Result<bool> {
+        let _lock = self.lock.lock().unwrap();
+        if self.path(key).exists() {
+            return Ok(false);
+        }
+        self.write(key, value)?;
+        Ok(true)
+    }
+
+    fn remove(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let _lock = self.lock.lock().unwrap();
+        let path = self.path(key);
+        let value = Self::read(&path)?;
+        if value.is_some() {
+            fs::remove_file(path)?;
+            self.sync_directory()?;
+        }
+        Ok(value)
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = vec![];
+        for dir_entry in fs::read_dir(&self.directory)? {
+            let dir_entry = dir_entry?;
+            // skips temporary files and anything else not written by the store
+            let key = match dir_entry.file_name().to_str().and_then(hex_decode) {
+                Some(key) if key.starts_with(prefix) => key,
+                _ => continue,
+            };
+            if let Some(value) = Self::read(&dir_entry.path())? {
+                entries.push((key, value));
+            }
+        }
+        entries.sort_unstable();
+        Ok(entries)
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use {
+        super::KvStore,
+        rusqlite::{params, Connection, OptionalExtension},
+        std::{io, path::Path, sync::Mutex},
+    };
+
+    fn to_io_error(error: rusqlite::Error) -> io::Error {
+        io::Error::new(io::ErrorKind::Other, error)
+    }
+
+    /// Store kept in a table of an SQLite database
+    #[derive(Debug)]
+    pub struct SqliteStore {
+        connection: Mutex<Connection>,
+    }
+
+    impl SqliteStore {
+        /// Open the database at the path, creating it if needed
+        pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+            Self::new(Connection::open(path).map_err(to_io_error)?)
+        }
+
+        /// Use an open connection, creating the table if needed
+        pub fn new(connection: Connection) -> io::Result<Self> {
+            connection
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS kv (key BLOB PRIMARY KEY, value BLOB NOT NULL)",
+                    [],
+                )
+                .map_err(to_io_error)?;
+            Ok(Self {
+                connection: Mutex::new(connection),
+            })
+        }
+
+        fn select(connection: &Connection, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+            connection
+                .query_row("SELECT value FROM kv WHERE key = ?1", params![key], |row| {
+                    row.get(0)
+                })
+                .optional()
+                .map_err(to_io_error)
+        }
+    }
+
+    impl KvStore for SqliteStore {
+        fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+            Self::select(&self.connection.lock().unwrap(), key)
+        }
+
+        fn insert(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+            self.connection
+                .lock()
+                .unwrap()
+                .execute(
+                    "INSERT OR REPLACE INTO kv (key, value) VALUES (?1, ?2)",
+                    params![key, value],
+                )
+                .map_err(to_io_error)?;
+            Ok(())
+        }
+
+        fn insert_new(&self, key: &[u8], value: &[u8]) -> io::Result<bool> {
+            let inserted = self
+                .connection
+                .lock()
+                .unwrap()
+                .execute(
+                    "INSERT OR IGNORE INTO kv (key, value) VALUES (?1, ?2)",
+                    params![key, value],
+                )
+                .map_err(to_io_error)?;
+            Ok(inserted == 1)
+        }
+
+        fn remove(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+            let connection = self.connection.lock().unwrap();
+            let value = Self::select(&connection, key)?;
+            if value.is_some() {
+                connection
+                    .execute("DELETE FROM kv WHERE key = ?1", params![key])
+                    .map_err(to_io_error)?;
+            }
+            Ok(value)
+        }
+
+        fn scan_prefix(&self, prefix: &[u8]) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+            let connection = self.connection.lock().unwrap();
+            let mut statement = connection
+                .prepare("SELECT key, value FROM kv WHERE substr(key, 1, ?1) = ?2 ORDER BY key")
+                .map_err(to_io_error)?;
+            let rows = statement
+                .query_map(params![prefix.len(), prefix], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .map_err(to_io_error)?;
+            rows.collect::<Result<_, _>>().map_err(to_io_error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(store: &dyn KvStore) {
+        assert_eq!(store.get(b"a/1").unwrap(), None);
+        assert!(store.insert_new(b"a/1", b"one").unwrap());
+        assert!(!store.insert_new(b"a/1", b"uno").unwrap());
+        store.insert(b"a/2", b"two").unwrap();
+        store.insert(b"b/1", b"other").unwrap();
+        assert_eq!(store.get(b"a/1").unwrap(), Some(b"one".to_vec()));
+
+        assert_eq!(
+            store.scan_prefix(b"a/").unwrap(),
+            vec![
+                (b"a/1".to_vec(), b"one".to_vec()),
+                (b"a/2".to_vec(), b"two".to_vec())
+            ]
+        );
+
+        assert_eq!(store.remove(b"a/1").unwrap(), Some(b"one".to_vec()));
+        assert_eq!(store.remove(b"a/1").unwrap(), None);
+        assert_eq!(store.scan_prefix(b"a/").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        exercise(&MemoryStore::new());
+    }
+
+    #[test]
+    fn test_file_store() {
+        let directory = std::env::temp_dir().join(format!(
+            "spl-token-client-file-store-{}",
+            std::process::id()
+        ));
+        exercise(&FileStore::open(&directory).unwrap());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
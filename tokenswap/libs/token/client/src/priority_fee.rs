@@ -0,0 +1,93 @@
+//! Selection of a compute-unit price from recent prioritization fees
+
+use {
+    solana_sdk::{instruction::Instruction, pubkey::Pubkey},
+    std::collections::HashSet,
+};
+
+/// Maximum number of accounts accepted by `getRecentPrioritizationFees`
+pub const MAX_PRIORITIZATION_FEE_ACCOUNTS: usize = 128;
+
+/// How to choose the compute-unit price, in micro-lamports, of a transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriorityFeePolicy {
+    /// Always use the given price
+    Fixed(u64),
+    /// Lowest fee paid in recent slots
+    Min,
+    /// Median fee paid in recent slots
+    Median,
+    /// 90th percentile of the fees paid in recent slots
+    P90,
+}
+
+impl PriorityFeePolicy {
+    /// Choose a price from the fees paid in recent slots, or 0 if there are
+    /// none
+    pub fn select(&self, recent_fees: &[u64]) -> u64 {
+        if let Self::Fixed(compute_unit_price) = self {
+            return *compute_unit_price;
+        }
+        if recent_fees.is_empty() {
+            return 0;
+        }
+
+        let mut fees = recent_fees.to_vec();
+        fees.sort_unstable();
+        let index = match self {
+            Self::Fixed(_) | Self::Min => 0,
+            Self::Median => (fees.len() - 1) / 2,
+            Self::P90 => (fees.len() * 90 + 99) / 100 - 1,
+        };
+        fees[index]
+    }
+}
+
+/// Accounts write-locked by the instructions, whose local fee markets
+/// determine the price needed to land them
+pub fn fee_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut seen = HashSet::new();
+    instructions
+        .iter()
+        .flat_map(|instruction| instruction.accounts.iter())
+        .filter(|meta| meta.is_writable && seen.insert(meta.pubkey))
+        .map(|meta| meta.pubkey)
+        .take(MAX_PRIORITIZATION_FEE_ACCOUNTS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::instruction::AccountMeta};
+
+    #[test]
+    fn test_select() {
+        let fees = [50, 0, 10, 40, 20, 30, 60, 70, 80, 90];
+        assert_eq!(PriorityFeePolicy::Fixed(7).select(&fees), 7);
+        assert_eq!(PriorityFeePolicy::Min.select(&fees), 0);
+        assert_eq!(PriorityFeePolicy::Median.select(&fees), 40);
+        assert_eq!(PriorityFeePolicy::P90.select(&fees), 80);
+
+        assert_eq!(PriorityFeePolicy::P90.select(&[5]), 5);
+        assert_eq!(PriorityFeePolicy::Median.select(&[]), 0);
+        assert_eq!(PriorityFeePolicy::Fixed(7).select(&[]), 7);
+    }
+
+    #[test]
+    fn test_fee_accounts() {
+        let writable = Pubkey::new_unique();
+        let readonly = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(readonly, false),
+            ],
+        );
+        assert_eq!(
+            fee_accounts(&[instruction.clone(), instruction]),
+            vec![writable]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Reconstruction of the interest rate history of interest-bearing mints
+
+use {
+    crate::client::TransactionRecord,
+    solana_sdk::{clock::UnixTimestamp, pubkey::Pubkey, signature::Signature},
+    spl_token_2022::{
+        extension::interest_bearing_mint::{
+            instruction::{InitializeInstructionData, InterestBearingMintInstruction},
+            BasisPoints, InterestBearingConfig,
+        },
+        instruction::{decode_instruction_data, decode_instruction_type, TokenInstruction},
+    },
+};
+
+/// How the rate was set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateChangeKind {
+    Initialize,
+    Update,
+}
+
+/// Rate in effect from `timestamp` until the next change
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RateChange {
+    pub kind: RateChangeKind,
+    pub timestamp: UnixTimestamp,
+    /// Rate in basis points
+    pub rate: i16,
+    /// Transaction that set the rate, if it was found in the mint's history
+    pub signature: Option<Signature>,
+}
+
+/// Rate changes of the mint made by a successful transaction, timestamped with
+/// its block time
+pub(crate) fn parse_rate_changes(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    record: &TransactionRecord,
+) -> Vec<RateChange> {
+    let (None, Some(timestamp)) = (&record.err, record.block_time) else {
+        return vec![];
+    };
+    record
+        .instructions
+        .iter()
+        .chain(record.inner_instructions.iter().flatten())
+        .filter(|instruction| {
+            instruction.program_id == *program_id
+                && instruction.accounts.first().map(|meta| meta.pubkey) == Some(*mint)
+        })
+        .filter_map(|instruction| {
+            let Ok(TokenInstruction::InterestBearingMintExtension) =
+                TokenInstruction::unpack(&instruction.data)
+            else {
+                return None;
+            };
+            let input = &instruction.data[1..];
+            let (kind, rate) = match decode_instruction_type(input).ok()? {
+                InterestBearingMintInstruction::Initialize => {
+                    let data = decode_instruction_data::<InitializeInstructionData>(input).ok()?;
+                    (RateChangeKind::Initialize, data.rate)
+                }
+                InterestBearingMintInstruction::UpdateRate => {
+                    let rate = decode_instruction_data::<BasisPoints>(input).ok()?;
+                    (RateChangeKind::Update, *rate)
+                }
+            };
+            Some(RateChange {
+                kind,
+                timestamp,
+                rate: rate.into(),
+                signature: Some(record.signature),
+            })
+        })
+        .collect()
+}
+
+/// Combine the rate changes found in the mint's history with its current
+/// configuration, which is authoritative for the initialization and last
+/// update.
+///
+/// If the initialization was not found, the initial rate can still be
+/// recovered when there was at most one update, from the average rate before
+/// the last update.
+pub fn reconstruct_rate_history(
+    config: &InterestBearingConfig,
+    parsed_changes: &[RateChange],
+) -> Vec<RateChange> {
+    let initialization_timestamp = UnixTimestamp::from(config.initialization_timestamp);
+    let last_update_timestamp = UnixTimestamp::from(config.last_update_timestamp);
+    let current_rate = i16::from(config.current_rate);
+
+    let mut updates = parsed_changes
+        .iter()
+        .filter(|change| change.kind == RateChangeKind::Update)
+        .cloned()
+        .collect::<Vec<_>>();
+    updates.sort_by_key(|change| change.timestamp);
+    // the last update may be in the same block as the initialization, or be
+    // missing from the fetched history
+    let last_update = updates
+        .iter()
+        .rev()
+        .find(|change| change.rate == current_rate)
+        .cloned();
+    updates.retain(|change| {
+        change.timestamp > initialization_timestamp && change.timestamp < last_update_timestamp
+    });
+
+    let initialization = parsed_changes
+        .iter()
+        .find(|change| change.kind == RateChangeKind::Initialize);
+    let initial_rate = match initialization {
+        Some(initialization) => Some(initialization.rate),
+        None if last_update_timestamp == initialization_timestamp => Some(current_rate),
+        None if updates.is_empty() => Some(config.pre_update_average_rate.into()),
+        None => None,
+    };
+
+    let mut history = vec![];
+    if let Some(rate) = initial_rate {
+        history.push(RateChange {
+            kind: RateChangeKind::Initialize,
+            timestamp: initialization_timestamp,
+            rate,
+            signature: initialization.and_then(|change| change.signature),
+        });
+    }
+    history.extend(updates);
+    if last_update_timestamp > initialization_timestamp {
+        history.push(RateChange {
+            kind: RateChangeKind::Update,
+            timestamp: last_update_timestamp,
+            rate: current_rate,
+            signature: last_update.and_then(|change| change.signature),
+        });
+    }
+    history
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::instruction::Instruction,
+        spl_token_2022::extension::interest_bearing_mint::instruction::{initialize, update_rate},
+    };
+
+    fn config(
+        initialization_timestamp: UnixTimestamp,
+        pre_update_average_rate: i16,
+        last_update_timestamp: UnixTimestamp,
+        current_rate: i16,
+    ) -> InterestBearingConfig {
+        InterestBearingConfig {
+            rate_authority: Default::default(),
+            initialization_timestamp: initialization_timestamp.into(),
+            pre_update_average_rate: pre_update_average_rate.into(),
+            last_update_timestamp: last_update_timestamp.into(),
+            current_rate: current_rate.into(),
+        }
+    }
+
+    fn record(block_time: UnixTimestamp, instructions: Vec<Instruction>) -> TransactionRecord {
+        TransactionRecord {
+            signature: Signature::new_unique(),
+            slot: 0,
+            block_time: Some(block_time),
+            err: None,
+            inner_instructions: vec![vec![]; instructions.len()],
+            instructions,
+        }
+    }
+
+    #[test]
+    fn test_parse_rate_changes() {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+
+        let record = record(
+            100,
+            vec![
+                initialize(&program_id, &mint, Some(authority), 50).unwrap(),
+                update_rate(&program_id, &mint, &authority, &[], -20).unwrap(),
+                update_rate(&program_id, &Pubkey::new_unique(), &authority, &[], 10).unwrap(),
+            ],
+        );
+        let changes = parse_rate_changes(&program_id, &mint, &record);
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| (change.kind, change.timestamp, change.rate))
+                .collect::<Vec<_>>(),
+            vec![
+                (RateChangeKind::Initialize, 100, 50),
+                (RateChangeKind::Update, 100, -20),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reconstruct_from_parsed_history() {
+        let change = |kind, timestamp, rate| RateChange {
+            kind,
+            timestamp,
+            rate,
+            signature: Some(Signature::new_unique()),
+        };
+        let parsed = vec![
+            change(RateChangeKind::Update, 300, 30),
+            change(RateChangeKind::Initialize, 100, 10),
+            change(RateChangeKind::Update, 200, 20),
+        ];
+        let history = reconstruct_rate_history(&config(100, 15, 300, 30), &parsed);
+        assert_eq!(
+            history
+                .iter()
+                .map(|change| (change.timestamp, change.rate, change.signature))
+                .collect::<Vec<_>>(),
+            vec![
+                (100, 10, parsed[1].signature),
+                (200, 20, parsed[2].signature),
+                (300, 30, parsed[0].signature),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reconstruct_from_state_only() {
+        // never updated
+        let history = reconstruct_rate_history(&config(100, 10, 100, 10), &[]);
+        assert_eq!(history.len(), 1);
+        assert_eq!((history[0].timestamp, history[0].rate), (100, 10));
+
+        // updated once, the initial rate is the average before the update
+        let history = reconstruct_rate_history(&config(100, 10, 200, 20), &[]);
+        assert_eq!(
+            history
+                .iter()
+                .map(|change| (change.kind, change.timestamp, change.rate))
+                .collect::<Vec<_>>(),
+            vec![
+                (RateChangeKind::Initialize, 100, 10),
+                (RateChangeKind::Update, 200, 20),
+            ]
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Program client throttling its requests to stay under an endpoint's limits
+
+use {
+    crate::client::{
+        AccountOverrides, ClientMethod, ProgramClient, ProgramClientResult, ReadConsistency,
+        SendTransaction, SignatureStatus, SimulateTransaction, TransactionRecord,
+    },
+    async_trait::async_trait,
+    solana_program_test::tokio::time,
+    solana_rpc_client_api::filter::RpcFilterType,
+    solana_sdk::{
+        account::Account,
+        clock::{Clock, Slot},
+        commitment_config::CommitmentConfig,
+        epoch_info::EpochInfo,
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::Transaction,
+    },
+    std::{
+        collections::HashMap,
+        fmt,
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+};
+
+/// Default number of request units available at once
+pub const DEFAULT_BURST: u32 = 40;
+/// Default number of request units regained per second
+pub const DEFAULT_REQUESTS_PER_SECOND: u32 = 10;
+
+fn default_weight(method: ClientMethod) -> u32 {
+    match method {
+        // scans are by far the most expensive requests for a node
+        ClientMethod::GetProgramAccounts | ClientMethod::GetTokenLargestAccounts => 10,
+        _ => 1,
+    }
+}
+
+/// Token bucket holding up to `capacity` units, refilled continuously
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    refill_per_second: f64,
+    available: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, refill_per_second: u32, now: Instant) -> Self {
+        Self {
+            capacity: f64::from(capacity),
+            refill_per_second: f64::from(refill_per_second.max(1)),
+            available: f64::from(capacity),
+            refilled_at: now,
+        }
+    }
+
+    /// Take `weight` units, or return how long until they are available.
+    /// Requests heavier than the capacity only wait for a full bucket.
+    fn try_take(&mut self, weight: u32, now: Instant) -> Result<(), Duration> {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_second).min(self.capacity);
+        self.refilled_at = now;
+
+        let weight = f64::from(weight).min(self.capacity);
+        if self.available >= weight {
+            self.available -= weight;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (weight - self.available) / self.refill_per_second,
+            ))
+        }
+    }
+}
+
+/// Wraps a program client, delaying requests with a token bucket so that a
+/// batch of operations stays under an endpoint's rate limit.
+///
+/// Each method costs its weight in units, one by default.
+pub struct RateLimitedProgramClient<C> {
+    inner: C,
+    bucket: Mutex<TokenBucket>,
+    weights: HashMap<ClientMethod, u32>,
+}
+
+impl<C> fmt::Debug for RateLimitedProgramClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimitedProgramClient")
+            .field("bucket", &self.bucket.lock().unwrap())
+            .field("weights", &self.weights)
+            .finish()
+    }
+}
+
+impl<C> RateLimitedProgramClient<C> {
+    pub fn new(inner: C) -> Self {
+        Self::with_limit(inner, DEFAULT_BURST, DEFAULT_REQUESTS_PER_SECOND)
+    }
+
+    /// Allow bursts of up to `burst` units, regaining `requests_per_second`
+    /// units every second
+    pub fn with_limit(inner: C, burst: u32, requests_per_second: u32) -> Self {
+        Self {
+            inner,
+            bucket: Mutex::new(TokenBucket::new(burst, requests_per_second, Instant::now())),
+            weights: HashMap::new(),
+        }
+    }
+
+    pub fn with_weight(mut self, method: ClientMethod, weight: u32) -> Self {
+        self.weights.insert(method, weight);
+        self
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    fn weight(&self, method: ClientMethod) -> u32 {
+        self.weights
+            .get(&method)
+            .copied()
+            .unwrap_or_else(|| default_weight(method))
+    }
+
+    /// Wait until the method's weight can be taken from the bucket
+    async fn acquire(&self, method: ClientMethod) {
+        let weight = self.weight(method);
+        loop {
+            let wait = match self.bucket.lock().unwrap().try_take(weight, Instant::now()) {
+                Ok(()) => return,
+                Err(wait) => wait,
+            };
+            time::sleep(wait).await;
+        }
+    }
+}
+
+#[async_trait]
+impl<ST, C> ProgramClient<ST> for RateLimitedProgramClient<C>
+where
+    ST: SendTransaction + SimulateTransaction + Send + Sync,
+    C: ProgramClient<ST> + Send + Sync,
+{
+    async fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> ProgramClientResult<u64> {
+        self.acquire(ClientMethod::GetMinimumBalanceForRentExemption)
+            .await;
+        self.inner
+            .get_minimum_balance_for_rent_exemption(data_len)
+            .await
+    }
+
+    async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
+        self.acquire(ClientMethod::GetLatestBlockhash).await;
+        self.inner.get_latest_blockhash().await
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> ProgramClientResult<ST::Output> {
+        self.acquire(ClientMethod::SendTransaction).await;
+        self.inner.send_transaction(transaction).await
+    }
+
+    async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>> {
+        self.acquire(ClientMethod::GetAccount).await;
+        self.inner.get_account(address).await
+    }
+
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.acquire(ClientMethod::GetAccount).await;
+        self.inner
+            .get_account_with_consistency(address, consistency)
+            .await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.acquire(ClientMethod::GetAccount).await;
+        self.inner
+            .get_account_with_commitment(address, commitment)
+            .await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.acquire(ClientMethod::SimulateTransaction).await;
+        self.inner.simulate_transaction(transaction).await
+    }
+
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.acquire(ClientMethod::SimulateTransaction).await;
+        self.inner
+            .simulate_transaction_with_overrides(transaction, overrides)
+            .await
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        self.acquire(ClientMethod::GetSignatureStatuses).await;
+        self.inner.get_signature_statuses(signatures).await
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        self.acquire(ClientMethod::GetRecentPrioritizationFees)
+            .await;
+        self.inner.get_recent_prioritization_fees(addresses).await
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        self.acquire(ClientMethod::GetSignaturesForAddress).await;
+        self.inner
+            .get_signatures_for_address(address, before, until, limit)
+            .await
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        self.acquire(ClientMethod::GetTransaction).await;
+        self.inner.get_transaction(signature).await
+    }
+
+    async fn get_program_accounts_with_filters(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        self.acquire(ClientMethod::GetProgramAccounts).await;
+        self.inner
+            .get_program_accounts_with_filters(program_id, filters)
+            .await
+    }
+
+    async fn get_program_accounts_with_context(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        self.acquire(ClientMethod::GetProgramAccounts).await;
+        self.inner
+            .get_program_accounts_with_context(program_id, filters)
+            .await
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        self.acquire(ClientMethod::GetEpochInfo).await;
+        self.inner.get_epoch_info().await
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        self.acquire(ClientMethod::GetSysvarClock).await;
+        self.inner.get_sysvar_clock().await
+    }
+
+    async fn get_token_largest_accounts(
+        &self,
+        mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        self.acquire(ClientMethod::GetTokenLargestAccounts).await;
+        self.inner.get_token_largest_accounts(mint).await
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        self.acquire(ClientMethod::GetMultipleAccounts).await;
+        self.inner.get_multiple_accounts(addresses).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2, 10, start);
+        assert_eq!(bucket.try_take(1, start), Ok(()));
+        assert_eq!(bucket.try_take(1, start), Ok(()));
+        assert_eq!(bucket.try_take(1, start), Err(Duration::from_millis(100)));
+
+        // refilled, but never above capacity
+        let later = start + Duration::from_secs(10);
+        assert_eq!(bucket.try_take(2, later), Ok(()));
+        assert!(bucket.try_take(1, later).is_err());
+
+        // heavier than the capacity waits for a full bucket
+        let even_later = later + Duration::from_secs(10);
+        assert_eq!(bucket.try_take(10, even_later), Ok(()));
+    }
+}
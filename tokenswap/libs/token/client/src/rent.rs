@@ -0,0 +1,218 @@
+//! Cache of rent-exempt minimums, shared by the tokens of a client, and
+//! offline calculation of the minimums for sets of extensions
+
+use {
+    solana_sdk::{
+        clock::{Epoch, DEFAULT_MS_PER_SLOT, DEFAULT_SLOTS_PER_EPOCH},
+        program_error::ProgramError,
+        rent::Rent,
+    },
+    spl_token_2022::{
+        extension::{AccountType, ExtensionType},
+        state::{Account, Mint},
+    },
+    std::{
+        collections::HashMap,
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+};
+
+/// Default age after which cached minimums are queried again, about one epoch
+pub const DEFAULT_RENT_CACHE_MAX_AGE: Duration =
+    Duration::from_millis(DEFAULT_SLOTS_PER_EPOCH * DEFAULT_MS_PER_SLOT);
+
+#[derive(Debug, Default)]
+struct RentCacheState {
+    epoch: Option<Epoch>,
+    filled_at: Option<Instant>,
+    minimums: HashMap<usize, u64>,
+}
+
+/// Rent-exempt minimums keyed by account size.
+///
+/// Rent can only change at an epoch boundary, so everything is dropped when a
+/// new epoch is observed, or after about an epoch's time if none is.
+#[derive(Debug)]
+pub struct RentCache {
+    state: Mutex<RentCacheState>,
+    max_age: Duration,
+}
+
+impl Default for RentCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RentCache {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(RentCacheState::default()),
+            max_age: DEFAULT_RENT_CACHE_MAX_AGE,
+        }
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn get(&self, space: usize) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        if state
+            .filled_at
+            .map(|filled_at| filled_at.elapsed() >= self.max_age)
+            .unwrap_or(false)
+        {
+            state.filled_at = None;
+            state.minimums.clear();
+        }
+        state.minimums.get(&space).copied()
+    }
+
+    pub fn insert(&self, space: usize, lamports: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.filled_at.get_or_insert_with(Instant::now);
+        state.minimums.insert(space, lamports);
+    }
+
+    /// Record the current epoch, dropping the cached minimums if it changed
+    pub fn observe_epoch(&self, epoch: Epoch) {
+        let mut state = self.state.lock().unwrap();
+        if state.epoch.map(|cached| cached != epoch).unwrap_or(false) {
+            state.filled_at = None;
+            state.minimums.clear();
+        }
+        state.epoch = Some(epoch);
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.filled_at = None;
+        state.minimums.clear();
+    }
+}
+
+/// Length of a mint with the given extensions
+pub fn mint_len(extensions: &[ExtensionType]) -> Result<usize, ProgramError> {
+    ExtensionType::try_calculate_account_len::<Mint>(extensions)
+}
+
+/// Length of a token account with the given extensions
+pub fn account_len(extensions: &[ExtensionType]) -> Result<usize, ProgramError> {
+    ExtensionType::try_calculate_account_len::<Account>(extensions)
+}
+
+/// Length of a token account of a mint with the given extensions, with the
+/// account extensions they require
+pub fn account_len_for_mint(mint_extensions: &[ExtensionType]) -> Result<usize, ProgramError> {
+    account_len(&ExtensionType::get_required_init_account_extensions(
+        mint_extensions,
+    ))
+}
+
+/// Length of a mint or token account holding the extensions. Fails for
+/// extensions of the other kind of account and for variable-length
+/// extensions, whose size depends on their content.
+pub fn len_for(
+    account_type: AccountType,
+    extensions: &[ExtensionType],
+) -> Result<usize, ProgramError> {
+    if extensions
+        .iter()
+        .any(|extension| extension.get_account_type() != account_type)
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
+    match account_type {
+        AccountType::Mint => mint_len(extensions),
+        AccountType::Account => account_len(extensions),
+        AccountType::Uninitialized => Err(ProgramError::InvalidArgument),
+    }
+}
+
+/// Rent-exempt minimum of a mint or token account holding the extensions, as
+/// sized by `len_for`, without asking a node
+pub fn required_rent_for(
+    account_type: AccountType,
+    extensions: &[ExtensionType],
+    rent: &Rent,
+) -> Result<u64, ProgramError> {
+    len_for(account_type, extensions).map(|len| rent.minimum_balance(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::program_pack::Pack};
+
+    #[test]
+    fn test_invalidated_on_new_epoch() {
+        let cache = RentCache::new();
+        cache.observe_epoch(1);
+        cache.insert(165, 2_039_280);
+        assert_eq!(cache.get(165), Some(2_039_280));
+        assert_eq!(cache.get(82), None);
+
+        cache.observe_epoch(1);
+        assert_eq!(cache.get(165), Some(2_039_280));
+        cache.observe_epoch(2);
+        assert_eq!(cache.get(165), None);
+    }
+
+    #[test]
+    fn test_expires_after_max_age() {
+        let cache = RentCache::new().with_max_age(Duration::ZERO);
+        cache.insert(165, 2_039_280);
+        assert_eq!(cache.get(165), None);
+    }
+
+    #[test]
+    fn test_required_rent_for() {
+        let rent = Rent::default();
+        assert_eq!(len_for(AccountType::Account, &[]), Ok(Account::LEN));
+        assert_eq!(
+            required_rent_for(AccountType::Account, &[], &rent),
+            Ok(2_039_280)
+        );
+        assert_eq!(len_for(AccountType::Mint, &[]), Ok(Mint::LEN));
+        assert_eq!(
+            required_rent_for(AccountType::Mint, &[], &rent),
+            Ok(1_461_600)
+        );
+
+        // mints with extensions are padded to the length of an account
+        let mint_extensions = [ExtensionType::MintCloseAuthority];
+        assert_eq!(
+            len_for(AccountType::Mint, &mint_extensions),
+            Ok(Account::LEN + 1 + 4 + 32)
+        );
+        assert_eq!(
+            required_rent_for(AccountType::Mint, &mint_extensions, &rent),
+            Ok(rent.minimum_balance(mint_len(&mint_extensions).unwrap()))
+        );
+        assert_eq!(
+            len_for(AccountType::Account, &mint_extensions),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            len_for(AccountType::Uninitialized, &[]),
+            Err(ProgramError::InvalidArgument)
+        );
+
+        assert_eq!(
+            account_len_for_mint(&[ExtensionType::TransferFeeConfig]),
+            account_len(&[ExtensionType::TransferFeeAmount])
+        );
+        assert_eq!(
+            len_for(
+                AccountType::Mint,
+                &[
+                    ExtensionType::MintCloseAuthority,
+                    ExtensionType::ImmutableOwner
+                ]
+            ),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,680 @@
+//! Program clients recording a live session to a fixture, and replaying it
+//! deterministically, e.g. to regression-test a flow against mainnet data
+
+use {
+    crate::client::{
+        AccountOverrides, ProgramClient, ProgramClientResult, ReadConsistency, SendTransaction,
+        SignatureStatus, SimulateTransaction, TransactionRecord,
+    },
+    async_trait::async_trait,
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    solana_rpc_client_api::filter::RpcFilterType,
+    solana_sdk::{
+        account::Account,
+        clock::{Clock, Slot},
+        commitment_config::CommitmentConfig,
+        epoch_info::EpochInfo,
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::Transaction,
+    },
+    std::{
+        fmt,
+        fs::File,
+        io::{self, BufReader, BufWriter, Write},
+        path::Path,
+        sync::Mutex,
+    },
+};
+
+/// Request made to a program client, with its arguments
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Request {
+    GetMinimumBalanceForRentExemption {
+        data_len: usize,
+    },
+    GetLatestBlockhash,
+    SendTransaction {
+        transaction: Transaction,
+    },
+    GetAccount {
+        address: Pubkey,
+    },
+    GetAccountWithConsistency {
+        address: Pubkey,
+        consistency: ReadConsistency,
+    },
+    GetAccountWithCommitment {
+        address: Pubkey,
+        commitment: CommitmentConfig,
+    },
+    SimulateTransaction {
+        transaction: Transaction,
+    },
+    SimulateTransactionWithOverrides {
+        transaction: Transaction,
+        /// Sorted by address, so equal overrides always compare equal
+        overrides: Vec<(Pubkey, Account)>,
+    },
+    GetSignatureStatuses {
+        signatures: Vec<Signature>,
+    },
+    GetRecentPrioritizationFees {
+        addresses: Vec<Pubkey>,
+    },
+    GetSignaturesForAddress {
+        address: Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    },
+    GetTransaction {
+        signature: Signature,
+    },
+    GetProgramAccountsWithFilters {
+        program_id: Pubkey,
+        filters: Vec<RpcFilterType>,
+    },
+    GetProgramAccountsWithContext {
+        program_id: Pubkey,
+        filters: Vec<RpcFilterType>,
+    },
+    GetEpochInfo,
+    GetSysvarClock,
+    GetTokenLargestAccounts {
+        mint: Pubkey,
+    },
+    GetMultipleAccounts {
+        addresses: Vec<Pubkey>,
+    },
+}
+
+impl Request {
+    fn simulate_with_overrides(transaction: &Transaction, overrides: &AccountOverrides) -> Self {
+        let mut overrides = overrides
+            .iter()
+            .map(|(address, account)| (*address, account.clone()))
+            .collect::<Vec<_>>();
+        overrides.sort_unstable_by_key(|(address, _)| *address);
+        Self::SimulateTransactionWithOverrides {
+            transaction: transaction.clone(),
+            overrides,
+        }
+    }
+}
+
+/// A request and the response it got. Errors are kept as their message.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub request: Request,
+    pub response: Result<serde_json::Value, String>,
+}
+
+/// Wraps a program client, recording every request and its response, to be
+/// saved as a fixture for `ReplayProgramClient`
+pub struct RecordingProgramClient<C> {
+    inner: C,
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl<C> fmt::Debug for RecordingProgramClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordingProgramClient")
+            .field("exchanges", &self.exchanges.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl<C> RecordingProgramClient<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            exchanges: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Exchanges recorded so far, in the order they completed
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.lock().unwrap().clone()
+    }
+
+    /// Write the exchanges recorded so far as a JSON fixture
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, &*self.exchanges.lock().unwrap())?;
+        Ok(())
+    }
+
+    /// Save the exchanges recorded so far to a fixture file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    fn record<T: Serialize>(
+        &self,
+        request: Request,
+        result: ProgramClientResult<T>,
+    ) -> ProgramClientResult<T> {
+        let response = match &result {
+            Ok(value) => serde_json::to_value(value)
+                .map_err(|error| format!("Response could not be recorded: {}", error)),
+            Err(error) => Err(error.to_string()),
+        };
+        self.exchanges
+            .lock()
+            .unwrap()
+            .push(Exchange { request, response });
+        result
+    }
+}
+
+#[async_trait]
+impl<ST, C> ProgramClient<ST> for RecordingProgramClient<C>
+where
+    ST: SendTransaction + SimulateTransaction + Send + Sync,
+    ST::Output: Serialize,
+    ST::SimulationOutput: Serialize,
+    C: ProgramClient<ST> + Send + Sync,
+{
+    async fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> ProgramClientResult<u64> {
+        let result = self
+            .inner
+            .get_minimum_balance_for_rent_exemption(data_len)
+            .await;
+        self.record(
+            Request::GetMinimumBalanceForRentExemption { data_len },
+            result,
+        )
+    }
+
+    async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
+        let result = self.inner.get_latest_blockhash().await;
+        self.record(Request::GetLatestBlockhash, result)
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> ProgramClientResult<ST::Output> {
+        let result = self.inner.send_transaction(transaction).await;
+        self.record(
+            Request::SendTransaction {
+                transaction: transaction.clone(),
+            },
+            result,
+        )
+    }
+
+    async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>> {
+        let result = self.inner.get_account(address).await;
+        self.record(Request::GetAccount { address }, result)
+    }
+
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        let result = self
+            .inner
+            .get_account_with_consistency(address, consistency)
+            .await;
+        self.record(
+            Request::GetAccountWithConsistency {
+                address,
+                consistency,
+            },
+            result,
+        )
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        let result = self
+            .inner
+            .get_account_with_commitment(address, commitment)
+            .await;
+        self.record(
+            Request::GetAccountWithCommitment {
+                address,
+                commitment,
+            },
+            result,
+        )
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        let result = self.inner.simulate_transaction(transaction).await;
+        self.record(
+            Request::SimulateTransaction {
+                transaction: transaction.clone(),
+            },
+            result,
+        )
+    }
+
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        let result = self
+            .inner
+            .simulate_transaction_with_overrides(transaction, overrides)
+            .await;
+        self.record(
+            Request::simulate_with_overrides(transaction, overrides),
+            result,
+        )
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        let result = self.inner.get_signature_statuses(signatures).await;
+        self.record(
+            Request::GetSignatureStatuses {
+                signatures: signatures.to_vec(),
+            },
+            result,
+        )
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        let result = self.inner.get_recent_prioritization_fees(addresses).await;
+        self.record(
+            Request::GetRecentPrioritizationFees {
+                addresses: addresses.to_vec(),
+            },
+            result,
+        )
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        let result = self
+            .inner
+            .get_signatures_for_address(address, before, until, limit)
+            .await;
+        self.record(
+            Request::GetSignaturesForAddress {
+                address: *address,
+                before,
+                until,
+                limit,
+            },
+            result,
+        )
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        let result = self.inner.get_transaction(signature).await;
+        self.record(
+            Request::GetTransaction {
+                signature: *signature,
+            },
+            result,
+        )
+    }
+
+    async fn get_program_accounts_with_filters(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        let request = Request::GetProgramAccountsWithFilters {
+            program_id: *program_id,
+            filters: filters.clone(),
+        };
+        let result = self
+            .inner
+            .get_program_accounts_with_filters(program_id, filters)
+            .await;
+        self.record(request, result)
+    }
+
+    async fn get_program_accounts_with_context(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        let request = Request::GetProgramAccountsWithContext {
+            program_id: *program_id,
+            filters: filters.clone(),
+        };
+        let result = self
+            .inner
+            .get_program_accounts_with_context(program_id, filters)
+            .await;
+        self.record(request, result)
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        let result = self.inner.get_epoch_info().await;
+        self.record(Request::GetEpochInfo, result)
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        let result = self.inner.get_sysvar_clock().await;
+        self.record(Request::GetSysvarClock, result)
+    }
+
+    async fn get_token_largest_accounts(
+        &self,
+        mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        let result = self.inner.get_token_largest_accounts(mint).await;
+        self.record(Request::GetTokenLargestAccounts { mint: *mint }, result)
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        let result = self.inner.get_multiple_accounts(addresses).await;
+        self.record(
+            Request::GetMultipleAccounts {
+                addresses: addresses.to_vec(),
+            },
+            result,
+        )
+    }
+}
+
+/// Program client serving the responses of a recorded fixture, without any
+/// network access.
+///
+/// Each request is answered by the first unused exchange with an equal
+/// request, so concurrent requests may complete in a different order than
+/// when they were recorded. Requests are compared exactly: a flow sending
+/// transactions must sign them with the same keypairs as the recorded one.
+/// Requests without a recorded response fail.
+///
+/// Confidential transfer flows cannot be replayed: their ciphertexts and
+/// zero-knowledge proofs are randomized, so the transactions they send never
+/// equal the recorded ones, even with the same keys.
+pub struct ReplayProgramClient {
+    exchanges: Mutex<Vec<Option<Exchange>>>,
+}
+
+impl fmt::Debug for ReplayProgramClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplayProgramClient")
+            .field("remaining", &self.remaining())
+            .finish()
+    }
+}
+
+impl ReplayProgramClient {
+    pub fn new(exchanges: Vec<Exchange>) -> Self {
+        Self {
+            exchanges: Mutex::new(exchanges.into_iter().map(Some).collect()),
+        }
+    }
+
+    /// Read a fixture written by `RecordingProgramClient::write_to`
+    pub fn from_reader<R: io::Read>(reader: R) -> io::Result<Self> {
+        Ok(Self::new(serde_json::from_reader(reader)?))
+    }
+
+    /// Load a fixture saved by `RecordingProgramClient::save`
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Number of recorded exchanges not replayed yet, e.g. to check that a
+    /// flow made every request it made when recorded
+    pub fn remaining(&self) -> usize {
+        self.exchanges.lock().unwrap().iter().flatten().count()
+    }
+
+    fn replay<T: DeserializeOwned>(&self, request: Request) -> ProgramClientResult<T> {
+        let exchange = self
+            .exchanges
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|exchange| matches!(exchange, Some(exchange) if exchange.request == request))
+            .and_then(Option::take)
+            .ok_or_else(|| format!("No recorded response for {:?}", request))?;
+        match exchange.response {
+            Ok(value) => serde_json::from_value(value).map_err(Into::into),
+            Err(message) => Err(message.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl<ST> ProgramClient<ST> for ReplayProgramClient
+where
+    ST: SendTransaction + SimulateTransaction + Send + Sync,
+    ST::Output: DeserializeOwned,
+    ST::SimulationOutput: DeserializeOwned,
+{
+    async fn get_minimum_balance_for_rent_exemption(
+        &self,
+        data_len: usize,
+    ) -> ProgramClientResult<u64> {
+        self.replay(Request::GetMinimumBalanceForRentExemption { data_len })
+    }
+
+    async fn get_latest_blockhash(&self) -> ProgramClientResult<Hash> {
+        self.replay(Request::GetLatestBlockhash)
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> ProgramClientResult<ST::Output> {
+        self.replay(Request::SendTransaction {
+            transaction: transaction.clone(),
+        })
+    }
+
+    async fn get_account(&self, address: Pubkey) -> ProgramClientResult<Option<Account>> {
+        self.replay(Request::GetAccount { address })
+    }
+
+    async fn get_account_with_consistency(
+        &self,
+        address: Pubkey,
+        consistency: ReadConsistency,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.replay(Request::GetAccountWithConsistency {
+            address,
+            consistency,
+        })
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        address: Pubkey,
+        commitment: CommitmentConfig,
+    ) -> ProgramClientResult<Option<Account>> {
+        self.replay(Request::GetAccountWithCommitment {
+            address,
+            commitment,
+        })
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.replay(Request::SimulateTransaction {
+            transaction: transaction.clone(),
+        })
+    }
+
+    async fn simulate_transaction_with_overrides(
+        &self,
+        transaction: &Transaction,
+        overrides: &AccountOverrides,
+    ) -> ProgramClientResult<ST::SimulationOutput> {
+        self.replay(Request::simulate_with_overrides(transaction, overrides))
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> ProgramClientResult<Vec<Option<SignatureStatus>>> {
+        self.replay(Request::GetSignatureStatuses {
+            signatures: signatures.to_vec(),
+        })
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<u64>> {
+        self.replay(Request::GetRecentPrioritizationFees {
+            addresses: addresses.to_vec(),
+        })
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> ProgramClientResult<Vec<Signature>> {
+        self.replay(Request::GetSignaturesForAddress {
+            address: *address,
+            before,
+            until,
+            limit,
+        })
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Option<TransactionRecord>> {
+        self.replay(Request::GetTransaction {
+            signature: *signature,
+        })
+    }
+
+    async fn get_program_accounts_with_filters(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<Vec<(Pubkey, Account)>> {
+        self.replay(Request::GetProgramAccountsWithFilters {
+            program_id: *program_id,
+            filters,
+        })
+    }
+
+    async fn get_program_accounts_with_context(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> ProgramClientResult<(Slot, Vec<(Pubkey, Account)>)> {
+        self.replay(Request::GetProgramAccountsWithContext {
+            program_id: *program_id,
+            filters,
+        })
+    }
+
+    async fn get_epoch_info(&self) -> ProgramClientResult<EpochInfo> {
+        self.replay(Request::GetEpochInfo)
+    }
+
+    async fn get_sysvar_clock(&self) -> ProgramClientResult<Clock> {
+        self.replay(Request::GetSysvarClock)
+    }
+
+    async fn get_token_largest_accounts(
+        &self,
+        mint: &Pubkey,
+    ) -> ProgramClientResult<Vec<(Pubkey, u64)>> {
+        self.replay(Request::GetTokenLargestAccounts { mint: *mint })
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> ProgramClientResult<Vec<Option<Account>>> {
+        self.replay(Request::GetMultipleAccounts {
+            addresses: addresses.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ClientMethod, ProgramRpcClientSendTransaction},
+            mock::MockProgramClient,
+        },
+    };
+
+    type Client = dyn ProgramClient<ProgramRpcClientSendTransaction>;
+
+    #[test]
+    fn test_record_replay() {
+        let address = Pubkey::new_unique();
+        let account = Account {
+            lamports: 42,
+            data: vec![1, 2, 3],
+            owner: Pubkey::new_unique(),
+            ..Account::default()
+        };
+        let mock = MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+            .with_account(&address, account.clone());
+        mock.fail_next(ClientMethod::GetLatestBlockhash, "unavailable");
+
+        let recording = RecordingProgramClient::new(mock);
+        let client: &Client = &recording;
+        futures::executor::block_on(async {
+            assert!(client.get_latest_blockhash().await.is_err());
+            client.get_latest_blockhash().await.unwrap();
+            client.get_account(address).await.unwrap();
+        });
+        let mut fixture = vec![];
+        recording.write_to(&mut fixture).unwrap();
+
+        let replay = ReplayProgramClient::from_reader(fixture.as_slice()).unwrap();
+        let client: &Client = &replay;
+        futures::executor::block_on(async {
+            // answered by request, not by position
+            assert_eq!(client.get_account(address).await.unwrap(), Some(account));
+            assert_eq!(
+                client.get_latest_blockhash().await.unwrap_err().to_string(),
+                "unavailable"
+            );
+            assert_eq!(
+                client.get_latest_blockhash().await.unwrap(),
+                recording.inner().get_latest_blockhash().await.unwrap()
+            );
+            assert_eq!(replay.remaining(), 0);
+            assert!(client.get_latest_blockhash().await.is_err());
+        });
+    }
+}
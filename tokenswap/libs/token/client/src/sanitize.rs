@@ -0,0 +1,199 @@
+//! Validation of externally supplied instructions before they are included in
+//! a transaction paid for and signed by the client
+
+use {
+    solana_sdk::{instruction::Instruction, pubkey::Pubkey},
+    std::collections::HashSet,
+    thiserror::Error,
+};
+
+/// Rules that externally supplied instructions must follow
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InstructionPolicy {
+    /// Programs the instructions may invoke
+    pub allowed_program_ids: HashSet<Pubkey>,
+    /// Accounts that must not be required as signers or writable, typically
+    /// the fee payer and any other keys signing on our side
+    pub protected_accounts: HashSet<Pubkey>,
+    /// Maximum instruction data length, if any
+    pub max_data_len: Option<usize>,
+    /// Maximum number of accounts per instruction, if any
+    pub max_accounts: Option<usize>,
+}
+impl InstructionPolicy {
+    /// Create a policy allowing the given programs
+    pub fn new(allowed_program_ids: &[Pubkey]) -> Self {
+        Self {
+            allowed_program_ids: allowed_program_ids.iter().copied().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Forbid the given account from being a signer or writable
+    pub fn with_protected_account(mut self, account: Pubkey) -> Self {
+        self.protected_accounts.insert(account);
+        self
+    }
+
+    /// Limit the instruction data length
+    pub fn with_max_data_len(mut self, max_data_len: usize) -> Self {
+        self.max_data_len = Some(max_data_len);
+        self
+    }
+
+    /// Limit the number of accounts per instruction
+    pub fn with_max_accounts(mut self, max_accounts: usize) -> Self {
+        self.max_accounts = Some(max_accounts);
+        self
+    }
+}
+
+/// Reason an instruction was rejected, with the index of the offending
+/// instruction
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum InstructionRejection {
+    #[error("instruction {index}: program {program_id} is not allowed")]
+    ProgramNotAllowed { index: usize, program_id: Pubkey },
+    #[error("instruction {index}: protected account {account} required as signer")]
+    ProtectedSigner { index: usize, account: Pubkey },
+    #[error("instruction {index}: protected account {account} required as writable")]
+    ProtectedWritable { index: usize, account: Pubkey },
+    #[error("instruction {index}: data length {len} exceeds maximum {max}")]
+    DataTooLong {
+        index: usize,
+        len: usize,
+        max: usize,
+    },
+    #[error("instruction {index}: {count} accounts exceeds maximum {max}")]
+    TooManyAccounts {
+        index: usize,
+        count: usize,
+        max: usize,
+    },
+}
+
+/// Check every instruction against the policy, returning all rejections found
+pub fn check_instructions(
+    instructions: &[Instruction],
+    policy: &InstructionPolicy,
+) -> Result<(), Vec<InstructionRejection>> {
+    let mut rejections = vec![];
+    for (index, instruction) in instructions.iter().enumerate() {
+        if !policy.allowed_program_ids.contains(&instruction.program_id) {
+            rejections.push(InstructionRejection::ProgramNotAllowed {
+                index,
+                program_id: instruction.program_id,
+            });
+        }
+        for meta in &instruction.accounts {
+            if policy.protected_accounts.contains(&meta.pubkey) {
+                if meta.is_signer {
+                    rejections.push(InstructionRejection::ProtectedSigner {
+                        index,
+                        account: meta.pubkey,
+                    });
+                }
+                if meta.is_writable {
+                    rejections.push(InstructionRejection::ProtectedWritable {
+                        index,
+                        account: meta.pubkey,
+                    });
+                }
+            }
+        }
+        if let Some(max) = policy.max_data_len {
+            if instruction.data.len() > max {
+                rejections.push(InstructionRejection::DataTooLong {
+                    index,
+                    len: instruction.data.len(),
+                    max,
+                });
+            }
+        }
+        if let Some(max) = policy.max_accounts {
+            if instruction.accounts.len() > max {
+                rejections.push(InstructionRejection::TooManyAccounts {
+                    index,
+                    count: instruction.accounts.len(),
+                    max,
+                });
+            }
+        }
+    }
+
+    if rejections.is_empty() {
+        Ok(())
+    } else {
+        Err(rejections)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::instruction::AccountMeta};
+
+    #[test]
+    fn accepts_allowed_instructions() {
+        let program_id = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let policy = InstructionPolicy::new(&[program_id]).with_protected_account(payer);
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[1, 2, 3],
+            vec![
+                AccountMeta::new_readonly(payer, false),
+                AccountMeta::new(Pubkey::new_unique(), true),
+            ],
+        );
+        assert_eq!(check_instructions(&[instruction], &policy), Ok(()));
+    }
+
+    #[test]
+    fn reports_every_rejection() {
+        let program_id = Pubkey::new_unique();
+        let other_program_id = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let policy = InstructionPolicy::new(&[program_id])
+            .with_protected_account(payer)
+            .with_max_data_len(2)
+            .with_max_accounts(1);
+        let instructions = [
+            Instruction::new_with_bytes(other_program_id, &[], vec![]),
+            Instruction::new_with_bytes(
+                program_id,
+                &[1, 2, 3],
+                vec![
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new_readonly(Pubkey::new_unique(), false),
+                ],
+            ),
+        ];
+        assert_eq!(
+            check_instructions(&instructions, &policy),
+            Err(vec![
+                InstructionRejection::ProgramNotAllowed {
+                    index: 0,
+                    program_id: other_program_id,
+                },
+                InstructionRejection::ProtectedSigner {
+                    index: 1,
+                    account: payer,
+                },
+                InstructionRejection::ProtectedWritable {
+                    index: 1,
+                    account: payer,
+                },
+                InstructionRejection::DataTooLong {
+                    index: 1,
+                    len: 3,
+                    max: 2,
+                },
+                InstructionRejection::TooManyAccounts {
+                    index: 1,
+                    count: 2,
+                    max: 1,
+                },
+            ])
+        );
+    }
+}
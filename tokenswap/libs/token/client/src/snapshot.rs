@@ -0,0 +1,320 @@
+//! Slot-stamped snapshots of a mint's token accounts, and their differences
+
+use {
+    crate::{
+        holders::{self, TokenHolder},
+        token::{TokenError, TokenResult},
+    },
+    solana_sdk::{clock::Slot, pubkey::Pubkey},
+    std::collections::BTreeMap,
+};
+
+/// Balance of a token account in a snapshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SnapshotAccount {
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+/// Every token account of a mint, and its supply, as of a slot
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HolderSnapshot {
+    pub mint: Pubkey,
+    pub slot: Slot,
+    pub supply: u64,
+    pub decimals: u8,
+    pub accounts: BTreeMap<Pubkey, SnapshotAccount>,
+}
+
+impl HolderSnapshot {
+    /// Accounts holding at least `min_balance`, largest first, with their UI
+    /// amounts
+    pub fn holders(&self, min_balance: u64) -> Vec<TokenHolder> {
+        let holders = self
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.amount >= min_balance)
+            .map(|(address, account)| {
+                TokenHolder::new(*address, account.owner, account.amount, self.decimals)
+            })
+            .collect();
+        holders::largest(holders, usize::MAX)
+    }
+}
+
+/// Change of one token account between two snapshots. The balance is `None`
+/// where the account does not exist.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountDelta {
+    pub address: Pubkey,
+    /// Owner in the later snapshot, or in the earlier one for closed accounts
+    pub owner: Pubkey,
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+}
+
+impl AccountDelta {
+    /// Change of the balance, counting missing accounts as empty
+    pub fn change(&self) -> i128 {
+        i128::from(self.after.unwrap_or(0)) - i128::from(self.before.unwrap_or(0))
+    }
+}
+
+/// Differences between two snapshots of a mint, each list sorted by address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub mint: Pubkey,
+    pub from_slot: Slot,
+    pub to_slot: Slot,
+    pub supply_before: u64,
+    pub supply_after: u64,
+    /// Accounts in both snapshots whose balance or owner changed
+    pub changed: Vec<AccountDelta>,
+    /// Accounts only in the later snapshot
+    pub opened: Vec<AccountDelta>,
+    /// Accounts only in the earlier snapshot
+    pub closed: Vec<AccountDelta>,
+}
+
+impl SnapshotDiff {
+    pub fn supply_change(&self) -> i128 {
+        i128::from(self.supply_after) - i128::from(self.supply_before)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.supply_before == self.supply_after
+            && self.changed.is_empty()
+            && self.opened.is_empty()
+            && self.closed.is_empty()
+    }
+}
+
+/// Compare two snapshots of the same mint, in either order: the differences
+/// always go from the earlier slot to the later one
+pub fn diff(a: &HolderSnapshot, b: &HolderSnapshot) -> TokenResult<SnapshotDiff> {
+    if a.mint != b.mint {
+        return Err(TokenError::AccountInvalidMint);
+    }
+    let (from, to) = if a.slot <= b.slot { (a, b) } else { (b, a) };
+
+    let mut changed = vec![];
+    let mut closed = vec![];
+    for (address, before) in &from.accounts {
+        match to.accounts.get(address) {
+            Some(after) if after != before => changed.push(AccountDelta {
+                address: *address,
+                owner: after.owner,
+                before: Some(before.amount),
+                after: Some(after.amount),
+            }),
+            Some(_) => {}
+            None => closed.push(AccountDelta {
+                address: *address,
+                owner: before.owner,
+                before: Some(before.amount),
+                after: None,
+            }),
+        }
+    }
+    let opened = to
+        .accounts
+        .iter()
+        .filter(|(address, _)| !from.accounts.contains_key(address))
+        .map(|(address, after)| AccountDelta {
+            address: *address,
+            owner: after.owner,
+            before: None,
+            after: Some(after.amount),
+        })
+        .collect();
+
+    Ok(SnapshotDiff {
+        mint: from.mint,
+        from_slot: from.slot,
+        to_slot: to.slot,
+        supply_before: from.supply,
+        supply_after: to.supply,
+        changed,
+        opened,
+        closed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ClientMethod, ProgramClient, ProgramRpcClientSendTransaction},
+            mock::MockProgramClient,
+            token::Token,
+        },
+        solana_sdk::{
+            hash::Hash,
+            signature::{Keypair, Signer},
+            transaction::Transaction,
+        },
+        spl_token_2022::state::{Account, AccountState, Mint},
+        std::sync::Arc,
+    };
+
+    #[test]
+    fn test_snapshot_slot() {
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let client = Arc::new(
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+                .with_mint(
+                    &mint,
+                    &spl_token_2022::id(),
+                    Mint {
+                        supply: 100,
+                        decimals: 2,
+                        is_initialized: true,
+                        ..Mint::default()
+                    },
+                )
+                .with_token_account(
+                    &account,
+                    &spl_token_2022::id(),
+                    Account {
+                        mint,
+                        owner,
+                        amount: 100,
+                        state: AccountState::Initialized,
+                        ..Account::default()
+                    },
+                ),
+        );
+        let token = Token::new(
+            client.clone(),
+            &spl_token_2022::id(),
+            &mint,
+            None,
+            Arc::new(Keypair::new()),
+        );
+        let payer = Keypair::new();
+        let transaction = Transaction::new_signed_with_payer(
+            &[],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        );
+
+        futures::executor::block_on(async {
+            client.send_transaction(&transaction).await.unwrap();
+            client.clear_calls();
+            let snapshot = token.snapshot().await.unwrap();
+            assert_eq!(snapshot.slot, 1);
+            assert_eq!(snapshot.supply, 100);
+            assert_eq!(
+                snapshot.accounts,
+                BTreeMap::from([(account, SnapshotAccount { owner, amount: 100 })])
+            );
+            // the slot comes from the scan itself, not a separate clock read
+            assert!(!client.calls().contains(&ClientMethod::GetSysvarClock));
+
+            let holders = token.snapshot_holders(100).await.unwrap();
+            assert_eq!(holders.slot, 1);
+            assert_eq!(holders.holders(0)[0].ui_amount, "1");
+            assert!(token
+                .snapshot_holders(101)
+                .await
+                .unwrap()
+                .accounts
+                .is_empty());
+        });
+    }
+
+    #[test]
+    fn test_holders() {
+        let owner = Pubkey::new_unique();
+        let (small, large, empty) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let snapshot = HolderSnapshot {
+            mint: Pubkey::new_unique(),
+            slot: 10,
+            supply: 2_500_010,
+            decimals: 6,
+            accounts: BTreeMap::from([
+                (small, SnapshotAccount { owner, amount: 10 }),
+                (
+                    large,
+                    SnapshotAccount {
+                        owner,
+                        amount: 2_500_000,
+                    },
+                ),
+                (empty, SnapshotAccount { owner, amount: 0 }),
+            ]),
+        };
+
+        let holders = snapshot.holders(1);
+        assert_eq!(
+            holders
+                .iter()
+                .map(|holder| (holder.address, holder.ui_amount.as_str()))
+                .collect::<Vec<_>>(),
+            [(large, "2.5"), (small, "0.00001")]
+        );
+        assert_eq!(snapshot.holders(0).len(), 3);
+    }
+
+    #[test]
+    fn test_diff() {
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let (kept, unchanged, opened, closed) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let account = |amount| SnapshotAccount { owner, amount };
+        let earlier = HolderSnapshot {
+            mint,
+            slot: 10,
+            supply: 100,
+            decimals: 0,
+            accounts: BTreeMap::from([
+                (kept, account(60)),
+                (unchanged, account(10)),
+                (closed, account(30)),
+            ]),
+        };
+        let later = HolderSnapshot {
+            mint,
+            slot: 20,
+            supply: 90,
+            decimals: 0,
+            accounts: BTreeMap::from([
+                (kept, account(40)),
+                (unchanged, account(10)),
+                (opened, account(40)),
+            ]),
+        };
+
+        // order of the arguments does not matter
+        let diff = diff(&later, &earlier).unwrap();
+        assert_eq!((diff.from_slot, diff.to_slot), (10, 20));
+        assert_eq!(diff.supply_change(), -10);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].change(), -20);
+        assert_eq!(diff.opened[0].address, opened);
+        assert_eq!(diff.closed[0].address, closed);
+        assert_eq!(diff.closed[0].change(), -30);
+
+        let other = HolderSnapshot {
+            mint: Pubkey::new_unique(),
+            ..earlier.clone()
+        };
+        assert_eq!(
+            super::diff(&earlier, &other),
+            Err(TokenError::AccountInvalidMint)
+        );
+    }
+}
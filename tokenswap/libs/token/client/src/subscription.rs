@@ -0,0 +1,330 @@
+//! Push notifications of account changes and transaction confirmations
+
+use {
+    crate::client::{ProgramClientResult, SignatureStatus},
+    async_trait::async_trait,
+    futures_util::StreamExt,
+    solana_account_decoder::UiAccountEncoding,
+    solana_program_test::tokio::{
+        self,
+        sync::{mpsc, Mutex},
+        task::JoinHandle,
+        time,
+    },
+    solana_pubsub_client::nonblocking::pubsub_client::PubsubClient,
+    solana_rpc_client_api::{
+        config::{RpcAccountInfoConfig, RpcSignatureSubscribeConfig},
+        response::{ProcessedSignatureResult, RpcSignatureResult},
+    },
+    solana_sdk::{
+        account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature,
+    },
+    std::{fmt, sync::Arc, time::Duration},
+};
+
+/// Default time to wait before reconnecting a dropped subscription
+pub const DEFAULT_RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Notifications of one subscription, delivered by a background task that is
+/// stopped when the subscription is dropped
+pub struct Subscription<N> {
+    receiver: mpsc::UnboundedReceiver<N>,
+    task: JoinHandle<()>,
+}
+
+impl<N> fmt::Debug for Subscription<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("finished", &self.task.is_finished())
+            .finish()
+    }
+}
+
+impl<N> Subscription<N> {
+    pub fn new(receiver: mpsc::UnboundedReceiver<N>, task: JoinHandle<()>) -> Self {
+        Self { receiver, task }
+    }
+
+    /// Next notification, or `None` once the subscription has ended
+    pub async fn next(&mut self) -> Option<N> {
+        self.receiver.recv().await
+    }
+}
+
+impl<N> Drop for Subscription<N> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Companion to `ProgramClient` for backends that can push changes instead
+/// of being polled
+#[async_trait]
+pub trait SubscriptionClient: Send + Sync {
+    /// Every new state of the account
+    async fn subscribe_account(
+        &self,
+        address: &Pubkey,
+    ) -> ProgramClientResult<Subscription<Account>>;
+
+    /// The status of the transaction once it is confirmed or has failed,
+    /// after which the subscription ends
+    async fn subscribe_signature(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Subscription<SignatureStatus>>;
+}
+
+/// Connection shared by several subscriptions, replaced once it drops
+#[derive(Default)]
+struct SharedConnection {
+    client: Mutex<Option<Arc<PubsubClient>>>,
+}
+
+impl fmt::Debug for SharedConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedConnection").finish()
+    }
+}
+
+impl SharedConnection {
+    /// The current connection, opening one if there is none
+    async fn get(&self, url: &str) -> ProgramClientResult<Arc<PubsubClient>> {
+        let mut client = self.client.lock().await;
+        if let Some(client) = &*client {
+            return Ok(client.clone());
+        }
+        let connected = Arc::new(PubsubClient::new(url).await?);
+        *client = Some(connected.clone());
+        Ok(connected)
+    }
+
+    /// Replace the dropped connection, retrying until a new one opens. Every
+    /// subscription of the dropped connection gets the same replacement.
+    async fn reconnect(
+        &self,
+        url: &str,
+        dropped: &Arc<PubsubClient>,
+        reconnect_interval: Duration,
+    ) -> Arc<PubsubClient> {
+        loop {
+            {
+                let mut client = self.client.lock().await;
+                if let Some(current) = &*client {
+                    if !Arc::ptr_eq(current, dropped) {
+                        return current.clone();
+                    }
+                }
+                if let Ok(connected) = PubsubClient::new(url).await {
+                    let connected = Arc::new(connected);
+                    *client = Some(connected.clone());
+                    return connected;
+                }
+            }
+            time::sleep(reconnect_interval).await;
+        }
+    }
+}
+
+/// Subscription client for the WebSocket pubsub interface of an RPC node.
+///
+/// Each account subscription uses its own connection, while signature
+/// subscriptions, which are many and short-lived, share a single one.
+/// Connections are reopened and resubscribed whenever they drop. Changes made
+/// while disconnected are only seen with the next notification.
+#[derive(Debug, Clone)]
+pub struct PubsubSubscriptionClient {
+    url: String,
+    commitment: CommitmentConfig,
+    reconnect_interval: Duration,
+    signature_connection: Arc<SharedConnection>,
+}
+
+impl PubsubSubscriptionClient {
+    pub fn new<U: Into<String>>(url: U) -> Self {
+        Self {
+            url: url.into(),
+            commitment: CommitmentConfig::confirmed(),
+            reconnect_interval: DEFAULT_RECONNECT_INTERVAL,
+            signature_connection: Arc::default(),
+        }
+    }
+
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn with_reconnect_interval(mut self, reconnect_interval: Duration) -> Self {
+        self.reconnect_interval = reconnect_interval;
+        self
+    }
+
+    /// Connection for the first subscription attempt, opened up front so
+    /// that an unreachable node is reported to the caller
+    async fn connect(&self) -> ProgramClientResult<PubsubClient> {
+        PubsubClient::new(&self.url).await.map_err(Into::into)
+    }
+}
+
+/// Open a new connection, retrying until it succeeds
+async fn reconnect(url: &str, reconnect_interval: Duration) -> PubsubClient {
+    loop {
+        if let Ok(client) = PubsubClient::new(url).await {
+            return client;
+        }
+        time::sleep(reconnect_interval).await;
+    }
+}
+
+#[async_trait]
+impl SubscriptionClient for PubsubSubscriptionClient {
+    async fn subscribe_account(
+        &self,
+        address: &Pubkey,
+    ) -> ProgramClientResult<Subscription<Account>> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut connection = Some(self.connect().await?);
+        let url = self.url.clone();
+        let reconnect_interval = self.reconnect_interval;
+        let address = *address;
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64Zstd),
+            commitment: Some(self.commitment),
+            ..RpcAccountInfoConfig::default()
+        };
+
+        let task = tokio::spawn(async move {
+            loop {
+                let client = match connection.take() {
+                    Some(client) => client,
+                    None => reconnect(&url, reconnect_interval).await,
+                };
+                if let Ok((mut notifications, _unsubscribe)) = client
+                    .account_subscribe(&address, Some(config.clone()))
+                    .await
+                {
+                    while let Some(response) = notifications.next().await {
+                        let Some(account) = response.value.decode::<Account>() else {
+                            continue;
+                        };
+                        if sender.send(account).is_err() {
+                            return;
+                        }
+                    }
+                }
+                time::sleep(reconnect_interval).await;
+            }
+        });
+        Ok(Subscription::new(receiver, task))
+    }
+
+    async fn subscribe_signature(
+        &self,
+        signature: &Signature,
+    ) -> ProgramClientResult<Subscription<SignatureStatus>> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let shared = self.signature_connection.clone();
+        let mut client = shared.get(&self.url).await?;
+        let url = self.url.clone();
+        let reconnect_interval = self.reconnect_interval;
+        let signature = *signature;
+        let config = RpcSignatureSubscribeConfig {
+            commitment: Some(self.commitment),
+            enable_received_notification: Some(false),
+        };
+
+        // the node ends a signature subscription once it notifies it
+        let task = tokio::spawn(async move {
+            loop {
+                if let Ok((mut notifications, _unsubscribe)) = client
+                    .signature_subscribe(&signature, Some(config.clone()))
+                    .await
+                {
+                    while let Some(response) = notifications.next().await {
+                        if let RpcSignatureResult::ProcessedSignature(ProcessedSignatureResult {
+                            err,
+                        }) = response.value
+                        {
+                            let _ = sender.send(SignatureStatus {
+                                slot: response.context.slot,
+                                err,
+                                confirmed: true,
+                            });
+                            return;
+                        }
+                    }
+                }
+                time::sleep(reconnect_interval).await;
+                client = shared.reconnect(&url, &client, reconnect_interval).await;
+            }
+        });
+        Ok(Subscription::new(receiver, task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            client::{ClientMethod, ProgramRpcClientSendTransaction},
+            mock::MockProgramClient,
+            token::{Token, TokenError},
+        },
+        futures_util::{future, StreamExt},
+        solana_sdk::{pubkey::Pubkey, signature::Keypair},
+        spl_token_2022::state::{Account, AccountState, Mint},
+        std::sync::Arc,
+    };
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn test_watch_account_polling() {
+        let mint = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let client = Arc::new(
+            MockProgramClient::<ProgramRpcClientSendTransaction>::new()
+                .with_mint(
+                    &mint,
+                    &spl_token_2022::id(),
+                    Mint {
+                        is_initialized: true,
+                        ..Mint::default()
+                    },
+                )
+                .with_token_account(
+                    &account,
+                    &spl_token_2022::id(),
+                    Account {
+                        mint,
+                        owner: Pubkey::new_unique(),
+                        amount: 5,
+                        state: AccountState::Initialized,
+                        ..Account::default()
+                    },
+                ),
+        );
+        let token = Token::new(
+            client.clone(),
+            &spl_token_2022::id(),
+            &mint,
+            None,
+            Arc::new(Keypair::new()),
+        )
+        .with_sleep(Arc::new(|_| Box::pin(future::ready(()))));
+
+        futures::executor::block_on(async {
+            let mut states = token.watch_account(&account).await.unwrap();
+            assert_send(&states);
+
+            // a failed poll is reported, and polling goes on
+            client.fail_next(ClientMethod::GetAccount, "unavailable");
+            assert!(matches!(
+                states.next().await,
+                Some(Err(TokenError::Client(_)))
+            ));
+            assert_eq!(states.next().await.unwrap().unwrap().base.amount, 5);
+        });
+    }
+}
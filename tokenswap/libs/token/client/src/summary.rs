@@ -0,0 +1,291 @@
+//! Consolidated views of how a mint is configured and of the decrypted
+//! state of confidential accounts
+
+use {
+    crate::token::{TokenError, TokenResult},
+    solana_sdk::{program_error::ProgramError, pubkey::Pubkey},
+    spl_token_2022::{
+        extension::{
+            confidential_transfer::{
+                ConfidentialTransferAccount, EncryptedBalance, PENDING_BALANCE_LO_BIT_LENGTH,
+            },
+            default_account_state::DefaultAccountState,
+            group_member_pointer::GroupMemberPointer,
+            group_pointer::GroupPointer,
+            interest_bearing_mint::InterestBearingConfig,
+            metadata_pointer::MetadataPointer,
+            mint_close_authority::MintCloseAuthority,
+            permanent_delegate::PermanentDelegate,
+            transfer_fee::TransferFeeConfig,
+            transfer_hook::TransferHook,
+            BaseStateWithExtensions, ExtensionType, StateWithExtensionsOwned,
+        },
+        solana_zk_token_sdk::encryption::{
+            auth_encryption::{AeCiphertext, AeKey},
+            elgamal::{ElGamalCiphertext, ElGamalKeypair},
+        },
+        state::{AccountState, Mint},
+    },
+};
+
+/// Configuration of a mint: its base state and the settings of its
+/// extensions, `None` where the extension is absent or the setting unset
+#[derive(Clone, Debug, PartialEq)]
+pub struct MintSummary {
+    pub address: Pubkey,
+    pub decimals: u8,
+    pub supply: u64,
+    pub mint_authority: Option<Pubkey>,
+    pub freeze_authority: Option<Pubkey>,
+    pub close_authority: Option<Pubkey>,
+    pub permanent_delegate: Option<Pubkey>,
+    pub default_account_state: Option<AccountState>,
+    pub transfer_fee_config: Option<TransferFeeConfig>,
+    /// Current rate of an interest-bearing mint, in basis points
+    pub interest_rate: Option<i16>,
+    pub transfer_hook_program_id: Option<Pubkey>,
+    pub metadata_address: Option<Pubkey>,
+    pub group_address: Option<Pubkey>,
+    pub group_member_address: Option<Pubkey>,
+    /// Every extension of the mint, in the order of its data
+    pub extensions: Vec<ExtensionType>,
+}
+
+impl MintSummary {
+    pub fn new(
+        address: Pubkey,
+        mint: &StateWithExtensionsOwned<Mint>,
+    ) -> Result<Self, ProgramError> {
+        let default_account_state = mint
+            .get_extension::<DefaultAccountState>()
+            .ok()
+            .map(|extension| AccountState::try_from(extension.state))
+            .transpose()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        Ok(Self {
+            address,
+            decimals: mint.base.decimals,
+            supply: mint.base.supply,
+            mint_authority: mint.base.mint_authority.into(),
+            freeze_authority: mint.base.freeze_authority.into(),
+            close_authority: mint
+                .get_extension::<MintCloseAuthority>()
+                .ok()
+                .and_then(|extension| extension.close_authority.into()),
+            permanent_delegate: mint
+                .get_extension::<PermanentDelegate>()
+                .ok()
+                .and_then(|extension| extension.delegate.into()),
+            default_account_state,
+            transfer_fee_config: mint.get_extension::<TransferFeeConfig>().ok().copied(),
+            interest_rate: mint
+                .get_extension::<InterestBearingConfig>()
+                .ok()
+                .map(|extension| extension.current_rate.into()),
+            transfer_hook_program_id: mint
+                .get_extension::<TransferHook>()
+                .ok()
+                .and_then(|extension| extension.program_id.into()),
+            metadata_address: mint
+                .get_extension::<MetadataPointer>()
+                .ok()
+                .and_then(|extension| extension.metadata_address.into()),
+            group_address: mint
+                .get_extension::<GroupPointer>()
+                .ok()
+                .and_then(|extension| extension.group_address.into()),
+            group_member_address: mint
+                .get_extension::<GroupMemberPointer>()
+                .ok()
+                .and_then(|extension| extension.member_address.into()),
+            extensions: mint.get_extension_types()?,
+        })
+    }
+
+    pub fn has_extension(&self, extension_type: ExtensionType) -> bool {
+        self.extensions.contains(&extension_type)
+    }
+}
+
+/// Decrypted state of the confidential transfer extension of an account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConfidentialAccountSummary {
+    pub address: Pubkey,
+    pub approved: bool,
+    /// Low 16 bits of the pending balance
+    pub pending_balance_lo: u64,
+    /// High 48 bits of the pending balance
+    pub pending_balance_hi: u64,
+    pub pending_balance: u64,
+    /// Available balance, as of the decryptable balance kept with the AE key
+    pub available_balance: u64,
+    pub allow_confidential_credits: bool,
+    pub allow_non_confidential_credits: bool,
+    pub pending_balance_credit_counter: u64,
+    pub maximum_pending_balance_credit_counter: u64,
+    pub expected_pending_balance_credit_counter: u64,
+    pub actual_pending_balance_credit_counter: u64,
+}
+
+impl ConfidentialAccountSummary {
+    /// Decrypt the balances of the extension. Pending balance halves are
+    /// decrypted with the ElGamal key, which only succeeds while each fits
+    /// in 32 bits, as `ApplyPendingBalance` requires anyway.
+    pub fn new(
+        address: Pubkey,
+        account: &ConfidentialTransferAccount,
+        elgamal_keypair: &ElGamalKeypair,
+        aes_key: &AeKey,
+    ) -> TokenResult<Self> {
+        let decrypt = |ciphertext: EncryptedBalance| -> TokenResult<u64> {
+            let ciphertext: ElGamalCiphertext = ciphertext
+                .try_into()
+                .map_err(|_| TokenError::AccountDecryption)?;
+            elgamal_keypair
+                .secret()
+                .decrypt_u32(&ciphertext)
+                .ok_or(TokenError::AccountDecryption)
+        };
+        let pending_balance_lo = decrypt(account.pending_balance_lo)?;
+        let pending_balance_hi = decrypt(account.pending_balance_hi)?;
+        let pending_balance = pending_balance_hi
+            .checked_shl(PENDING_BALANCE_LO_BIT_LENGTH)
+            .and_then(|hi| hi.checked_add(pending_balance_lo))
+            .ok_or(TokenError::AccountDecryption)?;
+        let available_balance = AeCiphertext::try_from(account.decryptable_available_balance)
+            .ok()
+            .and_then(|ciphertext| aes_key.decrypt(&ciphertext))
+            .ok_or(TokenError::AccountDecryption)?;
+
+        Ok(Self {
+            address,
+            approved: account.approved.into(),
+            pending_balance_lo,
+            pending_balance_hi,
+            pending_balance,
+            available_balance,
+            allow_confidential_credits: account.allow_confidential_credits.into(),
+            allow_non_confidential_credits: account.allow_non_confidential_credits.into(),
+            pending_balance_credit_counter: account.pending_balance_credit_counter.into(),
+            maximum_pending_balance_credit_counter: account
+                .maximum_pending_balance_credit_counter
+                .into(),
+            expected_pending_balance_credit_counter: account
+                .expected_pending_balance_credit_counter
+                .into(),
+            actual_pending_balance_credit_counter: account
+                .actual_pending_balance_credit_counter
+                .into(),
+        })
+    }
+
+    /// Whether incoming confidential credits would exceed the maximum before
+    /// the pending balance is applied
+    pub fn pending_balance_credits_exhausted(&self) -> bool {
+        self.pending_balance_credit_counter >= self.maximum_pending_balance_credit_counter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        bytemuck::Zeroable,
+        solana_sdk::{program_option::COption, program_pack::Pack},
+        spl_token_2022::extension::StateWithExtensionsMut,
+    };
+
+    #[test]
+    fn test_mint_summary() {
+        let mint_authority = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let extension_types = [
+            ExtensionType::PermanentDelegate,
+            ExtensionType::DefaultAccountState,
+        ];
+        let len = ExtensionType::try_calculate_account_len::<Mint>(&extension_types).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.base = Mint {
+            mint_authority: COption::Some(mint_authority),
+            supply: 42,
+            decimals: 6,
+            is_initialized: true,
+            ..Mint::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        state
+            .init_extension::<PermanentDelegate>(true)
+            .unwrap()
+            .delegate = Some(delegate).try_into().unwrap();
+        state
+            .init_extension::<DefaultAccountState>(true)
+            .unwrap()
+            .state = AccountState::Frozen.into();
+
+        let address = Pubkey::new_unique();
+        let mint = StateWithExtensionsOwned::<Mint>::unpack(data).unwrap();
+        let summary = MintSummary::new(address, &mint).unwrap();
+        assert_eq!((summary.decimals, summary.supply), (6, 42));
+        assert_eq!(summary.mint_authority, Some(mint_authority));
+        assert_eq!(summary.freeze_authority, None);
+        assert_eq!(summary.permanent_delegate, Some(delegate));
+        assert_eq!(summary.default_account_state, Some(AccountState::Frozen));
+        assert_eq!(summary.transfer_fee_config, None);
+        assert_eq!(summary.extensions, extension_types);
+        assert!(summary.has_extension(ExtensionType::PermanentDelegate));
+        assert!(!summary.has_extension(ExtensionType::TransferHook));
+
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut data);
+        let mint = StateWithExtensionsOwned::<Mint>::unpack(data).unwrap();
+        let summary = MintSummary::new(address, &mint).unwrap();
+        assert!(summary.extensions.is_empty());
+        assert_eq!(summary.default_account_state, None);
+    }
+
+    #[test]
+    fn test_confidential_account_summary() {
+        let elgamal_keypair = ElGamalKeypair::new_rand();
+        let aes_key = AeKey::new_rand();
+        let mut account = ConfidentialTransferAccount::zeroed();
+        account.approved = true.into();
+        account.elgamal_pubkey = (*elgamal_keypair.pubkey()).into();
+        account.pending_balance_lo = elgamal_keypair.pubkey().encrypt(5_u64).into();
+        account.pending_balance_hi = elgamal_keypair.pubkey().encrypt(2_u64).into();
+        account.available_balance = elgamal_keypair.pubkey().encrypt(100_u64).into();
+        account.decryptable_available_balance = aes_key.encrypt(100).into();
+        account.allow_confidential_credits = true.into();
+        account.pending_balance_credit_counter = 2.into();
+        account.maximum_pending_balance_credit_counter = 2.into();
+
+        let address = Pubkey::new_unique();
+        let summary =
+            ConfidentialAccountSummary::new(address, &account, &elgamal_keypair, &aes_key).unwrap();
+        assert!(summary.approved);
+        assert_eq!(
+            (summary.pending_balance_lo, summary.pending_balance_hi),
+            (5, 2)
+        );
+        assert_eq!(summary.pending_balance, (2 << 16) + 5);
+        assert_eq!(summary.available_balance, 100);
+        assert!(summary.allow_confidential_credits);
+        assert!(!summary.allow_non_confidential_credits);
+        assert!(summary.pending_balance_credits_exhausted());
+
+        assert_eq!(
+            ConfidentialAccountSummary::new(
+                address,
+                &account,
+                &elgamal_keypair,
+                &AeKey::new_rand()
+            ),
+            Err(TokenError::AccountDecryption)
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Supply of a mint, with its UI amount
+
+use {
+    crate::format::{self, AmountFormat},
+    solana_sdk::clock::UnixTimestamp,
+    spl_token_2022::extension::interest_bearing_mint::InterestBearingConfig,
+};
+
+/// Supply of a mint
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenSupply {
+    pub amount: u64,
+    pub decimals: u8,
+    /// Supply as the token program displays it, including the interest
+    /// accrued by interest-bearing mints
+    pub ui_amount: String,
+    /// Time the interest was accrued until, for interest-bearing mints
+    pub interest_timestamp: Option<UnixTimestamp>,
+}
+
+impl TokenSupply {
+    pub fn new(amount: u64, decimals: u8) -> Self {
+        Self {
+            amount,
+            decimals,
+            ui_amount: format::amount(amount, decimals, &AmountFormat::new()),
+            interest_timestamp: None,
+        }
+    }
+
+    /// Supply of an interest-bearing mint, with interest accrued until
+    /// `unix_timestamp`
+    pub fn with_interest(
+        amount: u64,
+        decimals: u8,
+        config: &InterestBearingConfig,
+        unix_timestamp: UnixTimestamp,
+    ) -> Self {
+        // interest is only defined in floating point on chain, so match it
+        match config.amount_to_ui_amount(amount, decimals, unix_timestamp) {
+            Some(ui_amount) => Self {
+                amount,
+                decimals,
+                ui_amount,
+                interest_timestamp: Some(unix_timestamp),
+            },
+            None => Self::new(amount, decimals),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, spl_token_2022::extension::interest_bearing_mint::BasisPoints};
+
+    #[test]
+    fn test_ui_amount() {
+        let supply = TokenSupply::new(u64::MAX, 9);
+        assert_eq!(supply.ui_amount, "18446744073.709551615");
+        assert_eq!(supply.interest_timestamp, None);
+
+        let config = InterestBearingConfig {
+            current_rate: BasisPoints::from(500),
+            ..InterestBearingConfig::default()
+        };
+        let supply = TokenSupply::with_interest(1_000_000, 6, &config, 0);
+        assert_eq!(supply.ui_amount, "1");
+        let supply = TokenSupply::with_interest(1_000_000, 6, &config, 31_556_736);
+        assert!(supply.ui_amount.starts_with("1.05127"));
+    }
+}
@@ -1,21 +1,67 @@
 use {
     crate::{
-        client::{ProgramClient, ProgramClientError, SendTransaction, SimulateTransaction},
+        ata::AtaCache,
+        audit::{self, AccountAudit, AuditPackage, SignedAuditPackage},
+        authority::Authority,
+        batch::{self, BatchOutcome},
+        bundle::{BundleSender, MAX_BUNDLE_TRANSACTIONS},
+        client::{
+            AccountOverrides, ProgramClient, ProgramClientError, ReadConsistency, SendTransaction,
+            SimulateTransaction, SimulationResult, TransactionRecord, Unsupported,
+            MAX_LARGEST_ACCOUNTS,
+        },
+        confirmation::ConfirmationService,
+        distribution::{DistributionPlan, DistributionProjection, TransferProjection},
+        events::{self, AccountOperation, TokenEventKind},
+        format::{self, AmountFormat, AmountParseError},
+        holders::{self, DelegatedAccount, TokenHolder},
+        hook,
+        idempotency::{IdempotencyRecord, IdempotencyStore},
+        journal::{Journal, JournalEntry},
+        keyring::ConfidentialAccountKeys,
+        metadata::{self, MetadataSource, ResolvedTokenMetadata},
+        multisig::MultisigInfo,
+        orchestrator::{OrchestrationError, Orchestrator, DEFAULT_MAX_CONCURRENCY},
+        payment::{
+            ConfidentialKeys, Confidentiality, CreateAtaPolicy, PaymentAmount, PaymentDestination,
+            PaymentKind, PaymentReceipt, PaymentRequest,
+        },
+        priority_fee::{fee_accounts, PriorityFeePolicy},
         proof_generation::transfer_with_fee_split_proof_data,
+        rate_history::{parse_rate_changes, reconstruct_rate_history, RateChange},
+        rent::RentCache,
+        snapshot::{HolderSnapshot, SnapshotAccount},
+        subscription::{Subscription, SubscriptionClient},
+        summary::{ConfidentialAccountSummary, MintSummary},
+        supply::TokenSupply,
+    },
+    futures::{
+        future::{self, try_join_all, BoxFuture, Either},
+        stream::{self, BoxStream},
+        FutureExt, StreamExt,
     },
-    futures::{future::join_all, try_join},
     futures_util::TryFutureExt,
     solana_program_test::tokio::time,
+    solana_rpc_client_api::filter::{Memcmp, RpcFilterType},
     solana_sdk::{
         account::Account as BaseAccount,
-        hash::Hash,
+        account_utils::StateMut,
+        clock::{Clock, Epoch, Slot, UnixTimestamp},
+        commitment_config::CommitmentConfig,
+        epoch_info::EpochInfo,
+        hash::{Hash, Hasher},
         instruction::{AccountMeta, Instruction},
         message::Message,
+        nonce,
         program_error::ProgramError,
+        program_option::COption,
         program_pack::Pack,
+        program_utils::limited_deserialize,
         pubkey::Pubkey,
+        signature::Signature,
         signer::{signers::Signers, Signer, SignerError},
-        system_instruction,
+        system_instruction::{self, SystemInstruction},
+        system_program,
         transaction::Transaction,
     },
     spl_associated_token_account::{
@@ -24,6 +70,7 @@ use {
             create_associated_token_account, create_associated_token_account_idempotent,
         },
     },
+    spl_tlv_account_resolution::{account::ExtraAccountMeta, state::ExtraAccountMetaList},
     spl_token_2022::{
         extension::{
             confidential_transfer::{
@@ -36,21 +83,33 @@ use {
                 instruction::{
                     TransferSplitContextStateAccounts, TransferWithFeeSplitContextStateAccounts,
                 },
-                ConfidentialTransferAccount, DecryptableBalance,
+                ConfidentialTransferAccount, ConfidentialTransferMint, DecryptableBalance,
+                EncryptedBalance, PENDING_BALANCE_LO_BIT_LENGTH,
             },
             confidential_transfer_fee::{
                 self, account_info::WithheldTokensInfo, ConfidentialTransferFeeAmount,
                 ConfidentialTransferFeeConfig,
             },
-            cpi_guard, default_account_state, group_member_pointer, group_pointer,
-            interest_bearing_mint, memo_transfer, metadata_pointer, transfer_fee, transfer_hook,
-            BaseStateWithExtensions, Extension, ExtensionType, StateWithExtensionsOwned,
+            cpi_guard,
+            default_account_state::{self, DefaultAccountState},
+            group_member_pointer::{self, GroupMemberPointer},
+            group_pointer::{self, GroupPointer},
+            interest_bearing_mint::{self, InterestBearingConfig},
+            memo_transfer,
+            metadata_pointer::{self, MetadataPointer},
+            mint_close_authority::MintCloseAuthority,
+            non_transferable::NonTransferable,
+            permanent_delegate::PermanentDelegate,
+            transfer_fee::{self, TransferFee, TransferFeeAmount, TransferFeeConfig},
+            transfer_hook::{self, TransferHook},
+            AccountType, BaseStateWithExtensions, Extension, ExtensionType,
+            StateWithExtensionsOwned,
         },
         instruction, offchain,
         proof::ProofLocation,
         solana_zk_token_sdk::{
             encryption::{
-                auth_encryption::AeKey,
+                auth_encryption::{AeCiphertext, AeKey},
                 elgamal::{ElGamalCiphertext, ElGamalKeypair, ElGamalPubkey, ElGamalSecretKey},
             },
             instruction::*,
@@ -63,10 +122,15 @@ use {
     },
     spl_token_group_interface::state::{TokenGroup, TokenGroupMember},
     spl_token_metadata_interface::state::{Field, TokenMetadata},
+    spl_transfer_hook_interface::{
+        get_extra_account_metas_address, instruction::initialize_extra_account_meta_list,
+    },
     std::{
+        collections::{HashMap, HashSet},
         fmt, io,
         mem::size_of,
-        sync::{Arc, RwLock},
+        ops::Range,
+        sync::{Arc, Mutex, RwLock},
         time::{Duration, Instant},
     },
     thiserror::Error,
@@ -104,6 +168,64 @@ pub enum TokenError {
     MissingDecimals,
     #[error("decimals specified, but incorrect")]
     InvalidDecimals,
+    #[error("transaction not confirmed after {0} attempts")]
+    ConfirmationTimeout(usize),
+    #[error("an identical operation is already in flight")]
+    OperationInFlight,
+    #[error("idempotency store required, but missing")]
+    MissingIdempotencyStore,
+    #[error("operation with this idempotency key was already submitted")]
+    DuplicateOperation(Option<Signature>),
+    #[error("journal required, but missing")]
+    MissingJournal,
+    #[error("journal entry not found")]
+    JournalEntryNotFound,
+    #[error("time lock active until {0}")]
+    TimelockActive(UnixTimestamp),
+    #[error("durable nonce required, but missing")]
+    MissingNonce,
+    #[error("invalid nonce account")]
+    InvalidNonceAccount,
+    #[error("nonce account already used by a pending transaction")]
+    NonceAccountInUse,
+    #[error("insufficient payer funds: {required} lamports required, {available} available")]
+    InsufficientPayerFunds { required: u64, available: u64 },
+    #[error("{0}")]
+    Orchestration(OrchestrationError),
+    #[error("tokens composed in one transaction must share a payer")]
+    PayerMismatch,
+    #[error("transfer of zero tokens")]
+    ZeroAmountTransfer,
+    #[error("transfer source and destination are the same account")]
+    SelfTransfer,
+    #[error("burn of {amount} exceeds the account balance of {balance}")]
+    BurnExceedsBalance { amount: u64, balance: u64 },
+    #[error("mint has no {0:?} extension")]
+    MissingExtension(ExtensionType),
+    #[error("subscription client required, but missing")]
+    MissingSubscriptionClient,
+    #[error("invalid amount: {0}")]
+    InvalidAmount(AmountParseError),
+    #[error("transfer fee of {fee} exceeds the tolerated {max_fee}")]
+    FeeExceedsTolerance { fee: u64, max_fee: u64 },
+    #[error("confidential transfer not possible between these accounts")]
+    ConfidentialTransferUnavailable,
+    #[error("owner {0} is off curve")]
+    OwnerOffCurve(Pubkey),
+    #[error("mint still has a supply of {0}")]
+    MintHasSupply(u64),
+    #[error("existing mint has a different {0}")]
+    MintMismatch(MintProperty),
+    #[error("transfer of {amount} exceeds the delegated amount of {delegated_amount}")]
+    InsufficientDelegatedAmount { amount: u64, delegated_amount: u64 },
+    #[error("persistence error: {0}")]
+    Persistence(io::Error),
+    #[error("no keys stored for confidential account {0}")]
+    MissingConfidentialKeys(Pubkey),
+    #[error("{0} is not the delegate of the account")]
+    InvalidDelegate(Pubkey),
+    #[error("{0} is not the close authority of the mint")]
+    InvalidCloseAuthority(Pubkey),
 }
 impl PartialEq for TokenError {
     fn eq(&self, other: &Self) -> bool {
@@ -127,6 +249,73 @@ impl PartialEq for TokenError {
             (Self::MissingMemoSigner, Self::MissingMemoSigner) => true,
             (Self::MissingDecimals, Self::MissingDecimals) => true,
             (Self::InvalidDecimals, Self::InvalidDecimals) => true,
+            (Self::ConfirmationTimeout(ref a), Self::ConfirmationTimeout(ref b)) => a == b,
+            (Self::OperationInFlight, Self::OperationInFlight) => true,
+            (Self::MissingIdempotencyStore, Self::MissingIdempotencyStore) => true,
+            (Self::DuplicateOperation(ref a), Self::DuplicateOperation(ref b)) => a == b,
+            (Self::MissingJournal, Self::MissingJournal) => true,
+            (Self::JournalEntryNotFound, Self::JournalEntryNotFound) => true,
+            (Self::TimelockActive(ref a), Self::TimelockActive(ref b)) => a == b,
+            (Self::MissingNonce, Self::MissingNonce) => true,
+            (Self::InvalidNonceAccount, Self::InvalidNonceAccount) => true,
+            (Self::NonceAccountInUse, Self::NonceAccountInUse) => true,
+            (
+                Self::InsufficientPayerFunds {
+                    required: a_required,
+                    available: a_available,
+                },
+                Self::InsufficientPayerFunds {
+                    required: b_required,
+                    available: b_available,
+                },
+            ) => a_required == b_required && a_available == b_available,
+            (Self::Orchestration(ref a), Self::Orchestration(ref b)) => a == b,
+            (Self::PayerMismatch, Self::PayerMismatch) => true,
+            (Self::ZeroAmountTransfer, Self::ZeroAmountTransfer) => true,
+            (Self::SelfTransfer, Self::SelfTransfer) => true,
+            (
+                Self::BurnExceedsBalance {
+                    amount: a_amount,
+                    balance: a_balance,
+                },
+                Self::BurnExceedsBalance {
+                    amount: b_amount,
+                    balance: b_balance,
+                },
+            ) => a_amount == b_amount && a_balance == b_balance,
+            (Self::MissingExtension(ref a), Self::MissingExtension(ref b)) => a == b,
+            (Self::MissingSubscriptionClient, Self::MissingSubscriptionClient) => true,
+            (Self::InvalidAmount(ref a), Self::InvalidAmount(ref b)) => a == b,
+            (
+                Self::FeeExceedsTolerance {
+                    fee: a_fee,
+                    max_fee: a_max_fee,
+                },
+                Self::FeeExceedsTolerance {
+                    fee: b_fee,
+                    max_fee: b_max_fee,
+                },
+            ) => a_fee == b_fee && a_max_fee == b_max_fee,
+            (Self::ConfidentialTransferUnavailable, Self::ConfidentialTransferUnavailable) => true,
+            (Self::OwnerOffCurve(ref a), Self::OwnerOffCurve(ref b)) => a == b,
+            (Self::MintHasSupply(ref a), Self::MintHasSupply(ref b)) => a == b,
+            (Self::MintMismatch(ref a), Self::MintMismatch(ref b)) => a == b,
+            (
+                Self::InsufficientDelegatedAmount {
+                    amount: a_amount,
+                    delegated_amount: a_delegated_amount,
+                },
+                Self::InsufficientDelegatedAmount {
+                    amount: b_amount,
+                    delegated_amount: b_delegated_amount,
+                },
+            ) => a_amount == b_amount && a_delegated_amount == b_delegated_amount,
+            (Self::Persistence(ref a), Self::Persistence(ref b)) => {
+                a.kind() == b.kind() && a.to_string() == b.to_string()
+            }
+            (Self::MissingConfidentialKeys(ref a), Self::MissingConfidentialKeys(ref b)) => a == b,
+            (Self::InvalidDelegate(ref a), Self::InvalidDelegate(ref b)) => a == b,
+            (Self::InvalidCloseAuthority(ref a), Self::InvalidCloseAuthority(ref b)) => a == b,
             _ => false,
         }
     }
@@ -201,6 +390,112 @@ impl ExtensionInitializationParams {
             Self::GroupMemberPointer { .. } => ExtensionType::GroupMemberPointer,
         }
     }
+    /// Whether the mint's extension is configured as these parameters
+    /// initialize it, as of its latest update. The transfer fee compared is
+    /// the newest one, and the interest rate the current one.
+    fn matches(&self, mint: &StateWithExtensionsOwned<Mint>) -> bool {
+        match self {
+            Self::ConfidentialTransferMint {
+                authority,
+                auto_approve_new_accounts,
+                auditor_elgamal_pubkey,
+            } => mint
+                .get_extension::<ConfidentialTransferMint>()
+                .map_or(false, |extension| {
+                    Option::<Pubkey>::from(extension.authority) == *authority
+                        && bool::from(extension.auto_approve_new_accounts)
+                            == *auto_approve_new_accounts
+                        && Option::<PodElGamalPubkey>::from(extension.auditor_elgamal_pubkey)
+                            == *auditor_elgamal_pubkey
+                }),
+            Self::DefaultAccountState { state } => mint
+                .get_extension::<DefaultAccountState>()
+                .map_or(false, |extension| extension.state == *state as u8),
+            Self::MintCloseAuthority { close_authority } => mint
+                .get_extension::<MintCloseAuthority>()
+                .map_or(false, |extension| {
+                    Option::<Pubkey>::from(extension.close_authority) == *close_authority
+                }),
+            Self::TransferFeeConfig {
+                transfer_fee_config_authority,
+                withdraw_withheld_authority,
+                transfer_fee_basis_points,
+                maximum_fee,
+            } => mint
+                .get_extension::<TransferFeeConfig>()
+                .map_or(false, |extension| {
+                    Option::<Pubkey>::from(extension.transfer_fee_config_authority)
+                        == *transfer_fee_config_authority
+                        && Option::<Pubkey>::from(extension.withdraw_withheld_authority)
+                            == *withdraw_withheld_authority
+                        && u16::from(extension.newer_transfer_fee.transfer_fee_basis_points)
+                            == *transfer_fee_basis_points
+                        && u64::from(extension.newer_transfer_fee.maximum_fee) == *maximum_fee
+                }),
+            Self::InterestBearingConfig {
+                rate_authority,
+                rate,
+            } => mint
+                .get_extension::<InterestBearingConfig>()
+                .map_or(false, |extension| {
+                    Option::<Pubkey>::from(extension.rate_authority) == *rate_authority
+                        && i16::from(extension.current_rate) == *rate
+                }),
+            Self::NonTransferable => mint.get_extension::<NonTransferable>().is_ok(),
+            Self::PermanentDelegate { delegate } => mint
+                .get_extension::<PermanentDelegate>()
+                .map_or(false, |extension| {
+                    Option::<Pubkey>::from(extension.delegate) == Some(*delegate)
+                }),
+            Self::TransferHook {
+                authority,
+                program_id,
+            } => mint
+                .get_extension::<TransferHook>()
+                .map_or(false, |extension| {
+                    Option::<Pubkey>::from(extension.authority) == *authority
+                        && Option::<Pubkey>::from(extension.program_id) == *program_id
+                }),
+            Self::MetadataPointer {
+                authority,
+                metadata_address,
+            } => mint
+                .get_extension::<MetadataPointer>()
+                .map_or(false, |extension| {
+                    Option::<Pubkey>::from(extension.authority) == *authority
+                        && Option::<Pubkey>::from(extension.metadata_address) == *metadata_address
+                }),
+            Self::ConfidentialTransferFeeConfig {
+                authority,
+                withdraw_withheld_authority_elgamal_pubkey,
+            } => mint
+                .get_extension::<ConfidentialTransferFeeConfig>()
+                .map_or(false, |extension| {
+                    Option::<Pubkey>::from(extension.authority) == *authority
+                        && extension.withdraw_withheld_authority_elgamal_pubkey
+                            == *withdraw_withheld_authority_elgamal_pubkey
+                }),
+            Self::GroupPointer {
+                authority,
+                group_address,
+            } => mint
+                .get_extension::<GroupPointer>()
+                .map_or(false, |extension| {
+                    Option::<Pubkey>::from(extension.authority) == *authority
+                        && Option::<Pubkey>::from(extension.group_address) == *group_address
+                }),
+            Self::GroupMemberPointer {
+                authority,
+                member_address,
+            } => mint
+                .get_extension::<GroupMemberPointer>()
+                .map_or(false, |extension| {
+                    Option::<Pubkey>::from(extension.authority) == *authority
+                        && Option::<Pubkey>::from(extension.member_address) == *member_address
+                }),
+        }
+    }
+
     /// Generate an appropriate initialization instruction for the given mint
     pub fn instruction(
         self,
@@ -314,8 +609,197 @@ impl ExtensionInitializationParams {
 
 pub type TokenResult<T> = Result<T, TokenError>;
 
-#[derive(Debug)]
-struct TokenMemo {
+/// Schedule used to resubmit a transaction with a higher compute-unit price
+/// when it fails to confirm in time
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeeEscalation {
+    /// Compute-unit prices, in micro-lamports, tried in order
+    pub compute_unit_prices: Vec<u64>,
+    /// How long to wait for each attempt to confirm before escalating
+    pub confirmation_timeout: Duration,
+}
+impl Default for FeeEscalation {
+    fn default() -> Self {
+        Self {
+            compute_unit_prices: vec![0, 1_000, 10_000, 100_000],
+            confirmation_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Checks made while building transfers and burns, rejecting operations that
+/// would waste fees or fail on chain with an unhelpful error. All of them are
+/// off by default, leaving every case to the program, and are opted into one
+/// by one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OperationGuards {
+    pub reject_zero_amount_transfers: bool,
+    pub reject_self_transfers: bool,
+    /// Costs a query of the source account's balance for every burn
+    pub reject_burns_exceeding_balance: bool,
+    /// Reject associated accounts for owners off the ed25519 curve, like the
+    /// CLI without `--allow-owner-off-curve`. Program derived addresses
+    /// routinely own associated accounts, so only enable this for wallets.
+    pub reject_owners_off_curve: bool,
+}
+impl OperationGuards {
+    /// No checks, leaving every case to the program
+    pub fn none() -> Self {
+        Self::default()
+    }
+}
+
+/// Whether the extensions of an account let it be closed: no withheld fees
+/// or confidential balances
+fn extensions_closable(account: &StateWithExtensionsOwned<Account>) -> bool {
+    account
+        .get_extension::<ConfidentialTransferAccount>()
+        .map_or(Ok(()), |extension| extension.closable())
+        .and(
+            account
+                .get_extension::<ConfidentialTransferFeeAmount>()
+                .map_or(Ok(()), |extension| extension.closable()),
+        )
+        .and(
+            account
+                .get_extension::<TransferFeeAmount>()
+                .map_or(Ok(()), |extension| extension.closable()),
+        )
+        .is_ok()
+}
+
+/// Property of an existing mint that differs from the one requested
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MintProperty {
+    Decimals,
+    MintAuthority,
+    FreezeAuthority,
+    Extensions,
+    /// Configuration of an extension, e.g. its authorities or fees
+    ExtensionConfig(ExtensionType),
+}
+impl fmt::Display for MintProperty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decimals => f.write_str("decimals"),
+            Self::MintAuthority => f.write_str("mint authority"),
+            Self::FreezeAuthority => f.write_str("freeze authority"),
+            Self::Extensions => f.write_str("set of extensions"),
+            Self::ExtensionConfig(extension_type) => {
+                write!(f, "configuration of the {:?} extension", extension_type)
+            }
+        }
+    }
+}
+
+/// Hash identifying a set of instructions, independent of the compute budget
+/// instructions added around them
+fn instructions_key(instructions: &[Instruction]) -> Hash {
+    let mut hasher = Hasher::default();
+    for instruction in instructions {
+        hasher.hash(instruction.program_id.as_ref());
+        for meta in &instruction.accounts {
+            hasher.hash(meta.pubkey.as_ref());
+            hasher.hash(&[meta.is_signer as u8, meta.is_writable as u8]);
+        }
+        hasher.hash(&instruction.data);
+    }
+    hasher.result()
+}
+
+/// Fee withheld from a transfer of `amount` under the given transfer fee,
+/// zero without one
+fn transfer_fee_amount(transfer_fee: Option<&TransferFee>, amount: u64) -> TokenResult<u64> {
+    match transfer_fee {
+        Some(transfer_fee) => Ok(transfer_fee
+            .calculate_fee(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?),
+        None => Ok(0),
+    }
+}
+
+/// Amount the destination receives from a transfer of `amount`
+fn post_fee_amount(transfer_fee: Option<&TransferFee>, amount: u64) -> TokenResult<u64> {
+    match transfer_fee {
+        Some(transfer_fee) => Ok(transfer_fee
+            .calculate_post_fee_amount(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?),
+        None => Ok(amount),
+    }
+}
+
+/// Amount to transfer for the destination to receive `post_fee_amount`, along
+/// with the fee withheld from it. Fails if no amount does, e.g. when the fee
+/// is 100%.
+fn pre_fee_amount(
+    transfer_fee: Option<&TransferFee>,
+    post_fee_amount: u64,
+) -> TokenResult<(u64, u64)> {
+    match transfer_fee {
+        Some(transfer_fee) => {
+            let pre_fee_amount = transfer_fee
+                .calculate_pre_fee_amount(post_fee_amount)
+                .ok_or(ProgramError::ArithmeticOverflow)?;
+            let fee = pre_fee_amount
+                .checked_sub(post_fee_amount)
+                .ok_or(ProgramError::ArithmeticOverflow)?;
+            Ok((pre_fee_amount, fee))
+        }
+        None => Ok((post_fee_amount, 0)),
+    }
+}
+
+/// Epoch of a past slot, counting back from the current epoch in epochs of
+/// its length, which holds once the cluster is past its warmup epochs
+fn epoch_at_slot(epoch_info: &EpochInfo, slot: Slot) -> Epoch {
+    let epoch_start = epoch_info.absolute_slot - epoch_info.slot_index;
+    if slot >= epoch_start {
+        return epoch_info.epoch;
+    }
+    let epochs_back = (epoch_start - slot - 1) / epoch_info.slots_in_epoch.max(1) + 1;
+    epoch_info.epoch.saturating_sub(epochs_back)
+}
+
+/// Token account state of the mint from a raw account owned by the program
+fn unpack_token_account(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    account: BaseAccount,
+) -> TokenResult<StateWithExtensionsOwned<Account>> {
+    if account.owner != *program_id {
+        return Err(TokenError::AccountInvalidOwner);
+    }
+    let account = StateWithExtensionsOwned::<Account>::unpack(account.data)?;
+    if account.base.mint != *mint {
+        return Err(TokenError::AccountInvalidMint);
+    }
+
+    Ok(account)
+}
+
+/// Marks an instruction set as in flight until dropped, so the mark is
+/// cleared even if the escalation fails early or its future is dropped
+struct InFlightGuard<'a> {
+    in_flight: &'a Mutex<HashSet<Hash>>,
+    key: Hash,
+}
+impl<'a> InFlightGuard<'a> {
+    fn acquire(in_flight: &'a Mutex<HashSet<Hash>>, key: Hash) -> Option<Self> {
+        in_flight
+            .lock()
+            .unwrap()
+            .insert(key)
+            .then_some(Self { in_flight, key })
+    }
+}
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight.lock().unwrap().remove(&self.key);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct TokenMemo {
     text: String,
     signers: Vec<Pubkey>,
 }
@@ -328,6 +812,80 @@ impl TokenMemo {
     }
 }
 
+/// Async sleep used while polling, allowing callers to plug in the timer of
+/// their own runtime
+pub type SleepFn = Arc<dyn Fn(Duration) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Default time to wait for a new blockhash
+pub const DEFAULT_BLOCKHASH_POLL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default interval between blockhash queries
+pub const DEFAULT_BLOCKHASH_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Base fee charged per transaction signature
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+/// Compute units assumed per instruction when no limit is requested
+const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+/// Maximum compute units a transaction may consume
+const MAX_COMPUTE_UNIT_LIMIT: u64 = 1_400_000;
+/// Offset of the owner in a token account: after its mint
+const ACCOUNT_OWNER_OFFSET: usize = 32;
+/// Offset of the delegate in a token account: after its mint, owner and
+/// amount
+const ACCOUNT_DELEGATE_OFFSET: usize = 72;
+/// Seed of the temporary account of a partial unwrap, created and closed in
+/// the same transaction
+const UNWRAP_SEED: &str = "unwrap";
+
+/// Lamports moved out of `payer` by system program instructions
+fn lamports_debited_from(payer: &Pubkey, instructions: &[Instruction]) -> u64 {
+    instructions
+        .iter()
+        .filter(|instruction| instruction.program_id == system_program::id())
+        .filter(|instruction| {
+            instruction
+                .accounts
+                .first()
+                .map(|meta| meta.pubkey == *payer)
+                .unwrap_or(false)
+        })
+        .filter_map(|instruction| limited_deserialize::<SystemInstruction>(&instruction.data).ok())
+        .map(|system_instruction| match system_instruction {
+            SystemInstruction::CreateAccount { lamports, .. }
+            | SystemInstruction::CreateAccountWithSeed { lamports, .. }
+            | SystemInstruction::Transfer { lamports }
+            | SystemInstruction::TransferWithSeed { lamports, .. } => lamports,
+            _ => 0,
+        })
+        .fold(0u64, |total, lamports| total.saturating_add(lamports))
+}
+
+/// Signature and priority fees charged for a message, whether or not it
+/// succeeds
+fn network_fee(
+    message: &Message,
+    additional_compute_budget: Option<u32>,
+    compute_unit_price: Option<u64>,
+) -> u64 {
+    let signature_fee =
+        LAMPORTS_PER_SIGNATURE.saturating_mul(message.header.num_required_signatures as u64);
+    let compute_unit_limit = additional_compute_budget
+        .map(u64::from)
+        .unwrap_or_else(|| {
+            DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT.saturating_mul(message.instructions.len() as u64)
+        })
+        .min(MAX_COMPUTE_UNIT_LIMIT);
+    let priority_fee = compute_unit_price
+        .unwrap_or(0)
+        .saturating_mul(compute_unit_limit)
+        .saturating_add(999_999)
+        / 1_000_000;
+    signature_fee.saturating_add(priority_fee)
+}
+
+pub(crate) fn default_sleep() -> SleepFn {
+    Arc::new(|duration| Box::pin(time::sleep(duration)))
+}
+
 pub struct Token<T> {
     client: Arc<dyn ProgramClient<T>>,
     pubkey: Pubkey, /* token mint */
@@ -339,8 +897,54 @@ pub struct Token<T> {
     nonce_blockhash: Option<Hash>,
     memo: Arc<RwLock<Option<TokenMemo>>>,
     transfer_hook_accounts: Option<Vec<AccountMeta>>,
+    compute_unit_price: Option<u64>,
+    priority_fee_policy: Option<PriorityFeePolicy>,
+    heap_frame_size: Option<u32>,
+    fee_escalation: FeeEscalation,
+    in_flight_escalations: Arc<Mutex<HashSet<Hash>>>,
+    idempotency_store: Option<Arc<dyn IdempotencyStore>>,
+    journal: Option<Arc<dyn Journal>>,
+    blockhash_poll_timeout: Duration,
+    blockhash_poll_interval: Duration,
+    sleep: SleepFn,
+    check_payer_balance: bool,
+    confirmation_service: Arc<ConfirmationService<T>>,
+    bundle_sender: Option<Arc<dyn BundleSender>>,
+    max_concurrency: usize,
+    rate_history_cache: Arc<Mutex<RateHistoryCache>>,
+    rent_cache: Arc<RentCache>,
+    guards: OperationGuards,
+    subscription_client: Option<Arc<dyn SubscriptionClient>>,
+    ata_cache: Arc<AtaCache>,
+    read_commitment: Option<CommitmentConfig>,
+    sync_native_destinations: bool,
+}
+
+/// Interval between polls of an account watched without a subscription
+/// client
+pub const WATCH_ACCOUNT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where the states of a watched account come from
+enum WatchSource {
+    Pushed(Subscription<BaseAccount>),
+    Polled {
+        address: Pubkey,
+        /// Data of the last state seen, or empty once polled without one
+        last_data: Option<Vec<u8>>,
+    },
+}
+
+/// Rate changes parsed from the mint's history, up to the newest transaction
+/// seen
+#[derive(Default)]
+struct RateHistoryCache {
+    newest_signature: Option<Signature>,
+    changes: Vec<RateChange>,
 }
 
+/// Number of signatures requested per page of an address's history
+const SIGNATURES_PAGE_SIZE: usize = 1_000;
+
 impl<T> fmt::Debug for Token<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Token")
@@ -356,6 +960,16 @@ impl<T> fmt::Debug for Token<T> {
             .field("nonce_blockhash", &self.nonce_blockhash)
             .field("memo", &self.memo.read().unwrap())
             .field("transfer_hook_accounts", &self.transfer_hook_accounts)
+            .field("compute_unit_price", &self.compute_unit_price)
+            .field("priority_fee_policy", &self.priority_fee_policy)
+            .field("heap_frame_size", &self.heap_frame_size)
+            .field("fee_escalation", &self.fee_escalation)
+            .field("guards", &self.guards)
+            .field("subscriptions", &self.subscription_client.is_some())
+            .field("read_commitment", &self.read_commitment)
+            .field("sync_native_destinations", &self.sync_native_destinations)
+            .field("blockhash_poll_timeout", &self.blockhash_poll_timeout)
+            .field("blockhash_poll_interval", &self.blockhash_poll_interval)
             .finish()
     }
 }
@@ -392,6 +1006,7 @@ where
         payer: Arc<dyn Signer>,
     ) -> Self {
         Token {
+            confirmation_service: Arc::new(ConfirmationService::new(client.clone())),
             client,
             pubkey: *address,
             decimals,
@@ -402,6 +1017,26 @@ where
             nonce_blockhash: None,
             memo: Arc::new(RwLock::new(None)),
             transfer_hook_accounts: None,
+            compute_unit_price: None,
+            priority_fee_policy: None,
+            heap_frame_size: None,
+            fee_escalation: FeeEscalation::default(),
+            in_flight_escalations: Arc::new(Mutex::new(HashSet::new())),
+            idempotency_store: None,
+            journal: None,
+            blockhash_poll_timeout: DEFAULT_BLOCKHASH_POLL_TIMEOUT,
+            blockhash_poll_interval: DEFAULT_BLOCKHASH_POLL_INTERVAL,
+            sleep: default_sleep(),
+            check_payer_balance: false,
+            bundle_sender: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            rate_history_cache: Arc::new(Mutex::new(RateHistoryCache::default())),
+            rent_cache: Arc::new(RentCache::new()),
+            guards: OperationGuards::default(),
+            subscription_client: None,
+            ata_cache: Arc::new(AtaCache::new()),
+            read_commitment: None,
+            sync_native_destinations: true,
         }
     }
 
@@ -428,6 +1063,71 @@ where
         &self.pubkey
     }
 
+    /// Schedule for `process_ixs_with_fee_escalation`, set with
+    /// `with_fee_escalation`
+    pub fn fee_escalation(&self) -> &FeeEscalation {
+        &self.fee_escalation
+    }
+
+    pub(crate) fn payer_pubkey(&self) -> Pubkey {
+        self.payer.pubkey()
+    }
+
+    pub(crate) fn client(&self) -> &Arc<dyn ProgramClient<T>> {
+        &self.client
+    }
+
+    pub(crate) fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    pub(crate) fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        (self.sleep)(duration)
+    }
+
+    /// Signatures of the transactions naming the address, newest first, a
+    /// page at a time. Stops after `until`, which is excluded, or after
+    /// `limit` signatures; callers may also stop early by dropping the stream.
+    pub(crate) fn signature_pages(
+        &self,
+        address: &Pubkey,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> BoxStream<'_, TokenResult<Vec<Signature>>> {
+        let client = self.client.clone();
+        let address = *address;
+        stream::unfold(
+            (None, limit),
+            move |(before, remaining): (Option<Signature>, usize)| {
+                let client = client.clone();
+                async move {
+                    if remaining == 0 {
+                        return None;
+                    }
+                    let page_size = SIGNATURES_PAGE_SIZE.min(remaining);
+                    match client
+                        .get_signatures_for_address(&address, before, until, page_size)
+                        .await
+                    {
+                        Ok(page) if page.is_empty() => None,
+                        Ok(page) => {
+                            // a short page is the last one
+                            let remaining = if page.len() < page_size {
+                                0
+                            } else {
+                                remaining - page.len()
+                            };
+                            let before = page.last().copied();
+                            Some((Ok(page), (before, remaining)))
+                        }
+                        Err(error) => Some((Err(TokenError::Client(error)), (before, 0))),
+                    }
+                }
+            },
+        )
+        .boxed()
+    }
+
     pub fn with_payer(mut self, payer: Arc<dyn Signer>) -> Self {
         self.payer = payer;
         self
@@ -451,6 +1151,128 @@ where
         self
     }
 
+    pub fn with_compute_unit_price(mut self, compute_unit_price: u64) -> Self {
+        self.compute_unit_price = Some(compute_unit_price);
+        self
+    }
+
+    /// Escalation schedule returned by `fee_escalation`, e.g. the one of the
+    /// cluster's profile
+    pub fn with_fee_escalation(mut self, fee_escalation: FeeEscalation) -> Self {
+        self.fee_escalation = fee_escalation;
+        self
+    }
+
+    /// Choose the compute-unit price of transactions from the fees recently
+    /// paid for the accounts they write to, unless a price is set explicitly
+    pub fn with_priority_fee_policy(mut self, priority_fee_policy: PriorityFeePolicy) -> Self {
+        self.priority_fee_policy = Some(priority_fee_policy);
+        self
+    }
+
+    /// Request a larger heap for every transaction, e.g. for transfer-hook
+    /// programs that allocate more than the default 32KiB.
+    ///
+    /// The size must be a multiple of 1024 between 32KiB and 256KiB, or the
+    /// runtime rejects the transaction.
+    pub fn with_heap_frame_size(mut self, bytes: u32) -> Self {
+        self.heap_frame_size = Some(bytes);
+        self
+    }
+
+    pub fn with_idempotency_store(mut self, idempotency_store: Arc<dyn IdempotencyStore>) -> Self {
+        self.idempotency_store = Some(idempotency_store);
+        self
+    }
+
+    pub fn with_journal(mut self, journal: Arc<dyn Journal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Configure how long `get_new_latest_blockhash` waits for a new
+    /// blockhash, and how often it polls
+    pub fn with_blockhash_polling(mut self, timeout: Duration, poll_interval: Duration) -> Self {
+        self.blockhash_poll_timeout = timeout;
+        self.blockhash_poll_interval = poll_interval;
+        self
+    }
+
+    /// Use a custom sleep implementation when polling
+    pub fn with_sleep(mut self, sleep: SleepFn) -> Self {
+        self.sleep = sleep;
+        self
+    }
+
+    /// Verify before sending that the payer can cover the lamports moved by
+    /// system instructions plus estimated fees
+    pub fn with_payer_balance_check(mut self, check_payer_balance: bool) -> Self {
+        self.check_payer_balance = check_payer_balance;
+        self
+    }
+
+    /// Share a confirmation service, e.g. between tokens using the same client
+    pub fn with_confirmation_service(
+        mut self,
+        confirmation_service: Arc<ConfirmationService<T>>,
+    ) -> Self {
+        self.confirmation_service = confirmation_service;
+        self
+    }
+
+    /// Submit instruction groups passed to `process_ix_bundle` as bundles
+    pub fn with_bundle_sender(mut self, bundle_sender: Arc<dyn BundleSender>) -> Self {
+        self.bundle_sender = Some(bundle_sender);
+        self
+    }
+
+    /// Limit how many transactions or requests of a multi-step flow, such as a
+    /// transfer with split proofs in parallel, are in flight at once
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency;
+        self
+    }
+
+    /// Share rent-exempt minimums with other tokens of the same client
+    pub fn with_rent_cache(mut self, rent_cache: Arc<RentCache>) -> Self {
+        self.rent_cache = rent_cache;
+        self
+    }
+
+    pub fn with_guards(mut self, guards: OperationGuards) -> Self {
+        self.guards = guards;
+        self
+    }
+
+    /// Share memoized associated token account addresses with other tokens
+    pub fn with_ata_cache(mut self, ata_cache: Arc<AtaCache>) -> Self {
+        self.ata_cache = ata_cache;
+        self
+    }
+
+    /// Receive account changes pushed by the node, e.g. for `watch_account`
+    pub fn with_subscription_client(
+        mut self,
+        subscription_client: Arc<dyn SubscriptionClient>,
+    ) -> Self {
+        self.subscription_client = Some(subscription_client);
+        self
+    }
+
+    /// Read accounts at the given commitment level instead of the client's
+    /// default, e.g. `finalized` before relying on a balance
+    pub fn with_read_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.read_commitment = Some(commitment);
+        self
+    }
+
+    /// Sync the destination of transfers of wrapped SOL, so lamports sent to
+    /// it elsewhere in the same flow count in its balance. On by default.
+    pub fn with_sync_native_destinations(mut self, sync_native_destinations: bool) -> Self {
+        self.sync_native_destinations = sync_native_destinations;
+        self
+    }
+
     pub fn with_memo<M: AsRef<str>>(&self, memo: M, signers: Vec<Pubkey>) -> &Self {
         let mut w_memo = self.memo.write().unwrap();
         *w_memo = Some(TokenMemo {
@@ -468,7 +1290,7 @@ where
             .map_err(TokenError::Client)?;
         let start = Instant::now();
         let mut num_retries = 0;
-        while start.elapsed().as_secs() < 5 {
+        while start.elapsed() < self.blockhash_poll_timeout {
             let new_blockhash = self
                 .client
                 .get_latest_blockhash()
@@ -478,7 +1300,7 @@ where
                 return Ok(new_blockhash);
             }
 
-            time::sleep(Duration::from_millis(200)).await;
+            (self.sleep)(self.blockhash_poll_interval).await;
             num_retries += 1;
         }
 
@@ -493,7 +1315,7 @@ where
         ))))
     }
 
-    fn get_multisig_signers<'a>(
+    pub(crate) fn get_multisig_signers<'a>(
         &self,
         authority: &Pubkey,
         signing_pubkeys: &'a [Pubkey],
@@ -505,30 +1327,90 @@ where
         }
     }
 
+    /// Memo set with `with_memo` and not sent yet, to put back with
+    /// `restore_memo` if the transaction taking it is not sent
+    pub(crate) fn pending_memo(&self) -> Option<TokenMemo> {
+        self.memo.read().unwrap().clone()
+    }
+
+    /// Put back a memo taken by a transaction that was not sent, unless
+    /// another memo was set since
+    pub(crate) fn restore_memo(&self, memo: Option<TokenMemo>) {
+        let mut w_memo = self.memo.write().unwrap();
+        if w_memo.is_none() {
+            *w_memo = memo;
+        }
+    }
+
+    /// Take the memo set with `with_memo`, if any, checking that its signers
+    /// sign the transaction
+    pub(crate) fn take_memo_instruction<S: Signers>(
+        &self,
+        signing_keypairs: &S,
+    ) -> TokenResult<Option<Instruction>> {
+        let mut w_memo = self.memo.write().unwrap();
+        let Some(memo) = w_memo.take() else {
+            return Ok(None);
+        };
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        if !memo
+            .signers
+            .iter()
+            .all(|signer| signing_pubkeys.contains(signer))
+        {
+            return Err(TokenError::MissingMemoSigner);
+        }
+        Ok(Some(memo.to_instruction()))
+    }
+
     async fn construct_tx<S: Signers>(
         &self,
         token_instructions: &[Instruction],
         additional_compute_budget: Option<u32>,
+        compute_unit_price: Option<u64>,
+        signing_keypairs: &S,
+    ) -> TokenResult<Transaction> {
+        self.construct_tx_with_blockhash(
+            token_instructions,
+            additional_compute_budget,
+            compute_unit_price,
+            None,
+            signing_keypairs,
+        )
+        .await
+    }
+
+    /// The nonce's blockhash if the token uses a durable nonce, else the
+    /// latest blockhash
+    async fn recent_blockhash(&self) -> TokenResult<Hash> {
+        match (self.nonce_account, self.nonce_blockhash) {
+            (Some(_), Some(nonce_blockhash)) if self.nonce_authority.is_some() => {
+                Ok(nonce_blockhash)
+            }
+            _ => self
+                .client
+                .get_latest_blockhash()
+                .await
+                .map_err(TokenError::Client),
+        }
+    }
+
+    /// Build a transaction like `construct_tx`, signed with the given
+    /// blockhash instead of the latest one, unless the token uses a nonce
+    async fn construct_tx_with_blockhash<S: Signers>(
+        &self,
+        token_instructions: &[Instruction],
+        additional_compute_budget: Option<u32>,
+        compute_unit_price: Option<u64>,
+        recent_blockhash: Option<Hash>,
         signing_keypairs: &S,
     ) -> TokenResult<Transaction> {
         let mut instructions = vec![];
         let payer_key = self.payer.pubkey();
         let fee_payer = Some(&payer_key);
 
-        {
-            let mut w_memo = self.memo.write().unwrap();
-            if let Some(memo) = w_memo.take() {
-                let signing_pubkeys = signing_keypairs.pubkeys();
-                if !memo
-                    .signers
-                    .iter()
-                    .all(|signer| signing_pubkeys.contains(signer))
-                {
-                    return Err(TokenError::MissingMemoSigner);
-                }
-
-                instructions.push(memo.to_instruction());
-            }
+        if let Some(memo_instruction) = self.take_memo_instruction(signing_keypairs)? {
+            instructions.push(memo_instruction);
         }
 
         instructions.extend_from_slice(token_instructions);
@@ -541,6 +1423,25 @@ where
             );
         }
 
+        if let Some(heap_frame_size) = self.heap_frame_size {
+            instructions.push(
+                solana_sdk::compute_budget::ComputeBudgetInstruction::request_heap_frame(
+                    heap_frame_size,
+                ),
+            );
+        }
+
+        let compute_unit_price = self
+            .resolve_compute_unit_price(compute_unit_price, &instructions)
+            .await?;
+        if let Some(compute_unit_price) = compute_unit_price {
+            instructions.push(
+                solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(
+                    compute_unit_price,
+                ),
+            );
+        }
+
         let (message, blockhash) =
             if let (Some(nonce_account), Some(nonce_authority), Some(nonce_blockhash)) = (
                 self.nonce_account,
@@ -548,7 +1449,7 @@ where
                 self.nonce_blockhash,
             ) {
                 let mut message = Message::new_with_nonce(
-                    token_instructions.to_vec(),
+                    instructions,
                     fee_payer,
                     &nonce_account,
                     &nonce_authority.pubkey(),
@@ -556,17 +1457,21 @@ where
                 message.recent_blockhash = nonce_blockhash;
                 (message, nonce_blockhash)
             } else {
-                let latest_blockhash = self
-                    .client
-                    .get_latest_blockhash()
-                    .await
-                    .map_err(TokenError::Client)?;
+                let latest_blockhash = match recent_blockhash {
+                    Some(recent_blockhash) => recent_blockhash,
+                    None => self.recent_blockhash().await?,
+                };
                 (
                     Message::new_with_blockhash(&instructions, fee_payer, &latest_blockhash),
                     latest_blockhash,
                 )
             };
 
+        if self.check_payer_balance {
+            self.check_payer_funds(&message, additional_compute_budget, compute_unit_price)
+                .await?;
+        }
+
         let mut transaction = Transaction::new_unsigned(message);
 
         transaction
@@ -584,18 +1489,113 @@ where
         Ok(transaction)
     }
 
-    pub async fn simulate_ixs<S: Signers>(
+    /// Compute-unit price for a transaction: the explicit one, else the
+    /// token's, else one chosen by the priority fee policy from recent fees
+    /// paid to write the instructions' accounts
+    async fn resolve_compute_unit_price(
         &self,
-        token_instructions: &[Instruction],
-        signing_keypairs: &S,
-    ) -> TokenResult<T::SimulationOutput> {
-        let transaction = self
-            .construct_tx(token_instructions, None, signing_keypairs)
-            .await?;
+        compute_unit_price: Option<u64>,
+        instructions: &[Instruction],
+    ) -> TokenResult<Option<u64>> {
+        match (
+            compute_unit_price.or(self.compute_unit_price),
+            self.priority_fee_policy,
+        ) {
+            (None, Some(priority_fee_policy)) => {
+                let recent_fees = self
+                    .client
+                    .get_recent_prioritization_fees(&fee_accounts(instructions))
+                    .await
+                    .map_err(TokenError::Client)?;
+                Ok(Some(priority_fee_policy.select(&recent_fees)))
+            }
+            (compute_unit_price, _) => Ok(compute_unit_price),
+        }
+    }
 
-        self.client
-            .simulate_transaction(&transaction)
-            .await
+    /// Estimate the lamports the payer needs for a message and compare with
+    /// its current balance
+    async fn check_payer_funds(
+        &self,
+        message: &Message,
+        additional_compute_budget: Option<u32>,
+        compute_unit_price: Option<u64>,
+    ) -> TokenResult<()> {
+        let payer = self.payer.pubkey();
+        let instructions = message
+            .instructions
+            .iter()
+            .map(|compiled| Instruction {
+                program_id: message.account_keys[compiled.program_id_index as usize],
+                accounts: compiled
+                    .accounts
+                    .iter()
+                    .map(|index| AccountMeta {
+                        pubkey: message.account_keys[*index as usize],
+                        is_signer: message.is_signer(*index as usize),
+                        is_writable: message.is_writable(*index as usize),
+                    })
+                    .collect(),
+                data: compiled.data.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let required = lamports_debited_from(&payer, &instructions).saturating_add(network_fee(
+            message,
+            additional_compute_budget,
+            compute_unit_price.or(self.compute_unit_price),
+        ));
+        let available = self
+            .client
+            .get_account(payer)
+            .await
+            .map_err(TokenError::Client)?
+            .map(|account| account.lamports)
+            .unwrap_or(0);
+
+        if available < required {
+            Err(TokenError::InsufficientPayerFunds {
+                required,
+                available,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Simulate instructions, returning the output of the backend, which exposes
+    /// logs, compute units and errors through `SimulationResult`
+    pub async fn simulate_ixs<S: Signers>(
+        &self,
+        token_instructions: &[Instruction],
+        signing_keypairs: &S,
+    ) -> TokenResult<T::SimulationOutput> {
+        let transaction = self
+            .construct_tx(token_instructions, None, None, signing_keypairs)
+            .await?;
+
+        self.client
+            .simulate_transaction(&transaction)
+            .await
+            .map_err(TokenError::Client)
+    }
+
+    /// Simulate instructions as if the given accounts had the given states,
+    /// e.g. to preview a transfer against a hypothetical balance or to an
+    /// associated account that does not exist yet
+    pub async fn simulate_ixs_with_account_overrides<S: Signers>(
+        &self,
+        token_instructions: &[Instruction],
+        overrides: &AccountOverrides,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::SimulationOutput> {
+        let transaction = self
+            .construct_tx(token_instructions, None, None, signing_keypairs)
+            .await?;
+
+        self.client
+            .simulate_transaction_with_overrides(&transaction, overrides)
+            .await
             .map_err(TokenError::Client)
     }
 
@@ -605,7 +1605,7 @@ where
         signing_keypairs: &S,
     ) -> TokenResult<T::Output> {
         let transaction = self
-            .construct_tx(token_instructions, None, signing_keypairs)
+            .construct_tx(token_instructions, None, None, signing_keypairs)
             .await?;
 
         self.client
@@ -624,6 +1624,7 @@ where
             .construct_tx(
                 token_instructions,
                 Some(additional_compute_budget),
+                None,
                 signing_keypairs,
             )
             .await?;
@@ -634,6 +1635,318 @@ where
             .map_err(TokenError::Client)
     }
 
+    /// Send groups of instructions packed into as few transactions as fit,
+    /// at most `max_concurrency` at a time. Each group lands whole in one
+    /// transaction, and the outcome tells which transaction carried it.
+    ///
+    /// ```
+    /// use {
+    ///     solana_sdk::{pubkey::Pubkey, signature::{Keypair, Signer}},
+    ///     spl_token_client::{
+    ///         client::ProgramRpcClientSendTransaction, mock::MockProgramClient, token::Token,
+    ///     },
+    ///     std::sync::Arc,
+    /// };
+    ///
+    /// let program_id = spl_token_2022::id();
+    /// let mint = Pubkey::new_unique();
+    /// let mint_authority = Keypair::new();
+    /// let client = Arc::new(MockProgramClient::<ProgramRpcClientSendTransaction>::new());
+    /// let token = Token::new(client.clone(), &program_id, &mint, None, Arc::new(Keypair::new()));
+    ///
+    /// // airdrop to 50 recipients, one mint each
+    /// let groups = (0..50)
+    ///     .map(|_| {
+    ///         let recipient = Pubkey::new_unique();
+    ///         let mint_authority = mint_authority.pubkey();
+    ///         spl_token_2022::instruction::mint_to(&program_id, &mint, &recipient, &mint_authority, &[], 1)
+    ///             .map(|instruction| vec![instruction])
+    ///     })
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// let outcome =
+    ///     futures::executor::block_on(token.process_ix_batches(&groups, &[&mint_authority]));
+    /// assert!(outcome.is_success());
+    ///
+    /// let sent = client.sent_transactions();
+    /// assert!(sent.len() > 1 && sent.len() < groups.len());
+    /// let instructions = sent.iter().map(|transaction| transaction.message.instructions.len());
+    /// assert_eq!(instructions.sum::<usize>(), groups.len());
+    /// ```
+    pub async fn process_ix_batches<S: Signers>(
+        &self,
+        groups: &[Vec<Instruction>],
+        signing_keypairs: &S,
+    ) -> BatchOutcome<T::Output> {
+        let runs = batch::pack(groups, &self.payer.pubkey());
+        let transactions = stream::iter(runs.iter().map(|run| {
+            let instructions = groups[run.clone()].concat();
+            async move { self.process_ixs(&instructions, signing_keypairs).await }
+        }))
+        .buffered(self.max_concurrency.max(1))
+        .collect()
+        .await;
+        BatchOutcome::new(transactions, &runs)
+    }
+
+    /// Send instructions and wait for the transaction to be confirmed through
+    /// the confirmation service, returning its signature.
+    ///
+    /// Combined with a client that does not confirm on send, such as
+    /// `ProgramRpcClientSendTransactionUnconfirmed`, many of these can be in
+    /// flight at once while sharing a single status query per poll.
+    pub async fn process_ixs_confirmed<S: Signers>(
+        &self,
+        token_instructions: &[Instruction],
+        signing_keypairs: &S,
+    ) -> TokenResult<Signature> {
+        let transaction = self
+            .construct_tx(token_instructions, None, None, signing_keypairs)
+            .await?;
+        let signature = transaction.signatures[0];
+
+        self.client
+            .send_transaction(&transaction)
+            .await
+            .map_err(TokenError::Client)?;
+
+        self.confirm_transaction(signature).await?;
+        Ok(signature)
+    }
+
+    async fn confirm_transaction(&self, signature: Signature) -> TokenResult<()> {
+        let status = self
+            .confirmation_service
+            .confirm(signature)
+            .await
+            .map_err(TokenError::Client)?;
+        match status.err {
+            Some(err) => Err(TokenError::Client(Box::new(err))),
+            None => Ok(()),
+        }
+    }
+
+    /// Send each group of instructions as its own transaction, all landing in
+    /// the same block through the bundle sender, and wait for them to be
+    /// confirmed. The sender's tip, if any, is paid by a separate transaction
+    /// at the end of the bundle.
+    ///
+    /// Without a bundle sender, with too many groups, or if the endpoint
+    /// rejects the bundle, the same signed transactions are instead sent and
+    /// confirmed one by one in order, without the tip, so a bundle that lands
+    /// regardless cannot be executed twice.
+    pub async fn process_ix_bundle<S: Signers>(
+        &self,
+        groups: &[(&[Instruction], &S)],
+    ) -> TokenResult<Vec<Signature>> {
+        let mut transactions = Vec::with_capacity(groups.len());
+        for (instructions, signing_keypairs) in groups.iter() {
+            transactions.push(
+                self.construct_tx(instructions, None, None, *signing_keypairs)
+                    .await?,
+            );
+        }
+        let signatures = transactions
+            .iter()
+            .map(|transaction| transaction.signatures[0])
+            .collect::<Vec<_>>();
+
+        if let Some(bundle_sender) = &self.bundle_sender {
+            let tip_instruction = bundle_sender.tip_instruction(&self.payer.pubkey());
+            if transactions.len() + usize::from(tip_instruction.is_some())
+                <= MAX_BUNDLE_TRANSACTIONS
+            {
+                let mut bundle = transactions.clone();
+                if let Some(tip_instruction) = tip_instruction {
+                    bundle.push(
+                        self.construct_tx::<[&dyn Signer; 0]>(&[tip_instruction], None, None, &[])
+                            .await?,
+                    );
+                }
+                if bundle_sender.send_bundle(&bundle).await.is_ok() {
+                    try_join_all(
+                        signatures
+                            .iter()
+                            .map(|signature| self.confirm_transaction(*signature)),
+                    )
+                    .await?;
+                    return Ok(signatures);
+                }
+            }
+        }
+
+        for transaction in transactions.iter() {
+            self.client
+                .send_transaction(transaction)
+                .await
+                .map_err(TokenError::Client)?;
+            self.confirm_transaction(transaction.signatures[0]).await?;
+        }
+        Ok(signatures)
+    }
+
+    /// Send instructions at most once for the given idempotency key.
+    ///
+    /// The signature of the transaction is recorded in the configured
+    /// idempotency store before it is sent, so a retry after a timeout returns
+    /// `TokenError::DuplicateOperation` with that signature instead of
+    /// submitting the operation a second time. The key is kept even if
+    /// sending fails, since the transaction may still land; callers that have
+    /// confirmed it did not should remove the key from the store to retry.
+    pub async fn process_ixs_idempotent<S: Signers>(
+        &self,
+        idempotency_key: &str,
+        token_instructions: &[Instruction],
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let store = self
+            .idempotency_store
+            .as_ref()
+            .ok_or(TokenError::MissingIdempotencyStore)?;
+
+        if !store
+            .reserve(idempotency_key)
+            .map_err(TokenError::Persistence)?
+        {
+            let signature = match store
+                .get(idempotency_key)
+                .map_err(TokenError::Persistence)?
+            {
+                Some(IdempotencyRecord::Submitted(signature)) => Some(signature),
+                _ => None,
+            };
+            return Err(TokenError::DuplicateOperation(signature));
+        }
+
+        let transaction = match self
+            .construct_tx(token_instructions, None, None, signing_keypairs)
+            .await
+        {
+            Ok(transaction) => transaction,
+            Err(error) => {
+                // nothing was signed, so the operation can safely be retried
+                store
+                    .remove(idempotency_key)
+                    .map_err(TokenError::Persistence)?;
+                return Err(error);
+            }
+        };
+        store
+            .record(idempotency_key, transaction.signatures[0])
+            .map_err(TokenError::Persistence)?;
+
+        self.client
+            .send_transaction(&transaction)
+            .await
+            .map_err(TokenError::Client)
+    }
+
+    /// Send instructions, resubmitting the transaction with the next
+    /// compute-unit price in `escalation` every time an attempt is not
+    /// confirmed within the configured timeout, and return the signature of
+    /// the attempt that landed. The token's own schedule is
+    /// `self.fee_escalation()`.
+    ///
+    /// The token must use a durable nonce: every attempt is signed with it,
+    /// so at most one of them can ever land, whereas attempts on a live
+    /// blockhash could all execute. Before escalating, the earlier attempts
+    /// are checked, and one that has landed in the meantime is waited for
+    /// instead of sending another. Attempts are timed with the token's sleep.
+    ///
+    /// Only one escalation per instruction set may be in flight on this token
+    /// at a time, so a caller retrying the same operation concurrently gets
+    /// `TokenError::OperationInFlight` instead of a second submission.
+    pub async fn process_ixs_with_fee_escalation<S: Signers>(
+        &self,
+        token_instructions: &[Instruction],
+        escalation: &FeeEscalation,
+        signing_keypairs: &S,
+    ) -> TokenResult<Signature> {
+        if self.nonce_account.is_none() {
+            return Err(TokenError::MissingNonce);
+        }
+        let _in_flight = InFlightGuard::acquire(
+            &self.in_flight_escalations,
+            instructions_key(token_instructions),
+        )
+        .ok_or(TokenError::OperationInFlight)?;
+
+        // the memo goes into every attempt, not only the first one
+        let mut instructions = vec![];
+        if let Some(memo_instruction) = self.take_memo_instruction(signing_keypairs)? {
+            instructions.push(memo_instruction);
+        }
+        instructions.extend_from_slice(token_instructions);
+        let blockhash = self.recent_blockhash().await?;
+
+        let mut signatures = vec![];
+        for compute_unit_price in escalation.compute_unit_prices.iter() {
+            if let Some(signature) = self.landed_attempt(&signatures).await? {
+                return Ok(signature);
+            }
+
+            let transaction = self
+                .construct_tx_with_blockhash(
+                    &instructions,
+                    None,
+                    Some(*compute_unit_price),
+                    Some(blockhash),
+                    signing_keypairs,
+                )
+                .await?;
+            let signature = transaction.signatures[0];
+            signatures.push(signature);
+
+            let attempt = async {
+                self.client
+                    .send_transaction(&transaction)
+                    .await
+                    .map_err(TokenError::Client)?;
+                self.confirm_transaction(signature).await
+            };
+            futures::pin_mut!(attempt);
+            let timeout = self.sleep(escalation.confirmation_timeout);
+            if let Either::Left((result, _)) = future::select(attempt, timeout).await {
+                return result.map(|_| signature);
+            }
+        }
+
+        // the last attempt may have landed after its timeout
+        match self.landed_attempt(&signatures).await? {
+            Some(signature) => Ok(signature),
+            None => Err(TokenError::ConfirmationTimeout(
+                escalation.compute_unit_prices.len(),
+            )),
+        }
+    }
+
+    /// The first of the given attempts the cluster has seen, once it is
+    /// confirmed, failing if it failed
+    async fn landed_attempt(&self, signatures: &[Signature]) -> TokenResult<Option<Signature>> {
+        if signatures.is_empty() {
+            return Ok(None);
+        }
+        let statuses = self
+            .client
+            .get_signature_statuses(signatures)
+            .await
+            .map_err(TokenError::Client)?;
+        let Some((signature, status)) = signatures
+            .iter()
+            .zip(statuses)
+            .find_map(|(signature, status)| status.map(|status| (*signature, status)))
+        else {
+            return Ok(None);
+        };
+        if let Some(err) = status.err {
+            return Err(TokenError::Client(Box::new(err)));
+        }
+        if !status.confirmed {
+            self.confirm_transaction(signature).await?;
+        }
+        Ok(Some(signature))
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn create_mint<'a, S: Signers>(
         &self,
@@ -653,10 +1966,7 @@ where
         let mut instructions = vec![system_instruction::create_account(
             &self.payer.pubkey(),
             &self.pubkey,
-            self.client
-                .get_minimum_balance_for_rent_exemption(space)
-                .await
-                .map_err(TokenError::Client)?,
+            self.get_minimum_balance_for_rent_exemption(space).await?,
             space as u64,
             &self.program_id,
         )];
@@ -676,76 +1986,465 @@ where
         self.process_ixs(&instructions, signing_keypairs).await
     }
 
-    /// Create native mint
-    pub async fn create_native_mint(
-        client: Arc<dyn ProgramClient<T>>,
-        program_id: &Pubkey,
-        payer: Arc<dyn Signer>,
-    ) -> TokenResult<Self> {
-        let token = Self::new_native(client, program_id, payer);
-        token
-            .process_ixs::<[&dyn Signer; 0]>(
-                &[instruction::create_native_mint(
-                    program_id,
-                    &token.payer.pubkey(),
-                )?],
-                &[],
-            )
-            .await?;
+    /// Create the mint unless it already exists, e.g. so deployment scripts
+    /// can be run again. An existing mint must match the request, down to
+    /// the configuration of each requested extension, or a `MintMismatch`
+    /// error tells what differs. Extensions the mint grows after its
+    /// creation, like token metadata, are not compared.
+    /// Returns `None` if the mint already existed.
+    pub async fn create_mint_idempotent<'a, S: Signers>(
+        &self,
+        mint_authority: &'a Pubkey,
+        freeze_authority: Option<&'a Pubkey>,
+        extension_initialization_params: Vec<ExtensionInitializationParams>,
+        signing_keypairs: &S,
+    ) -> TokenResult<Option<T::Output>> {
+        let mint = match self.get_mint_info().await {
+            Ok(mint) => mint,
+            Err(TokenError::AccountNotFound) => {
+                return self
+                    .create_mint(
+                        mint_authority,
+                        freeze_authority,
+                        extension_initialization_params,
+                        signing_keypairs,
+                    )
+                    .await
+                    .map(Some);
+            }
+            Err(error) => return Err(error),
+        };
 
-        Ok(token)
+        if self
+            .decimals
+            .map_or(false, |decimals| decimals != mint.base.decimals)
+        {
+            return Err(TokenError::MintMismatch(MintProperty::Decimals));
+        }
+        if Option::<Pubkey>::from(mint.base.mint_authority) != Some(*mint_authority) {
+            return Err(TokenError::MintMismatch(MintProperty::MintAuthority));
+        }
+        if Option::<Pubkey>::from(mint.base.freeze_authority) != freeze_authority.copied() {
+            return Err(TokenError::MintMismatch(MintProperty::FreezeAuthority));
+        }
+        let mut extension_types = mint.get_extension_types()?;
+        // the mint grows these after its creation
+        extension_types.retain(|extension_type| {
+            !matches!(
+                extension_type,
+                ExtensionType::TokenMetadata
+                    | ExtensionType::TokenGroup
+                    | ExtensionType::TokenGroupMember
+            )
+        });
+        let mut requested_types = extension_initialization_params
+            .iter()
+            .map(|e| e.extension())
+            .collect::<Vec<_>>();
+        extension_types.sort_by_key(|extension_type| u16::from(*extension_type));
+        requested_types.sort_by_key(|extension_type| u16::from(*extension_type));
+        if extension_types != requested_types {
+            return Err(TokenError::MintMismatch(MintProperty::Extensions));
+        }
+        if let Some(params) = extension_initialization_params
+            .iter()
+            .find(|params| !params.matches(&mint))
+        {
+            return Err(TokenError::MintMismatch(MintProperty::ExtensionConfig(
+                params.extension(),
+            )));
+        }
+        Ok(None)
     }
 
-    /// Create multisig
-    pub async fn create_multisig(
+    /// Create the mint at an address derived from a base key and a seed,
+    /// e.g. to find it again without keeping its keypair. The token's address
+    /// must be the derived one, see `get_address_with_seed`, and the base
+    /// must sign.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_mint_with_seed<'a, S: Signers>(
         &self,
-        account: &dyn Signer,
-        multisig_members: &[&Pubkey],
-        minimum_signers: u8,
+        base: &Pubkey,
+        seed: &str,
+        mint_authority: &'a Pubkey,
+        freeze_authority: Option<&'a Pubkey>,
+        extension_initialization_params: Vec<ExtensionInitializationParams>,
+        signing_keypairs: &S,
     ) -> TokenResult<T::Output> {
-        let instructions = vec![
-            system_instruction::create_account(
-                &self.payer.pubkey(),
-                &account.pubkey(),
-                self.client
-                    .get_minimum_balance_for_rent_exemption(Multisig::LEN)
-                    .await
-                    .map_err(TokenError::Client)?,
-                Multisig::LEN as u64,
-                &self.program_id,
-            ),
-            instruction::initialize_multisig(
-                &self.program_id,
-                &account.pubkey(),
-                multisig_members,
-                minimum_signers,
-            )?,
-        ];
+        let decimals = self.decimals.ok_or(TokenError::MissingDecimals)?;
+        if self.get_address_with_seed(base, seed)? != self.pubkey {
+            return Err(TokenError::AccountInvalidMint);
+        }
 
-        self.process_ixs(&instructions, &[account]).await
-    }
+        let extension_types = extension_initialization_params
+            .iter()
+            .map(|e| e.extension())
+            .collect::<Vec<_>>();
+        let space = ExtensionType::try_calculate_account_len::<Mint>(&extension_types)?;
 
-    /// Get the address for the associated token account.
-    pub fn get_associated_token_address(&self, owner: &Pubkey) -> Pubkey {
-        get_associated_token_address_with_program_id(owner, &self.pubkey, &self.program_id)
+        let mut instructions = vec![system_instruction::create_account_with_seed(
+            &self.payer.pubkey(),
+            &self.pubkey,
+            base,
+            seed,
+            self.get_minimum_balance_for_rent_exemption(space).await?,
+            space as u64,
+            &self.program_id,
+        )];
+
+        for params in extension_initialization_params {
+            instructions.push(params.instruction(&self.program_id, &self.pubkey)?);
+        }
+
+        instructions.push(instruction::initialize_mint(
+            &self.program_id,
+            &self.pubkey,
+            mint_authority,
+            freeze_authority,
+            decimals,
+        )?);
+
+        self.process_ixs(&instructions, signing_keypairs).await
     }
 
-    /// Create and initialize the associated account.
-    pub async fn create_associated_token_account(&self, owner: &Pubkey) -> TokenResult<T::Output> {
-        self.process_ixs::<[&dyn Signer; 0]>(
-            &[create_associated_token_account(
-                &self.payer.pubkey(),
-                owner,
-                &self.pubkey,
-                &self.program_id,
-            )],
-            &[],
-        )
-        .await
+    /// Address of an account of the token program created with a seed from a
+    /// base key
+    pub fn get_address_with_seed(&self, base: &Pubkey, seed: &str) -> TokenResult<Pubkey> {
+        Pubkey::create_with_seed(base, seed, &self.program_id)
+            .map_err(|error| TokenError::Client(error.into()))
     }
 
-    /// Create and initialize a new token account.
-    pub async fn create_auxiliary_token_account(
+    /// Create a mint holding its own token metadata, in one transaction: the
+    /// metadata pointer designates the mint, the account is funded for the
+    /// metadata it grows by, and the metadata is initialized along with any
+    /// additional fields. Any metadata pointer in the extensions is replaced.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_mint_with_metadata<'a, S: Signers>(
+        &self,
+        mint_authority: &'a Pubkey,
+        freeze_authority: Option<&'a Pubkey>,
+        update_authority: &Pubkey,
+        name: String,
+        symbol: String,
+        uri: String,
+        additional_metadata: Vec<(String, String)>,
+        extension_initialization_params: Vec<ExtensionInitializationParams>,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let decimals = self.decimals.ok_or(TokenError::MissingDecimals)?;
+
+        let mut extension_initialization_params = extension_initialization_params
+            .into_iter()
+            .filter(|params| params.extension() != ExtensionType::MetadataPointer)
+            .collect::<Vec<_>>();
+        extension_initialization_params.push(ExtensionInitializationParams::MetadataPointer {
+            authority: Some(*update_authority),
+            metadata_address: Some(self.pubkey),
+        });
+        let extension_types = extension_initialization_params
+            .iter()
+            .map(|e| e.extension())
+            .collect::<Vec<_>>();
+        let space = ExtensionType::try_calculate_account_len::<Mint>(&extension_types)?;
+
+        let token_metadata = TokenMetadata {
+            update_authority: Some(*update_authority).try_into()?,
+            mint: self.pubkey,
+            name,
+            symbol,
+            uri,
+            additional_metadata,
+        };
+        // the metadata is reallocated into the account, which must already
+        // hold the rent for it
+        let metadata_space = token_metadata.tlv_size_of()?;
+
+        let mut instructions = vec![system_instruction::create_account(
+            &self.payer.pubkey(),
+            &self.pubkey,
+            self.get_minimum_balance_for_rent_exemption(space + metadata_space)
+                .await?,
+            space as u64,
+            &self.program_id,
+        )];
+        for params in extension_initialization_params {
+            instructions.push(params.instruction(&self.program_id, &self.pubkey)?);
+        }
+        instructions.push(instruction::initialize_mint(
+            &self.program_id,
+            &self.pubkey,
+            mint_authority,
+            freeze_authority,
+            decimals,
+        )?);
+        instructions.push(spl_token_metadata_interface::instruction::initialize(
+            &self.program_id,
+            &self.pubkey,
+            update_authority,
+            &self.pubkey,
+            mint_authority,
+            token_metadata.name,
+            token_metadata.symbol,
+            token_metadata.uri,
+        ));
+        for (key, value) in token_metadata.additional_metadata {
+            instructions.push(spl_token_metadata_interface::instruction::update_field(
+                &self.program_id,
+                &self.pubkey,
+                update_authority,
+                Field::Key(key),
+                value,
+            ));
+        }
+
+        self.process_ixs(&instructions, signing_keypairs).await
+    }
+
+    /// Create a mint that is its own token group, in one transaction: the
+    /// group pointer designates the mint, the account is funded for the
+    /// group it grows by, and the group is initialized. Any group pointer in
+    /// the extensions is replaced.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_group_mint<'a, S: Signers>(
+        &self,
+        mint_authority: &'a Pubkey,
+        freeze_authority: Option<&'a Pubkey>,
+        update_authority: &Pubkey,
+        max_size: u32,
+        extension_initialization_params: Vec<ExtensionInitializationParams>,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let pointer = ExtensionInitializationParams::GroupPointer {
+            authority: Some(*update_authority),
+            group_address: Some(self.pubkey),
+        };
+        let group_instruction = spl_token_group_interface::instruction::initialize_group(
+            &self.program_id,
+            &self.pubkey,
+            &self.pubkey,
+            mint_authority,
+            Some(*update_authority),
+            max_size,
+        );
+        self.create_mint_with_self_pointer(
+            mint_authority,
+            freeze_authority,
+            extension_initialization_params,
+            pointer,
+            ExtensionType::TokenGroup,
+            group_instruction,
+            signing_keypairs,
+        )
+        .await
+    }
+
+    /// Create a mint that is its own member of a token group, in one
+    /// transaction: the group member pointer designates the mint, the account
+    /// is funded for the membership it grows by, and the member is
+    /// initialized, signed by the group's update authority. Any group member
+    /// pointer in the extensions is replaced.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_member_mint<'a, S: Signers>(
+        &self,
+        mint_authority: &'a Pubkey,
+        freeze_authority: Option<&'a Pubkey>,
+        group: &Pubkey,
+        group_update_authority: &Pubkey,
+        extension_initialization_params: Vec<ExtensionInitializationParams>,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let pointer = ExtensionInitializationParams::GroupMemberPointer {
+            authority: Some(*mint_authority),
+            member_address: Some(self.pubkey),
+        };
+        let member_instruction = spl_token_group_interface::instruction::initialize_member(
+            &self.program_id,
+            &self.pubkey,
+            &self.pubkey,
+            mint_authority,
+            group,
+            group_update_authority,
+        );
+        self.create_mint_with_self_pointer(
+            mint_authority,
+            freeze_authority,
+            extension_initialization_params,
+            pointer,
+            ExtensionType::TokenGroupMember,
+            member_instruction,
+            signing_keypairs,
+        )
+        .await
+    }
+
+    /// Create a mint with a pointer to itself, funded for the fixed-length
+    /// extension that the final instruction adds to it
+    #[allow(clippy::too_many_arguments)]
+    async fn create_mint_with_self_pointer<'a, S: Signers>(
+        &self,
+        mint_authority: &'a Pubkey,
+        freeze_authority: Option<&'a Pubkey>,
+        extension_initialization_params: Vec<ExtensionInitializationParams>,
+        pointer: ExtensionInitializationParams,
+        pointed_extension: ExtensionType,
+        pointed_instruction: Instruction,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let decimals = self.decimals.ok_or(TokenError::MissingDecimals)?;
+
+        let mut extension_initialization_params = extension_initialization_params
+            .into_iter()
+            .filter(|params| params.extension() != pointer.extension())
+            .collect::<Vec<_>>();
+        extension_initialization_params.push(pointer);
+        let mut extension_types = extension_initialization_params
+            .iter()
+            .map(|e| e.extension())
+            .collect::<Vec<_>>();
+        let space = ExtensionType::try_calculate_account_len::<Mint>(&extension_types)?;
+        // the pointed extension is reallocated into the account, which must
+        // already hold the rent for it
+        extension_types.push(pointed_extension);
+        let funded_space = ExtensionType::try_calculate_account_len::<Mint>(&extension_types)?;
+
+        let mut instructions = vec![system_instruction::create_account(
+            &self.payer.pubkey(),
+            &self.pubkey,
+            self.get_minimum_balance_for_rent_exemption(funded_space)
+                .await?,
+            space as u64,
+            &self.program_id,
+        )];
+        for params in extension_initialization_params {
+            instructions.push(params.instruction(&self.program_id, &self.pubkey)?);
+        }
+        instructions.push(instruction::initialize_mint(
+            &self.program_id,
+            &self.pubkey,
+            mint_authority,
+            freeze_authority,
+            decimals,
+        )?);
+        instructions.push(pointed_instruction);
+
+        self.process_ixs(&instructions, signing_keypairs).await
+    }
+
+    /// Create native mint
+    pub async fn create_native_mint(
+        client: Arc<dyn ProgramClient<T>>,
+        program_id: &Pubkey,
+        payer: Arc<dyn Signer>,
+    ) -> TokenResult<Self> {
+        let token = Self::new_native(client, program_id, payer);
+        token
+            .process_ixs::<[&dyn Signer; 0]>(
+                &[instruction::create_native_mint(
+                    program_id,
+                    &token.payer.pubkey(),
+                )?],
+                &[],
+            )
+            .await?;
+
+        Ok(token)
+    }
+
+    /// Create the native mint unless it already exists, in which case nothing
+    /// is sent
+    pub async fn create_native_mint_idempotent(
+        client: Arc<dyn ProgramClient<T>>,
+        program_id: &Pubkey,
+        payer: Arc<dyn Signer>,
+    ) -> TokenResult<Self> {
+        let token = Self::new_native(client, program_id, payer);
+        match token.get_mint_info().await {
+            Ok(_) => return Ok(token),
+            Err(TokenError::AccountNotFound) => {}
+            Err(error) => return Err(error),
+        }
+        token
+            .process_ixs::<[&dyn Signer; 0]>(
+                &[instruction::create_native_mint(
+                    program_id,
+                    &token.payer.pubkey(),
+                )?],
+                &[],
+            )
+            .await?;
+
+        Ok(token)
+    }
+
+    /// Create multisig
+    pub async fn create_multisig(
+        &self,
+        account: &dyn Signer,
+        multisig_members: &[&Pubkey],
+        minimum_signers: u8,
+    ) -> TokenResult<T::Output> {
+        let instructions = vec![
+            system_instruction::create_account(
+                &self.payer.pubkey(),
+                &account.pubkey(),
+                self.get_minimum_balance_for_rent_exemption(Multisig::LEN)
+                    .await?,
+                Multisig::LEN as u64,
+                &self.program_id,
+            ),
+            instruction::initialize_multisig(
+                &self.program_id,
+                &account.pubkey(),
+                multisig_members,
+                minimum_signers,
+            )?,
+        ];
+
+        self.process_ixs(&instructions, &[account]).await
+    }
+
+    /// Get the address for the associated token account.
+    pub fn get_associated_token_address(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.pubkey, &self.program_id)
+    }
+
+    /// Get the address for the associated token account, rejecting owners off
+    /// the curve, e.g. program derived addresses, unless allowed
+    pub fn get_associated_token_address_checked(
+        &self,
+        owner: &Pubkey,
+        allow_owner_off_curve: bool,
+    ) -> TokenResult<Pubkey> {
+        if !allow_owner_off_curve && !owner.is_on_curve() {
+            return Err(TokenError::OwnerOffCurve(*owner));
+        }
+        Ok(self.get_associated_token_address(owner))
+    }
+
+    /// Get the addresses of the associated accounts of many owners, in order,
+    /// remembering them for later batches
+    pub fn derive_atas(&self, owners: &[Pubkey]) -> Vec<Pubkey> {
+        self.ata_cache
+            .derive(owners, &self.pubkey, &self.program_id)
+    }
+
+    /// Create and initialize the associated account.
+    pub async fn create_associated_token_account(&self, owner: &Pubkey) -> TokenResult<T::Output> {
+        self.get_associated_token_address_checked(owner, !self.guards.reject_owners_off_curve)?;
+        self.process_ixs::<[&dyn Signer; 0]>(
+            &[create_associated_token_account(
+                &self.payer.pubkey(),
+                owner,
+                &self.pubkey,
+                &self.program_id,
+            )],
+            &[],
+        )
+        .await
+    }
+
+    /// Create and initialize a new token account.
+    pub async fn create_auxiliary_token_account(
         &self,
         account: &dyn Signer,
         owner: &Pubkey,
@@ -761,235 +2460,1731 @@ where
         owner: &Pubkey,
         extensions: Vec<ExtensionType>,
     ) -> TokenResult<T::Output> {
-        let state = self.get_mint_info().await?;
-        let mint_extensions: Vec<ExtensionType> = state.get_extension_types()?;
-        let mut required_extensions =
-            ExtensionType::get_required_init_account_extensions(&mint_extensions);
-        for extension_type in extensions.into_iter() {
-            if !required_extensions.contains(&extension_type) {
-                required_extensions.push(extension_type);
-            }
-        }
+        let required_extensions = self.required_account_extensions(extensions).await?;
         let space = ExtensionType::try_calculate_account_len::<Account>(&required_extensions)?;
         let mut instructions = vec![system_instruction::create_account(
             &self.payer.pubkey(),
             &account.pubkey(),
-            self.client
-                .get_minimum_balance_for_rent_exemption(space)
-                .await
-                .map_err(TokenError::Client)?,
+            self.get_minimum_balance_for_rent_exemption(space).await?,
             space as u64,
             &self.program_id,
         )];
-
-        if required_extensions.contains(&ExtensionType::ImmutableOwner) {
-            instructions.push(instruction::initialize_immutable_owner(
-                &self.program_id,
-                &account.pubkey(),
-            )?)
-        }
-
-        instructions.push(instruction::initialize_account(
-            &self.program_id,
+        instructions.extend(self.initialize_account_instructions(
             &account.pubkey(),
-            &self.pubkey,
             owner,
+            &required_extensions,
         )?);
 
         self.process_ixs(&instructions, &[account]).await
     }
 
-    /// Retrieve a raw account
-    pub async fn get_account(&self, account: Pubkey) -> TokenResult<BaseAccount> {
-        self.client
-            .get_account(account)
-            .await
-            .map_err(TokenError::Client)?
-            .ok_or(TokenError::AccountNotFound)
+    /// Create and initialize a new token account at an address derived from
+    /// a base key and a seed, see `get_address_with_seed`, e.g. so a service
+    /// can find its accounts again without keeping their keypairs. The base
+    /// must sign.
+    pub async fn create_auxiliary_token_account_with_seed<S: Signers>(
+        &self,
+        base: &Pubkey,
+        seed: &str,
+        owner: &Pubkey,
+        extensions: Vec<ExtensionType>,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let account = self.get_address_with_seed(base, seed)?;
+        let required_extensions = self.required_account_extensions(extensions).await?;
+        let space = ExtensionType::try_calculate_account_len::<Account>(&required_extensions)?;
+        let mut instructions = vec![system_instruction::create_account_with_seed(
+            &self.payer.pubkey(),
+            &account,
+            base,
+            seed,
+            self.get_minimum_balance_for_rent_exemption(space).await?,
+            space as u64,
+            &self.program_id,
+        )];
+        instructions.extend(self.initialize_account_instructions(
+            &account,
+            owner,
+            &required_extensions,
+        )?);
+
+        self.process_ixs(&instructions, signing_keypairs).await
+    }
+
+    /// Extensions of a new account of the mint: those the mint requires, and
+    /// the requested ones
+    async fn required_account_extensions(
+        &self,
+        extensions: Vec<ExtensionType>,
+    ) -> TokenResult<Vec<ExtensionType>> {
+        let state = self.get_mint_info().await?;
+        let mint_extensions: Vec<ExtensionType> = state.get_extension_types()?;
+        let mut required_extensions =
+            ExtensionType::get_required_init_account_extensions(&mint_extensions);
+        for extension_type in extensions.into_iter() {
+            if !required_extensions.contains(&extension_type) {
+                required_extensions.push(extension_type);
+            }
+        }
+        Ok(required_extensions)
+    }
+
+    fn initialize_account_instructions(
+        &self,
+        account: &Pubkey,
+        owner: &Pubkey,
+        extensions: &[ExtensionType],
+    ) -> TokenResult<Vec<Instruction>> {
+        let mut instructions = vec![];
+        if extensions.contains(&ExtensionType::ImmutableOwner) {
+            instructions.push(instruction::initialize_immutable_owner(
+                &self.program_id,
+                account,
+            )?)
+        }
+
+        instructions.push(instruction::initialize_account(
+            &self.program_id,
+            account,
+            &self.pubkey,
+            owner,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Retrieve a raw account, at the read commitment if one is configured
+    pub async fn get_account(&self, account: Pubkey) -> TokenResult<BaseAccount> {
+        if let Some(commitment) = self.read_commitment {
+            return self.get_account_with_commitment(account, commitment).await;
+        }
+        self.client
+            .get_account(account)
+            .await
+            .map_err(TokenError::Client)?
+            .ok_or(TokenError::AccountNotFound)
+    }
+
+    /// Retrieve a raw account at the given commitment level
+    pub async fn get_account_with_commitment(
+        &self,
+        account: Pubkey,
+        commitment: CommitmentConfig,
+    ) -> TokenResult<BaseAccount> {
+        self.client
+            .get_account_with_commitment(account, commitment)
+            .await
+            .map_err(TokenError::Client)?
+            .ok_or(TokenError::AccountNotFound)
+    }
+
+    /// Get any account, observing at least the given consistency
+    pub async fn get_account_with_consistency(
+        &self,
+        account: Pubkey,
+        consistency: ReadConsistency,
+    ) -> TokenResult<BaseAccount> {
+        self.client
+            .get_account_with_consistency(account, consistency)
+            .await
+            .map_err(TokenError::Client)?
+            .ok_or(TokenError::AccountNotFound)
+    }
+
+    fn unpack_mint_info(
+        &self,
+        account: BaseAccount,
+    ) -> TokenResult<StateWithExtensionsOwned<Mint>> {
+        if account.owner != self.program_id {
+            return Err(TokenError::AccountInvalidOwner);
+        }
+
+        let mint_result =
+            StateWithExtensionsOwned::<Mint>::unpack(account.data).map_err(Into::into);
+
+        if let (Ok(mint), Some(decimals)) = (&mint_result, self.decimals) {
+            if decimals != mint.base.decimals {
+                return Err(TokenError::InvalidDecimals);
+            }
+        }
+
+        mint_result
+    }
+
+    /// Rent-exempt minimum for an account of the given size, cached until
+    /// rent may have changed
+    pub async fn get_minimum_balance_for_rent_exemption(&self, space: usize) -> TokenResult<u64> {
+        if let Some(lamports) = self.rent_cache.get(space) {
+            return Ok(lamports);
+        }
+        let lamports = self
+            .client
+            .get_minimum_balance_for_rent_exemption(space)
+            .await
+            .map_err(TokenError::Client)?;
+        self.rent_cache.insert(space, lamports);
+        Ok(lamports)
+    }
+
+    /// Retrive mint information.
+    pub async fn get_mint_info(&self) -> TokenResult<StateWithExtensionsOwned<Mint>> {
+        let account = self.get_account(self.pubkey).await?;
+        self.unpack_mint_info(account)
+    }
+
+    /// Retrieve mint information, observing at least the given consistency,
+    /// e.g. to verify a transaction that was just sent.
+    pub async fn get_mint_info_with_consistency(
+        &self,
+        consistency: ReadConsistency,
+    ) -> TokenResult<StateWithExtensionsOwned<Mint>> {
+        let account = self
+            .get_account_with_consistency(self.pubkey, consistency)
+            .await?;
+        self.unpack_mint_info(account)
+    }
+
+    /// Retrieve mint information at the given commitment level
+    pub async fn get_mint_info_with_commitment(
+        &self,
+        commitment: CommitmentConfig,
+    ) -> TokenResult<StateWithExtensionsOwned<Mint>> {
+        let account = self
+            .get_account_with_commitment(self.pubkey, commitment)
+            .await?;
+        self.unpack_mint_info(account)
+    }
+
+    /// Retrieve a fixed-length extension of the mint, failing with
+    /// `TokenError::MissingExtension` if the mint does not have it
+    pub async fn get_mint_extension<V: Extension + Pod>(&self) -> TokenResult<V> {
+        let mint = self.get_mint_info().await?;
+        mint.get_extension::<V>()
+            .copied()
+            .map_err(|_| TokenError::MissingExtension(V::TYPE))
+    }
+
+    /// The whole configuration of the mint, from a single fetch
+    pub async fn get_mint_summary(&self) -> TokenResult<MintSummary> {
+        let mint = self.get_mint_info().await?;
+        Ok(MintSummary::new(self.pubkey, &mint)?)
+    }
+
+    pub async fn get_transfer_fee_config(&self) -> TokenResult<TransferFeeConfig> {
+        self.get_mint_extension::<TransferFeeConfig>().await
+    }
+
+    pub async fn get_interest_bearing_config(&self) -> TokenResult<InterestBearingConfig> {
+        self.get_mint_extension::<InterestBearingConfig>().await
+    }
+
+    pub async fn get_metadata_pointer(&self) -> TokenResult<MetadataPointer> {
+        self.get_mint_extension::<MetadataPointer>().await
+    }
+
+    pub async fn get_transfer_hook(&self) -> TokenResult<TransferHook> {
+        self.get_mint_extension::<TransferHook>().await
+    }
+
+    pub async fn get_group_pointer(&self) -> TokenResult<GroupPointer> {
+        self.get_mint_extension::<GroupPointer>().await
+    }
+
+    pub async fn get_group_member_pointer(&self) -> TokenResult<GroupMemberPointer> {
+        self.get_mint_extension::<GroupMemberPointer>().await
+    }
+
+    pub async fn get_mint_close_authority(&self) -> TokenResult<MintCloseAuthority> {
+        self.get_mint_extension::<MintCloseAuthority>().await
+    }
+
+    pub async fn get_permanent_delegate(&self) -> TokenResult<PermanentDelegate> {
+        self.get_mint_extension::<PermanentDelegate>().await
+    }
+
+    pub async fn get_default_account_state(&self) -> TokenResult<DefaultAccountState> {
+        self.get_mint_extension::<DefaultAccountState>().await
+    }
+
+    pub async fn get_confidential_transfer_mint(&self) -> TokenResult<ConfidentialTransferMint> {
+        self.get_mint_extension::<ConfidentialTransferMint>().await
+    }
+
+    pub async fn get_confidential_transfer_fee_config(
+        &self,
+    ) -> TokenResult<ConfidentialTransferFeeConfig> {
+        self.get_mint_extension::<ConfidentialTransferFeeConfig>()
+            .await
+    }
+
+    /// Resolve the mint's metadata through its metadata pointer, whether it
+    /// points to the mint itself or to another account.
+    ///
+    /// With the `metaplex` feature, the metadata of mints without a metadata
+    /// pointer is read from their Metaplex metadata account.
+    pub async fn get_token_metadata(&self) -> TokenResult<ResolvedTokenMetadata> {
+        let mint = self.get_mint_info().await?;
+        match mint.get_extension::<MetadataPointer>() {
+            Ok(pointer) => {
+                let address = Option::<Pubkey>::from(pointer.metadata_address)
+                    .ok_or(TokenError::MissingExtension(ExtensionType::TokenMetadata))?;
+                if address == self.pubkey {
+                    let token_metadata = mint
+                        .get_variable_len_extension::<TokenMetadata>()
+                        .map_err(|_| TokenError::MissingExtension(ExtensionType::TokenMetadata))?;
+                    Ok(ResolvedTokenMetadata::new(
+                        MetadataSource::Mint,
+                        token_metadata,
+                    ))
+                } else {
+                    let account = self.get_account(address).await?;
+                    Ok(metadata::unpack_external_metadata(address, &account.data)?)
+                }
+            }
+            #[cfg(feature = "metaplex")]
+            Err(_) => {
+                let address = metadata::metaplex_metadata_address(&self.pubkey);
+                let account = self.get_account(address).await?;
+                Ok(metadata::unpack_metaplex_metadata(address, &account.data)?)
+            }
+            #[cfg(not(feature = "metaplex"))]
+            Err(_) => Err(TokenError::MissingExtension(ExtensionType::MetadataPointer)),
+        }
+    }
+
+    /// Retrieve the token group stored in the mint itself
+    pub async fn get_token_group(&self) -> TokenResult<TokenGroup> {
+        self.get_mint_extension::<TokenGroup>().await
+    }
+
+    /// Find the members of this group mint whose membership follows only
+    /// their group member pointer, the usual layout of member mints, as their
+    /// mint and member number, ordered by member number. See
+    /// `get_group_members_with_layout` for other layouts.
+    pub async fn get_group_members(&self) -> TokenResult<Vec<(Pubkey, u64)>> {
+        self.get_group_members_with_layout(&[ExtensionType::GroupMemberPointer])
+            .await
+    }
+
+    /// Find the members of this group mint whose membership extension comes
+    /// right after the given extensions, as their mint and member number,
+    /// ordered by member number.
+    ///
+    /// The membership extension is not at a fixed offset, so the node can
+    /// only match the group for one layout of member mints at a time: the
+    /// extensions, in order, that the mints had before joining the group.
+    pub async fn get_group_members_with_layout(
+        &self,
+        preceding_extensions: &[ExtensionType],
+    ) -> TokenResult<Vec<(Pubkey, u64)>> {
+        let member_offset = if preceding_extensions.is_empty() {
+            Account::LEN + 1
+        } else {
+            ExtensionType::try_calculate_account_len::<Mint>(preceding_extensions)?
+        };
+        // the extension's type and length, then the member mint, precede the
+        // group
+        let group_offset = member_offset + 4 + 32;
+        let mints = self
+            .client
+            .get_program_accounts_with_filters(
+                &self.program_id,
+                vec![
+                    RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                        Account::LEN,
+                        &[AccountType::Mint as u8],
+                    )),
+                    RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                        group_offset,
+                        self.pubkey.as_ref(),
+                    )),
+                ],
+            )
+            .await
+            .map_err(TokenError::Client)?;
+        let mut members = mints
+            .into_iter()
+            .filter_map(|(address, account)| {
+                let mint = StateWithExtensionsOwned::<Mint>::unpack(account.data).ok()?;
+                let member = mint.get_extension::<TokenGroupMember>().ok()?;
+                (member.group == self.pubkey).then_some((address, u64::from(member.member_number)))
+            })
+            .collect::<Vec<_>>();
+        members.sort_by_key(|(_, member_number)| *member_number);
+        Ok(members)
+    }
+
+    /// Retrieve the group membership stored in the mint itself
+    pub async fn get_token_group_member(&self) -> TokenResult<TokenGroupMember> {
+        self.get_mint_extension::<TokenGroupMember>().await
+    }
+
+    /// Retrieve account information.
+    pub async fn get_account_info(
+        &self,
+        account: &Pubkey,
+    ) -> TokenResult<StateWithExtensionsOwned<Account>> {
+        let account = self.get_account(*account).await?;
+        self.unpack_account_info(account)
+    }
+
+    /// Retrieve account information, observing at least the given
+    /// consistency, e.g. to verify a transaction that was just sent.
+    pub async fn get_account_info_with_consistency(
+        &self,
+        account: &Pubkey,
+        consistency: ReadConsistency,
+    ) -> TokenResult<StateWithExtensionsOwned<Account>> {
+        let account = self
+            .get_account_with_consistency(*account, consistency)
+            .await?;
+        self.unpack_account_info(account)
+    }
+
+    /// Retrieve account information at the given commitment level, e.g.
+    /// `processed` for a quick balance check or `finalized` for a proof
+    pub async fn get_account_info_with_commitment(
+        &self,
+        account: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> TokenResult<StateWithExtensionsOwned<Account>> {
+        let account = self
+            .get_account_with_commitment(*account, commitment)
+            .await?;
+        self.unpack_account_info(account)
+    }
+
+    pub async fn is_frozen(&self, account: &Pubkey) -> TokenResult<bool> {
+        Ok(self.get_account_info(account).await?.base.is_frozen())
+    }
+
+    /// Whether the token program would let the account be closed: it holds
+    /// no tokens, unless native, and no withheld fees or confidential
+    /// balances
+    pub async fn is_closable(&self, account: &Pubkey) -> TokenResult<bool> {
+        let account_info = self.get_account_info(account).await?;
+        if !account_info.base.is_native() && account_info.base.amount != 0 {
+            return Ok(false);
+        }
+        Ok(extensions_closable(&account_info))
+    }
+
+    /// Whether confidential credits are waiting to be applied to the
+    /// account's available balance
+    pub async fn has_pending_confidential_balance(&self, account: &Pubkey) -> TokenResult<bool> {
+        let account_info = self.get_account_info(account).await?;
+        Ok(account_info
+            .get_extension::<ConfidentialTransferAccount>()
+            .map(|extension| u64::from(extension.pending_balance_credit_counter) > 0)
+            .unwrap_or(false))
+    }
+
+    /// Retrieve a multisig account of the token program, e.g. to find which
+    /// signatures a transaction still needs
+    pub async fn get_multisig_info(&self, address: &Pubkey) -> TokenResult<MultisigInfo> {
+        let account = self.get_account(*address).await?;
+        if account.owner != self.program_id {
+            return Err(TokenError::AccountInvalidOwner);
+        }
+        let multisig = Multisig::unpack(&account.data)?;
+        Ok(MultisigInfo::new(*address, &multisig))
+    }
+
+    /// Stream every new state of a token account, e.g. to react to incoming
+    /// transfers.
+    ///
+    /// States are pushed by the subscription client if there is one, else the
+    /// account is polled every `WATCH_ACCOUNT_POLL_INTERVAL`, which only sees
+    /// its latest state at each poll. States that are not token accounts of
+    /// the program, e.g. once the account is closed, are skipped. Failed polls
+    /// are yielded as errors, and polling goes on after them.
+    pub async fn watch_account(
+        &self,
+        account: &Pubkey,
+    ) -> TokenResult<BoxStream<'_, TokenResult<StateWithExtensionsOwned<Account>>>> {
+        let source = match &self.subscription_client {
+            Some(subscription_client) => WatchSource::Pushed(
+                subscription_client
+                    .subscribe_account(account)
+                    .await
+                    .map_err(TokenError::Client)?,
+            ),
+            None => WatchSource::Polled {
+                address: *account,
+                last_data: None,
+            },
+        };
+        // the stream owns what it polls with, since the token itself is not
+        // `Sync` whatever its signers
+        let client = self.client.clone();
+        let sleep = self.sleep.clone();
+        let read_commitment = self.read_commitment;
+        let (program_id, mint) = (self.program_id, self.pubkey);
+        Ok(stream::unfold(source, move |mut source| {
+            let (client, sleep) = (client.clone(), sleep.clone());
+            async move {
+                loop {
+                    let account = match &mut source {
+                        WatchSource::Pushed(subscription) => subscription.next().await?,
+                        WatchSource::Polled { address, last_data } => {
+                            if last_data.is_some() {
+                                sleep(WATCH_ACCOUNT_POLL_INTERVAL).await;
+                            }
+                            let account = match read_commitment {
+                                Some(commitment) => {
+                                    client
+                                        .get_account_with_commitment(*address, commitment)
+                                        .await
+                                }
+                                None => client.get_account(*address).await,
+                            };
+                            // a first poll finding nothing still waits for the next
+                            let seen = last_data.get_or_insert_with(Vec::new);
+                            match account {
+                                Ok(Some(account)) if *seen != account.data => {
+                                    *seen = account.data.clone();
+                                    account
+                                }
+                                Ok(_) => continue,
+                                Err(error) => {
+                                    return Some((Err(TokenError::Client(error)), source))
+                                }
+                            }
+                        }
+                    };
+                    if let Ok(state) = unpack_token_account(&program_id, &mint, account) {
+                        return Some((Ok(state), source));
+                    }
+                }
+            }
+        })
+        .boxed())
+    }
+
+    /// Retrieve every account of the mint matching all of the filters, e.g. a
+    /// memcmp on the owner
+    pub async fn get_token_accounts(
+        &self,
+        filters: Vec<RpcFilterType>,
+    ) -> TokenResult<Vec<(Pubkey, StateWithExtensionsOwned<Account>)>> {
+        let accounts = self
+            .client
+            .get_program_accounts_with_filters(
+                &self.program_id,
+                self.token_account_filters(filters),
+            )
+            .await
+            .map_err(TokenError::Client)?;
+        Ok(self.unpack_token_accounts(accounts))
+    }
+
+    /// Token accounts of the mint matching all of the filters, with the slot
+    /// the node scanned them at
+    pub async fn get_token_accounts_with_slot(
+        &self,
+        filters: Vec<RpcFilterType>,
+    ) -> TokenResult<(Slot, Vec<(Pubkey, StateWithExtensionsOwned<Account>)>)> {
+        let (slot, accounts) = self
+            .client
+            .get_program_accounts_with_context(
+                &self.program_id,
+                self.token_account_filters(filters),
+            )
+            .await
+            .map_err(TokenError::Client)?;
+        Ok((slot, self.unpack_token_accounts(accounts)))
+    }
+
+    fn token_account_filters(&self, filters: Vec<RpcFilterType>) -> Vec<RpcFilterType> {
+        let mut all_filters = vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            0,
+            self.pubkey.as_ref(),
+        ))];
+        if self.program_id == spl_token::id() {
+            all_filters.push(RpcFilterType::DataSize(Account::LEN as u64));
+        }
+        all_filters.extend(filters);
+        all_filters
+    }
+
+    fn unpack_token_accounts(
+        &self,
+        accounts: Vec<(Pubkey, BaseAccount)>,
+    ) -> Vec<(Pubkey, StateWithExtensionsOwned<Account>)> {
+        accounts
+            .into_iter()
+            // mints with the same bytes at the start are not token accounts
+            .filter_map(|(address, account)| {
+                self.unpack_account_info(account)
+                    .ok()
+                    .map(|account| (address, account))
+            })
+            .collect()
+    }
+
+    /// Accounts of the mint owned by `owner` other than its associated token
+    /// account, e.g. to sweep them into it or close them
+    pub async fn get_auxiliary_token_accounts(
+        &self,
+        owner: &Pubkey,
+    ) -> TokenResult<Vec<(Pubkey, StateWithExtensionsOwned<Account>)>> {
+        let associated_token_address = self.get_associated_token_address(owner);
+        let accounts = self
+            .get_token_accounts(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                ACCOUNT_OWNER_OFFSET,
+                owner.as_ref(),
+            ))])
+            .await?;
+        Ok(accounts
+            .into_iter()
+            .filter(|(address, _)| *address != associated_token_address)
+            .collect())
+    }
+
+    /// Find every account of the mint with an active delegation to the
+    /// delegate, e.g. to revoke or monitor them
+    pub async fn get_delegated_accounts(
+        &self,
+        delegate: &Pubkey,
+    ) -> TokenResult<Vec<DelegatedAccount>> {
+        // the delegate is a `COption<Pubkey>`, tagged `Some` by a 1u32
+        let mut delegate_bytes = 1u32.to_le_bytes().to_vec();
+        delegate_bytes.extend_from_slice(delegate.as_ref());
+        let accounts = self
+            .get_token_accounts(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                ACCOUNT_DELEGATE_OFFSET,
+                &delegate_bytes,
+            ))])
+            .await?;
+        Ok(accounts
+            .into_iter()
+            .filter(|(_, account)| account.base.delegated_amount > 0)
+            .map(|(address, account)| DelegatedAccount {
+                address,
+                owner: account.base.owner,
+                delegated_amount: account.base.delegated_amount,
+            })
+            .collect())
+    }
+
+    /// Balance-changing operations of an account of the mint, from its
+    /// `limit` most recent transactions, newest first. Transfers into the
+    /// account are net of the transfer fee, which is reported separately,
+    /// and computed from the mint's fee at the time when the instruction
+    /// does not state it.
+    pub async fn get_account_history(
+        &self,
+        address: &Pubkey,
+        limit: usize,
+    ) -> TokenResult<Vec<AccountOperation>> {
+        let mint = self.get_mint_info().await?;
+        let fee_config = mint.get_extension::<TransferFeeConfig>().ok().copied();
+        let epoch_info = match fee_config {
+            Some(_) => Some(
+                self.client
+                    .get_epoch_info()
+                    .await
+                    .map_err(TokenError::Client)?,
+            ),
+            None => None,
+        };
+
+        let mut signatures = vec![];
+        let mut pages = self.signature_pages(address, None, limit);
+        while let Some(page) = pages.next().await {
+            signatures.extend(page?);
+        }
+
+        let mut operations = vec![];
+        for signature in &signatures {
+            let Some(record) = self
+                .client
+                .get_transaction(signature)
+                .await
+                .map_err(TokenError::Client)?
+            else {
+                continue;
+            };
+            let mut decoded = events::decode_events(&self.program_id, &self.pubkey, &record);
+            if let (Some(config), Some(epoch_info)) = (&fee_config, &epoch_info) {
+                let transfer_fee = config.get_epoch_fee(epoch_at_slot(epoch_info, record.slot));
+                for event in &mut decoded {
+                    if event.kind == TokenEventKind::Transfer && event.fee.is_none() {
+                        if let Some(amount) = event.amount {
+                            event.fee = Some(transfer_fee_amount(Some(transfer_fee), amount)?);
+                        }
+                    }
+                }
+            }
+            // operations within a transaction stay in execution order
+            operations.extend(events::account_operations(address, &decoded));
+        }
+        Ok(operations)
+    }
+
+    /// Supply of the mint, with its UI amount as of the cluster's current
+    /// time, including accrued interest for interest-bearing mints
+    pub async fn get_supply(&self) -> TokenResult<TokenSupply> {
+        let mint = self.get_mint_info().await?;
+        match mint.get_extension::<InterestBearingConfig>() {
+            Ok(config) => Ok(TokenSupply::with_interest(
+                mint.base.supply,
+                mint.base.decimals,
+                config,
+                self.get_clock().await?.unix_timestamp,
+            )),
+            Err(_) => Ok(TokenSupply::new(mint.base.supply, mint.base.decimals)),
+        }
+    }
+
+    /// Convert a raw amount to its UI representation as of `unix_timestamp`,
+    /// accruing interest locally for interest-bearing mints, like the token
+    /// program's `AmountToUiAmount` without asking it
+    pub async fn amount_to_ui_amount(
+        &self,
+        amount: u64,
+        unix_timestamp: UnixTimestamp,
+    ) -> TokenResult<String> {
+        let mint = self.get_mint_info().await?;
+        let decimals = mint.base.decimals;
+        match mint.get_extension::<InterestBearingConfig>() {
+            Ok(config) => Ok(config
+                .amount_to_ui_amount(amount, decimals, unix_timestamp)
+                .ok_or(ProgramError::InvalidArgument)?),
+            Err(_) => Ok(format::amount(amount, decimals, &AmountFormat::new())),
+        }
+    }
+
+    /// Convert a UI amount as of `unix_timestamp` back to a raw amount,
+    /// removing the interest accrued by interest-bearing mints, like the token
+    /// program's `UiAmountToAmount` without asking it
+    pub async fn ui_amount_to_amount(
+        &self,
+        ui_amount: &str,
+        unix_timestamp: UnixTimestamp,
+    ) -> TokenResult<u64> {
+        let mint = self.get_mint_info().await?;
+        let decimals = mint.base.decimals;
+        match mint.get_extension::<InterestBearingConfig>() {
+            Ok(config) => {
+                Ok(config.try_ui_amount_into_amount(ui_amount, decimals, unix_timestamp)?)
+            }
+            Err(_) => format::parse_amount(ui_amount, decimals, &AmountFormat::new())
+                .map_err(TokenError::InvalidAmount),
+        }
+    }
+
+    /// Convert a raw amount to its UI representation as the token program
+    /// does it now, by simulating `AmountToUiAmount`, e.g. to match on-chain
+    /// rounding exactly
+    pub async fn amount_to_ui_amount_onchain(&self, amount: u64) -> TokenResult<String> {
+        let data = self
+            .simulate_return_data(instruction::amount_to_ui_amount(
+                &self.program_id,
+                &self.pubkey,
+                amount,
+            )?)
+            .await?;
+        String::from_utf8(data).map_err(|error| TokenError::Client(error.into()))
+    }
+
+    /// Convert a UI amount to a raw amount as the token program does it now,
+    /// by simulating `UiAmountToAmount`
+    pub async fn ui_amount_to_amount_onchain(&self, ui_amount: &str) -> TokenResult<u64> {
+        let mut data = self
+            .simulate_return_data(instruction::ui_amount_to_amount(
+                &self.program_id,
+                &self.pubkey,
+                ui_amount,
+            )?)
+            .await?;
+        // the runtime trims trailing zero bytes from return data
+        if data.len() > 8 {
+            return Err(TokenError::Client("unexpected return data".into()));
+        }
+        data.resize(8, 0);
+        Ok(u64::from_le_bytes(data.try_into().unwrap()))
+    }
+
+    /// Data returned by the token program when simulating the instruction
+    async fn simulate_return_data(&self, instruction: Instruction) -> TokenResult<Vec<u8>> {
+        let simulation = self
+            .simulate_ixs(&[instruction], &[] as &[&dyn Signer; 0])
+            .await?;
+        if let Some(error) = simulation.error() {
+            return Err(TokenError::Client(Box::new(error)));
+        }
+        Ok(simulation
+            .return_data()
+            .filter(|return_data| return_data.program_id == self.program_id)
+            .map(|return_data| return_data.data)
+            .unwrap_or_default())
+    }
+
+    /// Largest holders of the mint, largest first.
+    ///
+    /// Served by the node's index of largest accounts when it covers `limit`,
+    /// else, or if the client does not support the index, by a scan of every
+    /// account of the mint. Accounts closed since the index was read are
+    /// skipped.
+    pub async fn get_largest_holders(&self, limit: usize) -> TokenResult<Vec<TokenHolder>> {
+        let decimals = self.get_mint_info().await?.base.decimals;
+        let largest_accounts = if limit <= MAX_LARGEST_ACCOUNTS {
+            match self.client.get_token_largest_accounts(&self.pubkey).await {
+                Ok(largest_accounts) => Some(largest_accounts),
+                Err(error) if error.downcast_ref::<Unsupported>().is_some() => None,
+                Err(error) => return Err(TokenError::Client(error)),
+            }
+        } else {
+            None
+        };
+
+        match largest_accounts {
+            Some(largest_accounts) => {
+                // the index only has balances, owners must be read
+                let largest_accounts = largest_accounts.into_iter().take(limit).collect::<Vec<_>>();
+                let addresses = largest_accounts
+                    .iter()
+                    .map(|(address, _)| *address)
+                    .collect::<Vec<_>>();
+                let accounts = self
+                    .client
+                    .get_multiple_accounts(&addresses)
+                    .await
+                    .map_err(TokenError::Client)?;
+                let mut holders = vec![];
+                for ((address, amount), account) in largest_accounts.into_iter().zip(accounts) {
+                    let Some(account) = account else {
+                        continue;
+                    };
+                    let account = unpack_token_account(&self.program_id, &self.pubkey, account)?;
+                    holders.push(TokenHolder::new(
+                        address,
+                        account.base.owner,
+                        amount,
+                        decimals,
+                    ));
+                }
+                Ok(holders)
+            }
+            None => {
+                let holders = self
+                    .get_token_accounts(vec![])
+                    .await?
+                    .into_iter()
+                    .map(|(address, account)| {
+                        TokenHolder::new(address, account.base.owner, account.base.amount, decimals)
+                    })
+                    .collect();
+                Ok(holders::largest(holders, limit))
+            }
+        }
+    }
+
+    /// Take a snapshot of every account of the mint and its supply.
+    ///
+    /// The snapshot is stamped with the slot the node scanned the accounts
+    /// at. The supply is read from a node at least at that slot, so it
+    /// reflects at least every change the accounts do.
+    pub async fn snapshot(&self) -> TokenResult<HolderSnapshot> {
+        let (slot, accounts) = self.get_token_accounts_with_slot(vec![]).await?;
+        let mint = self
+            .get_mint_info_with_consistency(ReadConsistency::MinContextSlot(slot))
+            .await?;
+        Ok(HolderSnapshot {
+            mint: self.pubkey,
+            slot,
+            supply: mint.base.supply,
+            decimals: mint.base.decimals,
+            accounts: accounts
+                .into_iter()
+                .map(|(address, account)| {
+                    (
+                        address,
+                        SnapshotAccount {
+                            owner: account.base.owner,
+                            amount: account.base.amount,
+                        },
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    /// Snapshot of the accounts of the mint holding at least `min_balance`,
+    /// e.g. for governance snapshots and airdrop eligibility.
+    /// `HolderSnapshot::holders` lists them largest first.
+    pub async fn snapshot_holders(&self, min_balance: u64) -> TokenResult<HolderSnapshot> {
+        let mut snapshot = self.snapshot().await?;
+        snapshot
+            .accounts
+            .retain(|_, account| account.amount >= min_balance);
+        Ok(snapshot)
+    }
+
+    fn unpack_account_info(
+        &self,
+        account: BaseAccount,
+    ) -> TokenResult<StateWithExtensionsOwned<Account>> {
+        unpack_token_account(&self.program_id, &self.pubkey, account)
+    }
+
+    /// Retrieve the associated account or create one if not found.
+    pub async fn get_or_create_associated_account_info(
+        &self,
+        owner: &Pubkey,
+    ) -> TokenResult<StateWithExtensionsOwned<Account>> {
+        let account = self.get_associated_token_address(owner);
+        match self.get_account_info(&account).await {
+            Ok(account) => Ok(account),
+            // AccountInvalidOwner is possible if account already received some lamports.
+            Err(TokenError::AccountNotFound) | Err(TokenError::AccountInvalidOwner) => {
+                self.create_associated_token_account(owner).await?;
+                self.get_account_info(&account).await
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Assign a new authority to the account.
+    pub async fn set_authority<S: Signers>(
+        &self,
+        account: &Pubkey,
+        authority: &Pubkey,
+        new_authority: Option<&Pubkey>,
+        authority_type: instruction::AuthorityType,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        self.process_ixs(
+            &[instruction::set_authority(
+                &self.program_id,
+                account,
+                new_authority,
+                authority_type,
+                authority,
+                &multisig_signers,
+            )?],
+            signing_keypairs,
+        )
+        .await
+    }
+
+    /// Apply the same authority change to many accounts of the same
+    /// authority, e.g. to rotate owners or close authorities, packed into as
+    /// few transactions as fit. The outcome holds the result for each
+    /// account, in order.
+    pub async fn set_authority_many<S: Signers>(
+        &self,
+        accounts: &[Pubkey],
+        authority: &Pubkey,
+        new_authority: Option<&Pubkey>,
+        authority_type: instruction::AuthorityType,
+        signing_keypairs: &S,
+    ) -> TokenResult<BatchOutcome<T::Output>> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        let groups = accounts
+            .iter()
+            .map(|account| {
+                Ok(vec![instruction::set_authority(
+                    &self.program_id,
+                    account,
+                    new_authority,
+                    authority_type.clone(),
+                    authority,
+                    &multisig_signers,
+                )?])
+            })
+            .collect::<TokenResult<Vec<_>>>()?;
+
+        Ok(self.process_ix_batches(&groups, signing_keypairs).await)
+    }
+
+    /// Change any authority of the mint or of one of its accounts, checking
+    /// first that the mint has the extension holding it
+    pub async fn update_authority<S: Signers>(
+        &self,
+        authority_kind: Authority,
+        current_authority: &Pubkey,
+        new_authority: Option<&Pubkey>,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let account = match authority_kind.account() {
+            Some(account) => {
+                self.get_account_info(account).await?;
+                *account
+            }
+            None => {
+                let mint = self.get_mint_info().await?;
+                if !authority_kind.is_supported_by(&mint) {
+                    return Err(TokenError::MissingExtension(
+                        authority_kind.required_extension().unwrap(),
+                    ));
+                }
+                self.pubkey
+            }
+        };
+
+        match (authority_kind, authority_kind.authority_type()) {
+            (_, Some(authority_type)) => {
+                self.set_authority(
+                    &account,
+                    current_authority,
+                    new_authority,
+                    authority_type,
+                    signing_keypairs,
+                )
+                .await
+            }
+            (Authority::TokenMetadata, None) => {
+                self.token_metadata_update_authority(
+                    current_authority,
+                    new_authority.copied(),
+                    signing_keypairs,
+                )
+                .await
+            }
+            (_, None) => {
+                self.token_group_update_authority(
+                    current_authority,
+                    new_authority.copied(),
+                    signing_keypairs,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Mint new tokens
+    pub async fn mint_to<S: Signers>(
+        &self,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        let instructions =
+            [self.mint_to_instruction(destination, authority, &multisig_signers, amount)?];
+
+        self.process_ixs(&instructions, signing_keypairs).await
+    }
+
+    /// Mint to the associated account of the owner, creating it first if it
+    /// does not exist, in one transaction
+    pub async fn mint_to_owner<S: Signers>(
+        &self,
+        owner: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+        let destination =
+            self.get_associated_token_address_checked(owner, !self.guards.reject_owners_off_curve)?;
+
+        let instructions = [
+            create_associated_token_account_idempotent(
+                &self.payer.pubkey(),
+                owner,
+                &self.pubkey,
+                &self.program_id,
+            ),
+            self.mint_to_instruction(&destination, authority, &multisig_signers, amount)?,
+        ];
+
+        self.process_ixs(&instructions, signing_keypairs).await
+    }
+
+    /// Mint to the associated accounts of many owners, creating the missing
+    /// ones, e.g. for an airdrop. Recipients are packed into as few
+    /// transactions as fit, and the outcome holds the result for each of
+    /// them, in order.
+    pub async fn mint_to_many<S: Signers>(
+        &self,
+        recipients: &[(Pubkey, u64)],
+        authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<BatchOutcome<T::Output>> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        let owners = recipients
+            .iter()
+            .map(|(owner, _)| *owner)
+            .collect::<Vec<_>>();
+        let destinations = self.derive_atas(&owners);
+        let groups = recipients
+            .iter()
+            .zip(&destinations)
+            .map(|((owner, amount), destination)| {
+                Ok(vec![
+                    create_associated_token_account_idempotent(
+                        &self.payer.pubkey(),
+                        owner,
+                        &self.pubkey,
+                        &self.program_id,
+                    ),
+                    self.mint_to_instruction(destination, authority, &multisig_signers, *amount)?,
+                ])
+            })
+            .collect::<TokenResult<Vec<_>>>()?;
+
+        Ok(self.process_ix_batches(&groups, signing_keypairs).await)
+    }
+
+    pub(crate) fn mint_to_instruction(
+        &self,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        multisig_signers: &[&Pubkey],
+        amount: u64,
+    ) -> TokenResult<Instruction> {
+        let instruction = if let Some(decimals) = self.decimals {
+            instruction::mint_to_checked(
+                &self.program_id,
+                &self.pubkey,
+                destination,
+                authority,
+                multisig_signers,
+                amount,
+                decimals,
+            )?
+        } else {
+            instruction::mint_to(
+                &self.program_id,
+                &self.pubkey,
+                destination,
+                authority,
+                multisig_signers,
+                amount,
+            )?
+        };
+        Ok(instruction)
+    }
+
+    /// Transfer tokens to another account
+    #[allow(clippy::too_many_arguments)]
+    pub async fn transfer<S: Signers>(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        self.check_transfer(source, destination, amount)?;
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        let mut instructions = vec![
+            self.transfer_instruction(source, destination, authority, &multisig_signers, amount)
+                .await?,
+        ];
+        instructions.extend(self.sync_native_destination_instruction(destination)?);
+
+        self.process_ixs(&instructions, signing_keypairs).await
+    }
+
+    /// `SyncNative` for the destination of a transfer, if the mint is native
+    /// and destinations are synced
+    fn sync_native_destination_instruction(
+        &self,
+        destination: &Pubkey,
+    ) -> TokenResult<Option<Instruction>> {
+        if self.sync_native_destinations && self.is_native() {
+            Ok(Some(instruction::sync_native(
+                &self.program_id,
+                destination,
+            )?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Transfer from one source to many destinations, in a single transaction
+    /// if they fit, else in as few as needed. The accounts of the mint's
+    /// transfer hook are resolved for each destination. The outcome holds the
+    /// result for each destination, in order.
+    ///
+    /// Every transfer is a `transfer_checked`, with the mint's decimals
+    /// fetched once if the token was not given them.
+    pub async fn transfer_many<S: Signers>(
+        &self,
+        source: &Pubkey,
+        destinations: &[(Pubkey, u64)],
+        authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<BatchOutcome<T::Output>> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+        let decimals = self.get_decimals().await?;
+
+        let mut groups = Vec::with_capacity(destinations.len());
+        for (destination, amount) in destinations {
+            self.check_transfer(source, destination, *amount)?;
+            let mut group = vec![
+                self.transfer_checked_instruction(
+                    source,
+                    destination,
+                    authority,
+                    &multisig_signers,
+                    *amount,
+                    decimals,
+                )
+                .await?,
+            ];
+            group.extend(self.sync_native_destination_instruction(destination)?);
+            groups.push(group);
+        }
+
+        Ok(self.process_ix_batches(&groups, signing_keypairs).await)
+    }
+
+    /// Transfer tokens as the delegate of the source, checking first that the
+    /// delegate is the account's and that enough is still delegated to it.
+    /// The transfer is a `transfer_checked`, with the mint's decimals fetched
+    /// if the token was not given them.
+    pub async fn transfer_as_delegate<S: Signers>(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        delegate: &Pubkey,
+        amount: u64,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        self.check_transfer(source, destination, amount)?;
+        let account = self.get_account_info(source).await?;
+        if account.base.delegate != COption::Some(*delegate) {
+            return Err(TokenError::InvalidDelegate(*delegate));
+        }
+        if amount > account.base.delegated_amount {
+            return Err(TokenError::InsufficientDelegatedAmount {
+                amount,
+                delegated_amount: account.base.delegated_amount,
+            });
+        }
+        let decimals = self.get_decimals().await?;
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(delegate, &signing_pubkeys);
+
+        let mut instructions = vec![
+            self.transfer_checked_instruction(
+                source,
+                destination,
+                delegate,
+                &multisig_signers,
+                amount,
+                decimals,
+            )
+            .await?,
+        ];
+        instructions.extend(self.sync_native_destination_instruction(destination)?);
+
+        self.process_ixs(&instructions, signing_keypairs).await
+    }
+
+    /// Seize tokens from any account of the mint through its permanent
+    /// delegate, e.g. for compliance, resolving the accounts of the mint's
+    /// transfer hook. The memo, if any, records the reason. It is not signed,
+    /// so a multisig delegate does not have to be among the signers.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn clawback<S: Signers>(
+        &self,
+        from_account: &Pubkey,
+        to_account: &Pubkey,
+        amount: u64,
+        permanent_delegate: &Pubkey,
+        memo: Option<&str>,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        self.check_transfer(from_account, to_account, amount)?;
+        let mint = self.get_mint_info().await?;
+        let extension = mint
+            .get_extension::<PermanentDelegate>()
+            .map_err(|_| TokenError::MissingExtension(ExtensionType::PermanentDelegate))?;
+        if Option::<Pubkey>::from(extension.delegate) != Some(*permanent_delegate) {
+            return Err(TokenError::InvalidDelegate(*permanent_delegate));
+        }
+
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(permanent_delegate, &signing_pubkeys);
+
+        let mut instructions = vec![];
+        if let Some(memo) = memo {
+            instructions.push(spl_memo::build_memo(memo.as_bytes(), &[]));
+        }
+        instructions.push(
+            self.transfer_checked_instruction(
+                from_account,
+                to_account,
+                permanent_delegate,
+                &multisig_signers,
+                amount,
+                mint.base.decimals,
+            )
+            .await?,
+        );
+
+        self.process_ixs(&instructions, signing_keypairs).await
     }
 
-    fn unpack_mint_info(
+    /// Transfer an amount given in UI units, e.g. `"12.345"`, parsed exactly
+    /// against the mint's decimals. Precision beyond them is rejected rather
+    /// than rounded. Interest-bearing mints are not accounted for: the
+    /// amount is read as whole and fractional base units.
+    ///
+    /// The transfer is checked against the same decimals, so the program
+    /// rejects it if they do not match the mint's.
+    pub async fn transfer_ui_amount<S: Signers>(
         &self,
-        account: BaseAccount,
-    ) -> TokenResult<StateWithExtensionsOwned<Mint>> {
-        if account.owner != self.program_id {
-            return Err(TokenError::AccountInvalidOwner);
-        }
-
-        let mint_result =
-            StateWithExtensionsOwned::<Mint>::unpack(account.data).map_err(Into::into);
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        ui_amount: &str,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let decimals = self.get_decimals().await?;
+        let amount = format::parse_amount(ui_amount, decimals, &AmountFormat::new())
+            .map_err(TokenError::InvalidAmount)?;
+        self.check_transfer(source, destination, amount)?;
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
 
-        if let (Ok(mint), Some(decimals)) = (&mint_result, self.decimals) {
-            if decimals != mint.base.decimals {
-                return Err(TokenError::InvalidDecimals);
-            }
-        }
+        let mut instructions = vec![
+            self.transfer_checked_instruction(
+                source,
+                destination,
+                authority,
+                &multisig_signers,
+                amount,
+                decimals,
+            )
+            .await?,
+        ];
+        instructions.extend(self.sync_native_destination_instruction(destination)?);
 
-        mint_result
+        self.process_ixs(&instructions, signing_keypairs).await
     }
 
-    /// Retrive mint information.
-    pub async fn get_mint_info(&self) -> TokenResult<StateWithExtensionsOwned<Mint>> {
-        let account = self.get_account(self.pubkey).await?;
-        self.unpack_mint_info(account)
+    /// Decimals of the mint, as configured or else fetched
+    pub(crate) async fn get_decimals(&self) -> TokenResult<u8> {
+        match self.decimals {
+            Some(decimals) => Ok(decimals),
+            None => Ok(self.get_mint_info().await?.base.decimals),
+        }
     }
 
-    /// Retrieve account information.
-    pub async fn get_account_info(
+    pub(crate) fn check_transfer(
         &self,
-        account: &Pubkey,
-    ) -> TokenResult<StateWithExtensionsOwned<Account>> {
-        let account = self.get_account(*account).await?;
-        if account.owner != self.program_id {
-            return Err(TokenError::AccountInvalidOwner);
+        source: &Pubkey,
+        destination: &Pubkey,
+        amount: u64,
+    ) -> TokenResult<()> {
+        if self.guards.reject_zero_amount_transfers && amount == 0 {
+            return Err(TokenError::ZeroAmountTransfer);
         }
-        let account = StateWithExtensionsOwned::<Account>::unpack(account.data)?;
-        if account.base.mint != *self.get_address() {
-            return Err(TokenError::AccountInvalidMint);
+        if self.guards.reject_self_transfers && source == destination {
+            return Err(TokenError::SelfTransfer);
         }
-
-        Ok(account)
+        Ok(())
     }
 
-    /// Retrieve the associated account or create one if not found.
-    pub async fn get_or_create_associated_account_info(
-        &self,
-        owner: &Pubkey,
-    ) -> TokenResult<StateWithExtensionsOwned<Account>> {
-        let account = self.get_associated_token_address(owner);
-        match self.get_account_info(&account).await {
-            Ok(account) => Ok(account),
-            // AccountInvalidOwner is possible if account already received some lamports.
-            Err(TokenError::AccountNotFound) | Err(TokenError::AccountInvalidOwner) => {
-                self.create_associated_token_account(owner).await?;
-                self.get_account_info(&account).await
+    /// Reject burns exceeding the source's balance, if it can be read, e.g.
+    /// not when signing offline
+    pub(crate) async fn check_burn(&self, source: &Pubkey, amount: u64) -> TokenResult<()> {
+        if !self.guards.reject_burns_exceeding_balance {
+            return Ok(());
+        }
+        if let Ok(account) = self.get_account_info(source).await {
+            let balance = account.base.amount;
+            if amount > balance {
+                return Err(TokenError::BurnExceedsBalance { amount, balance });
             }
-            Err(error) => Err(error),
         }
+        Ok(())
     }
 
-    /// Assign a new authority to the account.
-    pub async fn set_authority<S: Signers>(
+    /// Accounts the mint's transfer hook requires for a transfer, to append
+    /// to a transfer built by the caller, e.g. for a CPI. Empty for mints
+    /// without a hook. Accounts configured with `with_transfer_hook_accounts`
+    /// are returned as they are. See `hook::resolve_transfer_hook_metas_offline`
+    /// to resolve them without a client.
+    pub async fn resolve_transfer_hook_metas(
         &self,
-        account: &Pubkey,
+        source: &Pubkey,
+        destination: &Pubkey,
         authority: &Pubkey,
-        new_authority: Option<&Pubkey>,
-        authority_type: instruction::AuthorityType,
-        signing_keypairs: &S,
-    ) -> TokenResult<T::Output> {
-        let signing_pubkeys = signing_keypairs.pubkeys();
-        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
-
-        self.process_ixs(
-            &[instruction::set_authority(
-                &self.program_id,
-                account,
-                new_authority,
-                authority_type,
-                authority,
-                &multisig_signers,
-            )?],
-            signing_keypairs,
+        amount: u64,
+    ) -> TokenResult<Vec<AccountMeta>> {
+        if let Some(transfer_hook_accounts) = &self.transfer_hook_accounts {
+            return Ok(transfer_hook_accounts.clone());
+        }
+        hook::extra_account_metas(
+            &self.program_id,
+            self.get_address(),
+            source,
+            destination,
+            authority,
+            amount,
+            |address| {
+                self.client
+                    .get_account(address)
+                    .map_ok(|opt| opt.map(|acc| acc.data))
+            },
         )
         .await
     }
 
-    /// Mint new tokens
-    pub async fn mint_to<S: Signers>(
+    /// Build a transfer instruction, including any accounts required by the
+    /// mint's transfer hook
+    pub(crate) async fn transfer_instruction(
         &self,
+        source: &Pubkey,
         destination: &Pubkey,
         authority: &Pubkey,
+        multisig_signers: &[&Pubkey],
         amount: u64,
-        signing_keypairs: &S,
-    ) -> TokenResult<T::Output> {
-        let signing_pubkeys = signing_keypairs.pubkeys();
-        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+    ) -> TokenResult<Instruction> {
+        let instruction = if let Some(decimals) = self.decimals {
+            self.transfer_checked_instruction(
+                source,
+                destination,
+                authority,
+                multisig_signers,
+                amount,
+                decimals,
+            )
+            .await?
+        } else {
+            #[allow(deprecated)]
+            instruction::transfer(
+                &self.program_id,
+                source,
+                destination,
+                authority,
+                multisig_signers,
+                amount,
+            )?
+        };
 
-        let instructions = if let Some(decimals) = self.decimals {
-            [instruction::mint_to_checked(
+        Ok(instruction)
+    }
+
+    /// `transfer_checked` with the given decimals, e.g. from a fetched mint,
+    /// along with the accounts required by the mint's transfer hook
+    pub(crate) async fn transfer_checked_instruction(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        multisig_signers: &[&Pubkey],
+        amount: u64,
+        decimals: u8,
+    ) -> TokenResult<Instruction> {
+        if let Some(transfer_hook_accounts) = &self.transfer_hook_accounts {
+            let mut instruction = instruction::transfer_checked(
                 &self.program_id,
-                &self.pubkey,
+                source,
+                self.get_address(),
                 destination,
                 authority,
-                &multisig_signers,
+                multisig_signers,
                 amount,
                 decimals,
-            )?]
+            )?;
+            instruction.accounts.extend(transfer_hook_accounts.clone());
+            Ok(instruction)
         } else {
-            [instruction::mint_to(
+            let fetch_account_data_fn = |address| {
+                self.client
+                    .get_account(address)
+                    .map_ok(|opt| opt.map(|acc| acc.data))
+            };
+            offchain::create_transfer_checked_instruction_with_extra_metas(
                 &self.program_id,
-                &self.pubkey,
+                source,
+                self.get_address(),
                 destination,
                 authority,
-                &multisig_signers,
+                multisig_signers,
                 amount,
-            )?]
+                decimals,
+                fetch_account_data_fn,
+            )
+            .await
+            .map_err(|_| TokenError::AccountNotFound)
+        }
+    }
+
+    /// Project the transfer fees, transfer hook effects and network fees of a
+    /// plan of transfers at current prices, without sending anything.
+    ///
+    /// Transfers are simulated without their authorities' signatures, so the
+    /// plan may use accounts whose keys are not at hand.
+    pub async fn simulate_distribution(
+        &self,
+        plan: &DistributionPlan,
+    ) -> TokenResult<DistributionProjection> {
+        let mint = self.get_mint_info().await?;
+        let epoch = self.get_epoch().await?;
+        let transfer_fee_config = mint.get_extension::<TransferFeeConfig>().ok();
+        let hook_program_id = transfer_hook::get_program_id(&mint);
+
+        let mut instructions = Vec::with_capacity(plan.transfers.len());
+        for transfer in &plan.transfers {
+            instructions.push(
+                self.transfer_checked_instruction(
+                    &transfer.source,
+                    &transfer.destination,
+                    &transfer.authority,
+                    &[],
+                    transfer.amount,
+                    mint.base.decimals,
+                )
+                .await?,
+            );
+        }
+        let compute_unit_price = match instructions.first() {
+            Some(instruction) => {
+                self.resolve_compute_unit_price(None, std::slice::from_ref(instruction))
+                    .await?
+            }
+            None => None,
         };
 
-        self.process_ixs(&instructions, signing_keypairs).await
+        let mut projections = Vec::with_capacity(plan.transfers.len());
+        for (transfer, instruction) in plan.transfers.iter().zip(instructions) {
+            // source, mint, destination and authority precede the hook's accounts
+            let hook_accounts = if hook_program_id.is_some() {
+                instruction.accounts.iter().skip(4).cloned().collect()
+            } else {
+                vec![]
+            };
+
+            let transaction = self
+                .construct_tx(
+                    &[instruction],
+                    None,
+                    compute_unit_price,
+                    &[] as &[&dyn Signer; 0],
+                )
+                .await?;
+            let simulation = self
+                .client
+                .simulate_transaction(&transaction)
+                .await
+                .map_err(TokenError::Client)?;
+
+            projections.push(TransferProjection {
+                transfer: transfer.clone(),
+                transfer_fee: transfer_fee_config
+                    .and_then(|config| config.calculate_epoch_fee(epoch, transfer.amount))
+                    .unwrap_or(0),
+                hook_program_id,
+                hook_accounts,
+                units_consumed: simulation.units_consumed(),
+                network_fee: network_fee(&transaction.message, None, compute_unit_price),
+                logs: simulation.logs().unwrap_or_default(),
+                return_data: simulation.return_data(),
+                error: simulation.error(),
+            });
+        }
+
+        Ok(DistributionProjection::new(projections, compute_unit_price))
     }
 
-    /// Transfer tokens to another account
+    /// Current clock, read from the clock sysvar
+    async fn get_clock(&self) -> TokenResult<Clock> {
+        let clock = self
+            .client
+            .get_sysvar_clock()
+            .await
+            .map_err(TokenError::Client)?;
+        self.rent_cache.observe_epoch(clock.epoch);
+        Ok(clock)
+    }
+
+    /// Current epoch, read from the clock sysvar
+    async fn get_epoch(&self) -> TokenResult<Epoch> {
+        Ok(self.get_clock().await?.epoch)
+    }
+
+    /// Transfer fee the mint currently charges, or `None` if the mint has no
+    /// transfer fee
+    async fn get_current_transfer_fee(&self) -> TokenResult<Option<TransferFee>> {
+        let mint = self.get_mint_info().await?;
+        self.get_epoch_transfer_fee(&mint).await
+    }
+
+    /// Transfer fee the given state of the mint charges in the current epoch
+    async fn get_epoch_transfer_fee(
+        &self,
+        mint: &StateWithExtensionsOwned<Mint>,
+    ) -> TokenResult<Option<TransferFee>> {
+        match mint.get_extension::<TransferFeeConfig>() {
+            Ok(config) => Ok(Some(*config.get_epoch_fee(self.get_epoch().await?))),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Fee withheld from a transfer of `amount` in the current epoch, zero
+    /// if the mint has no transfer fee
+    pub async fn calculate_transfer_fee(&self, amount: u64) -> TokenResult<u64> {
+        transfer_fee_amount(self.get_current_transfer_fee().await?.as_ref(), amount)
+    }
+
+    /// Amount the destination receives from a transfer of `amount` in the
+    /// current epoch
+    pub async fn calculate_post_fee_amount(&self, amount: u64) -> TokenResult<u64> {
+        post_fee_amount(self.get_current_transfer_fee().await?.as_ref(), amount)
+    }
+
+    /// Amount to transfer in the current epoch for the destination to receive
+    /// `post_fee_amount`, along with the fee withheld from it
+    pub async fn calculate_pre_fee_amount(&self, post_fee_amount: u64) -> TokenResult<(u64, u64)> {
+        pre_fee_amount(
+            self.get_current_transfer_fee().await?.as_ref(),
+            post_fee_amount,
+        )
+    }
+
+    /// Sign a transfer now against a durable nonce, and hold it in the journal
+    /// until `release_after` instead of sending it.
+    ///
+    /// Advancing the nonce invalidates every transaction signed against it, so
+    /// each pending time-locked transfer must use its own nonce account.
+    /// Returns the signature identifying the journal entry.
     #[allow(clippy::too_many_arguments)]
-    pub async fn transfer<S: Signers>(
+    pub async fn prepare_timelocked_transfer<S: Signers>(
         &self,
         source: &Pubkey,
         destination: &Pubkey,
         authority: &Pubkey,
         amount: u64,
+        release_after: UnixTimestamp,
+        nonce_account: &Pubkey,
+        nonce_authority: Arc<dyn Signer>,
         signing_keypairs: &S,
-    ) -> TokenResult<T::Output> {
+    ) -> TokenResult<Signature> {
+        let journal = self.journal.as_ref().ok_or(TokenError::MissingJournal)?;
+        if journal
+            .entries()
+            .map_err(TokenError::Persistence)?
+            .iter()
+            .any(|entry| entry.nonce_account == *nonce_account)
+        {
+            return Err(TokenError::NonceAccountInUse);
+        }
+
+        let nonce_data = match StateMut::<nonce::state::Versions>::state(
+            &self.get_account(*nonce_account).await?,
+        )
+        .map_err(|_| TokenError::InvalidNonceAccount)?
+        .state()
+        {
+            nonce::State::Initialized(data) => data.clone(),
+            nonce::State::Uninitialized => return Err(TokenError::InvalidNonceAccount),
+        };
+        if nonce_data.authority != nonce_authority.pubkey() {
+            return Err(TokenError::InvalidNonceAccount);
+        }
+        let nonce_blockhash = nonce_data.blockhash();
+
         let signing_pubkeys = signing_keypairs.pubkeys();
         let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+        let instruction = self
+            .transfer_instruction(source, destination, authority, &multisig_signers, amount)
+            .await?;
 
-        let fetch_account_data_fn = |address| {
-            self.client
-                .get_account(address)
-                .map_ok(|opt| opt.map(|acc| acc.data))
-        };
+        let payer_key = self.payer.pubkey();
+        let mut message = Message::new_with_nonce(
+            vec![instruction],
+            Some(&payer_key),
+            nonce_account,
+            &nonce_authority.pubkey(),
+        );
+        message.recent_blockhash = nonce_blockhash;
 
-        let instruction = if let Some(decimals) = self.decimals {
-            if let Some(transfer_hook_accounts) = &self.transfer_hook_accounts {
-                let mut instruction = instruction::transfer_checked(
-                    &self.program_id,
-                    source,
-                    self.get_address(),
-                    destination,
-                    authority,
-                    &multisig_signers,
-                    amount,
-                    decimals,
-                )?;
-                instruction.accounts.extend(transfer_hook_accounts.clone());
-                instruction
-            } else {
-                offchain::create_transfer_checked_instruction_with_extra_metas(
-                    &self.program_id,
-                    source,
-                    self.get_address(),
-                    destination,
-                    authority,
-                    &multisig_signers,
-                    amount,
-                    decimals,
-                    fetch_account_data_fn,
-                )
-                .await
-                .map_err(|_| TokenError::AccountNotFound)?
-            }
-        } else {
-            #[allow(deprecated)]
-            instruction::transfer(
-                &self.program_id,
-                source,
-                destination,
-                authority,
-                &multisig_signers,
-                amount,
-            )?
+        let mut transaction = Transaction::new_unsigned(message);
+        transaction
+            .try_partial_sign(&vec![self.payer.clone()], nonce_blockhash)
+            .map_err(|error| TokenError::Client(error.into()))?;
+        transaction
+            .try_partial_sign(&vec![nonce_authority.clone()], nonce_blockhash)
+            .map_err(|error| TokenError::Client(error.into()))?;
+        transaction
+            .try_partial_sign(signing_keypairs, nonce_blockhash)
+            .map_err(|error| TokenError::Client(error.into()))?;
+
+        let entry = JournalEntry {
+            transaction,
+            release_after,
+            nonce_account: *nonce_account,
+            nonce_authority: nonce_authority.pubkey(),
         };
+        let signature = entry.signature();
+        journal.insert(entry).map_err(TokenError::Persistence)?;
+        Ok(signature)
+    }
 
-        self.process_ixs(&[instruction], signing_keypairs).await
+    /// Submit a time-locked transfer from the journal once the cluster's clock
+    /// has reached its release time
+    pub async fn release_timelocked_transfer(
+        &self,
+        signature: &Signature,
+    ) -> TokenResult<T::Output> {
+        let journal = self.journal.as_ref().ok_or(TokenError::MissingJournal)?;
+        let entry = journal
+            .get(signature)
+            .map_err(TokenError::Persistence)?
+            .ok_or(TokenError::JournalEntryNotFound)?;
+
+        // the cluster's clock, so a skewed local clock cannot release early
+        let now = self.get_clock().await?.unix_timestamp;
+        if now < entry.release_after {
+            return Err(TokenError::TimelockActive(entry.release_after));
+        }
+
+        let output = self
+            .client
+            .send_transaction(&entry.transaction)
+            .await
+            .map_err(TokenError::Client)?;
+        journal.remove(signature).map_err(TokenError::Persistence)?;
+        Ok(output)
+    }
+
+    /// Cancel a time-locked transfer by advancing its durable nonce, which
+    /// invalidates the withheld transaction, and remove it from the journal
+    pub async fn cancel_timelocked_transfer<S: Signers>(
+        &self,
+        signature: &Signature,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let journal = self.journal.as_ref().ok_or(TokenError::MissingJournal)?;
+        let entry = journal
+            .get(signature)
+            .map_err(TokenError::Persistence)?
+            .ok_or(TokenError::JournalEntryNotFound)?;
+
+        let output = self
+            .process_ixs(
+                &[system_instruction::advance_nonce_account(
+                    &entry.nonce_account,
+                    &entry.nonce_authority,
+                )],
+                signing_keypairs,
+            )
+            .await?;
+        journal.remove(signature).map_err(TokenError::Persistence)?;
+        Ok(output)
     }
 
     /// Transfer tokens to an associated account, creating it if it does not
@@ -1005,6 +4200,7 @@ where
         fee: Option<u64>,
         signing_keypairs: &S,
     ) -> TokenResult<T::Output> {
+        self.check_transfer(source, destination, amount)?;
         let signing_pubkeys = signing_keypairs.pubkeys();
         let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
 
@@ -1014,7 +4210,12 @@ where
                 .map_ok(|opt| opt.map(|acc| acc.data))
         };
 
-        if *destination != self.get_associated_token_address(destination_owner) {
+        if *destination
+            != self.get_associated_token_address_checked(
+                destination_owner,
+                !self.guards.reject_owners_off_curve,
+            )?
+        {
             return Err(TokenError::AccountInvalidAssociatedAddress);
         }
 
@@ -1082,6 +4283,7 @@ where
                 amount,
             )?);
         }
+        instructions.extend(self.sync_native_destination_instruction(destination)?);
 
         self.process_ixs(&instructions, signing_keypairs).await
     }
@@ -1097,6 +4299,7 @@ where
         fee: u64,
         signing_keypairs: &S,
     ) -> TokenResult<T::Output> {
+        self.check_transfer(source, destination, amount)?;
         let signing_pubkeys = signing_keypairs.pubkeys();
         let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
         let decimals = self.decimals.ok_or(TokenError::MissingDecimals)?;
@@ -1118,6 +4321,252 @@ where
         .await
     }
 
+    /// Send a payment, picking the transfer variant required by the mint and
+    /// the destination: a plain or hooked transfer, a transfer asserting the
+    /// transfer fee, or a confidential transfer.
+    ///
+    /// Confidential payments are drawn from the source's available
+    /// confidential balance, with proofs generated inline, and require a
+    /// destination configured for confidential transfers, so they never
+    /// create the destination.
+    pub async fn send_payment<S: Signers>(
+        &self,
+        request: &PaymentRequest<'_>,
+        signing_keypairs: &S,
+    ) -> TokenResult<PaymentReceipt<T::Output>> {
+        let mint = self.get_mint_info().await?;
+        let amount = match &request.amount {
+            PaymentAmount::Raw(amount) => *amount,
+            PaymentAmount::Ui(amount) => {
+                format::parse_amount(amount, mint.base.decimals, &AmountFormat::new())
+                    .map_err(TokenError::InvalidAmount)?
+            }
+        };
+
+        let (destination, destination_owner, destination_account) = match request.destination {
+            PaymentDestination::Account(address) => {
+                (address, None, Some(self.get_account_info(&address).await?))
+            }
+            PaymentDestination::Owner(owner) => {
+                let address = self.get_associated_token_address(&owner);
+                match self.get_account_info(&address).await {
+                    Ok(account) => (address, Some(owner), Some(account)),
+                    Err(TokenError::AccountNotFound)
+                        if request.create_ata == CreateAtaPolicy::IfMissing =>
+                    {
+                        (address, Some(owner), None)
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+        };
+        self.check_transfer(&request.source, &destination, amount)?;
+
+        let epoch_fee = self.get_epoch_transfer_fee(&mint).await?;
+        let fee = transfer_fee_amount(epoch_fee.as_ref(), amount)?;
+        if let Some(max_fee) = request.max_fee {
+            if fee > max_fee {
+                return Err(TokenError::FeeExceedsTolerance { fee, max_fee });
+            }
+        }
+
+        let confidential = match request.confidentiality {
+            Confidentiality::Public => None,
+            // only the absence of confidential transfers falls back to a
+            // public transfer, not e.g. a failure to fetch the source
+            Confidentiality::Preferred => match self
+                .confidential_payment_keys(request, &mint, destination_account.as_ref(), amount)
+                .await
+            {
+                Ok(confidential) => Some(confidential),
+                Err(TokenError::ConfidentialTransferUnavailable) => None,
+                Err(error) => return Err(error),
+            },
+            Confidentiality::Required => Some(
+                self.confidential_payment_keys(
+                    request,
+                    &mint,
+                    destination_account.as_ref(),
+                    amount,
+                )
+                .await?,
+            ),
+        };
+
+        let receipt = |output, kind| PaymentReceipt {
+            output,
+            kind,
+            destination,
+            amount,
+            fee,
+            created_destination: destination_account.is_none(),
+        };
+
+        if let Some((keys, account_info, destination_elgamal_pubkey, auditor_elgamal_pubkey)) =
+            confidential
+        {
+            let withdraw_withheld_authority_elgamal_pubkey = match epoch_fee {
+                Some(_) => Some(
+                    ElGamalPubkey::try_from(
+                        mint.get_extension::<ConfidentialTransferFeeConfig>()?
+                            .withdraw_withheld_authority_elgamal_pubkey,
+                    )
+                    .map_err(|_| ProgramError::InvalidAccountData)?,
+                ),
+                None => None,
+            };
+
+            // the memo is taken by the transaction the confidential transfer
+            // sends next, and dropped if it fails before sending one
+            if let Some(memo) = &request.memo {
+                self.with_memo(memo, vec![]);
+            }
+            let output =
+                if let (Some(transfer_fee), Some(withdraw_withheld_authority_elgamal_pubkey)) =
+                    (epoch_fee, withdraw_withheld_authority_elgamal_pubkey)
+                {
+                    self.confidential_transfer_transfer_with_fee(
+                        &request.source,
+                        &destination,
+                        &request.authority,
+                        None,
+                        amount,
+                        Some(account_info),
+                        keys.elgamal_keypair,
+                        keys.aes_key,
+                        &destination_elgamal_pubkey,
+                        auditor_elgamal_pubkey.as_ref(),
+                        &withdraw_withheld_authority_elgamal_pubkey,
+                        transfer_fee.transfer_fee_basis_points.into(),
+                        transfer_fee.maximum_fee.into(),
+                        signing_keypairs,
+                    )
+                    .await
+                } else {
+                    self.confidential_transfer_transfer(
+                        &request.source,
+                        &destination,
+                        &request.authority,
+                        None,
+                        amount,
+                        Some(account_info),
+                        keys.elgamal_keypair,
+                        keys.aes_key,
+                        &destination_elgamal_pubkey,
+                        auditor_elgamal_pubkey.as_ref(),
+                        signing_keypairs,
+                    )
+                    .await
+                };
+            if output.is_err() {
+                self.memo.write().unwrap().take();
+            }
+            return Ok(receipt(output?, PaymentKind::Confidential));
+        }
+
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(&request.authority, &signing_pubkeys);
+        let mut instructions = vec![];
+        if let Some(memo) = &request.memo {
+            instructions.push(spl_memo::build_memo(memo.as_bytes(), &[]));
+        }
+        if let (None, Some(owner)) = (&destination_account, destination_owner) {
+            instructions.push(create_associated_token_account_idempotent(
+                &self.payer.pubkey(),
+                &owner,
+                &self.pubkey,
+                &self.program_id,
+            ));
+        }
+        let kind = if transfer_hook::get_program_id(&mint).is_some() {
+            PaymentKind::HookedTransfer
+        } else if epoch_fee.is_some() {
+            PaymentKind::TransferWithFee
+        } else {
+            PaymentKind::Transfer
+        };
+        instructions.push(match kind {
+            PaymentKind::TransferWithFee => transfer_fee::instruction::transfer_checked_with_fee(
+                &self.program_id,
+                &request.source,
+                &self.pubkey,
+                &destination,
+                &request.authority,
+                &multisig_signers,
+                amount,
+                mint.base.decimals,
+                fee,
+            )?,
+            _ => {
+                self.transfer_checked_instruction(
+                    &request.source,
+                    &destination,
+                    &request.authority,
+                    &multisig_signers,
+                    amount,
+                    mint.base.decimals,
+                )
+                .await?
+            }
+        });
+
+        let output = self.process_ixs(&instructions, signing_keypairs).await?;
+        Ok(receipt(output, kind))
+    }
+
+    /// Keys and source state for a confidential payment, failing unless the
+    /// mint and both accounts allow it and the source's available balance
+    /// covers the amount
+    async fn confidential_payment_keys<'a>(
+        &self,
+        request: &PaymentRequest<'a>,
+        mint: &StateWithExtensionsOwned<Mint>,
+        destination_account: Option<&StateWithExtensionsOwned<Account>>,
+        amount: u64,
+    ) -> TokenResult<(
+        ConfidentialKeys<'a>,
+        TransferAccountInfo,
+        ElGamalPubkey,
+        Option<ElGamalPubkey>,
+    )> {
+        let keys = request
+            .confidential_keys
+            .ok_or(TokenError::ConfidentialTransferUnavailable)?;
+        let confidential_mint = mint
+            .get_extension::<confidential_transfer::ConfidentialTransferMint>()
+            .map_err(|_| TokenError::ConfidentialTransferUnavailable)?;
+        let destination = destination_account
+            .and_then(|account| account.get_extension::<ConfidentialTransferAccount>().ok())
+            .filter(|destination| {
+                bool::from(destination.approved)
+                    && bool::from(destination.allow_confidential_credits)
+            })
+            .ok_or(TokenError::ConfidentialTransferUnavailable)?;
+        let source = self.get_account_info(&request.source).await?;
+        let account_info = TransferAccountInfo::new(
+            source
+                .get_extension::<ConfidentialTransferAccount>()
+                .map_err(|_| TokenError::ConfidentialTransferUnavailable)?,
+        );
+        account_info
+            .new_decryptable_available_balance(amount, keys.aes_key)
+            .map_err(|_| TokenError::NotEnoughFunds)?;
+
+        let destination_elgamal_pubkey = ElGamalPubkey::try_from(destination.elgamal_pubkey)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let auditor_elgamal_pubkey =
+            Option::<PodElGamalPubkey>::from(confidential_mint.auditor_elgamal_pubkey)
+                .map(ElGamalPubkey::try_from)
+                .transpose()
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+        Ok((
+            keys,
+            account_info,
+            destination_elgamal_pubkey,
+            auditor_elgamal_pubkey,
+        ))
+    }
+
     /// Burn tokens from account
     pub async fn burn<S: Signers>(
         &self,
@@ -1126,31 +4575,54 @@ where
         amount: u64,
         signing_keypairs: &S,
     ) -> TokenResult<T::Output> {
+        self.check_burn(source, amount).await?;
         let signing_pubkeys = signing_keypairs.pubkeys();
         let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
 
-        let instructions = if let Some(decimals) = self.decimals {
-            [instruction::burn_checked(
+        let instructions = [self.burn_instruction(source, authority, &multisig_signers, amount)?];
+
+        self.process_ixs(&instructions, signing_keypairs).await
+    }
+
+    /// Burn the whole balance of an account
+    pub async fn burn_all<S: Signers>(
+        &self,
+        source: &Pubkey,
+        authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let amount = self.get_account_info(source).await?.base.amount;
+        self.burn(source, authority, amount, signing_keypairs).await
+    }
+
+    pub(crate) fn burn_instruction(
+        &self,
+        source: &Pubkey,
+        authority: &Pubkey,
+        multisig_signers: &[&Pubkey],
+        amount: u64,
+    ) -> TokenResult<Instruction> {
+        let instruction = if let Some(decimals) = self.decimals {
+            instruction::burn_checked(
                 &self.program_id,
                 source,
                 &self.pubkey,
                 authority,
-                &multisig_signers,
+                multisig_signers,
                 amount,
                 decimals,
-            )?]
+            )?
         } else {
-            [instruction::burn(
+            instruction::burn(
                 &self.program_id,
                 source,
                 &self.pubkey,
                 authority,
-                &multisig_signers,
+                multisig_signers,
                 amount,
-            )?]
+            )?
         };
-
-        self.process_ixs(&instructions, signing_keypairs).await
+        Ok(instruction)
     }
 
     /// Approve a delegate to spend tokens
@@ -1248,6 +4720,41 @@ where
         self.process_ixs(&instructions, signing_keypairs).await
     }
 
+    /// Close the mint through its close authority, which requires the
+    /// `MintCloseAuthority` extension and no supply left
+    pub async fn close_mint<S: Signers>(
+        &self,
+        lamports_destination: &Pubkey,
+        close_authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(close_authority, &signing_pubkeys);
+
+        let mint = self.get_mint_info().await?;
+        if mint.base.supply != 0 {
+            return Err(TokenError::MintHasSupply(mint.base.supply));
+        }
+        let extension = mint
+            .get_extension::<MintCloseAuthority>()
+            .map_err(|_| TokenError::MissingExtension(ExtensionType::MintCloseAuthority))?;
+        if Option::<Pubkey>::from(extension.close_authority) != Some(*close_authority) {
+            return Err(TokenError::InvalidCloseAuthority(*close_authority));
+        }
+
+        self.process_ixs(
+            &[instruction::close_account(
+                &self.program_id,
+                &self.pubkey,
+                lamports_destination,
+                close_authority,
+                &multisig_signers,
+            )?],
+            signing_keypairs,
+        )
+        .await
+    }
+
     /// Close an account, reclaiming its lamports and tokens
     pub async fn empty_and_close_account<S: Signers>(
         &self,
@@ -1316,6 +4823,179 @@ where
         self.process_ixs(&instructions, signing_keypairs).await
     }
 
+    /// Empty and close many accounts of the same authority, packed into as
+    /// few transactions as fit. Balances are moved to `tokens_destination`,
+    /// or burned without one. The outcome holds the result for each account,
+    /// in order.
+    pub async fn empty_and_close_many<S: Signers>(
+        &self,
+        accounts: &[Pubkey],
+        lamports_destination: &Pubkey,
+        tokens_destination: Option<&Pubkey>,
+        authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<BatchOutcome<T::Output>> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        // this implicitly validates that the mint on self is correct
+        let account_states = try_join_all(
+            accounts
+                .iter()
+                .map(|account| self.get_account_info(account)),
+        )
+        .await?;
+        let sync_destination = match self.client.get_account(*lamports_destination).await {
+            Ok(Some(destination_account)) => {
+                StateWithExtensionsOwned::<Account>::unpack(destination_account.data)
+                    .map(|destination| destination.base.is_native())
+                    .unwrap_or(false)
+            }
+            _ => false,
+        };
+
+        let mut groups = Vec::with_capacity(accounts.len());
+        for (account, account_state) in accounts.iter().zip(&account_states) {
+            let amount = account_state.base.amount;
+            let mut instructions = vec![];
+            if !self.is_native() && amount > 0 {
+                instructions.push(match tokens_destination {
+                    Some(tokens_destination) => {
+                        self.transfer_instruction(
+                            account,
+                            tokens_destination,
+                            authority,
+                            &multisig_signers,
+                            amount,
+                        )
+                        .await?
+                    }
+                    None => self.burn_instruction(account, authority, &multisig_signers, amount)?,
+                });
+            }
+            instructions.push(instruction::close_account(
+                &self.program_id,
+                account,
+                lamports_destination,
+                authority,
+                &multisig_signers,
+            )?);
+            // transactions may land in any order, so each one syncs
+            if sync_destination {
+                instructions.push(instruction::sync_native(
+                    &self.program_id,
+                    lamports_destination,
+                )?);
+            }
+            groups.push(instructions);
+        }
+
+        Ok(self.process_ix_batches(&groups, signing_keypairs).await)
+    }
+
+    /// Close the owner's empty accounts of the mint other than its associated
+    /// account, reclaiming their rent to the owner, like `spl-token gc`. With
+    /// `consolidate`, accounts holding tokens are first emptied into the
+    /// associated account, which is created if needed.
+    ///
+    /// Frozen accounts, accounts with another close authority and accounts
+    /// holding withheld fees or confidential balances are left alone. Returns
+    /// the accounts swept, in the order of the outcome's items.
+    pub async fn gc<S: Signers>(
+        &self,
+        owner: &Pubkey,
+        consolidate: bool,
+        signing_keypairs: &S,
+    ) -> TokenResult<(Vec<Pubkey>, BatchOutcome<T::Output>)> {
+        self.sweep_auxiliary_accounts(owner, consolidate, true, signing_keypairs)
+            .await
+    }
+
+    /// Move the balances of the owner's auxiliary accounts into its associated
+    /// account, which is created if needed, and close the emptied accounts if
+    /// `close`. Returns the accounts swept, in the order of the outcome's
+    /// items.
+    pub async fn consolidate<S: Signers>(
+        &self,
+        owner: &Pubkey,
+        close: bool,
+        signing_keypairs: &S,
+    ) -> TokenResult<(Vec<Pubkey>, BatchOutcome<T::Output>)> {
+        self.sweep_auxiliary_accounts(owner, true, close, signing_keypairs)
+            .await
+    }
+
+    /// Empty the owner's auxiliary accounts into its associated account if
+    /// `consolidate`, and close them if `close`
+    async fn sweep_auxiliary_accounts<S: Signers>(
+        &self,
+        owner: &Pubkey,
+        consolidate: bool,
+        close: bool,
+        signing_keypairs: &S,
+    ) -> TokenResult<(Vec<Pubkey>, BatchOutcome<T::Output>)> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(owner, &signing_pubkeys);
+        let associated_token_address = self.get_associated_token_address(owner);
+
+        let mut swept = vec![];
+        let mut groups = vec![];
+        let mut transfers = false;
+        for (address, account) in self.get_auxiliary_token_accounts(owner).await? {
+            let amount = account.base.amount;
+            if account.base.is_frozen() || (amount > 0 && !consolidate) {
+                continue;
+            }
+            let mut instructions = vec![];
+            if amount > 0 {
+                transfers = true;
+                instructions.push(
+                    self.transfer_instruction(
+                        &address,
+                        &associated_token_address,
+                        owner,
+                        &multisig_signers,
+                        amount,
+                    )
+                    .await?,
+                );
+            }
+            let close_authority = account.base.close_authority.unwrap_or(account.base.owner);
+            if close && close_authority == *owner && extensions_closable(&account) {
+                instructions.push(instruction::close_account(
+                    &self.program_id,
+                    &address,
+                    owner,
+                    owner,
+                    &multisig_signers,
+                )?);
+            }
+            if !instructions.is_empty() {
+                swept.push(address);
+                groups.push(instructions);
+            }
+        }
+
+        // the destination must exist before any of the batches lands
+        if transfers {
+            self.process_ixs::<[&dyn Signer; 0]>(
+                &[create_associated_token_account_idempotent(
+                    &self.payer.pubkey(),
+                    owner,
+                    &self.pubkey,
+                    &self.program_id,
+                )],
+                &[],
+            )
+            .await?;
+        }
+
+        Ok((
+            swept,
+            self.process_ix_batches(&groups, signing_keypairs).await,
+        ))
+    }
+
     /// Freeze a token account
     pub async fn freeze<S: Signers>(
         &self,
@@ -1362,6 +5042,60 @@ where
         .await
     }
 
+    /// Freeze many accounts of the mint, packed into as few transactions as
+    /// fit. The outcome holds the result for each account, in order.
+    pub async fn freeze_many<S: Signers>(
+        &self,
+        accounts: &[Pubkey],
+        authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<BatchOutcome<T::Output>> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        let groups = accounts
+            .iter()
+            .map(|account| {
+                Ok(vec![instruction::freeze_account(
+                    &self.program_id,
+                    account,
+                    &self.pubkey,
+                    authority,
+                    &multisig_signers,
+                )?])
+            })
+            .collect::<TokenResult<Vec<_>>>()?;
+
+        Ok(self.process_ix_batches(&groups, signing_keypairs).await)
+    }
+
+    /// Thaw many accounts of the mint, packed into as few transactions as
+    /// fit. The outcome holds the result for each account, in order.
+    pub async fn thaw_many<S: Signers>(
+        &self,
+        accounts: &[Pubkey],
+        authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<BatchOutcome<T::Output>> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        let groups = accounts
+            .iter()
+            .map(|account| {
+                Ok(vec![instruction::thaw_account(
+                    &self.program_id,
+                    account,
+                    &self.pubkey,
+                    authority,
+                    &multisig_signers,
+                )?])
+            })
+            .collect::<TokenResult<Vec<_>>>()?;
+
+        Ok(self.process_ix_batches(&groups, signing_keypairs).await)
+    }
+
     /// Wrap lamports into native account
     pub async fn wrap<S: Signers>(
         &self,
@@ -1442,7 +5176,52 @@ where
             )?);
         };
 
-        Ok(instructions)
+        Ok(instructions)
+    }
+
+    /// Unwrap part of the owner's wrapped SOL: the amount moves from the
+    /// owner's associated account to a temporary account derived from the
+    /// owner, which is closed to the owner in the same transaction. The owner
+    /// signs and funds the temporary account, getting its rent back.
+    pub async fn unwrap<S: Signers>(
+        &self,
+        amount: u64,
+        owner: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        if !self.is_native() {
+            return Err(TokenError::AccountInvalidMint);
+        }
+
+        let source = self.get_associated_token_address(owner);
+        let account = self.get_address_with_seed(owner, UNWRAP_SEED)?;
+        let extensions = self.required_account_extensions(vec![]).await?;
+        let space = ExtensionType::try_calculate_account_len::<Account>(&extensions)?;
+
+        let mut instructions = vec![system_instruction::create_account_with_seed(
+            owner,
+            &account,
+            owner,
+            UNWRAP_SEED,
+            self.get_minimum_balance_for_rent_exemption(space).await?,
+            space as u64,
+            &self.program_id,
+        )];
+        instructions.extend(self.initialize_account_instructions(&account, owner, &extensions)?);
+        instructions.push(
+            self.transfer_instruction(&source, &account, owner, &[], amount)
+                .await?,
+        );
+        instructions.push(instruction::close_account(
+            &self.program_id,
+            &account,
+            owner,
+            owner,
+            &[],
+        )?);
+        instructions.push(instruction::sync_native(&self.program_id, &source)?);
+
+        self.process_ixs(&instructions, signing_keypairs).await
     }
 
     /// Sync native account lamports
@@ -1572,6 +5351,87 @@ where
         .await
     }
 
+    /// Withdraw withheld tokens from any number of accounts, split across as
+    /// many transactions as needed to fit. The outcome holds the result for
+    /// each source, in order.
+    pub async fn withdraw_withheld_tokens_from_many_accounts<S: Signers>(
+        &self,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        sources: &[&Pubkey],
+        signing_keypairs: &S,
+    ) -> TokenResult<BatchOutcome<T::Output>> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        let instruction = |run: Range<usize>| {
+            transfer_fee::instruction::withdraw_withheld_tokens_from_accounts(
+                &self.program_id,
+                &self.pubkey,
+                destination,
+                authority,
+                &multisig_signers,
+                &sources[run],
+            )
+        };
+        let runs = batch::chunk(sources.len(), &self.payer.pubkey(), instruction)?;
+        let groups = runs
+            .iter()
+            .map(|run| Ok(vec![instruction(run.clone())?]))
+            .collect::<TokenResult<Vec<_>>>()?;
+
+        Ok(self
+            .process_ix_batches(&groups, signing_keypairs)
+            .await
+            .for_runs(&runs))
+    }
+
+    /// Find every account of the mint withholding transfer fees, with the
+    /// amount withheld, e.g. as the sources to withdraw withheld tokens from.
+    ///
+    /// The extension is not at a fixed offset, so accounts are scanned by mint
+    /// and their withheld amounts checked locally.
+    pub async fn find_accounts_with_withheld_fees(&self) -> TokenResult<Vec<(Pubkey, u64)>> {
+        Ok(self
+            .get_token_accounts(vec![])
+            .await?
+            .into_iter()
+            .filter_map(|(address, account)| {
+                let withheld_amount: u64 = account
+                    .get_extension::<TransferFeeAmount>()
+                    .ok()?
+                    .withheld_amount
+                    .into();
+                (withheld_amount > 0).then_some((address, withheld_amount))
+            })
+            .collect())
+    }
+
+    /// Transfer fees withheld in the given accounts, read in one request,
+    /// e.g. when a client cannot scan the mint's accounts. Accounts that are
+    /// closed, of another mint, or withhold nothing are left out.
+    pub async fn get_withheld_fees(&self, accounts: &[Pubkey]) -> TokenResult<Vec<(Pubkey, u64)>> {
+        let states = self
+            .client
+            .get_multiple_accounts(accounts)
+            .await
+            .map_err(TokenError::Client)?;
+        Ok(accounts
+            .iter()
+            .zip(states)
+            .filter_map(|(address, account)| {
+                let account =
+                    unpack_token_account(&self.program_id, &self.pubkey, account?).ok()?;
+                let withheld_amount: u64 = account
+                    .get_extension::<TransferFeeAmount>()
+                    .ok()?
+                    .withheld_amount
+                    .into();
+                (withheld_amount > 0).then_some((*address, withheld_amount))
+            })
+            .collect())
+    }
+
     /// Reallocate a token account to be large enough for a set of
     /// ExtensionTypes
     pub async fn reallocate<S: Signers>(
@@ -1598,6 +5458,35 @@ where
         .await
     }
 
+    /// Reallocate an account for the extensions the mint requires of its
+    /// accounts but the account lacks, e.g. after the mint gained some. The
+    /// payer funds the additional rent. Returns `None` without sending if the
+    /// account already has them all.
+    pub async fn upgrade_account_extensions<S: Signers>(
+        &self,
+        account: &Pubkey,
+        authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<Option<T::Output>> {
+        let account_extensions = self
+            .get_account_info(account)
+            .await?
+            .get_extension_types()?;
+        let missing_extensions = self
+            .required_account_extensions(vec![])
+            .await?
+            .into_iter()
+            .filter(|extension_type| !account_extensions.contains(extension_type))
+            .collect::<Vec<_>>();
+        if missing_extensions.is_empty() {
+            return Ok(None);
+        }
+
+        self.reallocate(account, authority, &missing_extensions, signing_keypairs)
+            .await
+            .map(Some)
+    }
+
     /// Require memos on transfers into this account
     pub async fn enable_required_transfer_memos<S: Signers>(
         &self,
@@ -1709,6 +5598,55 @@ where
         .await
     }
 
+    /// Reconstruct the history of the mint's interest rate from its current
+    /// configuration and the transactions that initialized or updated it.
+    ///
+    /// Transactions already parsed are cached, so later calls only fetch the
+    /// mint's newer history. Each call scans at most `limit` of the mint's
+    /// transactions, newest first; older rate changes are missing from the
+    /// history, apart from what the configuration records of the
+    /// initialization and last update.
+    pub async fn get_rate_history(&self, limit: usize) -> TokenResult<Vec<RateChange>> {
+        let mint = self.get_mint_info().await?;
+        let config = mint.get_extension::<InterestBearingConfig>()?;
+
+        let until = self.rate_history_cache.lock().unwrap().newest_signature;
+        let mut signatures = vec![];
+        let mut pages = self.signature_pages(&self.pubkey, until, limit);
+        while let Some(page) = pages.next().await {
+            signatures.extend(page?);
+        }
+
+        let mut orchestrator = Orchestrator::new(self.max_concurrency);
+        for signature in &signatures {
+            orchestrator = orchestrator.step(
+                signature.to_string(),
+                self.client
+                    .get_transaction(signature)
+                    .map_err(TokenError::Client)
+                    .boxed_local(),
+            );
+        }
+        let new_changes = orchestrator
+            .run()
+            .await?
+            .iter()
+            .flatten()
+            .rev()
+            .flat_map(|record| parse_rate_changes(&self.program_id, &self.pubkey, record))
+            .collect::<Vec<_>>();
+
+        let mut cache = self.rate_history_cache.lock().unwrap();
+        // another call may have already extended the cache
+        if cache.newest_signature == until {
+            if let Some(newest_signature) = signatures.first() {
+                cache.newest_signature = Some(*newest_signature);
+                cache.changes.extend(new_changes);
+            }
+        }
+        Ok(reconstruct_rate_history(config, &cache.changes))
+    }
+
     /// Update transfer hook program id
     pub async fn update_transfer_hook_program_id<S: Signers>(
         &self,
@@ -1732,6 +5670,37 @@ where
         .await
     }
 
+    /// Create the validation account listing the extra accounts the hook
+    /// program requires for transfers of the mint, funding it from the payer
+    /// for its rent. The hook program initializes it, requiring the mint
+    /// authority to sign.
+    pub async fn create_transfer_hook_validation_account<S: Signers>(
+        &self,
+        hook_program_id: &Pubkey,
+        mint_authority: &Pubkey,
+        extra_account_metas: &[ExtraAccountMeta],
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let validation_address = get_extra_account_metas_address(&self.pubkey, hook_program_id);
+        let space = ExtraAccountMetaList::size_of(extra_account_metas.len())?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
+
+        self.process_ixs(
+            &[
+                system_instruction::transfer(&self.payer.pubkey(), &validation_address, rent),
+                initialize_extra_account_meta_list(
+                    hook_program_id,
+                    &validation_address,
+                    &self.pubkey,
+                    mint_authority,
+                    extra_account_metas,
+                ),
+            ],
+            signing_keypairs,
+        )
+        .await
+    }
+
     /// Update metadata pointer address
     pub async fn update_metadata_address<S: Signers>(
         &self,
@@ -1882,6 +5851,29 @@ where
         .await
     }
 
+    /// `confidential_transfer_configure_token_account` with the account's
+    /// keys, e.g. from a `Keyring`
+    pub async fn confidential_transfer_configure_token_account_with_keys<S: Signers>(
+        &self,
+        account: &Pubkey,
+        authority: &Pubkey,
+        context_state_account: Option<&Pubkey>,
+        maximum_pending_balance_credit_counter: Option<u64>,
+        keys: &ConfidentialAccountKeys,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        self.confidential_transfer_configure_token_account(
+            account,
+            authority,
+            context_state_account,
+            maximum_pending_balance_credit_counter,
+            &keys.elgamal_keypair,
+            &keys.aes_key,
+            signing_keypairs,
+        )
+        .await
+    }
+
     /// Approves a token account for confidential transfers
     pub async fn confidential_transfer_approve_account<S: Signers>(
         &self,
@@ -1958,6 +5950,28 @@ where
         .await
     }
 
+    /// `confidential_transfer_empty_account` with the account's keys, e.g.
+    /// from a `Keyring`
+    pub async fn confidential_transfer_empty_account_with_keys<S: Signers>(
+        &self,
+        account: &Pubkey,
+        authority: &Pubkey,
+        context_state_account: Option<&Pubkey>,
+        account_info: Option<EmptyAccountAccountInfo>,
+        keys: &ConfidentialAccountKeys,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        self.confidential_transfer_empty_account(
+            account,
+            authority,
+            context_state_account,
+            account_info,
+            &keys.elgamal_keypair,
+            signing_keypairs,
+        )
+        .await
+    }
+
     /// Deposit SPL Tokens into the pending balance of a confidential token
     /// account
     pub async fn confidential_transfer_deposit<S: Signers>(
@@ -2063,11 +6077,7 @@ where
         // create withdraw proof context state
         let instruction_type = ProofInstruction::VerifyWithdraw;
         let space = size_of::<ProofContextState<WithdrawProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
 
         let withdraw_proof_context_state_info = ContextStateInfo {
             context_state_account,
@@ -2094,6 +6104,34 @@ where
         .await
     }
 
+    /// `confidential_transfer_withdraw` with the account's keys, e.g. from a
+    /// `Keyring`
+    #[allow(clippy::too_many_arguments)]
+    pub async fn confidential_transfer_withdraw_with_keys<S: Signers>(
+        &self,
+        account: &Pubkey,
+        authority: &Pubkey,
+        context_state_account: Option<&Pubkey>,
+        withdraw_amount: u64,
+        decimals: u8,
+        account_info: Option<WithdrawAccountInfo>,
+        keys: &ConfidentialAccountKeys,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        self.confidential_transfer_withdraw(
+            account,
+            authority,
+            context_state_account,
+            withdraw_amount,
+            decimals,
+            account_info,
+            &keys.elgamal_keypair,
+            &keys.aes_key,
+            signing_keypairs,
+        )
+        .await
+    }
+
     /// Transfer tokens confidentially
     #[allow(clippy::too_many_arguments)]
     pub async fn confidential_transfer_transfer<S: Signers>(
@@ -2177,6 +6215,38 @@ where
         self.process_ixs(&instructions, signing_keypairs).await
     }
 
+    /// `confidential_transfer_transfer` with the keys of the source account,
+    /// e.g. from a `Keyring`
+    #[allow(clippy::too_many_arguments)]
+    pub async fn confidential_transfer_transfer_with_keys<S: Signers>(
+        &self,
+        source_account: &Pubkey,
+        destination_account: &Pubkey,
+        source_authority: &Pubkey,
+        context_state_account: Option<&Pubkey>,
+        transfer_amount: u64,
+        account_info: Option<TransferAccountInfo>,
+        source_keys: &ConfidentialAccountKeys,
+        destination_elgamal_pubkey: &ElGamalPubkey,
+        auditor_elgamal_pubkey: Option<&ElGamalPubkey>,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        self.confidential_transfer_transfer(
+            source_account,
+            destination_account,
+            source_authority,
+            context_state_account,
+            transfer_amount,
+            account_info,
+            &source_keys.elgamal_keypair,
+            &source_keys.aes_key,
+            destination_elgamal_pubkey,
+            auditor_elgamal_pubkey,
+            signing_keypairs,
+        )
+        .await
+    }
+
     /// Transfer tokens confidentially using split proofs.
     ///
     /// This function assumes that proof context states have already been
@@ -2327,10 +6397,17 @@ where
                 range_proof_signers,
             );
 
-        try_join!(
-            transfer_with_equality_and_ciphertext_validity,
-            transfer_with_range_proof
-        )
+        let mut outputs = Orchestrator::new(self.max_concurrency)
+            .step(
+                "equality and ciphertext validity proofs",
+                transfer_with_equality_and_ciphertext_validity.boxed_local(),
+            )
+            .step("range proof", transfer_with_range_proof.boxed_local())
+            .run()
+            .await?
+            .into_iter();
+        // one output per step, in order
+        Ok((outputs.next().unwrap(), outputs.next().unwrap()))
     }
 
     /// Create equality proof context state account for a confidential transfer.
@@ -2344,11 +6421,7 @@ where
         // create equality proof context state
         let instruction_type = ProofInstruction::VerifyCiphertextCommitmentEquality;
         let space = size_of::<ProofContextState<CiphertextCommitmentEqualityProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
 
         let equality_proof_context_state_info = ContextStateInfo {
             context_state_account: context_state_accounts.equality_proof,
@@ -2386,11 +6459,7 @@ where
         let instruction_type = ProofInstruction::VerifyBatchedGroupedCiphertext2HandlesValidity;
         let space =
             size_of::<ProofContextState<BatchedGroupedCiphertext2HandlesValidityProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
 
         let ciphertext_validity_proof_context_state_info = ContextStateInfo {
             context_state_account: context_state_accounts.ciphertext_validity_proof,
@@ -2482,11 +6551,7 @@ where
         // create equality proof context state
         let instruction_type = ProofInstruction::VerifyCiphertextCommitmentEquality;
         let space = size_of::<ProofContextState<CiphertextCommitmentEqualityProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
         instructions.push(system_instruction::create_account(
             &self.payer.pubkey(),
             context_state_accounts.equality_proof,
@@ -2508,11 +6573,7 @@ where
         let instruction_type = ProofInstruction::VerifyBatchedGroupedCiphertext2HandlesValidity;
         let space =
             size_of::<ProofContextState<BatchedGroupedCiphertext2HandlesValidityProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
         instructions.push(system_instruction::create_account(
             &self.payer.pubkey(),
             context_state_accounts.ciphertext_validity_proof,
@@ -2547,11 +6608,7 @@ where
     ) -> TokenResult<T::Output> {
         let instruction_type = ProofInstruction::VerifyBatchedRangeProofU128;
         let space = size_of::<ProofContextState<BatchedRangeProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
         let range_proof_context_state_info = ContextStateInfo {
             context_state_account: context_state_accounts.range_proof,
             context_state_authority: context_state_accounts.authority,
@@ -2607,11 +6664,7 @@ where
     ) -> TokenResult<T::Output> {
         let instruction_type = ProofInstruction::VerifyBatchedRangeProofU128;
         let space = size_of::<ProofContextState<BatchedRangeProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
         let range_proof_context_state_info = ContextStateInfo {
             context_state_account: context_state_accounts.range_proof,
             context_state_authority: context_state_accounts.authority,
@@ -2941,11 +6994,25 @@ where
                 range_proof_signers,
             );
 
-        try_join!(
-            transfer_with_equality_and_ciphertext_valdity,
-            transfer_with_fee_sigma_and_ciphertext_validity,
-            transfer_with_range_proof,
-        )
+        let mut outputs = Orchestrator::new(self.max_concurrency)
+            .step(
+                "equality and ciphertext validity proofs",
+                transfer_with_equality_and_ciphertext_valdity.boxed_local(),
+            )
+            .step(
+                "fee sigma and fee ciphertext validity proofs",
+                transfer_with_fee_sigma_and_ciphertext_validity.boxed_local(),
+            )
+            .step("range proof", transfer_with_range_proof.boxed_local())
+            .run()
+            .await?
+            .into_iter();
+        // one output per step, in order
+        Ok((
+            outputs.next().unwrap(),
+            outputs.next().unwrap(),
+            outputs.next().unwrap(),
+        ))
     }
 
     /// Create equality and transfer amount ciphertext validity proof context
@@ -3014,11 +7081,7 @@ where
         // create equality proof context state
         let instruction_type = ProofInstruction::VerifyCiphertextCommitmentEquality;
         let space = size_of::<ProofContextState<CiphertextCommitmentEqualityProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
         instructions.push(system_instruction::create_account(
             &self.payer.pubkey(),
             context_state_accounts.equality_proof,
@@ -3040,11 +7103,7 @@ where
         let instruction_type = ProofInstruction::VerifyBatchedGroupedCiphertext2HandlesValidity;
         let space =
             size_of::<ProofContextState<BatchedGroupedCiphertext2HandlesValidityProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
         instructions.push(system_instruction::create_account(
             &self.payer.pubkey(),
             context_state_accounts.transfer_amount_ciphertext_validity_proof,
@@ -3136,11 +7195,7 @@ where
         // create fee sigma proof context state
         let instruction_type = ProofInstruction::VerifyFeeSigma;
         let space = size_of::<ProofContextState<FeeSigmaProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
         instructions.push(system_instruction::create_account(
             &self.payer.pubkey(),
             context_state_accounts.fee_sigma_proof,
@@ -3162,11 +7217,7 @@ where
         let instruction_type = ProofInstruction::VerifyBatchedGroupedCiphertext2HandlesValidity;
         let space =
             size_of::<ProofContextState<BatchedGroupedCiphertext2HandlesValidityProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
         instructions.push(system_instruction::create_account(
             &self.payer.pubkey(),
             context_state_accounts.fee_ciphertext_validity_proof,
@@ -3240,11 +7291,7 @@ where
     ) -> TokenResult<T::Output> {
         let instruction_type = ProofInstruction::VerifyBatchedRangeProofU256;
         let space = size_of::<ProofContextState<BatchedRangeProofContext>>();
-        let rent = self
-            .client
-            .get_minimum_balance_for_rent_exemption(space)
-            .await
-            .map_err(TokenError::Client)?;
+        let rent = self.get_minimum_balance_for_rent_exemption(space).await?;
         let range_proof_context_state_info = ContextStateInfo {
             context_state_account: context_state_accounts.range_proof,
             context_state_authority: context_state_accounts.authority,
@@ -3292,16 +7339,127 @@ where
             ApplyPendingBalanceAccountInfo::new(confidential_transfer_account)
         };
 
-        let expected_pending_balance_credit_counter = account_info.pending_balance_credit_counter();
-        let new_decryptable_available_balance = account_info
-            .new_decryptable_available_balance(elgamal_secret_key, aes_key)
-            .map_err(|_| TokenError::AccountDecryption)?;
+        let expected_pending_balance_credit_counter = account_info.pending_balance_credit_counter();
+        let new_decryptable_available_balance = account_info
+            .new_decryptable_available_balance(elgamal_secret_key, aes_key)
+            .map_err(|_| TokenError::AccountDecryption)?;
+
+        self.process_ixs(
+            &[confidential_transfer::instruction::apply_pending_balance(
+                &self.program_id,
+                account,
+                expected_pending_balance_credit_counter,
+                new_decryptable_available_balance,
+                authority,
+                &multisig_signers,
+            )?],
+            signing_keypairs,
+        )
+        .await
+    }
+
+    /// `confidential_transfer_apply_pending_balance` with the account's keys,
+    /// e.g. from a `Keyring`
+    pub async fn confidential_transfer_apply_pending_balance_with_keys<S: Signers>(
+        &self,
+        account: &Pubkey,
+        authority: &Pubkey,
+        account_info: Option<ApplyPendingBalanceAccountInfo>,
+        keys: &ConfidentialAccountKeys,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        self.confidential_transfer_apply_pending_balance(
+            account,
+            authority,
+            account_info,
+            keys.elgamal_keypair.secret(),
+            &keys.aes_key,
+            signing_keypairs,
+        )
+        .await
+    }
+
+    /// Decrypted balances, credit counters and flags of the confidential
+    /// transfer extension of a token account
+    pub async fn get_confidential_account_summary(
+        &self,
+        account: &Pubkey,
+        elgamal_keypair: &ElGamalKeypair,
+        aes_key: &AeKey,
+    ) -> TokenResult<ConfidentialAccountSummary> {
+        let account_info = self.get_account_info(account).await?;
+        let confidential_transfer_account =
+            account_info.get_extension::<ConfidentialTransferAccount>()?;
+        ConfidentialAccountSummary::new(
+            *account,
+            confidential_transfer_account,
+            elgamal_keypair,
+            aes_key,
+        )
+    }
+
+    /// Repair the decryptable available balance of a token account whose AE
+    /// ciphertext no longer matches its ElGamal available balance, e.g. after a
+    /// transfer was submitted from another wallet with a different AE key or a
+    /// stale balance.
+    ///
+    /// The available and pending balances are decrypted with the ElGamal key,
+    /// which only succeeds for balances that fit in 32 bits, and the corrected
+    /// balance is submitted with `ApplyPendingBalance`, the only instruction
+    /// that sets it without moving tokens. Any pending balance is applied as
+    /// part of the repair. Returns `None` if the account is already in sync and
+    /// has no pending balance.
+    pub async fn confidential_repair_decryptable_balance<S: Signers>(
+        &self,
+        account: &Pubkey,
+        authority: &Pubkey,
+        elgamal_secret_key: &ElGamalSecretKey,
+        aes_key: &AeKey,
+        signing_keypairs: &S,
+    ) -> TokenResult<Option<T::Output>> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        let account_info = self.get_account_info(account).await?;
+        let confidential_transfer_account =
+            account_info.get_extension::<ConfidentialTransferAccount>()?;
+
+        let decrypt = |ciphertext: EncryptedBalance| -> TokenResult<u64> {
+            let ciphertext: ElGamalCiphertext = ciphertext
+                .try_into()
+                .map_err(|_| TokenError::AccountDecryption)?;
+            elgamal_secret_key
+                .decrypt_u32(&ciphertext)
+                .ok_or(TokenError::AccountDecryption)
+        };
+        let available_balance = decrypt(confidential_transfer_account.available_balance)?;
+        let pending_balance_lo = decrypt(confidential_transfer_account.pending_balance_lo)?;
+        let pending_balance_hi = decrypt(confidential_transfer_account.pending_balance_hi)?;
+        let pending_balance = pending_balance_hi
+            .checked_shl(PENDING_BALANCE_LO_BIT_LENGTH)
+            .and_then(|hi| hi.checked_add(pending_balance_lo))
+            .ok_or(TokenError::AccountDecryption)?;
+
+        let stored_balance =
+            AeCiphertext::try_from(confidential_transfer_account.decryptable_available_balance)
+                .ok()
+                .and_then(|ciphertext| aes_key.decrypt(&ciphertext));
+        if stored_balance == Some(available_balance) && pending_balance == 0 {
+            return Ok(None);
+        }
+
+        let new_decryptable_available_balance = available_balance
+            .checked_add(pending_balance)
+            .map(|balance| aes_key.encrypt(balance))
+            .ok_or(TokenError::AccountDecryption)?;
 
         self.process_ixs(
             &[confidential_transfer::instruction::apply_pending_balance(
                 &self.program_id,
                 account,
-                expected_pending_balance_credit_counter,
+                confidential_transfer_account
+                    .pending_balance_credit_counter
+                    .into(),
                 new_decryptable_available_balance,
                 authority,
                 &multisig_signers,
@@ -3309,6 +7467,7 @@ where
             signing_keypairs,
         )
         .await
+        .map(Some)
     }
 
     /// Enable confidential transfer `Deposit` and `Transfer` instructions for a
@@ -3493,12 +7652,23 @@ where
         let account_info = if let Some(account_info) = withheld_tokens_info {
             account_info
         } else {
-            let futures = sources.iter().map(|source| self.get_account_info(source));
-            let sources_extensions = join_all(futures).await;
+            let sources_extensions = sources
+                .iter()
+                .fold(
+                    Orchestrator::new(self.max_concurrency),
+                    |orchestrator, source| {
+                        orchestrator.step(
+                            format!("fetch source {}", source),
+                            self.get_account_info(source).boxed_local(),
+                        )
+                    },
+                )
+                .run()
+                .await?;
 
             let mut aggregate_withheld_amount = ElGamalCiphertext::default();
             for source_extension in sources_extensions {
-                let withheld_amount: ElGamalCiphertext = source_extension?
+                let withheld_amount: ElGamalCiphertext = source_extension
                     .get_extension::<ConfidentialTransferFeeAmount>()?
                     .withheld_amount
                     .try_into()
@@ -3611,6 +7781,97 @@ where
         .await
     }
 
+    /// Export the confidential balance changes of `accounts` over a range of
+    /// slots as an audit package signed by `signer`.
+    ///
+    /// Deposits and withdrawals are public. Transfer amounts are decrypted
+    /// with whichever of `auditor_keys` each transfer was encrypted for, so
+    /// packages spanning a rotation of the mint's auditor need both keys.
+    /// Transfers proven with context state accounts are listed without an
+    /// amount, and the running balance changes are unknown from them on.
+    ///
+    /// Transactions are fetched at most `max_concurrency` at a time.
+    pub async fn export_confidential_audit(
+        &self,
+        accounts: &[Pubkey],
+        auditor_keys: &[&ElGamalKeypair],
+        range: Range<Slot>,
+        signer: &dyn Signer,
+    ) -> TokenResult<SignedAuditPackage> {
+        let mint = self.get_mint_info().await?;
+        let auditor_elgamal_pubkey = Option::<PodElGamalPubkey>::from(
+            mint.get_extension::<confidential_transfer::ConfidentialTransferMint>()?
+                .auditor_elgamal_pubkey,
+        )
+        .map(|pubkey| pubkey.to_string());
+
+        let mut audits = Vec::with_capacity(accounts.len());
+        for address in accounts {
+            let account = self.get_account_info(address).await?;
+            let extension = account.get_extension::<ConfidentialTransferAccount>()?;
+            let mut entries = self
+                .get_transactions_in_range(address, range.clone())
+                .await?
+                .iter()
+                .flat_map(|record| {
+                    audit::decode_audit_entries(&self.program_id, address, record, auditor_keys)
+                })
+                .collect::<Vec<_>>();
+            audit::accumulate(&mut entries);
+            audits.push(AccountAudit {
+                address: *address,
+                owner: account.base.owner,
+                approved: bool::from(extension.approved),
+                elgamal_pubkey: extension.elgamal_pubkey.to_string(),
+                entries,
+            });
+        }
+
+        let package = AuditPackage {
+            program_id: self.program_id,
+            mint: self.pubkey,
+            decimals: mint.base.decimals,
+            auditor_elgamal_pubkey,
+            from_slot: range.start,
+            to_slot: range.end,
+            generated_at_slot: self.get_clock().await?.slot,
+            accounts: audits,
+        };
+        package
+            .sign(signer)
+            .map_err(|error| TokenError::Client(error.into()))
+    }
+
+    /// Transactions mentioning an address within a range of slots, oldest
+    /// first
+    async fn get_transactions_in_range(
+        &self,
+        address: &Pubkey,
+        range: Range<Slot>,
+    ) -> TokenResult<Vec<TransactionRecord>> {
+        let client = &self.client;
+        let mut records = vec![];
+        let mut pages = self.signature_pages(address, None, usize::MAX);
+        'pages: while let Some(signatures) = pages.next().await {
+            let mut transactions = stream::iter(signatures?)
+                .map(|signature| async move { client.get_transaction(&signature).await })
+                .buffered(self.max_concurrency.max(1));
+            while let Some(record) = transactions.next().await {
+                let Some(record) = record.map_err(TokenError::Client)? else {
+                    continue;
+                };
+                if record.slot < range.start {
+                    break 'pages;
+                }
+                if record.slot < range.end {
+                    records.push(record);
+                }
+            }
+        }
+        records.reverse();
+        Ok(records)
+    }
+
     pub async fn withdraw_excess_lamports<S: Signers>(
         &self,
         source: &Pubkey,
@@ -3634,6 +7895,66 @@ where
         .await
     }
 
+    /// Sweep the lamports above the rent-exempt minimum of many mints, token
+    /// accounts or multisigs of the same authority to the destination, packed
+    /// into as few transactions as fit. Accounts without excess lamports,
+    /// native accounts and closed accounts are skipped. Returns the accounts
+    /// swept, in the order of the outcome's items.
+    ///
+    /// The sources are read in one request, and the rent-exempt minimum once
+    /// per account size.
+    pub async fn withdraw_excess_lamports_many<S: Signers>(
+        &self,
+        sources: &[Pubkey],
+        destination: &Pubkey,
+        authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<(Vec<Pubkey>, BatchOutcome<T::Output>)> {
+        let signing_pubkeys = signing_keypairs.pubkeys();
+        let multisig_signers = self.get_multisig_signers(authority, &signing_pubkeys);
+
+        let accounts = self
+            .client
+            .get_multiple_accounts(sources)
+            .await
+            .map_err(TokenError::Client)?;
+        let mut rent_exempt_minimums = HashMap::new();
+        for account in accounts.iter().flatten() {
+            let len = account.data.len();
+            if !rent_exempt_minimums.contains_key(&len) {
+                let rent_exempt_minimum = self.get_minimum_balance_for_rent_exemption(len).await?;
+                rent_exempt_minimums.insert(len, rent_exempt_minimum);
+            }
+        }
+
+        let mut swept = vec![];
+        let mut groups = vec![];
+        for (source, account) in sources.iter().zip(accounts) {
+            let Some(account) = account else {
+                continue;
+            };
+            let rent_exempt_minimum = rent_exempt_minimums[&account.data.len()];
+            let is_native = StateWithExtensionsOwned::<Account>::unpack(account.data)
+                .map_or(false, |state| state.base.is_native());
+            if is_native || account.lamports <= rent_exempt_minimum {
+                continue;
+            }
+            swept.push(*source);
+            groups.push(vec![instruction::withdraw_excess_lamports(
+                &self.program_id,
+                source,
+                destination,
+                authority,
+                &multisig_signers,
+            )?]);
+        }
+
+        Ok((
+            swept,
+            self.process_ix_batches(&groups, signing_keypairs).await,
+        ))
+    }
+
     /// Initialize token-metadata on a mint
     pub async fn token_metadata_initialize<S: Signers>(
         &self,
@@ -3670,10 +7991,8 @@ where
         let new_account_len = mint_state
             .try_get_new_account_len_for_variable_len_extension::<TokenMetadata>(token_metadata)?;
         let new_rent_exempt_minimum = self
-            .client
             .get_minimum_balance_for_rent_exemption(new_account_len)
-            .await
-            .map_err(TokenError::Client)?;
+            .await?;
         Ok(new_rent_exempt_minimum.saturating_sub(account_lamports))
     }
 
@@ -3744,19 +8063,27 @@ where
         &self,
         field: Field,
         value: String,
+    ) -> TokenResult<u64> {
+        self.get_additional_rent_for_updated_metadata_fields(&[(field, value)])
+            .await
+    }
+
+    async fn get_additional_rent_for_updated_metadata_fields(
+        &self,
+        fields: &[(Field, String)],
     ) -> TokenResult<u64> {
         let account = self.get_account(self.pubkey).await?;
         let account_lamports = account.lamports;
         let mint_state = self.unpack_mint_info(account)?;
         let mut token_metadata = mint_state.get_variable_len_extension::<TokenMetadata>()?;
-        token_metadata.update(field, value);
+        for (field, value) in fields {
+            token_metadata.update(field.clone(), value.clone());
+        }
         let new_account_len = mint_state
             .try_get_new_account_len_for_variable_len_extension::<TokenMetadata>(&token_metadata)?;
         let new_rent_exempt_minimum = self
-            .client
             .get_minimum_balance_for_rent_exemption(new_account_len)
-            .await
-            .map_err(TokenError::Client)?;
+            .await?;
         Ok(new_rent_exempt_minimum.saturating_sub(account_lamports))
     }
 
@@ -3796,6 +8123,39 @@ where
         self.process_ixs(&instructions, signing_keypairs).await
     }
 
+    /// Update many token-metadata fields on a mint in one transaction, with a
+    /// single transfer for the additional rent-exempt SOL the updates require
+    /// together
+    pub async fn token_metadata_update_fields<S: Signers>(
+        &self,
+        payer: &Pubkey,
+        update_authority: &Pubkey,
+        fields: &[(Field, String)],
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let additional_lamports = self
+            .get_additional_rent_for_updated_metadata_fields(fields)
+            .await?;
+        let mut instructions = vec![];
+        if additional_lamports > 0 {
+            instructions.push(system_instruction::transfer(
+                payer,
+                &self.pubkey,
+                additional_lamports,
+            ));
+        }
+        instructions.extend(fields.iter().map(|(field, value)| {
+            spl_token_metadata_interface::instruction::update_field(
+                &self.program_id,
+                &self.pubkey,
+                update_authority,
+                field.clone(),
+                value.clone(),
+            )
+        }));
+        self.process_ixs(&instructions, signing_keypairs).await
+    }
+
     /// Update the token-metadata authority in a mint
     pub async fn token_metadata_update_authority<S: Signers>(
         &self,
@@ -3836,6 +8196,52 @@ where
         .await
     }
 
+    /// Remove a token-metadata field on a mint and withdraw the rent freed by
+    /// the shrunk account to the destination, in one transaction. Lamports
+    /// beyond the new rent-exempt minimum are withdrawn through the mint
+    /// authority, and nothing is withdrawn if the account holds no excess.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn token_metadata_remove_key_and_reclaim_rent<S: Signers>(
+        &self,
+        update_authority: &Pubkey,
+        key: String,
+        idempotent: bool,
+        lamports_destination: &Pubkey,
+        mint_authority: &Pubkey,
+        signing_keypairs: &S,
+    ) -> TokenResult<T::Output> {
+        let account = self.get_account(self.pubkey).await?;
+        let account_lamports = account.lamports;
+        let mint_state = self.unpack_mint_info(account)?;
+        let mut token_metadata = mint_state.get_variable_len_extension::<TokenMetadata>()?;
+        token_metadata.remove_key(&key);
+        let new_account_len = mint_state
+            .try_get_new_account_len_for_variable_len_extension::<TokenMetadata>(&token_metadata)?;
+        let new_rent_exempt_minimum = self
+            .get_minimum_balance_for_rent_exemption(new_account_len)
+            .await?;
+
+        let mut instructions = vec![spl_token_metadata_interface::instruction::remove_key(
+            &self.program_id,
+            &self.pubkey,
+            update_authority,
+            key,
+            idempotent,
+        )];
+        if account_lamports > new_rent_exempt_minimum {
+            let signing_pubkeys = signing_keypairs.pubkeys();
+            let multisig_signers = self.get_multisig_signers(mint_authority, &signing_pubkeys);
+            instructions.push(instruction::withdraw_excess_lamports(
+                &self.program_id,
+                &self.pubkey,
+                lamports_destination,
+                mint_authority,
+                &multisig_signers,
+            )?);
+        }
+        self.process_ixs(&instructions, signing_keypairs).await
+    }
+
     /// Initialize token-group on a mint
     pub async fn token_group_initialize<S: Signers>(
         &self,
@@ -3869,10 +8275,8 @@ where
         } else {
             let new_account_len = mint_state.try_get_new_account_len::<V>()?;
             let new_rent_exempt_minimum = self
-                .client
                 .get_minimum_balance_for_rent_exemption(new_account_len)
-                .await
-                .map_err(TokenError::Client)?;
+                .await?;
             Ok(new_rent_exempt_minimum.saturating_sub(account_lamports))
         }
     }
@@ -4004,3 +8408,771 @@ where
         self.process_ixs(&instructions, signing_keypairs).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            client::{ClientMethod, ProgramRpcClientSendTransaction},
+            journal::InMemoryJournal,
+            mock::MockProgramClient,
+            persistence::MemoryStore,
+        },
+        solana_sdk::{
+            nonce::state::{Data, DurableNonce, Versions},
+            rent::Rent,
+            signature::Keypair,
+            sysvar,
+        },
+        spl_token_2022::{
+            extension::{BaseStateWithExtensionsMut, StateWithExtensionsMut},
+            instruction::TokenInstruction,
+        },
+        std::task::Poll,
+    };
+
+    type MockClient = MockProgramClient<ProgramRpcClientSendTransaction>;
+
+    /// Mint with 2 decimals in a mock, and a token for it that is not told
+    /// its decimals
+    fn mock_token() -> (Arc<MockClient>, Token<ProgramRpcClientSendTransaction>) {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let client = Arc::new(MockClient::new().with_mint(
+            &mint,
+            &program_id,
+            Mint {
+                decimals: 2,
+                is_initialized: true,
+                ..Mint::default()
+            },
+        ));
+        let token = Token::new(
+            client.clone(),
+            &program_id,
+            &mint,
+            None,
+            Arc::new(Keypair::new()),
+        );
+        (client, token)
+    }
+
+    /// Mint with 2 decimals, the given supply and one extension set up by
+    /// `init` in a mock, and a token for it that is not told its decimals
+    fn mock_token_with<E: Extension + bytemuck::Pod>(
+        supply: u64,
+        init: impl FnOnce(&mut E),
+    ) -> (Arc<MockClient>, Token<ProgramRpcClientSendTransaction>) {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let len = ExtensionType::try_calculate_account_len::<Mint>(&[E::TYPE]).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.base = Mint {
+            supply,
+            decimals: 2,
+            is_initialized: true,
+            ..Mint::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        init(state.init_extension::<E>(true).unwrap());
+        let client = Arc::new(MockClient::new().with_account(
+            &mint,
+            BaseAccount {
+                lamports: 1,
+                data,
+                owner: program_id,
+                ..BaseAccount::default()
+            },
+        ));
+        let token = Token::new(
+            client.clone(),
+            &program_id,
+            &mint,
+            None,
+            Arc::new(Keypair::new()),
+        );
+        (client, token)
+    }
+
+    /// Data of the token instructions of every sent transaction, in order
+    fn sent_token_instructions(client: &MockClient) -> Vec<Vec<u8>> {
+        client
+            .sent_transactions()
+            .into_iter()
+            .flat_map(|transaction| {
+                let message = transaction.message;
+                message
+                    .instructions
+                    .into_iter()
+                    .filter(|instruction| {
+                        message.account_keys[usize::from(instruction.program_id_index)]
+                            == spl_token_2022::id()
+                    })
+                    .map(|instruction| instruction.data)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Sleep that is pending once, so whatever it is raced against gets
+    /// polled again first
+    fn yield_once(_: Duration) -> BoxFuture<'static, ()> {
+        let mut yielded = false;
+        Box::pin(future::poll_fn(move |cx| {
+            if yielded {
+                Poll::Ready(())
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }))
+    }
+
+    #[test]
+    fn test_fee_escalation_lands_once() {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let mint_authority = Keypair::new();
+        let client = Arc::new(
+            MockClient::new()
+                .with_mint(
+                    &mint,
+                    &program_id,
+                    Mint {
+                        mint_authority: COption::Some(mint_authority.pubkey()),
+                        decimals: 2,
+                        is_initialized: true,
+                        ..Mint::default()
+                    },
+                )
+                .with_latency(Duration::from_millis(1))
+                .with_sleep(Arc::new(yield_once)),
+        );
+        // every attempt times out as soon as one has landed, before it is
+        // confirmed
+        let landed: SleepFn = {
+            let client = client.clone();
+            Arc::new(move |_| {
+                let client = client.clone();
+                Box::pin(future::poll_fn(move |cx| {
+                    if client.sent_transactions().is_empty() {
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    } else {
+                        Poll::Ready(())
+                    }
+                }))
+            })
+        };
+        let token = Token::new(
+            client.clone(),
+            &program_id,
+            &mint,
+            None,
+            Arc::new(Keypair::new()),
+        )
+        .with_sleep(landed);
+        let instructions = [instruction::mint_to(
+            &program_id,
+            &mint,
+            &Pubkey::new_unique(),
+            &mint_authority.pubkey(),
+            &[],
+            1,
+        )
+        .unwrap()];
+        let escalation = FeeEscalation {
+            compute_unit_prices: vec![1, 2, 3],
+            confirmation_timeout: Duration::from_secs(1),
+        };
+
+        // on a live blockhash, every attempt could land
+        assert_eq!(
+            futures::executor::block_on(token.process_ixs_with_fee_escalation(
+                &instructions,
+                &escalation,
+                &[&mint_authority],
+            ))
+            .unwrap_err(),
+            TokenError::MissingNonce
+        );
+        assert!(client.sent_transactions().is_empty());
+
+        let token = token.with_nonce(
+            &Pubkey::new_unique(),
+            Arc::new(Keypair::new()),
+            &Hash::new_unique(),
+        );
+        let signature = futures::executor::block_on(token.process_ixs_with_fee_escalation(
+            &instructions,
+            &escalation,
+            &[&mint_authority],
+        ))
+        .unwrap();
+        // the landed attempt is confirmed instead of escalating
+        let sent = client.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].signatures[0], signature);
+    }
+
+    #[test]
+    fn test_withdraw_excess_lamports_many() {
+        let program_id = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let (excess, exempt, closed, native) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let token_account = |is_native| {
+            let mut data = vec![0; Account::LEN];
+            Account::pack(
+                Account {
+                    mint,
+                    state: AccountState::Initialized,
+                    is_native,
+                    ..Account::default()
+                },
+                &mut data,
+            )
+            .unwrap();
+            solana_sdk::account::Account {
+                lamports: Rent::default().minimum_balance(data.len()) + 10,
+                data,
+                owner: program_id,
+                ..solana_sdk::account::Account::default()
+            }
+        };
+        let client = Arc::new(
+            MockClient::new()
+                .with_account(&excess, token_account(COption::None))
+                .with_token_account(&exempt, &program_id, Account::default())
+                .with_account(&native, token_account(COption::Some(0))),
+        );
+        let token = Token::new(
+            client.clone(),
+            &program_id,
+            &mint,
+            None,
+            Arc::new(Keypair::new()),
+        );
+        let authority = Keypair::new();
+
+        let (swept, outcome) = futures::executor::block_on(token.withdraw_excess_lamports_many(
+            &[excess, exempt, closed, native],
+            &Pubkey::new_unique(),
+            &authority.pubkey(),
+            &[&authority],
+        ))
+        .unwrap();
+        assert_eq!(swept, [excess]);
+        assert!(outcome.is_success());
+        assert_eq!(
+            sent_token_instructions(&client)
+                .iter()
+                .map(|data| TokenInstruction::unpack(data).unwrap())
+                .collect::<Vec<_>>(),
+            [TokenInstruction::WithdrawExcessLamports]
+        );
+        assert_eq!(
+            client
+                .calls()
+                .iter()
+                .filter(|method| **method == ClientMethod::GetMinimumBalanceForRentExemption)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_transfer_many_checked() {
+        let (client, token) = mock_token();
+        let authority = Keypair::new();
+        let destinations = [(Pubkey::new_unique(), 1), (Pubkey::new_unique(), 2)];
+        let outcome = futures::executor::block_on(token.transfer_many(
+            &Pubkey::new_unique(),
+            &destinations,
+            &authority.pubkey(),
+            &[&authority],
+        ))
+        .unwrap();
+        assert!(outcome.is_success());
+
+        let instructions = sent_token_instructions(&client)
+            .iter()
+            .map(|data| TokenInstruction::unpack(data).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            instructions,
+            [
+                TokenInstruction::TransferChecked {
+                    amount: 1,
+                    decimals: 2
+                },
+                TokenInstruction::TransferChecked {
+                    amount: 2,
+                    decimals: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transfer_as_delegate() {
+        let (client, token) = mock_token();
+        let (source, destination) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let delegate = Keypair::new();
+        let mut data = vec![0; Account::LEN];
+        Account::pack(
+            Account {
+                mint: *token.get_address(),
+                owner: Pubkey::new_unique(),
+                amount: 100,
+                delegate: COption::Some(delegate.pubkey()),
+                delegated_amount: 10,
+                state: AccountState::Initialized,
+                ..Account::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        client.set_account(
+            &source,
+            solana_sdk::account::Account {
+                lamports: 1,
+                data,
+                owner: spl_token_2022::id(),
+                ..solana_sdk::account::Account::default()
+            },
+        );
+
+        let other = Keypair::new();
+        assert_eq!(
+            futures::executor::block_on(token.transfer_as_delegate(
+                &source,
+                &destination,
+                &other.pubkey(),
+                1,
+                &[&other],
+            ))
+            .unwrap_err(),
+            TokenError::InvalidDelegate(other.pubkey())
+        );
+        assert_eq!(
+            futures::executor::block_on(token.transfer_as_delegate(
+                &source,
+                &destination,
+                &delegate.pubkey(),
+                11,
+                &[&delegate],
+            ))
+            .unwrap_err(),
+            TokenError::InsufficientDelegatedAmount {
+                amount: 11,
+                delegated_amount: 10,
+            }
+        );
+        assert!(client.sent_transactions().is_empty());
+
+        // checked against the fetched decimals
+        futures::executor::block_on(token.transfer_as_delegate(
+            &source,
+            &destination,
+            &delegate.pubkey(),
+            10,
+            &[&delegate],
+        ))
+        .unwrap();
+        let instructions = sent_token_instructions(&client);
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(
+            TokenInstruction::unpack(&instructions[0]).unwrap(),
+            TokenInstruction::TransferChecked {
+                amount: 10,
+                decimals: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_clawback() {
+        let delegate = Keypair::new();
+        let (client, token) = mock_token_with::<PermanentDelegate>(0, |extension| {
+            extension.delegate = Some(delegate.pubkey()).try_into().unwrap();
+        });
+        let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let other = Keypair::new();
+        assert_eq!(
+            futures::executor::block_on(token.clawback(
+                &from,
+                &to,
+                10,
+                &other.pubkey(),
+                None,
+                &[&other],
+            ))
+            .unwrap_err(),
+            TokenError::InvalidDelegate(other.pubkey())
+        );
+        assert!(client.sent_transactions().is_empty());
+
+        futures::executor::block_on(token.clawback(
+            &from,
+            &to,
+            10,
+            &delegate.pubkey(),
+            Some("court order"),
+            &[&delegate],
+        ))
+        .unwrap();
+        let instructions = sent_token_instructions(&client);
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(
+            TokenInstruction::unpack(&instructions[0]).unwrap(),
+            TokenInstruction::TransferChecked {
+                amount: 10,
+                decimals: 2
+            }
+        );
+
+        // only the payer and the delegate sign, not the memo
+        let transaction = &client.sent_transactions()[0];
+        assert_eq!(transaction.signatures.len(), 2);
+        let message = &transaction.message;
+        let memo = message
+            .instructions
+            .iter()
+            .find(|instruction| {
+                message.account_keys[usize::from(instruction.program_id_index)] == spl_memo::id()
+            })
+            .unwrap();
+        assert_eq!(memo.data, b"court order");
+        assert!(memo.accounts.is_empty());
+    }
+
+    #[test]
+    fn test_simulate_distribution() {
+        let hook_program_id = Pubkey::new_unique();
+        let (client, token) = mock_token_with::<TransferHook>(0, |extension| {
+            extension.program_id = Some(hook_program_id).try_into().unwrap();
+        });
+        let hook_account = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+        let token = token.with_transfer_hook_accounts(vec![hook_account.clone()]);
+        let authority = Pubkey::new_unique();
+        let plan = DistributionPlan::new()
+            .transfer(&Pubkey::new_unique(), &Pubkey::new_unique(), &authority, 1)
+            .transfer(&Pubkey::new_unique(), &Pubkey::new_unique(), &authority, 2);
+
+        let projection = futures::executor::block_on(token.simulate_distribution(&plan)).unwrap();
+        assert_eq!(projection.transfers.len(), 2);
+        for transfer in &projection.transfers {
+            assert_eq!(transfer.hook_program_id, Some(hook_program_id));
+            // only a checked transfer carries the hook's accounts
+            assert_eq!(transfer.hook_accounts, [hook_account.clone()]);
+            assert_eq!(transfer.error, None);
+        }
+        assert!(client.sent_transactions().is_empty());
+        let calls = client.calls();
+        let count = |method| calls.iter().filter(|call| **call == method).count();
+        assert_eq!(count(ClientMethod::GetAccount), 1);
+        assert_eq!(count(ClientMethod::SimulateTransaction), 2);
+    }
+
+    /// System account holding an initialized durable nonce
+    fn nonce_account(authority: &Pubkey, durable_nonce: DurableNonce) -> BaseAccount {
+        let state = Versions::new(nonce::State::Initialized(Data::new(
+            *authority,
+            durable_nonce,
+            LAMPORTS_PER_SIGNATURE,
+        )));
+        BaseAccount {
+            lamports: 1,
+            data: bincode::serialize(&state).unwrap(),
+            owner: system_program::id(),
+            ..BaseAccount::default()
+        }
+    }
+
+    fn set_clock(client: &MockClient, unix_timestamp: UnixTimestamp) {
+        client.set_account(
+            &sysvar::clock::id(),
+            BaseAccount {
+                lamports: 1,
+                data: bincode::serialize(&Clock {
+                    unix_timestamp,
+                    ..Clock::default()
+                })
+                .unwrap(),
+                owner: sysvar::id(),
+                ..BaseAccount::default()
+            },
+        );
+    }
+
+    fn is_advance_nonce(message: &Message, index: usize, nonce_account: &Pubkey) -> bool {
+        let instruction = &message.instructions[index];
+        message.account_keys[usize::from(instruction.program_id_index)] == system_program::id()
+            && limited_deserialize::<SystemInstruction>(&instruction.data).unwrap()
+                == SystemInstruction::AdvanceNonceAccount
+            && message.account_keys[usize::from(instruction.accounts[0])] == *nonce_account
+    }
+
+    #[test]
+    fn test_timelocked_transfer() {
+        let (client, token) = mock_token();
+        let journal = Arc::new(InMemoryJournal::new(MemoryStore::new()));
+        let token = token.with_journal(journal.clone());
+        let nonce_authority = Arc::new(Keypair::new());
+        let (nonce, other_nonce) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let durable_nonce = DurableNonce::from_blockhash(&Hash::new_unique());
+        client.set_account(
+            &nonce,
+            nonce_account(&nonce_authority.pubkey(), durable_nonce),
+        );
+        client.set_account(
+            &other_nonce,
+            nonce_account(
+                &nonce_authority.pubkey(),
+                DurableNonce::from_blockhash(&Hash::new_unique()),
+            ),
+        );
+        let (source, destination) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let authority = Keypair::new();
+
+        futures::executor::block_on(async {
+            let signature = token
+                .prepare_timelocked_transfer(
+                    &source,
+                    &destination,
+                    &authority.pubkey(),
+                    10,
+                    1_000,
+                    &nonce,
+                    nonce_authority.clone(),
+                    &[&authority],
+                )
+                .await
+                .unwrap();
+            // signed against the nonce and stored, not sent
+            let entry = journal.get(&signature).unwrap().unwrap();
+            assert_eq!(entry.release_after, 1_000);
+            assert_eq!(entry.nonce_account, nonce);
+            assert_eq!(
+                entry.transaction.message.recent_blockhash,
+                *durable_nonce.as_hash()
+            );
+            assert!(is_advance_nonce(&entry.transaction.message, 0, &nonce));
+            entry.transaction.verify().unwrap();
+            assert!(client.sent_transactions().is_empty());
+
+            // a second transaction on the nonce would be invalidated by the first
+            assert_eq!(
+                token
+                    .prepare_timelocked_transfer(
+                        &source,
+                        &destination,
+                        &authority.pubkey(),
+                        20,
+                        1_000,
+                        &nonce,
+                        nonce_authority.clone(),
+                        &[&authority],
+                    )
+                    .await
+                    .unwrap_err(),
+                TokenError::NonceAccountInUse
+            );
+
+            // the cluster's clock decides, not the host's
+            set_clock(&client, 999);
+            assert_eq!(
+                token
+                    .release_timelocked_transfer(&signature)
+                    .await
+                    .unwrap_err(),
+                TokenError::TimelockActive(1_000)
+            );
+            assert!(client.sent_transactions().is_empty());
+            assert!(journal.get(&signature).unwrap().is_some());
+
+            set_clock(&client, 1_000);
+            token.release_timelocked_transfer(&signature).await.unwrap();
+            assert_eq!(client.sent_transactions(), [entry.transaction]);
+            assert_eq!(journal.get(&signature).unwrap(), None);
+
+            let signature = token
+                .prepare_timelocked_transfer(
+                    &source,
+                    &destination,
+                    &authority.pubkey(),
+                    30,
+                    2_000,
+                    &other_nonce,
+                    nonce_authority.clone(),
+                    &[&authority],
+                )
+                .await
+                .unwrap();
+            token
+                .cancel_timelocked_transfer(&signature, &[nonce_authority.as_ref()])
+                .await
+                .unwrap();
+            let sent = client.sent_transactions();
+            assert_eq!(sent.len(), 2);
+            assert!(is_advance_nonce(&sent[1].message, 0, &other_nonce));
+            assert_eq!(journal.get(&signature).unwrap(), None);
+            assert_eq!(
+                token
+                    .release_timelocked_transfer(&signature)
+                    .await
+                    .unwrap_err(),
+                TokenError::JournalEntryNotFound
+            );
+        });
+    }
+
+    #[test]
+    fn test_close_mint() {
+        let close_authority = Keypair::new();
+        let destination = Pubkey::new_unique();
+        let set_close_authority = |extension: &mut MintCloseAuthority| {
+            extension.close_authority = Some(close_authority.pubkey()).try_into().unwrap();
+        };
+
+        let (client, token) = mock_token_with::<MintCloseAuthority>(5, set_close_authority);
+        assert_eq!(
+            futures::executor::block_on(token.close_mint(
+                &destination,
+                &close_authority.pubkey(),
+                &[&close_authority],
+            ))
+            .unwrap_err(),
+            TokenError::MintHasSupply(5)
+        );
+        assert!(client.sent_transactions().is_empty());
+
+        let (client, token) = mock_token();
+        assert_eq!(
+            futures::executor::block_on(token.close_mint(
+                &destination,
+                &close_authority.pubkey(),
+                &[&close_authority],
+            ))
+            .unwrap_err(),
+            TokenError::MissingExtension(ExtensionType::MintCloseAuthority)
+        );
+        assert!(client.sent_transactions().is_empty());
+
+        let (client, token) = mock_token_with::<MintCloseAuthority>(0, set_close_authority);
+        let other = Keypair::new();
+        assert_eq!(
+            futures::executor::block_on(
+                token.close_mint(&destination, &other.pubkey(), &[&other],)
+            )
+            .unwrap_err(),
+            TokenError::InvalidCloseAuthority(other.pubkey())
+        );
+        assert!(client.sent_transactions().is_empty());
+
+        futures::executor::block_on(token.close_mint(
+            &destination,
+            &close_authority.pubkey(),
+            &[&close_authority],
+        ))
+        .unwrap();
+        let instructions = sent_token_instructions(&client);
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(
+            TokenInstruction::unpack(&instructions[0]).unwrap(),
+            TokenInstruction::CloseAccount
+        );
+    }
+
+    #[test]
+    fn test_transfer_ui_amount_checked() {
+        let (client, token) = mock_token();
+        let authority = Keypair::new();
+        futures::executor::block_on(token.transfer_ui_amount(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &authority.pubkey(),
+            "1.5",
+            &[&authority],
+        ))
+        .unwrap();
+
+        let instructions = sent_token_instructions(&client);
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(
+            TokenInstruction::unpack(&instructions[0]).unwrap(),
+            TokenInstruction::TransferChecked {
+                amount: 150,
+                decimals: 2
+            }
+        );
+    }
+
+    fn transfer_fee(transfer_fee_basis_points: u16, maximum_fee: u64) -> TransferFee {
+        TransferFee {
+            epoch: 0.into(),
+            maximum_fee: maximum_fee.into(),
+            transfer_fee_basis_points: transfer_fee_basis_points.into(),
+        }
+    }
+
+    #[test]
+    fn test_transfer_fee_rounding() {
+        // 1% of 150 is 1.5, rounded up in favor of the fee
+        let fee = transfer_fee(100, u64::MAX);
+        assert_eq!(transfer_fee_amount(Some(&fee), 150).unwrap(), 2);
+        assert_eq!(post_fee_amount(Some(&fee), 150).unwrap(), 148);
+        assert_eq!(transfer_fee_amount(Some(&fee), 1).unwrap(), 1);
+        assert_eq!(post_fee_amount(Some(&fee), 1).unwrap(), 0);
+        assert_eq!(transfer_fee_amount(Some(&fee), 0).unwrap(), 0);
+
+        // both 100 and 101 net 99, and the smaller is chosen
+        assert_eq!(pre_fee_amount(Some(&fee), 99).unwrap(), (100, 1));
+        assert_eq!(post_fee_amount(Some(&fee), 101).unwrap(), 99);
+        assert_eq!(pre_fee_amount(Some(&fee), 148).unwrap(), (150, 2));
+        assert_eq!(pre_fee_amount(Some(&fee), 0).unwrap(), (0, 0));
+
+        // without a fee, amounts are unchanged
+        assert_eq!(transfer_fee_amount(None, 150).unwrap(), 0);
+        assert_eq!(post_fee_amount(None, 150).unwrap(), 150);
+        assert_eq!(pre_fee_amount(None, 150).unwrap(), (150, 0));
+        let free = transfer_fee(0, u64::MAX);
+        assert_eq!(post_fee_amount(Some(&free), 150).unwrap(), 150);
+        assert_eq!(pre_fee_amount(Some(&free), 150).unwrap(), (150, 0));
+    }
+
+    #[test]
+    fn test_transfer_fee_maximum() {
+        // 10% capped at 5
+        let fee = transfer_fee(1_000, 5);
+        assert_eq!(transfer_fee_amount(Some(&fee), 40).unwrap(), 4);
+        assert_eq!(transfer_fee_amount(Some(&fee), 50).unwrap(), 5);
+        assert_eq!(transfer_fee_amount(Some(&fee), 1_000).unwrap(), 5);
+        assert_eq!(post_fee_amount(Some(&fee), 1_000).unwrap(), 995);
+        assert_eq!(pre_fee_amount(Some(&fee), 995).unwrap(), (1_000, 5));
+        assert_eq!(pre_fee_amount(Some(&fee), 45).unwrap(), (50, 5));
+        assert_eq!(pre_fee_amount(Some(&fee), 36).unwrap(), (40, 4));
+
+        // a capped fee keeps large amounts from overflowing
+        assert_eq!(
+            pre_fee_amount(Some(&fee), u64::MAX - 5).unwrap(),
+            (u64::MAX, 5)
+        );
+        assert!(pre_fee_amount(Some(&fee), u64::MAX).is_err());
+
+        // nothing transferred nets a positive amount under a 100% fee
+        let everything = transfer_fee(10_000, u64::MAX);
+        assert_eq!(post_fee_amount(Some(&everything), 1_000).unwrap(), 0);
+        assert!(pre_fee_amount(Some(&everything), 1).is_err());
+    }
+}
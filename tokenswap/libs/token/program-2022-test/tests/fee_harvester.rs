@@ -0,0 +1,110 @@
+#![cfg(feature = "test-sbf")]
+
+mod program_test;
+use {
+    program_test::{TestContext, TokenContext},
+    solana_program_test::tokio,
+    solana_sdk::{signature::Signer, signer::keypair::Keypair},
+    spl_token_2022::extension::{
+        transfer_fee::{TransferFeeAmount, TransferFeeConfig},
+        BaseStateWithExtensions,
+    },
+    spl_token_client::{harvest::FeeHarvester, token::ExtensionInitializationParams},
+};
+
+const TEST_MAXIMUM_FEE: u64 = 10_000_000;
+const TEST_FEE_BASIS_POINTS: u16 = 250;
+
+#[tokio::test]
+async fn harvest_to_treasury() {
+    let withdraw_withheld_authority = Keypair::new();
+    let mut context = TestContext::new().await;
+    context
+        .init_token_with_mint(vec![ExtensionInitializationParams::TransferFeeConfig {
+            transfer_fee_config_authority: None,
+            withdraw_withheld_authority: Some(withdraw_withheld_authority.pubkey()),
+            transfer_fee_basis_points: TEST_FEE_BASIS_POINTS,
+            maximum_fee: TEST_MAXIMUM_FEE,
+        }])
+        .await
+        .unwrap();
+    let TokenContext {
+        mint_authority,
+        token,
+        alice,
+        bob,
+        ..
+    } = context.token_context.take().unwrap();
+
+    let alice_account = Keypair::new();
+    token
+        .create_auxiliary_token_account(&alice_account, &alice.pubkey())
+        .await
+        .unwrap();
+    let alice_account = alice_account.pubkey();
+    let bob_account = Keypair::new();
+    token
+        .create_auxiliary_token_account(&bob_account, &bob.pubkey())
+        .await
+        .unwrap();
+    let bob_account = bob_account.pubkey();
+    let treasury = Keypair::new();
+    token
+        .create_auxiliary_token_account(&treasury, &withdraw_withheld_authority.pubkey())
+        .await
+        .unwrap();
+    let treasury = treasury.pubkey();
+
+    let amount = 1_000;
+    token
+        .mint_to(
+            &alice_account,
+            &mint_authority.pubkey(),
+            amount,
+            &[&mint_authority],
+        )
+        .await
+        .unwrap();
+    token
+        .transfer(
+            &alice_account,
+            &bob_account,
+            &alice.pubkey(),
+            amount,
+            &[&alice],
+        )
+        .await
+        .unwrap();
+    let fee = token.calculate_transfer_fee(amount).await.unwrap();
+    assert!(fee > 0);
+
+    // banks clients cannot scan the mint's accounts, so they are listed
+    let harvester = FeeHarvester::new(&token)
+        .with_sources(vec![alice_account, bob_account])
+        .with_treasury(treasury, withdraw_withheld_authority.pubkey());
+    let report = harvester
+        .harvest(&[&withdraw_withheld_authority])
+        .await
+        .unwrap();
+    assert_eq!(report.sources, [(bob_account, fee)]);
+    assert!(report.harvest.is_success());
+    report.withdrawal.unwrap().unwrap();
+
+    let state = token.get_account_info(&bob_account).await.unwrap();
+    assert_eq!(state.base.amount, amount - fee);
+    let extension = state.get_extension::<TransferFeeAmount>().unwrap();
+    assert_eq!(extension.withheld_amount, 0.into());
+    let state = token.get_mint_info().await.unwrap();
+    let extension = state.get_extension::<TransferFeeConfig>().unwrap();
+    assert_eq!(extension.withheld_amount, 0.into());
+    let state = token.get_account_info(&treasury).await.unwrap();
+    assert_eq!(state.base.amount, fee);
+
+    // nothing left to harvest or withdraw
+    let report = harvester
+        .harvest(&[&withdraw_withheld_authority])
+        .await
+        .unwrap();
+    assert!(report.sources.is_empty());
+    assert!(report.withdrawal.is_none());
+}
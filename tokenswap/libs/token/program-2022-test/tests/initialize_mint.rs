@@ -25,8 +25,13 @@ use {
         solana_zk_token_sdk::zk_token_elgamal::pod::ElGamalPubkey,
         state::Mint,
     },
-    spl_token_client::token::ExtensionInitializationParams,
-    std::convert::TryInto,
+    spl_token_client::{
+        client::{ProgramBanksClient, ProgramBanksClientProcessTransaction, ProgramClient},
+        token::{
+            ExtensionInitializationParams, MintProperty, Token, TokenError as TokenClientError,
+        },
+    },
+    std::{convert::TryInto, sync::Arc},
 };
 
 #[tokio::test]
@@ -188,6 +193,107 @@ async fn success_extension_and_base() {
     );
 }
 
+#[tokio::test]
+async fn create_mint_idempotent() {
+    let transfer_fee_config = |maximum_fee| ExtensionInitializationParams::TransferFeeConfig {
+        transfer_fee_config_authority: None,
+        withdraw_withheld_authority: None,
+        transfer_fee_basis_points: 100,
+        maximum_fee,
+    };
+    let mut context = TestContext::new().await;
+    context
+        .init_token_with_mint(vec![transfer_fee_config(1_000)])
+        .await
+        .unwrap();
+    let TokenContext {
+        decimals,
+        mint_authority,
+        token,
+        ..
+    } = context.token_context.take().unwrap();
+    let mint_authority = mint_authority.pubkey();
+
+    // an identical mint is left as it is
+    assert_eq!(
+        token
+            .create_mint_idempotent(
+                &mint_authority,
+                None,
+                vec![transfer_fee_config(1_000)],
+                &[] as &[&Keypair; 0],
+            )
+            .await
+            .unwrap(),
+        None
+    );
+
+    let error = token
+        .create_mint_idempotent(
+            &mint_authority,
+            None,
+            vec![transfer_fee_config(2_000)],
+            &[] as &[&Keypair; 0],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error,
+        TokenClientError::MintMismatch(MintProperty::ExtensionConfig(
+            ExtensionType::TransferFeeConfig
+        ))
+    );
+
+    let error = token
+        .create_mint_idempotent(&mint_authority, None, vec![], &[] as &[&Keypair; 0])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error,
+        TokenClientError::MintMismatch(MintProperty::Extensions)
+    );
+
+    let error = token
+        .create_mint_idempotent(
+            &Pubkey::new_unique(),
+            None,
+            vec![transfer_fee_config(1_000)],
+            &[] as &[&Keypair; 0],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error,
+        TokenClientError::MintMismatch(MintProperty::MintAuthority)
+    );
+
+    let client: Arc<dyn ProgramClient<ProgramBanksClientProcessTransaction>> =
+        Arc::new(ProgramBanksClient::new_from_context(
+            Arc::clone(&context.context),
+            ProgramBanksClientProcessTransaction,
+        ));
+    let payer = Keypair::from_bytes(&context.context.lock().await.payer.to_bytes()).unwrap();
+    let error = Token::new(
+        client,
+        &spl_token_2022::id(),
+        token.get_address(),
+        Some(decimals + 1),
+        Arc::new(payer),
+    )
+    .create_mint_idempotent(
+        &mint_authority,
+        None,
+        vec![transfer_fee_config(1_000)],
+        &[] as &[&Keypair; 0],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TokenClientError::MintMismatch(MintProperty::Decimals)
+    );
+}
+
 #[tokio::test]
 async fn fail_init_overallocated_mint() {
     let context = TestContext::new().await;
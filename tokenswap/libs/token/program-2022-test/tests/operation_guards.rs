@@ -0,0 +1,144 @@
+#![cfg(feature = "test-sbf")]
+
+mod program_test;
+use {
+    program_test::{TestContext, TokenContext},
+    solana_program_test::tokio,
+    solana_sdk::{signature::Signer, signer::keypair::Keypair},
+    spl_token_client::token::{OperationGuards, TokenError as TokenClientError},
+};
+
+#[tokio::test]
+async fn off_by_default() {
+    let mut context = TestContext::new().await;
+    context.init_token_with_mint(vec![]).await.unwrap();
+    let TokenContext {
+        mint_authority,
+        token,
+        alice,
+        ..
+    } = context.token_context.unwrap();
+    assert_eq!(OperationGuards::default(), OperationGuards::none());
+
+    let alice_account = Keypair::new();
+    token
+        .create_auxiliary_token_account(&alice_account, &alice.pubkey())
+        .await
+        .unwrap();
+    let alice_account = alice_account.pubkey();
+    token
+        .mint_to(
+            &alice_account,
+            &mint_authority.pubkey(),
+            10,
+            &[&mint_authority],
+        )
+        .await
+        .unwrap();
+
+    // left to the program, which accepts both
+    token
+        .transfer(
+            &alice_account,
+            &alice_account,
+            &alice.pubkey(),
+            0,
+            &[&alice],
+        )
+        .await
+        .unwrap();
+    token
+        .transfer(
+            &alice_account,
+            &alice_account,
+            &alice.pubkey(),
+            1,
+            &[&alice],
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn reject() {
+    let mut context = TestContext::new().await;
+    context.init_token_with_mint(vec![]).await.unwrap();
+    let TokenContext {
+        mint_authority,
+        token,
+        alice,
+        bob,
+        ..
+    } = context.token_context.unwrap();
+    let token = token.with_guards(OperationGuards {
+        reject_zero_amount_transfers: true,
+        reject_self_transfers: true,
+        reject_burns_exceeding_balance: true,
+        ..OperationGuards::default()
+    });
+
+    let alice_account = Keypair::new();
+    token
+        .create_auxiliary_token_account(&alice_account, &alice.pubkey())
+        .await
+        .unwrap();
+    let alice_account = alice_account.pubkey();
+    let bob_account = Keypair::new();
+    token
+        .create_auxiliary_token_account(&bob_account, &bob.pubkey())
+        .await
+        .unwrap();
+    let bob_account = bob_account.pubkey();
+    let amount = 10;
+    token
+        .mint_to(
+            &alice_account,
+            &mint_authority.pubkey(),
+            amount,
+            &[&mint_authority],
+        )
+        .await
+        .unwrap();
+
+    let error = token
+        .transfer(&alice_account, &bob_account, &alice.pubkey(), 0, &[&alice])
+        .await
+        .unwrap_err();
+    assert_eq!(error, TokenClientError::ZeroAmountTransfer);
+
+    let error = token
+        .transfer(
+            &alice_account,
+            &alice_account,
+            &alice.pubkey(),
+            1,
+            &[&alice],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(error, TokenClientError::SelfTransfer);
+
+    let error = token
+        .burn(&alice_account, &alice.pubkey(), amount + 1, &[&alice])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error,
+        TokenClientError::BurnExceedsBalance {
+            amount: amount + 1,
+            balance: amount,
+        }
+    );
+
+    // operations within the guards still go through
+    token
+        .transfer(&alice_account, &bob_account, &alice.pubkey(), 1, &[&alice])
+        .await
+        .unwrap();
+    token
+        .burn(&alice_account, &alice.pubkey(), amount - 1, &[&alice])
+        .await
+        .unwrap();
+    let alice_state = token.get_account_info(&alice_account).await.unwrap();
+    assert_eq!(alice_state.base.amount, 0);
+}